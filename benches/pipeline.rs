@@ -0,0 +1,40 @@
+use catnip::core::bench::{collect_phase, match_phase, read_phase, render_phase};
+use catnip::io::vfs::MemoryFs;
+use catnip::utils::token_counter::count_tokens;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::path::PathBuf;
+
+/// A synthetic project of a few hundred source files spread over nested modules
+fn sample_tree() -> MemoryFs {
+    let body = "pub fn handler(input: &str) -> usize {\n    input.len()\n}\n".repeat(20);
+    MemoryFs::with_files((0..300).map(|i| {
+        (
+            format!("project/src/module_{}/sub_{}/file_{}.rs", i % 10, i % 7, i),
+            body.clone(),
+        )
+    }))
+}
+
+fn pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let vfs = sample_tree();
+    let paths = vec![PathBuf::from("project")];
+    let files = runtime.block_on(collect_phase(&vfs, &paths)).unwrap();
+    let output = runtime.block_on(render_phase(&vfs, &files)).unwrap();
+
+    c.bench_function("collect", |b| {
+        b.iter(|| runtime.block_on(collect_phase(&vfs, black_box(&paths))))
+    });
+    c.bench_function("match", |b| b.iter(|| match_phase(black_box(&files))));
+    c.bench_function("read", |b| {
+        b.iter(|| runtime.block_on(read_phase(&vfs, black_box(&files))))
+    });
+    c.bench_function("render", |b| {
+        b.iter(|| runtime.block_on(render_phase(&vfs, black_box(&files))))
+    });
+    c.bench_function("tokenize", |b| b.iter(|| count_tokens(black_box(&output))));
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -1,63 +1,532 @@
+use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::config::Settings;
+use crate::config::patterns::{DEFAULT_SKIP_DIRECTORIES, read_pattern_list};
+use crate::config::prompt::PromptPosition;
+use crate::config::settings::{LintSeverity, ShareProviderKind};
+use crate::core::binary_assets::{BinaryAssets, BinaryMode};
+use crate::core::changelog::DEFAULT_CHANGELOG;
+use crate::core::classifier::TestFilter;
+use crate::core::content_processor::{Anchors, GroupBy, Layout, OutputFormat};
+use crate::core::file_collector::{CollectOptions, VirtualEntry};
+use crate::core::filter_expr::FilterExpr;
+use crate::core::framework::Framework;
+use crate::core::io_errors::IoErrors;
+use crate::core::pruned_dirs::PrunedDirs;
+use crate::core::relevance::DEFAULT_QUERY_TOP;
+use crate::core::rust_cfg::Target;
+use crate::integrations::git::{NotMaterialized, SubmoduleMode, Submodules};
+use crate::io::encryption::EncryptionTarget;
+
 #[derive(Parser)]
 #[command(name = "catnip")]
 #[command(about = "Concatenate and patch codebases")]
 pub struct Args {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Don't lock catnip's state directories (for advanced use; concurrent runs may clobber state)
+    #[arg(long, global = true)]
+    pub no_lock: bool,
+
+    /// Print how long each pipeline stage (walk, filter, read, strip, render, clipboard) took
+    #[arg(long, global = true)]
+    pub timings: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Concatenate files content with directory structure
-    Cat {
-        /// Paths to process
-        paths: Vec<PathBuf>,
+    Cat(Box<CatArgs>),
+    /// List files changed since the last `cat` with the same paths and filters
+    Changed(CollectArgs),
+    /// Build or update the search index `cat --query` ranks files with
+    Index(CollectArgs),
+    /// Show line count, function count and nesting depth of each file, largest first
+    Stats(CollectArgs),
+    /// Time collection, pattern matching, reading, rendering and tokenization on a tree
+    Bench {
+        /// Directory to benchmark
+        path: PathBuf,
 
-        /// Output file name (optional)
-        #[arg(short = 'o', long)]
-        output: Option<String>,
+        /// Number of runs to average over
+        #[arg(short = 'n', long, default_value = "3")]
+        iterations: usize,
+    },
+    /// Apply code updates to files, from catnip's JSON format or a unified diff
+    Patch {
+        /// JSON or unified diff file, or https:// URL, containing updates; '-' to read from
+        /// stdin, or omit to read from clipboard
+        json_file: Option<String>,
 
-        /// Don't copy to clipboard
+        /// Dry run - show what would be changed without applying updates
         #[arg(long)]
-        no_copy: bool,
+        dry_run: bool,
+
+        /// Create backup files before updating
+        #[arg(short = 'b', long)]
+        backup: bool,
 
-        /// Additional patterns to exclude
-        #[arg(short = 'e', long)]
-        exclude: Vec<String>,
+        /// Apply clipboard patches even if they exceed the safety thresholds
+        #[arg(long)]
+        force: bool,
+
+        /// With --dry-run, write a shell script that applies the same changes
+        #[arg(long, value_name = "FILE", requires = "dry_run")]
+        emit_script: Option<String>,
 
-        /// Additional patterns to include
-        #[arg(short = 'i', long)]
-        include: Vec<String>,
+        /// Change nothing unless every file can be updated
+        #[arg(long, conflicts_with = "continue_on_error")]
+        fail_fast: bool,
 
-        /// Ignore code comments
+        /// As --fail-fast, and if writing a file fails, put back the files already written
+        #[arg(long, conflicts_with_all = ["continue_on_error", "fail_fast"])]
+        atomic: bool,
+
+        /// Update the files that can be and report the rest (the default)
         #[arg(long)]
-        ignore_comments: bool,
+        continue_on_error: bool,
 
-        /// Ignore docstrings
+        /// Print a machine-readable line as each update is validated and applied
         #[arg(long)]
-        ignore_docstrings: bool,
+        porcelain: bool,
 
-        /// Maximum file size in MB (default: 10MB)
-        #[arg(long, default_value = "10")]
-        max_size_mb: u64,
-        /// Include prompt instructions
-        #[arg(short = 'p', long = "prompt")]
-        prompt: bool,
-    },
-    /// Apply JSON-formatted code updates to files
-    Patch {
-        /// JSON file containing updates, '-' to read from stdin, or omit to read from clipboard
-        json_file: Option<String>,
+        /// Apply in a temporary worktree at this git ref and commit the result to a new branch
+        #[arg(long, value_name = "REF", conflicts_with_all = ["dry_run", "backup"])]
+        onto: Option<String>,
 
-        /// Dry run - show what would be changed without applying updates
+        /// Show each update as a colored diff and ask whether to apply it (y/n/a/q)
+        #[arg(long)]
+        interactive: bool,
+
+        /// age key file to decrypt a patch file encrypted for it
+        #[arg(long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+
+        /// Turn the placeholders of `cat --anonymize` back into the real values before
+        /// applying, using its map (default: the one in catnip's data directory)
+        #[arg(long, value_name = "MAP_FILE", num_args = 0..=1)]
+        deanonymize: Option<Option<PathBuf>>,
+
+        /// Apply placeholders as written, without looking for the anonymization map
+        #[arg(long, conflicts_with = "deanonymize")]
+        keep_placeholders: bool,
+
+        /// When old content isn't found exactly, accept a match that only differs in
+        /// whitespace and indentation, with at least this confidence (0-100, default 80)
+        #[arg(long, value_name = "MIN_CONFIDENCE", num_args = 0..=1, default_missing_value = "80", value_parser = clap::value_parser!(u8).range(0..=100))]
+        fuzzy: Option<u8>,
+
+        /// Severity for every patch lint rule, overriding `[patch.lint]` in the config
+        #[arg(long, value_enum, value_name = "LEVEL")]
+        lint: Option<LintSeverity>,
+
+        /// Append the analysis, time and changed files to a changelog (default: CHANGES.catnip.md)
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = DEFAULT_CHANGELOG)]
+        changelog: Option<String>,
+    },
+    /// Put back the files the last `patch` in this directory changed, as they were before it
+    Undo {
+        /// List the files that would be restored without touching them
         #[arg(long)]
         dry_run: bool,
 
-        /// Create backup files before updating
-        #[arg(short = 'b', long)]
-        backup: bool,
+        /// Undo even files edited since the patch, throwing those edits away
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print a JSON Schema for one of catnip's formats
+    Schema {
+        /// Schema of the JSON that `patch` applies
+        #[arg(long, required = true)]
+        patch: bool,
+    },
+    /// Upload a rendered snapshot and print a link to it
+    Share {
+        /// Rendered snapshot to upload (e.g. from `cat -o`), or '-' to read from stdin
+        file: String,
+
+        /// Where to upload, overriding `[share] provider` in the config
+        #[arg(long, value_enum)]
+        provider: Option<ShareProviderKind>,
+
+        /// Don't copy the link to the clipboard
+        #[arg(long)]
+        no_copy: bool,
+    },
+    /// Print a rendered snapshot, decrypting it when it was written with `cat --encrypt`
+    Inspect {
+        /// Rendered snapshot to read (e.g. from `cat -o`)
+        file: String,
+
+        /// age key file to decrypt the snapshot with
+        #[arg(long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+
+        /// Write the snapshot to this file instead of printing it
+        #[arg(short = 'o', long)]
+        output: Option<String>,
     },
 }
+
+/// Arguments selecting which files go into a snapshot
+#[derive(clap::Args, Debug)]
+pub struct CollectArgs {
+    /// Paths to process
+    pub paths: Vec<PathBuf>,
+
+    /// Additional patterns to exclude
+    #[arg(short = 'e', long)]
+    pub exclude: Vec<String>,
+
+    /// Additional patterns to include
+    #[arg(short = 'i', long)]
+    pub include: Vec<String>,
+
+    /// Read exclude patterns from a file, one glob per line
+    #[arg(long, value_name = "FILE")]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// Read include patterns from a file, one glob per line
+    #[arg(long, value_name = "FILE")]
+    pub include_from: Vec<PathBuf>,
+
+    /// Additional dotfile patterns to include, on top of .env.example, .editorconfig, ...
+    #[arg(long, value_name = "GLOB")]
+    pub dotfiles_allow: Vec<String>,
+
+    /// Maximum file size in MB (default: 10MB)
+    #[arg(long, default_value = "10")]
+    pub max_size_mb: u64,
+
+    /// Exclude test, bench and fixture code
+    #[arg(long, conflicts_with = "tests_only")]
+    pub no_tests: bool,
+
+    /// Include test code in full and only an outline of the code under test
+    #[arg(long)]
+    pub tests_only: bool,
+
+    /// Fail on any unreadable file instead of skipping it with a warning
+    #[arg(long)]
+    pub strict_io: bool,
+
+    /// List every skipped directory and why, with the size of skipped top-level trees
+    #[arg(long)]
+    pub prune_report: bool,
+
+    /// Descend into directories on the built-in skip list (.git, node_modules, target, ...)
+    #[arg(long)]
+    pub no_default_skips: bool,
+
+    /// Don't honor .gitignore, .ignore, .rgignore and .git/info/exclude
+    #[arg(long, alias = "no-gitignore")]
+    pub no_ignore: bool,
+
+    /// Leave git submodules out, include them fully, or include only their outlines
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = SubmoduleMode::Include)]
+    pub submodules: SubmoduleMode,
+
+    /// Only collect files matching an expression over path, name, ext, lang, size and lines,
+    /// e.g. 'lang == "rust" && lines < 2000 && !path.contains("generated")'
+    #[arg(long, value_name = "EXPR", value_parser = FilterExpr::parse)]
+    pub filter: Option<FilterExpr>,
+}
+
+impl CollectArgs {
+    pub fn test_filter(&self) -> TestFilter {
+        if self.no_tests {
+            TestFilter::ExcludeTests
+        } else if self.tests_only {
+            TestFilter::OnlyTests
+        } else {
+            TestFilter::All
+        }
+    }
+
+    /// Paths to collect: the given ones, or the configured workspace roots when none are given
+    pub fn input_paths(&self) -> Result<Vec<PathBuf>> {
+        if !self.paths.is_empty() {
+            return Ok(self.paths.clone());
+        }
+        Ok(Settings::load()?
+            .roots
+            .into_iter()
+            .map(|root| root.path)
+            .collect())
+    }
+
+    pub fn collect_options(&self, virtual_entries: Vec<VirtualEntry>) -> Result<CollectOptions> {
+        let settings = Settings::load()?;
+        let skip_directories = if self.no_default_skips {
+            Vec::new()
+        } else {
+            match settings.collect.skip_directories {
+                Some(skip_directories) => skip_directories,
+                None => DEFAULT_SKIP_DIRECTORIES
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            }
+        };
+
+        let mut excludes = self.exclude.clone();
+        for path in &self.exclude_from {
+            excludes.extend(read_pattern_list(path)?);
+        }
+        let mut includes = self.include.clone();
+        for path in &self.include_from {
+            includes.extend(read_pattern_list(path)?);
+        }
+
+        Ok(CollectOptions {
+            excludes,
+            includes,
+            dotfiles_allow: self.dotfiles_allow.clone(),
+            max_size_mb: self.max_size_mb,
+            virtual_entries,
+            test_filter: self.test_filter(),
+            io_errors: IoErrors::new(self.strict_io),
+            skip_directories,
+            pruned: PrunedDirs::default(),
+            prune_report: self.prune_report,
+            ignore_files: !self.no_ignore,
+            pinned: settings.collect.pinned,
+            roots: settings.roots,
+            submodule_mode: self.submodules,
+            submodules: Submodules::default(),
+            not_materialized: NotMaterialized::default(),
+            quiet: false,
+            filter: self.filter.clone(),
+            binaries: BinaryMode::default(),
+            binary_assets: BinaryAssets::default(),
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CatArgs {
+    #[command(flatten)]
+    pub collect: CollectArgs,
+
+    /// Output file name (optional)
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+
+    /// Write the list of files skipped due to I/O, permission or encoding problems
+    #[arg(long, value_name = "FILE")]
+    pub error_report: Option<String>,
+
+    /// Also write the project structure, with file metadata, as JSON
+    #[arg(long, value_name = "FILE")]
+    pub structure_json: Option<String>,
+
+    /// Write the output file even when it is a symlink, replacing the file it points to
+    #[arg(long, requires = "output")]
+    pub follow_symlinks: bool,
+
+    /// Encrypt the output file for an age recipient (age:age1...); the structure JSON,
+    /// error report and manifest files would be written in the clear, so they can't be added
+    #[arg(
+        long,
+        value_name = "SCHEME:RECIPIENT",
+        value_parser = EncryptionTarget::parse,
+        requires = "output",
+        conflicts_with_all = ["open", "structure_json", "error_report", "write_manifest"]
+    )]
+    pub encrypt: Option<EncryptionTarget>,
+
+    /// Open the output file once written ($EDITOR, $BROWSER for HTML, or the default handler)
+    #[arg(long, requires = "output")]
+    pub open: bool,
+
+    /// Don't copy to clipboard
+    #[arg(long)]
+    pub no_copy: bool,
+
+    /// End the output with a Caveats section listing unreadable, left-out and cut-down files
+    #[arg(long)]
+    pub caveats: bool,
+
+    /// After the file tree, prompt for extra exclude globs with a live token estimate
+    #[arg(long)]
+    pub refine: bool,
+
+    /// Include files that look like keys or credentials (*.pem, id_rsa, credentials*, .env, ...)
+    #[arg(long)]
+    pub allow_sensitive: bool,
+
+    /// Leave out files under this license, found in SPDX headers, license files and package
+    /// manifests (e.g. gpl-3.0, which covers -only and -or-later too); repeatable
+    #[arg(long = "exclude-license", value_name = "SPDX_ID")]
+    pub exclude_license: Vec<String>,
+
+    /// End the output with a License Notice section listing the licenses of the included files
+    #[arg(long)]
+    pub license_notice: bool,
+
+    /// Use a framework's profile, leaving out its build output and putting its config and
+    /// routes first; without a name, detect the framework in the first input directory
+    #[arg(long, value_enum, value_name = "FRAMEWORK", num_args = 0..=1)]
+    pub framework: Option<Option<Framework>>,
+
+    /// In Rust files, drop code behind `#[cfg(...)]` attributes that are off: `cfg(test)`,
+    /// features neither given with --features nor on by default and, with --target, other
+    /// platforms
+    #[arg(long)]
+    pub rust_cfg: bool,
+
+    /// Cargo features --rust-cfg treats as enabled (comma-separated)
+    #[arg(
+        long,
+        value_name = "FEATURES",
+        value_delimiter = ',',
+        requires = "rust_cfg"
+    )]
+    pub features: Vec<String>,
+
+    /// Leave the crate's default features off in --rust-cfg; they are on otherwise, read
+    /// from the nearest Cargo.toml
+    #[arg(long, requires = "rust_cfg")]
+    pub no_default_features: bool,
+
+    /// Target triple whose platform --rust-cfg keeps code for, e.g. x86_64-pc-windows-msvc
+    #[arg(long, value_name = "TRIPLE", value_parser = Target::parse, requires = "rust_cfg")]
+    pub target: Option<Target>,
+
+    /// Leave binary files out, or list each one's path, size, type and hash under a
+    /// "Binary Assets" heading
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = BinaryMode::Skip)]
+    pub binaries: BinaryMode,
+
+    /// Replace the emails, hosts, words and patterns under [anonymize] in the config with
+    /// placeholders, keeping them in a map (default: in catnip's data directory)
+    #[arg(long, value_name = "MAP_FILE", num_args = 0..=1)]
+    pub anonymize: Option<Option<PathBuf>>,
+
+    /// How file headings are arranged in the output
+    #[arg(long, value_enum, default_value_t = Layout::Flat)]
+    pub layout: Layout,
+
+    /// Add `path:line` anchors to headings and long files, for jumping to cited locations
+    #[arg(long, value_enum, default_value_t = Anchors::None)]
+    pub anchors: Anchors,
+
+    /// Write the snapshot as markdown, Claude-style `<documents>` XML, or JSON for other tools
+    #[arg(long, value_enum, default_value_t = OutputFormat::Markdown)]
+    pub format: OutputFormat,
+
+    /// Keep only the files most relevant to these keywords, plus pinned files
+    #[arg(long, value_name = "KEYWORDS")]
+    pub query: Option<String>,
+
+    /// Number of files `--query` keeps
+    #[arg(long, value_name = "N", requires = "query", default_value_t = DEFAULT_QUERY_TOP)]
+    pub top: usize,
+
+    /// Split the output into numbered parts of at most this many tokens, each with the structure
+    #[arg(long, value_name = "TOKENS")]
+    pub split_tokens: Option<usize>,
+
+    /// Group files by directory or language; implies `--layout grouped`
+    #[arg(long, value_enum)]
+    pub group_by: Option<GroupBy>,
+
+    /// Summarize each directory (README excerpt or detected purpose) before the code
+    #[arg(long)]
+    pub dir_summaries: bool,
+
+    /// Shrink files as needed to fit a model's context window (e.g. gpt-4o, claude-200k)
+    #[arg(long, value_name = "MODEL")]
+    pub fit_model: Option<String>,
+
+    /// List the estimated token count of each file under the total
+    #[arg(long)]
+    pub show_tokens: bool,
+
+    /// Print the input-token cost of the output for each model (e.g. gpt-4o,claude-3.5)
+    #[arg(long, value_name = "MODELS", value_delimiter = ',')]
+    pub estimate_cost: Vec<String>,
+
+    /// Also copy an HTML flavor for pasting into rich editors
+    #[arg(long, conflicts_with = "no_copy")]
+    pub rich_copy: bool,
+
+    /// Ignore code comments
+    #[arg(long)]
+    pub ignore_comments: bool,
+
+    /// Ignore docstrings
+    #[arg(long)]
+    pub ignore_docstrings: bool,
+
+    /// Include prompt instructions
+    #[arg(short = 'p', long = "prompt")]
+    pub prompt: bool,
+
+    /// Copy the prompt instructions as a separate clipboard entry once the snapshot is pasted
+    #[arg(long, conflicts_with = "prompt")]
+    pub prompt_clipboard: bool,
+
+    /// Put the prompt instructions before or after the snapshot
+    #[arg(long, value_enum, default_value_t = PromptPosition::End)]
+    pub prompt_position: PromptPosition,
+
+    /// Inject a synthetic file into the snapshot (name=path-or-literal)
+    #[arg(long = "virtual", value_name = "NAME=PATH_OR_LITERAL")]
+    pub virtual_files: Vec<String>,
+
+    /// Render size, line count, modification time and hash under each file heading
+    #[arg(long)]
+    pub file_meta: bool,
+
+    /// Note line count, function count and nesting depth under each file heading
+    #[arg(long)]
+    pub complexity: bool,
+
+    /// Only render files changed since a snapshot file, or 'last' for the previous run
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub delta_from: Option<String>,
+
+    /// Read files as of a git revision (tag, branch, commit) instead of the working tree
+    #[arg(long, value_name = "REF")]
+    pub at: Option<String>,
+
+    /// Embed the resolved options and file hashes of this run as a trailing HTML comment
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// Also write the run manifest next to the output file, as <name>.manifest.json
+    #[arg(long, requires = "output")]
+    pub write_manifest: bool,
+
+    /// Follow-up message: only files changed since the previous run, opened by a note
+    /// telling the model everything else is unchanged
+    #[arg(long, conflicts_with = "delta_from")]
+    pub since_snapshot: bool,
+
+    /// Exit with code 3 when no files are left to concatenate
+    #[arg(long)]
+    pub fail_if_empty: bool,
+
+    /// Exit with code 4 when the output is estimated above this many tokens
+    #[arg(long, value_name = "TOKENS")]
+    pub fail_over_tokens: Option<usize>,
+
+    /// Exit with code 4 when the output is larger than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub fail_over_bytes: Option<usize>,
+
+    /// Exit with code 5 when likely-sensitive files are found, even if left out, without
+    /// copying the output or running the post-cat hook
+    #[arg(long)]
+    pub fail_on_secrets: bool,
+
+    /// Exit with code 6 when anything was redacted: likely-sensitive files or files under an
+    /// excluded license left out, or values anonymized; nothing is copied and the post-cat hook
+    /// doesn't run
+    #[arg(long)]
+    pub fail_on_redactions: bool,
+}
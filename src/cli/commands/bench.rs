@@ -0,0 +1,25 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::core::bench::bench_pipeline;
+use crate::io::vfs::Vfs;
+
+pub async fn execute(vfs: &impl Vfs, path: PathBuf, iterations: usize) -> Result<()> {
+    println!(
+        "⏱️  Benchmarking {} over {} iterations...\n",
+        path.display(),
+        iterations
+    );
+
+    let report = bench_pipeline(vfs, std::slice::from_ref(&path), iterations).await?;
+
+    println!(
+        "{} files, {} bytes, {} tokens\n",
+        report.files, report.bytes, report.tokens
+    );
+    print!("{}", report.format_table());
+
+    info!("Benchmark completed");
+    Ok(())
+}
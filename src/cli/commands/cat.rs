@@ -1,56 +1,1053 @@
-use anyhow::Result;
-use std::path::PathBuf;
-use tracing::{error, info};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, trace_span, warn};
 
-use crate::config::prompt::PROMPT;
-use crate::core::content_processor::concatenate_files;
-use crate::core::file_collector::collect_files;
-use crate::io::clipboard::copy_to_clipboard;
+use crate::cli::args::CatArgs;
+use crate::cli::guards::{Guard, GuardFailure};
+use crate::config::Settings;
+use crate::config::models::{context_window_for, model_table, resolve_model};
+use crate::config::paths;
+use crate::config::prompt::{PROMPT, delta_preamble, place_prompt_with};
+use crate::config::settings::AnonymizeSettings;
+use crate::core::anonymizer::{Anonymization, AnonymizeMap, Anonymizer, anonymize_map_path};
+use crate::core::binary_assets::BINARY_ANNOTATION;
+use crate::core::content_processor::{
+    Layout, OutputFormat, Part, RenderOptions, concatenate_files_with, part_path,
+};
+use crate::core::file_collector::{
+    CollectOptions, CollectedFile, VirtualEntry, collect_files_with, diagnose_empty,
+};
+use crate::core::framework::{Framework, detect_framework};
+use crate::core::licenses::{FileLicense, LicenseFilter, LicenseNotice, detect_licenses};
+use crate::core::manifest::{Manifest, manifest_path};
+use crate::core::packer::{
+    BudgetShare, PackingPlan, Representation, plan_packing_shared, plan_packing_with_prompt,
+    split_into_parts,
+};
+use crate::core::patch_journal::clear_patch_journal;
+use crate::core::refine::refine_files;
+use crate::core::relevance::rank_by_relevance;
+use crate::core::rust_cfg::RustCfg;
+use crate::core::search_index::{SearchIndex, index_key, index_path};
+use crate::core::sensitive::find_sensitive;
+use crate::core::snapshot::{FileStatus, Snapshot, profile_key, record_snapshot, snapshot_path};
+use crate::core::structure_generator::{generate_directory_structure, generate_structure_tree};
+use crate::core::transform::Transforms;
+use crate::core::warnings::{WarningKind, Warnings};
+use crate::integrations::git::{GitRevisionFs, NOT_MATERIALIZED};
+use crate::io::clipboard::ClipboardProvider;
+use crate::io::file_operations::resolve_write_target;
+use crate::io::lock::lock_project_state;
+use crate::io::opener::open_path;
+use crate::io::shell::{run_shell, shell_command};
+use crate::io::vfs::{OverlayFs, Vfs};
+use crate::utils::file_metadata::FileMetadata;
+use crate::utils::html::markdown_to_html;
+use crate::utils::path_encoding::relative_to;
+use crate::utils::token_counter::count_tokens;
+
+/// Tokens set aside in each part of a split snapshot for headings and notes
+const PART_OVERHEAD_TOKENS: usize = 100;
 
-#[allow(clippy::too_many_arguments)]
 pub async fn execute(
-    paths: Vec<PathBuf>,
-    output: Option<String>,
-    no_copy: bool,
-    exclude: Vec<String>,
-    include: Vec<String>,
-    ignore_comments: bool,
-    ignore_docstrings: bool,
-    prompt: bool,
-    max_size_mb: u64,
+    clipboard: &dyn ClipboardProvider,
+    vfs: &impl Vfs,
+    args: CatArgs,
+) -> Result<()> {
+    match &args.at {
+        Some(revision) => {
+            let vfs = GitRevisionFs::load(vfs, revision)
+                .await
+                .with_context(|| format!("Failed to read files at {}", revision))?;
+            info!("Reading files as of {}", revision);
+            execute_with(clipboard, &vfs, args).await
+        }
+        None => execute_with(clipboard, vfs, args).await,
+    }
+}
+
+/// Like `execute`, reading files from `vfs` as they are
+async fn execute_with(
+    clipboard: &dyn ClipboardProvider,
+    vfs: &impl Vfs,
+    args: CatArgs,
 ) -> Result<()> {
-    if paths.is_empty() {
-        error!("No paths provided");
-        std::process::exit(1);
+    let input_paths = args.collect.input_paths()?;
+    if input_paths.is_empty() && args.virtual_files.is_empty() {
+        return Err(anyhow::anyhow!("No paths provided"));
+    }
+
+    // Prompts, preambles and HTML are text around the snapshot, which JSON has no room for
+    if args.format == OutputFormat::Json
+        && (args.prompt || args.prompt_clipboard || args.since_snapshot || args.rich_copy)
+    {
+        return Err(anyhow::anyhow!(
+            "--format json can't be combined with --prompt, --prompt-clipboard, --since-snapshot or --rich-copy"
+        ));
+    }
+
+    // Check the output target up front rather than after all the work of rendering
+    if let Some(output) = &args.output {
+        resolve_write_target(Path::new(output), args.follow_symlinks).await?;
+    }
+
+    let mut virtual_entries = Vec::new();
+    for spec in &args.virtual_files {
+        virtual_entries.push(VirtualEntry::resolve(vfs, spec).await?);
     }
+    let vfs = OverlayFs::new(
+        vfs,
+        virtual_entries
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.content.clone().into_bytes()))
+            .collect(),
+    );
 
-    let files = collect_files(&paths, &exclude, &include, max_size_mb).await?;
+    let mut collect_options = args.collect.collect_options(virtual_entries)?;
+    collect_options.binaries = args.binaries;
+    let framework = match args.framework {
+        Some(Some(framework)) => Some(framework),
+        Some(None) => Some(detect_input_framework(&vfs, &input_paths).await?),
+        None => None,
+    };
+    if let Some(framework) = framework {
+        println!("🧭 Using the {} profile", framework.label());
+        framework.profile().apply(&mut collect_options);
+    }
+    let mut collected = collect_files_with(&vfs, &input_paths, &collect_options).await?;
+    if let Some(framework) = framework {
+        framework.profile().order_files(&mut collected);
+    }
+    // An empty snapshot is never what was asked for, so say where the files went instead
+    if collected.is_empty() {
+        println!("🙀 No files matched:");
+        for finding in diagnose_empty(&vfs, &input_paths, &collect_options).await {
+            println!("   {}", finding);
+        }
+        if args.fail_if_empty {
+            return Err(GuardFailure::new(Guard::Empty, "No files to concatenate").into());
+        }
+        return Err(anyhow::anyhow!("No files to concatenate"));
+    }
+    let warnings = Warnings::default();
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    for skipped in collect_options.io_errors.skipped() {
+        warnings.push(
+            WarningKind::Unreadable,
+            &relative_to(&skipped.path, &current_dir),
+            skipped.reason,
+        );
+    }
+    let sensitive = guard_sensitive(&mut collected, args.allow_sensitive, &warnings);
+    let mut redactions = Redactions {
+        sensitive: if args.allow_sensitive { 0 } else { sensitive },
+        ..Redactions::default()
+    };
+    let licenses = if args.license_notice || !args.exclude_license.is_empty() {
+        let licenses = detect_licenses(&vfs, &collected).await;
+        redactions.licensed = guard_licenses(
+            &mut collected,
+            &licenses,
+            &LicenseFilter::new(&args.exclude_license),
+            &warnings,
+        );
+        licenses
+    } else {
+        BTreeMap::new()
+    };
+    if args.refine {
+        refine(&vfs, &mut collected).await?;
+    }
+
+    let settings = Settings::load()?;
+
+    // Everything downstream, snapshot included, sees the transformed content
+    let transforms = Transforms::new(&settings.transforms);
+    let mut transformed = transforms.apply(&vfs, &mut collected).await?;
+    let mut anonymization = None;
+    if let Some(map_path) = &args.anonymize {
+        let transformed_fs = OverlayFs::new(&vfs, transformed.clone());
+        let (anonymized, replaced, anonymizer) = anonymize_files(
+            &transformed_fs,
+            &mut collected,
+            &settings.anonymize,
+            map_path.as_deref(),
+        )
+        .await?;
+        transformed.extend(anonymized);
+        redactions.anonymized = replaced;
+        anonymization = Some(anonymizer);
+    }
+    let vfs = OverlayFs::new(&vfs, transformed);
+    if let Some(query) = &args.query {
+        select_relevant(&vfs, &mut collected, query, args.top, &collect_options).await?;
+    }
+    let files = CollectedFile::paths(&collected);
+    if files.is_empty() && args.fail_if_empty {
+        return Err(GuardFailure::new(Guard::Empty, "No files left to concatenate").into());
+    }
 
     info!("Found {} files to process", files.len());
 
-    let mut result = concatenate_files(
-        &files,
-        output.as_deref(),
-        ignore_comments,
-        ignore_docstrings,
-    )
-    .await?;
-
-    // Add prompt instructions if requested
-    if prompt {
-        result = format!(
-            "{}
-{}",
-            result, PROMPT
+    let snapshot = Snapshot::capture(&vfs, &files).await;
+    let profile = profile_key(&input_paths, &collect_options);
+
+    let delta_from = if args.since_snapshot {
+        Some("last")
+    } else {
+        args.delta_from.as_deref()
+    };
+    let delta = match delta_from {
+        Some(source) => {
+            let previous_path = if source == "last" {
+                snapshot_path(&paths::history_dir()?, &profile)
+            } else {
+                PathBuf::from(source)
+            };
+            let previous = Snapshot::load(&vfs, &previous_path)
+                .await?
+                .with_context(|| format!("No snapshot found at {}", previous_path.display()))?;
+            Some(snapshot.statuses_since(&previous))
+        }
+        None => None,
+    };
+
+    let mut prompt = PROMPT.to_string();
+    let packing = match &args.fit_model {
+        Some(model) => {
+            let with_prompt = args.prompt || args.prompt_clipboard;
+            let (plan, fitted_prompt) = plan_for_model(
+                &snapshot,
+                &files,
+                model,
+                with_prompt.then_some(PROMPT),
+                &collect_options,
+            )?;
+            if let Some(fitted_prompt) = fitted_prompt
+                && fitted_prompt.len() < prompt.len()
+            {
+                println!("✂️  Dropped optional sections of the prompt to make room for code");
+                prompt = fitted_prompt;
+            }
+            print_packing_report(&plan, model);
+            for (path, representation) in &plan.decisions {
+                if *representation != Representation::Full {
+                    warnings.push(
+                        WarningKind::Reduced,
+                        &relative_to(path, &current_dir),
+                        format!("{} to fit the context window", representation.label()),
+                    );
+                }
+            }
+            Some(plan.decisions)
+        }
+        None => None,
+    };
+
+    let mut render_options = RenderOptions {
+        output_file: args.output.clone(),
+        encrypt: args.encrypt.clone(),
+        ignore_comments: args.ignore_comments,
+        ignore_docstrings: args.ignore_docstrings,
+        test_filter: collect_options.test_filter,
+        rust_cfg: args.rust_cfg.then(|| RustCfg {
+            features: args.features.iter().cloned().collect(),
+            target: args.target.clone(),
+            no_default_features: args.no_default_features,
+        }),
+        io_errors: collect_options.io_errors.clone(),
+        processing: settings.processing.clone(),
+        fence_aliases: settings.fences.clone(),
+        warnings,
+        caveats: args.caveats,
+        submodules: collect_options.submodules.clone(),
+        submodule_mode: collect_options.submodule_mode,
+        not_materialized: collect_options.not_materialized.clone(),
+        binary_assets: collect_options.binary_assets.clone(),
+        license_notice: args
+            .license_notice
+            .then(|| LicenseNotice::new(&licenses, &files)),
+        manifest: None,
+        anonymization: anonymization
+            .as_ref()
+            .map(|(anonymization, _)| anonymization.clone()),
+        anchors: args.anchors,
+        format: args.format,
+        part: None,
+        quiet: false,
+        file_meta: args.file_meta,
+        complexity: args.complexity,
+        delta,
+        packing,
+        dir_summaries: args.dir_summaries,
+        layout: if args.group_by.is_some() {
+            Layout::Grouped
+        } else {
+            args.layout
+        },
+        group_by: args.group_by.unwrap_or_default(),
+    };
+    if args.manifest || args.write_manifest {
+        let manifest = Manifest::new(
+            std::env::args().collect(),
+            &input_paths,
+            &collect_options,
+            &render_options,
+            &settings.transforms,
+            &snapshot,
         );
+        if args.manifest {
+            render_options.manifest = Some(manifest.embedded()?);
+        }
+        if let Some(output) = args.output.as_deref().filter(|_| args.write_manifest) {
+            let path = manifest_path(Path::new(output));
+            vfs.write(&path, manifest.to_json()?.as_bytes()).await?;
+            println!("🧾 Manifest written to: {}", path.display());
+        }
+    }
+    let mut parts = match args.split_tokens {
+        Some(limit) => render_parts(&vfs, &snapshot, &collected, &render_options, limit).await?,
+        None => vec![concatenate_files_with(&vfs, &collected, &render_options).await?],
+    };
+
+    if let Some(structure_path) = &args.structure_json {
+        write_structure_json(&vfs, &snapshot, &collected, &render_options, structure_path).await?;
+        println!("🌳 Structure written to: {}", structure_path);
+    }
+
+    let skipped = collect_options.io_errors.skipped();
+    if !skipped.is_empty() {
+        println!("⚠️  {} files skipped due to read errors", skipped.len());
+    }
+    if let Some(report_path) = &args.error_report {
+        vfs.write(
+            Path::new(report_path),
+            collect_options.io_errors.report().as_bytes(),
+        )
+        .await?;
+        println!("🧾 Error report written to: {}", report_path);
+    }
+
+    // Remember what was sent so `catnip changed` can report on it later
+    let lock = lock_project_state().await?;
+    if let Some((anonymization, map_path)) = &anonymization {
+        // Paths and the manifest may have brought values the file contents didn't have
+        anonymization.map().save(&vfs, map_path).await?;
+    }
+    record_snapshot(&vfs, &snapshot, &profile).await;
+    // The new snapshot supersedes whatever was patched in the previous conversation
+    clear_patch_journal(&vfs).await;
+    drop(lock);
+
+    let copy_snapshot = !args.no_copy && args.output.is_none();
+    // Without a terminal to wait on, a separate prompt entry falls back to a marked section
+    let embed_prompt =
+        args.prompt || (args.prompt_clipboard && copy_snapshot && !std::io::stdin().is_terminal());
+
+    if args.since_snapshot
+        && let Some(delta) = &render_options.delta
+    {
+        parts[0].insert_str(0, &delta_preamble(delta));
+    }
+
+    // Add prompt instructions if requested, after the last part so they're read last
+    if embed_prompt && let Some(result) = parts.last_mut() {
+        *result = place_prompt_with(result, &prompt, args.prompt_position, args.prompt_clipboard);
         info!("Added prompt instructions from constant");
     }
 
+    let part_tokens: Vec<usize> = parts.iter().map(|part| count_tokens(part)).collect();
+    let tokens = part_tokens.iter().sum();
+    println!("🔢 Estimated tokens: {}", tokens);
+    if parts.len() > 1 {
+        for (i, part) in part_tokens.iter().enumerate() {
+            println!("   part {}: {} tokens", i + 1, part);
+        }
+    }
+    if args.show_tokens {
+        print_token_breakdown(&snapshot, &current_dir);
+    }
+    if !args.estimate_cost.is_empty() {
+        print_cost_estimate(tokens, &args.estimate_cost)?;
+    }
+
+    // Before anything leaves the machine, so a tripped guard copies and uploads nothing
+    check_disclosure_guards(&args, sensitive, &redactions)?;
+
     // Copy to clipboard by default unless --no-copy is specified or output file is provided
-    if !no_copy && output.is_none() {
-        copy_to_clipboard(&result).await?;
+    if copy_snapshot && parts.len() > 1 && !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "Copying {} parts one at a time needs a terminal; write them with -o instead",
+            parts.len()
+        ));
+    }
+    if copy_snapshot {
+        for (i, result) in parts.iter().enumerate() {
+            if i > 0 {
+                wait_for_enter(&format!(
+                    "📋 Part {} of {} copied. Paste it, then press Enter to copy the next part...",
+                    i,
+                    parts.len()
+                ))?;
+            }
+            trace_span!("clipboard").in_scope(|| {
+                if args.rich_copy {
+                    clipboard.copy_html(&markdown_to_html(result), result)
+                } else {
+                    clipboard.copy(result)
+                }
+            })?;
+        }
+    }
+
+    if args.prompt_clipboard && !embed_prompt {
+        copy_prompt(clipboard, &prompt, copy_snapshot)?;
     }
 
+    let outputs: Vec<Option<String>> = match &args.output {
+        Some(output) if parts.len() > 1 => (1..=parts.len())
+            .map(|i| Some(part_path(Path::new(output), i).display().to_string()))
+            .collect(),
+        output => vec![output.clone()],
+    };
+
+    if args.open
+        && let Some(Some(output)) = outputs.first()
+    {
+        open_path(Path::new(output))?;
+    }
+
+    if let Some(command) = &settings.hooks.post_cat {
+        for (result, output) in parts.iter().zip(&outputs) {
+            run_post_cat_hook(command, result, output.as_deref()).await;
+        }
+    }
+
+    check_size_guards(&args, tokens, parts.iter().map(String::len).sum())?;
+
     info!("Processing completed successfully");
     Ok(())
 }
+
+/// Replace the values `[anonymize]` matches with placeholders, keeping them in the map at
+/// `map_path` (default: in the history directory) for `patch --deanonymize`. Returns the
+/// anonymized contents by path, the number of values replaced, and the anonymizer with its
+/// map and the map's location, for the paths rendered later.
+async fn anonymize_files(
+    vfs: &impl Vfs,
+    collected: &mut [CollectedFile],
+    settings: &AnonymizeSettings,
+    map_path: Option<&Path>,
+) -> Result<(
+    BTreeMap<PathBuf, Vec<u8>>,
+    usize,
+    (Arc<Anonymization>, PathBuf),
+)> {
+    let anonymizer = Anonymizer::new(settings)?.context(
+        "--anonymize needs emails, hosts, words or patterns under [anonymize] in the config",
+    )?;
+    let map_path = match map_path {
+        Some(path) => path.to_path_buf(),
+        None => anonymize_map_path(&paths::history_dir()?),
+    };
+
+    let lock = lock_project_state().await?;
+    let mut map = AnonymizeMap::load(vfs, &map_path).await?;
+    let (anonymized, replaced) = anonymizer.apply(vfs, collected, &mut map).await?;
+    map.save(vfs, &map_path).await?;
+    drop(lock);
+    println!(
+        "🕶️  Anonymized {} values in {} files (map: {})",
+        replaced,
+        anonymized.len(),
+        map_path.display()
+    );
+    let anonymization = Arc::new(Anonymization::new(anonymizer, map));
+    Ok((anonymized, replaced, (anonymization, map_path)))
+}
+
+/// What this run kept out of the output, for `--fail-on-redactions`
+#[derive(Debug, Default)]
+struct Redactions {
+    /// Likely-sensitive files left out
+    sensitive: usize,
+    /// Files left out for their license
+    licensed: usize,
+    /// Values the anonymizer replaced
+    anonymized: usize,
+}
+
+impl Redactions {
+    /// What was redacted, `None` when nothing was
+    fn describe(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            (self.sensitive, "likely-sensitive files left out"),
+            (self.licensed, "files left out for their license"),
+            (self.anonymized, "values anonymized"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// Fail when the output holds likely secrets or was redacted, before it is copied or handed
+/// to the post-cat hook, after listing every guard tripped
+fn check_disclosure_guards(
+    args: &CatArgs,
+    sensitive: usize,
+    redactions: &Redactions,
+) -> Result<()> {
+    let mut failures = Vec::new();
+    if args.fail_on_secrets && sensitive > 0 {
+        failures.push(GuardFailure::new(
+            Guard::Secrets,
+            format!("Found {} likely-sensitive files", sensitive),
+        ));
+    }
+    if args.fail_on_redactions
+        && let Some(redacted) = redactions.describe()
+    {
+        failures.push(GuardFailure::new(
+            Guard::Redactions,
+            format!("Redacted the output: {}", redacted),
+        ));
+    }
+    fail_on_first(failures)
+}
+
+/// Fail when the output is over a size limit, once it is delivered, after listing every
+/// limit exceeded
+fn check_size_guards(args: &CatArgs, tokens: usize, bytes: usize) -> Result<()> {
+    let mut failures = Vec::new();
+    if let Some(limit) = args.fail_over_tokens
+        && tokens > limit
+    {
+        failures.push(GuardFailure::new(
+            Guard::OverLimit,
+            format!(
+                "Output is ~{} tokens, over the {} token limit",
+                tokens, limit
+            ),
+        ));
+    }
+    if let Some(limit) = args.fail_over_bytes
+        && bytes > limit
+    {
+        failures.push(GuardFailure::new(
+            Guard::OverLimit,
+            format!("Output is {} bytes, over the {} byte limit", bytes, limit),
+        ));
+    }
+    fail_on_first(failures)
+}
+
+/// Print every tripped guard, then fail with the first
+fn fail_on_first(failures: Vec<GuardFailure>) -> Result<()> {
+    for failure in &failures {
+        println!("❌ {}", failure);
+    }
+    match failures.into_iter().next() {
+        Some(failure) => Err(failure.into()),
+        None => Ok(()),
+    }
+}
+
+/// Copy the prompt instructions on their own, first letting the user paste the snapshot
+/// when it is what the clipboard currently holds
+fn copy_prompt(
+    clipboard: &dyn ClipboardProvider,
+    prompt: &str,
+    snapshot_copied: bool,
+) -> Result<()> {
+    if snapshot_copied {
+        wait_for_enter(
+            "📋 Snapshot copied. Paste it, then press Enter to copy the patch prompt...",
+        )?;
+    }
+
+    clipboard.copy(prompt)?;
+    println!("📋 Patch prompt copied to clipboard");
+    Ok(())
+}
+
+/// Print `message` and wait for the user to press Enter
+fn wait_for_enter(message: &str) -> Result<()> {
+    println!("{}", message);
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .context("Failed to wait for Enter")?;
+    Ok(())
+}
+
+/// Render the snapshot as parts of at most `limit` tokens, each with the whole structure tree
+async fn render_parts(
+    vfs: &impl Vfs,
+    snapshot: &Snapshot,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let paths = CollectedFile::paths(files);
+    // Every part repeats the structure tree and notes, so they come out of each part's budget
+    let fixed =
+        count_tokens(&generate_directory_structure(&paths).join("\n")) + PART_OVERHEAD_TOKENS;
+    let budget = limit.saturating_sub(fixed);
+    if budget == 0 {
+        return Err(anyhow::anyhow!(
+            "--split-tokens {} leaves no room for files next to the ~{}-token project structure",
+            limit,
+            fixed
+        ));
+    }
+
+    // Unreadable files cost nothing, but still go in a part so they're reported
+    let contents: Vec<(PathBuf, String)> = paths
+        .iter()
+        .map(|path| {
+            let content = snapshot
+                .files
+                .get(path)
+                .map(|entry| entry.content.clone())
+                .unwrap_or_default();
+            (path.clone(), content)
+        })
+        .collect();
+    let mut groups = split_into_parts(&contents, budget, options.format);
+
+    // The estimate can fall short, as for the sections closing the last part, so a part over
+    // the limit once rendered is split again while it holds more than one file
+    let parts = loop {
+        let parts = render_groups(vfs, files, options, &groups).await?;
+        let over = parts
+            .iter()
+            .zip(&groups)
+            .position(|(part, group)| group.len() > 1 && count_tokens(part) > limit);
+        match over {
+            Some(i) => {
+                let half = groups[i].len() / 2;
+                let rest = groups[i].split_off(half);
+                groups.insert(i + 1, rest);
+            }
+            None => break parts,
+        }
+    };
+
+    let mut oversized = 0;
+    for (i, (part, group)) in parts.iter().zip(&groups).enumerate() {
+        let tokens = count_tokens(part);
+        if tokens > limit {
+            oversized += 1;
+            println!(
+                "⚠️  Part {} is ~{} tokens, over the {} token limit: {} alone doesn't fit",
+                i + 1,
+                tokens,
+                limit,
+                group[0].display()
+            );
+        }
+    }
+    if oversized == 0 {
+        println!(
+            "✂️  Split into {} parts of up to {} tokens",
+            parts.len(),
+            limit
+        );
+    } else {
+        println!(
+            "✂️  Split into {} parts, {} of them over {} tokens",
+            parts.len(),
+            oversized,
+            limit
+        );
+    }
+    Ok(parts)
+}
+
+/// Render one part per group of files
+async fn render_groups(
+    vfs: &impl Vfs,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+    groups: &[Vec<PathBuf>],
+) -> Result<Vec<String>> {
+    let total = groups.len();
+    let mut parts = Vec::with_capacity(total);
+    for (i, group) in groups.iter().enumerate() {
+        let options = RenderOptions {
+            output_file: options
+                .output_file
+                .as_deref()
+                .map(|output| part_path(Path::new(output), i + 1).display().to_string()),
+            part: Some(Part {
+                index: i + 1,
+                total,
+                files: group.iter().cloned().collect(),
+            }),
+            ..options.clone()
+        };
+        parts.push(concatenate_files_with(vfs, files, &options).await?);
+    }
+    Ok(parts)
+}
+
+/// Hand the rendered output to the user's post-cat command, warning rather than failing
+/// since the snapshot itself was produced fine
+async fn run_post_cat_hook(command: &str, result: &str, output: Option<&str>) {
+    let mut cmd = shell_command(command);
+    if let Some(output) = output {
+        cmd.env("CATNIP_OUTPUT", output);
+    }
+
+    match run_shell(cmd, command, result.as_bytes(), false).await {
+        Ok(_) => info!("Ran post-cat hook: {}", command),
+        Err(e) => warn!("Post-cat hook failed: {:#}", e),
+    }
+}
+
+/// Dump the structure tree of the collected files as JSON, annotated like the rendered tree
+async fn write_structure_json(
+    vfs: &impl Vfs,
+    snapshot: &Snapshot,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+    path: &str,
+) -> Result<()> {
+    let mut tree_files = CollectedFile::paths(files);
+    let mut annotations = BTreeMap::new();
+    if let Some(delta) = &options.delta {
+        for (file, status) in delta {
+            if *status == FileStatus::Removed {
+                tree_files.push(file.clone());
+            }
+            annotations.insert(file.clone(), status.label().to_string());
+        }
+    }
+    for file in options.not_materialized.files() {
+        annotations.insert(file.clone(), NOT_MATERIALIZED.to_string());
+        tree_files.push(file);
+    }
+    for asset in options.binary_assets.assets() {
+        annotations.insert(asset.path.clone(), BINARY_ANNOTATION.to_string());
+        tree_files.push(asset.path);
+    }
+
+    let mut metadata = BTreeMap::new();
+    for file in files {
+        if let Some(entry) = snapshot.files.get(&file.path) {
+            metadata.insert(
+                file.path.clone(),
+                FileMetadata {
+                    size: file.size,
+                    lines: entry.content.lines().count(),
+                    modified: file.modified,
+                    sha256: file.sha256.clone(),
+                },
+            );
+        }
+    }
+
+    let tree = generate_structure_tree(&tree_files, &annotations, &metadata);
+    let json = serde_json::to_string_pretty(&tree).context("Failed to serialize structure")?;
+    let json = match &options.anonymization {
+        Some(anonymization) => anonymization.conceal(&json),
+        None => json,
+    };
+    vfs.write(Path::new(path), json.as_bytes()).await
+}
+
+fn plan_for_model(
+    snapshot: &Snapshot,
+    files: &[PathBuf],
+    model: &str,
+    prompt: Option<&str>,
+    collect_options: &CollectOptions,
+) -> Result<(PackingPlan, Option<String>)> {
+    let settings = Settings::load()?;
+    let window = context_window_for(&model_table(&settings.models), model)?;
+    let shares: Vec<BudgetShare> = settings
+        .budget
+        .iter()
+        .map(|(dir, percent)| BudgetShare {
+            dir: dir.clone(),
+            percent: percent.0,
+        })
+        .collect();
+    let allocated: f64 = shares.iter().map(|share| share.percent).sum();
+    if allocated > 100.0 {
+        warn!(
+            "Budget shares add up to {}%, scaling them down to 100%",
+            allocated
+        );
+    }
+
+    // The structure tree is always sent, so it comes out of the budget first
+    let fixed = count_tokens(&generate_directory_structure(files).join("\n"));
+    let budget = window.saturating_sub(fixed);
+
+    let contents: Vec<(PathBuf, String)> = snapshot
+        .files
+        .iter()
+        .map(|(path, entry)| (path.clone(), entry.content.clone()))
+        .collect();
+    let priority = |path: &Path| collect_options.priority(path);
+    Ok(match prompt {
+        Some(prompt) => {
+            let (plan, prompt) =
+                plan_packing_with_prompt(&contents, budget, priority, &shares, prompt);
+            (plan, Some(prompt))
+        }
+        None => (
+            plan_packing_shared(&contents, budget, priority, &shares),
+            None,
+        ),
+    })
+}
+
+fn print_packing_report(plan: &PackingPlan, model: &str) {
+    println!(
+        "\n📦 Packing for {}: ~{} of {} tokens available for file contents",
+        model, plan.total_tokens, plan.budget
+    );
+    for (path, representation) in &plan.decisions {
+        if *representation != Representation::Full {
+            println!(
+                "   {:<9} {} (~{} tokens)",
+                representation.label(),
+                path.display(),
+                plan.tokens[path]
+            );
+        }
+    }
+    println!(
+        "   {} full, {} minified, {} outlined, {} summarized, {} omitted",
+        plan.count(Representation::Full),
+        plan.count(Representation::Minified),
+        plan.count(Representation::Outline),
+        plan.count(Representation::Summary),
+        plan.count(Representation::Omitted)
+    );
+    if !plan.fits() {
+        warn!("Output still exceeds the {} token budget", plan.budget);
+    }
+}
+
+/// Tokens of each file's content, largest first
+fn print_token_breakdown(snapshot: &Snapshot, current_dir: &Path) {
+    for (path, tokens) in snapshot.token_counts() {
+        println!(
+            "   {:>8}  {}",
+            tokens,
+            relative_to(&path, current_dir).display()
+        );
+    }
+}
+
+fn print_cost_estimate(tokens: usize, models: &[String]) -> Result<()> {
+    let table = model_table(&Settings::load()?.models);
+
+    println!("\n💰 Estimated input cost for {} tokens:", tokens);
+    for name in models {
+        let spec = resolve_model(&table, name.trim())?;
+        println!(
+            "   {:<20} ${:.4} ({:.1}% of {}-token window)",
+            name.trim(),
+            spec.input_cost(tokens),
+            tokens as f64 * 100.0 / spec.context_window as f64,
+            spec.context_window
+        );
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Interactively narrow the collected files, when there is a terminal to ask on
+async fn refine(vfs: &impl Vfs, collected: &mut Vec<CollectedFile>) -> Result<()> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        warn!("Ignoring --refine: stdin is not a terminal");
+        return Ok(());
+    }
+
+    let patterns = refine_files(vfs, collected, &mut stdin.lock()).await?;
+    if !patterns.is_empty() {
+        info!("Refined with excludes: {}", patterns.join(", "));
+    }
+    Ok(())
+}
+
+/// Keep the `top` files most relevant to `query`, plus pinned files, in collection order
+async fn select_relevant(
+    vfs: &impl Vfs,
+    collected: &mut Vec<CollectedFile>,
+    query: &str,
+    top: usize,
+    collect_options: &CollectOptions,
+) -> Result<()> {
+    let mut contents = Vec::new();
+    for file in collected.iter() {
+        // Unreadable files can't be ranked, and are reported when rendering if kept
+        let content = vfs.read_text(&file.path).await.unwrap_or_default();
+        contents.push((file.path.clone(), content));
+    }
+
+    let ranked = match rank_with_index(&contents, query)? {
+        Some(ranked) => ranked,
+        None => rank_by_relevance(&contents, query),
+    };
+    if ranked.is_empty() {
+        return Err(anyhow::anyhow!("No files match the query: {}", query));
+    }
+
+    println!("🔎 Most relevant to \"{}\":", query);
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    for (path, score) in ranked.iter().take(top) {
+        println!(
+            "  {:>6.2}  {}",
+            score,
+            relative_to(path, &current_dir).display()
+        );
+    }
+
+    let keep: HashSet<&PathBuf> = ranked.iter().take(top).map(|(path, _)| path).collect();
+    collected.retain(|file| keep.contains(&file.path) || collect_options.is_pinned(&file.path));
+    info!("Kept {} of {} ranked files", keep.len(), ranked.len());
+    Ok(())
+}
+
+/// Rank `contents` with the search index when `catnip index` has built one, first indexing
+/// whatever changed since
+fn rank_with_index(
+    contents: &[(PathBuf, String)],
+    query: &str,
+) -> Result<Option<Vec<(PathBuf, f64)>>> {
+    let path = index_path(&paths::index_dir()?);
+    if !SearchIndex::exists(&path) {
+        return Ok(None);
+    }
+
+    let index = SearchIndex::open_or_create(&path)?;
+    let keyed: Vec<(String, String)> = contents
+        .iter()
+        .map(|(path, content)| (index_key(path), content.clone()))
+        .collect();
+    index.update(&keyed, false)?;
+
+    let files: HashMap<String, &PathBuf> = contents
+        .iter()
+        .map(|(path, _)| (index_key(path), path))
+        .collect();
+    let ranked = index
+        .search(query, index.file_count()?.max(1))?
+        .into_iter()
+        .filter_map(|(key, score)| Some(((*files.get(&key)?).clone(), score as f64)))
+        .collect();
+    info!("Ranked with the search index at {}", path.display());
+    Ok(Some(ranked))
+}
+
+/// Leave out files whose license `filter` excludes, saying which and why; returns how
+/// many were left out
+fn guard_licenses(
+    collected: &mut Vec<CollectedFile>,
+    licenses: &BTreeMap<PathBuf, FileLicense>,
+    filter: &LicenseFilter,
+    warnings: &Warnings,
+) -> usize {
+    let excluded: Vec<(PathBuf, &FileLicense)> = collected
+        .iter()
+        .filter_map(|file| {
+            licenses
+                .get(&file.path)
+                .filter(|license| filter.excludes(&license.expression))
+                .map(|license| (file.path.clone(), license))
+        })
+        .collect();
+    if excluded.is_empty() {
+        return 0;
+    }
+
+    println!(
+        "⚖️  Left out {} files under excluded licenses:",
+        excluded.len()
+    );
+    collected.retain(|file| !excluded.iter().any(|(path, _)| *path == file.path));
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    for (path, license) in &excluded {
+        println!("  {} is {}", path.display(), license.expression);
+        warnings.push(
+            WarningKind::Licensed,
+            &relative_to(path, &current_dir),
+            format!("licensed {}", license.expression),
+        );
+    }
+    excluded.len()
+}
+
+/// Leave out files that look like secrets unless explicitly allowed, saying which and why;
+/// returns how many were found
+fn guard_sensitive(
+    collected: &mut Vec<CollectedFile>,
+    allow_sensitive: bool,
+    warnings: &Warnings,
+) -> usize {
+    let sensitive = find_sensitive(collected);
+    if sensitive.is_empty() {
+        return 0;
+    }
+
+    if allow_sensitive {
+        println!("⚠️  Including {} likely-sensitive files:", sensitive.len());
+    } else {
+        println!(
+            "🔒 Left out {} likely-sensitive files; use --allow-sensitive to include them:",
+            sensitive.len()
+        );
+        collected.retain(|file| !sensitive.iter().any(|s| s.path == file.path));
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        for file in &sensitive {
+            warnings.push(
+                WarningKind::Redacted,
+                &relative_to(&file.path, &current_dir),
+                format!("matches {}", file.matched),
+            );
+        }
+    }
+    for file in &sensitive {
+        println!("  {} matches {}", file.path.display(), file.matched);
+    }
+    sensitive.len()
+}
+
+/// Framework of the first input directory, or of the current one when the first input is a file
+async fn detect_input_framework(vfs: &impl Vfs, input_paths: &[PathBuf]) -> Result<Framework> {
+    let dir = match input_paths.first() {
+        Some(path)
+            if vfs
+                .metadata(path)
+                .await
+                .is_ok_and(|metadata| metadata.is_dir) =>
+        {
+            path
+        }
+        _ => Path::new(""),
+    };
+    detect_framework(vfs, dir).await.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No framework recognized in {}; name one with --framework <nextjs|vite|django|rails>",
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+            .display()
+        )
+    })
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use tracing::info;
+
+use crate::cli::args::CollectArgs;
+use crate::config::paths;
+use crate::core::file_collector::{CollectedFile, collect_files_with};
+use crate::core::patch_journal::{PatchJournal, journal_path};
+use crate::core::snapshot::{FileChange, Snapshot, profile_key, snapshot_path};
+use crate::io::vfs::Vfs;
+
+pub async fn execute(vfs: &impl Vfs, args: CollectArgs) -> Result<()> {
+    let input_paths = args.input_paths()?;
+    if input_paths.is_empty() {
+        return Err(anyhow::anyhow!("No paths provided"));
+    }
+
+    let collect_options = args.collect_options(Vec::new())?;
+    let profile = profile_key(&input_paths, &collect_options);
+    let path = snapshot_path(&paths::history_dir()?, &profile);
+
+    let Some(previous) = Snapshot::load(vfs, &path).await? else {
+        println!("No previous snapshot for these paths and filters; run `catnip cat` first");
+        return Ok(());
+    };
+
+    let journal = PatchJournal::load(vfs, &journal_path(&paths::history_dir()?)).await?;
+    let drifted = journal.drifted(vfs).await;
+    if !drifted.is_empty() {
+        println!("⚠️  Files changed outside catnip after being patched:");
+        for path in &drifted {
+            println!("  {}", path.display());
+        }
+        println!(
+            "Later patches from the same conversation may no longer apply; run `catnip cat` to regenerate the snapshot\n"
+        );
+    }
+
+    let files =
+        CollectedFile::paths(&collect_files_with(vfs, &input_paths, &collect_options).await?);
+    let current = Snapshot::capture(vfs, &files).await;
+    let changes = current.changes_since(&previous);
+
+    if changes.is_empty() {
+        println!("No changes since the last snapshot");
+        return Ok(());
+    }
+
+    println!("Changes since the last snapshot:");
+    for change in &changes {
+        println!("  {}", change);
+    }
+
+    let count = |f: fn(&FileChange) -> bool| changes.iter().filter(|c| f(c)).count();
+    info!(
+        "{} added, {} modified, {} removed",
+        count(|c| matches!(c, FileChange::Added { .. })),
+        count(|c| matches!(c, FileChange::Modified { .. })),
+        count(|c| matches!(c, FileChange::Removed { .. }))
+    );
+
+    Ok(())
+}
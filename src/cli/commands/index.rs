@@ -0,0 +1,41 @@
+use anyhow::Result;
+use tracing::{debug, info};
+
+use crate::cli::args::CollectArgs;
+use crate::config::paths;
+use crate::core::file_collector::collect_files_with;
+use crate::core::search_index::{SearchIndex, index_key, index_path};
+use crate::io::vfs::Vfs;
+
+pub async fn execute(vfs: &impl Vfs, args: CollectArgs) -> Result<()> {
+    let input_paths = args.input_paths()?;
+    if input_paths.is_empty() {
+        return Err(anyhow::anyhow!("No paths provided"));
+    }
+
+    let collect_options = args.collect_options(Vec::new())?;
+    let files = collect_files_with(vfs, &input_paths, &collect_options).await?;
+
+    let mut contents = Vec::new();
+    for file in &files {
+        match vfs.read_text(&file.path).await {
+            Ok(content) => contents.push((index_key(&file.path), content)),
+            Err(e) => debug!("Not indexing {}: {}", file.path.display(), e),
+        }
+    }
+
+    let path = index_path(&paths::index_dir()?);
+    let index = SearchIndex::open_or_create(&path)?;
+    let update = index.update(&contents, true)?;
+
+    println!(
+        "📇 Indexed {} files: {} added, {} updated, {} removed, {} unchanged",
+        contents.len(),
+        update.added,
+        update.updated,
+        update.removed,
+        update.unchanged
+    );
+    info!("Index written to {}", path.display());
+    Ok(())
+}
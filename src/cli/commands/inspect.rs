@@ -0,0 +1,27 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::io::encryption::decrypt_text;
+use crate::io::vfs::Vfs;
+
+pub async fn execute(
+    vfs: &impl Vfs,
+    file: String,
+    identity: Option<PathBuf>,
+    output: Option<String>,
+) -> Result<()> {
+    let content = vfs
+        .read(Path::new(&file))
+        .await
+        .with_context(|| format!("Failed to read snapshot: {}", file))?;
+    let snapshot = decrypt_text(content, identity.as_deref(), &format!("snapshot {}", file))?;
+
+    match output {
+        Some(output) => {
+            vfs.write(Path::new(&output), snapshot.as_bytes()).await?;
+            println!("💾 Snapshot written to: {}", output);
+        }
+        None => print!("{}", snapshot),
+    }
+    Ok(())
+}
@@ -1,2 +1,10 @@
+pub mod bench;
 pub mod cat;
+pub mod changed;
+pub mod index;
+pub mod inspect;
 pub mod patch;
+pub mod schema;
+pub mod share;
+pub mod stats;
+pub mod undo;
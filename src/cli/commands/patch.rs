@@ -1,33 +1,248 @@
 use anyhow::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 use tracing::{debug, error, info, warn};
 
-use crate::io::clipboard::read_from_clipboard;
+use crate::config::settings::{LintSeverity, PatchLint, PatchSafety, Settings};
+use crate::core::anonymizer::{AnonymizeMap, anonymize_map_path};
+use crate::core::approval::Approver;
+use crate::core::changelog::{ChangedFile, append_changelog, format_entry};
+use crate::core::fuzzy_match::find_fuzzy;
+use crate::core::patch_journal::{UndoJournal, record_patched_files, record_undo};
+use crate::core::patch_script::PatchScript;
+use crate::core::snapshot::{Snapshot, load_latest_snapshot};
+use crate::core::unified_diff::{
+    DiffFile, format_diff, looks_like_unified_diff, parse_unified_diff,
+};
+use crate::integrations::git::{GitAttributes, Worktree, normalize_eol};
+use crate::io::clipboard::ClipboardProvider;
+use crate::io::encryption::decrypt_text;
+use crate::io::lock::lock_project_state;
+use crate::io::remote::{MAX_PATCH_BYTES, fetch_text, is_url};
+use crate::io::shell::run_with_stdin;
+use crate::io::vfs::{RootedFs, Vfs};
+use crate::utils::path_encoding::{decode_path, relative_to};
+use crate::utils::text_processing::{count_line_changes, find_json_object};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Code updates for `catnip patch` to apply
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct UpdateRequest {
+    /// Brief description of the changes
     pub analysis: String,
     pub files: Vec<FileUpdate>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Updates to one file
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct FileUpdate {
+    /// Path relative to the project root, as shown in the snapshot headings
     pub path: String,
+    /// What to do with the file; inferred from the updates when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<FileAction>,
+    /// Where a renamed file moves to, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    #[serde(default)]
     pub updates: Vec<CodeUpdate>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// What a patch does with a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    /// Apply the updates to an existing file
+    Modify,
+    /// Create a file that doesn't exist yet from the updates
+    Create,
+    /// Remove an existing file; takes no updates
+    Delete,
+    /// Move an existing file to `new_path`, applying any updates to its content
+    Rename,
+}
+
+/// One replacement within a file
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct CodeUpdate {
+    /// Exact code to replace; empty to create the file with `new_content`
     pub old_content: String,
+    /// Replacement code
     pub new_content: String,
     #[serde(default)]
     pub description: Option<String>,
+    /// Line where `old_content` starts, counting from 1 in the file as the earlier updates
+    /// leave it: picks the nearest of several matches, and places an empty `old_content`
+    /// before that line instead of at the end
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
 }
 
-pub async fn execute(json_file: Option<String>, dry_run: bool, backup: bool) -> Result<()> {
-    // Read JSON from file, stdin, or clipboard
+impl UpdateRequest {
+    /// Parse a patch, either as JSON or as a unified diff
+    pub fn parse(content: &str) -> Result<Self> {
+        if !looks_like_unified_diff(content) {
+            return serde_json::from_str(content).context("Failed to parse JSON content");
+        }
+
+        let files = parse_unified_diff(content).context("Failed to parse unified diff")?;
+        Ok(Self::from_diff(files))
+    }
+
+    /// Parse a patch a model may have wrapped in ```json fences or explained in prose,
+    /// falling back to the first JSON object in `content` that is a patch
+    pub fn parse_lenient(content: &str) -> Result<Self> {
+        let strict = Self::parse(content);
+        if strict.is_ok() || looks_like_unified_diff(content) {
+            return strict;
+        }
+
+        match find_json_object(content) {
+            Some(request) => {
+                info!("Extracted the patch JSON from the text around it");
+                Ok(request)
+            }
+            None => strict,
+        }
+    }
+
+    /// The updates of a unified diff: each hunk replaces the lines it expects at the line its
+    /// header gives, a created file gets all of its lines at once, and a deleted file is removed
+    pub fn from_diff(files: Vec<DiffFile>) -> Self {
+        let analysis = format!("Unified diff touching {} file(s)", files.len());
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let action = if file.deleted {
+                    Some(FileAction::Delete)
+                } else if file.created {
+                    Some(FileAction::Create)
+                } else {
+                    None
+                };
+                let updates = if file.deleted {
+                    Vec::new()
+                } else if file.created {
+                    vec![CodeUpdate {
+                        old_content: String::new(),
+                        new_content: file.hunks.into_iter().map(|hunk| hunk.new).collect(),
+                        description: None,
+                        line: None,
+                    }]
+                } else {
+                    file.hunks
+                        .into_iter()
+                        .map(|hunk| CodeUpdate {
+                            old_content: hunk.old,
+                            new_content: hunk.new,
+                            description: None,
+                            line: hunk.line,
+                        })
+                        .collect()
+                };
+                FileUpdate {
+                    path: file.path,
+                    action,
+                    new_path: None,
+                    updates,
+                }
+            })
+            .collect();
+
+        Self { analysis, files }
+    }
+
+    /// Turn the placeholders of `map` back into their original values in every path,
+    /// content and description, returning how many placeholders were restored
+    pub fn restore_placeholders(&mut self, map: &AnonymizeMap) -> usize {
+        let mut restored = 0;
+        let mut restore = |text: &mut String| {
+            let (original, count) = map.restore(text);
+            if count > 0 {
+                *text = original;
+                restored += count;
+            }
+        };
+
+        restore(&mut self.analysis);
+        for file in &mut self.files {
+            restore(&mut file.path);
+            if let Some(new_path) = &mut file.new_path {
+                restore(new_path);
+            }
+            for update in &mut file.updates {
+                restore(&mut update.old_content);
+                restore(&mut update.new_content);
+                if let Some(description) = &mut update.description {
+                    restore(description);
+                }
+            }
+        }
+        restored
+    }
+}
+
+/// JSON Schema of the patch format, for validating model output in other tools
+pub fn patch_schema() -> serde_json::Value {
+    schemars::schema_for!(UpdateRequest).to_value()
+}
+
+/// How `patch` applies an update request
+#[derive(Clone, Debug, Default)]
+pub struct PatchOptions {
+    /// Show what would change without writing anything
+    pub dry_run: bool,
+    pub backup: bool,
+    /// Apply clipboard and remote patches even past the safety thresholds
+    pub force: bool,
+    /// With `dry_run`, write a shell script making the same changes
+    pub emit_script: Option<String>,
+    /// Append a summary of the applied patch to this file
+    pub changelog: Option<String>,
+    /// Severity for every lint rule, overriding `[patch.lint]`
+    pub lint: Option<LintSeverity>,
+    pub on_error: OnError,
+    /// Print a `file=... update=n/total status=...` line for each update
+    pub porcelain: bool,
+    /// Apply in a temporary worktree at this ref and commit the result to a new branch
+    pub onto: Option<String>,
+    /// Minimum confidence, from 0 to 100, for an `old_content` that only matches with
+    /// whitespace ignored; `None` to only accept exact matches
+    pub fuzzy: Option<f64>,
+    /// Show each update as a diff and ask before applying it
+    pub interactive: bool,
+    /// age key file to decrypt an encrypted patch file with
+    pub identity: Option<PathBuf>,
+    /// Restore the placeholders of `cat --anonymize` from this map, or from the default
+    /// one when `Some(None)`; when `None`, from the default one if it exists
+    pub deanonymize: Option<Option<PathBuf>>,
+    /// Apply placeholders as written, even when an anonymization map exists
+    pub keep_placeholders: bool,
+}
+
+/// What `patch` does when one of the files can't be updated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// Update the files that can be, then report the ones that couldn't
+    #[default]
+    Continue,
+    /// Change nothing unless every file can be updated
+    FailFast,
+    /// As `FailFast`, and when writing a file fails, put back the files already written
+    Atomic,
+}
+
+pub async fn execute(
+    clipboard: &dyn ClipboardProvider,
+    vfs: &impl Vfs,
+    json_file: Option<String>,
+    options: &PatchOptions,
+) -> Result<()> {
+    // Read the patch from a URL, file, stdin, or clipboard
     let json_content = match json_file.as_deref() {
         Some("-") => {
             use std::io::{self, BufRead};
@@ -35,32 +250,322 @@ pub async fn execute(json_file: Option<String>, dry_run: bool, backup: bool) ->
             let lines: Result<Vec<_>, _> = stdin.lock().lines().collect();
             lines.context("Failed to read from stdin")?.join("\n")
         }
-        Some(file_path) => fs::read_to_string(file_path)
-            .with_context(|| format!("Failed to read JSON file: {}", file_path))?,
-        None => read_from_clipboard()
-            .await
-            .context("Failed to read from clipboard")?,
+        Some(url) if is_url(url) => fetch_text(url, MAX_PATCH_BYTES).await?,
+        Some(file_path) => decrypt_text(
+            fs::read(file_path)
+                .with_context(|| format!("Failed to read patch file: {}", file_path))?,
+            options.identity.as_deref(),
+            &format!("patch file {}", file_path),
+        )?,
+        None => clipboard.read().context("Failed to read from clipboard")?,
     };
 
-    let update_request: UpdateRequest =
-        serde_json::from_str(&json_content).context("Failed to parse JSON content")?;
+    if options.interactive && json_file.as_deref() == Some("-") {
+        return Err(anyhow::anyhow!(
+            "--interactive reads answers from stdin, so the patch can't come from there too"
+        ));
+    }
+
+    // Pasted answers often come with fences or prose despite the prompt asking for JSON only
+    let mut update_request = match json_file.as_deref() {
+        None | Some("-") => UpdateRequest::parse_lenient(&json_content)?,
+        Some(_) => UpdateRequest::parse(&json_content)?,
+    };
+    deanonymize(vfs, &mut update_request, options).await?;
 
     info!("Analysis: {}", update_request.analysis);
     info!("Processing {} files", update_request.files.len());
 
+    let settings = Settings::load()?;
+
+    // Clipboard and remote content are the most likely to be a hallucinated mega-patch
+    let unvetted = match json_file.as_deref() {
+        None => Some("clipboard"),
+        Some(source) if is_url(source) => Some("remote"),
+        Some(_) => None,
+    };
+
+    // The snapshot history belongs to this checkout, even when patching a worktree
+    let snapshot =
+        if options.lint.unwrap_or(settings.patch.lint.not_in_snapshot) == LintSeverity::Off {
+            None
+        } else {
+            load_latest_snapshot(vfs).await
+        };
+    let snapshot = snapshot.as_ref();
+
+    let Some(reference) = &options.onto else {
+        return apply_request(vfs, &update_request, &settings, snapshot, unvetted, options).await;
+    };
+
+    let worktree = Worktree::add(reference).await?;
+    println!(
+        "🌿 Applying onto {} in {}",
+        reference,
+        worktree.path.display()
+    );
+    // Patch paths are relative to the current directory, which sits as deep in the worktree
+    let worktree_fs = RootedFs::new(vfs, worktree.path.join(&worktree.prefix));
+    let committed = async {
+        apply_request(
+            &worktree_fs,
+            &update_request,
+            &settings,
+            snapshot,
+            unvetted,
+            options,
+        )
+        .await?;
+        worktree.commit(&update_request.analysis).await?;
+        worktree.diff_stat().await
+    }
+    .await;
+
+    match committed {
+        Ok(stat) => {
+            print!("{}", stat);
+            println!(
+                "🌿 Committed to branch {} (from {})",
+                worktree.branch, reference
+            );
+            worktree.remove(false).await
+        }
+        Err(e) => {
+            if let Err(cleanup) = worktree.remove(true).await {
+                warn!("Failed to remove worktree: {}", cleanup);
+            }
+            Err(e.context(format!("Nothing was committed onto {}", reference)))
+        }
+    }
+}
+
+/// Turn the placeholders `cat --anonymize` put in the snapshot back into the values they
+/// stand for, wherever the model repeated them in `request`. Without `--deanonymize`, the
+/// project's default map is used when there is one, so the round trip needs no flags.
+async fn deanonymize(
+    vfs: &impl Vfs,
+    request: &mut UpdateRequest,
+    options: &PatchOptions,
+) -> Result<()> {
+    if options.keep_placeholders {
+        return Ok(());
+    }
+
+    let required = options.deanonymize.is_some();
+    let map_path = match &options.deanonymize {
+        Some(Some(path)) => path.clone(),
+        _ => match crate::config::paths::history_dir() {
+            Ok(history_dir) => anonymize_map_path(&history_dir),
+            Err(e) if required => return Err(e),
+            Err(_) => return Ok(()),
+        },
+    };
+    if !vfs.exists(&map_path).await {
+        if !required {
+            return Ok(());
+        }
+        return Err(anyhow::anyhow!(
+            "No anonymization map at {}; run `catnip cat --anonymize` first",
+            map_path.display()
+        ));
+    }
+
+    let map = AnonymizeMap::load(vfs, &map_path).await?;
+    let restored = request.restore_placeholders(&map);
+    if restored > 0 {
+        println!(
+            "🕶️  Restored anonymized values in {} places (map: {})",
+            restored,
+            map_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Check `update_request` and apply it to the files of `vfs`; `snapshot` is the latest `cat`
+/// snapshot, for the not-in-snapshot lint
+async fn apply_request(
+    vfs: &impl Vfs,
+    update_request: &UpdateRequest,
+    settings: &Settings,
+    snapshot: Option<&Snapshot>,
+    unvetted: Option<&str>,
+    options: &PatchOptions,
+) -> Result<()> {
+    let dry_run = options.dry_run;
+    if let Some(kind) = unvetted
+        && !options.force
+    {
+        check_patch_safety(vfs, update_request, &settings.patch.safety)
+            .await
+            .with_context(|| {
+                format!("Refusing to apply {} patch (use --force to override)", kind)
+            })?;
+    }
+
+    report_lint(&lint_patch(
+        update_request,
+        snapshot,
+        &settings.patch.lint,
+        options.lint,
+    ))?;
+
+    if let Some(command) = &settings.hooks.pre_patch {
+        run_pre_patch_hook(command, update_request).await?;
+    }
+
     if dry_run {
         info!("DRY RUN MODE - No files will be modified");
     }
 
     let mut total_updates = 0;
     let mut successful_files = 0;
+    let mut patched_files = Vec::new();
+    let mut changed_files = Vec::new();
+    let mut undo = UndoJournal::new(&update_request.analysis);
+    let mut script = options
+        .emit_script
+        .is_some()
+        .then(|| PatchScript::new(&update_request.analysis));
+
+    let progress = Progress::new(options.porcelain);
+    let mut approver = options
+        .interactive
+        .then(|| Approver::new(std::io::BufReader::new(std::io::stdin())));
 
+    // Work out every file's new content before writing any, so a fail-fast patch that
+    // doesn't apply leaves the tree untouched
+    let attributes = GitAttributes::load(vfs, Path::new("")).await;
+    let mut staged = BTreeMap::new();
+    let mut prepared = Vec::new();
     for file_update in &update_request.files {
-        match process_file_update(file_update, dry_run, backup).await {
-            Ok(update_count) => {
-                total_updates += update_count;
+        match prepare_file_update(
+            vfs,
+            &staged,
+            &attributes,
+            file_update,
+            options.fuzzy,
+            approver.as_mut(),
+            progress,
+        )
+        .await
+        {
+            Ok(file) => {
+                // Later entries see a deleted or renamed file as gone
+                if let Some(from) = &file.renamed_from {
+                    staged.insert(from.clone(), None);
+                }
+                staged.insert(
+                    file.path.clone(),
+                    (!file.deleted).then(|| file.content.clone()),
+                );
+                prepared.push((file_update, Ok(file)));
+            }
+            Err(e) if options.on_error != OnError::Continue => {
+                let flag = match options.on_error {
+                    OnError::Atomic => "--atomic",
+                    _ => "--fail-fast",
+                };
+                return Err(e.context(format!(
+                    "No files were changed: {} could not be updated ({})",
+                    file_update.path, flag
+                )));
+            }
+            Err(e) => prepared.push((file_update, Err(e))),
+        }
+    }
+
+    // Held from the first write until the journals are recorded
+    let _lock = if dry_run {
+        None
+    } else {
+        lock_project_state().await?
+    };
+    for (file_update, prepared) in prepared {
+        if let Ok(file) = &prepared
+            && file.changes_nothing()
+        {
+            successful_files += 1;
+            info!("- {} - every update left out", file_update.path);
+            continue;
+        }
+
+        let result = match prepared {
+            Ok(file) => write_file_update(
+                vfs,
+                &file,
+                file_update,
+                dry_run,
+                options.backup,
+                script.as_mut(),
+            )
+            .await
+            .map(|()| file),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(file) => {
+                total_updates += file.updates;
                 successful_files += 1;
-                info!("✓ {} - {} updates applied", file_update.path, update_count);
+                if !dry_run {
+                    let total = file_update.updates.len();
+                    for &i in &file.applied {
+                        progress.report(&file_update.path, i, total, UpdateStatus::Applied);
+                    }
+                    changed_files.push(ChangedFile {
+                        path: file.path.clone(),
+                        updates: file.updates,
+                        created: file.created,
+                        deleted: file.deleted,
+                        renamed_from: file.renamed_from.clone(),
+                    });
+                    if !file.deleted {
+                        patched_files.push(file.path.clone());
+                    }
+                    if let Some(from) = &file.renamed_from {
+                        if file.on_disk.is_some() || undo.contains(from) {
+                            undo.record_removed(from, file.on_disk.as_deref());
+                        }
+                        undo.record(&file.path, None, &file.content);
+                    } else if file.on_disk.is_some() || undo.contains(&file.path) {
+                        if file.deleted {
+                            undo.record_removed(&file.path, file.on_disk.as_deref());
+                        } else {
+                            undo.record(&file.path, file.on_disk.as_deref(), &file.content);
+                        }
+                    } else if file.created {
+                        undo.record(&file.path, None, &file.content);
+                    }
+                }
+                if file.deleted {
+                    info!("✓ {} - deleted", file_update.path);
+                } else if let Some(new_path) = &file_update.new_path {
+                    info!(
+                        "✓ {} - renamed to {}, {} updates applied",
+                        file_update.path, new_path, file.updates
+                    );
+                } else {
+                    info!("✓ {} - {} updates applied", file_update.path, file.updates);
+                }
+            }
+            Err(e) if options.on_error == OnError::Atomic && !dry_run => {
+                // The undo journal holds the pre-image of every file written so far
+                if let Err(rollback) = undo.restore(vfs, false, true).await {
+                    error!(
+                        "Failed to put back the files already written: {:#}",
+                        rollback
+                    );
+                    return Err(e.context(format!(
+                        "{} could not be written, and some files are left patched",
+                        file_update.path
+                    )));
+                }
+                return Err(e.context(format!(
+                    "No files were changed: {} could not be written, so the {} files already written were put back (--atomic)",
+                    file_update.path,
+                    undo.files.len()
+                )));
             }
             Err(e) => {
                 error!("✗ {} - Error: {}", file_update.path, e);
@@ -68,6 +573,31 @@ pub async fn execute(json_file: Option<String>, dry_run: bool, backup: bool) ->
         }
     }
 
+    // The journal tracks the current checkout, not a worktree for another ref. A patch that
+    // only deletes files patches none, yet still replaces the last undo.
+    if !undo.files.is_empty() && options.onto.is_none() {
+        record_patched_files(vfs, &patched_files).await;
+        record_undo(vfs, &undo).await;
+    }
+
+    let changelog = options
+        .changelog
+        .as_ref()
+        .or(settings.patch.changelog.as_ref());
+    if let Some(changelog) = changelog
+        && !changed_files.is_empty()
+    {
+        let entry = format_entry(&update_request.analysis, SystemTime::now(), &changed_files);
+        append_changelog(vfs, Path::new(changelog), &entry).await?;
+        println!("📓 Changes logged to: {}", changelog);
+    }
+
+    if let (Some(script), Some(script_path)) = (&script, &options.emit_script) {
+        vfs.write(Path::new(script_path), script.render().as_bytes())
+            .await?;
+        println!("📜 Patch script written to: {}", script_path);
+    }
+
     info!(
         "Completed: {}/{} files processed successfully, {} total updates",
         successful_files,
@@ -76,141 +606,789 @@ pub async fn execute(json_file: Option<String>, dry_run: bool, backup: bool) ->
     );
 
     if successful_files != update_request.files.len() {
-        std::process::exit(1);
+        return Err(anyhow::anyhow!(
+            "{} of {} files could not be updated",
+            update_request.files.len() - successful_files,
+            update_request.files.len()
+        ));
     }
 
     Ok(())
 }
 
-async fn process_file_update(
-    file_update: &FileUpdate,
-    dry_run: bool,
-    create_backup: bool,
-) -> Result<usize> {
-    let file_path = PathBuf::from(&file_update.path);
-
-    debug!("Processing file: {}", file_path.display());
-
-    // Check if this is a file creation operation
-    let is_file_creation = file_update.updates.iter().all(|u| u.old_content.is_empty());
+/// A suspicious update spotted before applying a patch
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintFinding {
+    pub severity: LintSeverity,
+    pub path: String,
+    /// 1-based index of the update within its file, for findings about a single update
+    pub update: Option<usize>,
+    pub message: String,
+}
 
-    if is_file_creation {
-        if file_path.exists() {
-            return Err(anyhow::anyhow!(
-                "Cannot create file - already exists: {}",
-                file_path.display()
-            ));
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.update {
+            Some(update) => write!(f, "{} (update {}): {}", self.path, update, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
         }
+    }
+}
 
-        // Create parent directories if they don't exist
-        if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent).with_context(|| {
-                format!(
-                    "Failed to create parent directories for: {}",
-                    file_path.display()
-                )
-            })?;
+/// Flag updates that are probably mistakes: large blocks deleted with nothing in their
+/// place, edits to files the last snapshot didn't include, and changes to trailing
+/// whitespace only. `level` overrides the severity of every rule.
+pub fn lint_patch(
+    update_request: &UpdateRequest,
+    snapshot: Option<&Snapshot>,
+    lint: &PatchLint,
+    level: Option<LintSeverity>,
+) -> Vec<LintFinding> {
+    let severity = |rule: LintSeverity| level.unwrap_or(rule);
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let normalize = |path: &Path| -> PathBuf {
+        relative_to(path, &current_dir)
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect()
+    };
+    let snapshot_files: Option<HashSet<PathBuf>> =
+        snapshot.map(|snapshot| snapshot.files.keys().map(|path| normalize(path)).collect());
+
+    let mut findings = Vec::new();
+    let mut report = |severity: LintSeverity, path: &str, update, message: String| {
+        if severity != LintSeverity::Off {
+            findings.push(LintFinding {
+                severity,
+                path: path.to_string(),
+                update,
+                message,
+            });
         }
+    };
 
-        // For file creation, concatenate all new_content
-        let content: String = file_update
+    for file_update in &update_request.files {
+        let path = &file_update.path;
+        let edits_existing = file_update
             .updates
             .iter()
-            .map(|u| u.new_content.as_str())
-            .collect::<Vec<_>>()
-            .join("");
+            .any(|update| !update.old_content.is_empty());
+        let verb = match file_update.action {
+            Some(FileAction::Delete) => Some("deletes"),
+            Some(FileAction::Rename) => Some("renames"),
+            _ if edits_existing => Some("edits"),
+            _ => None,
+        };
+        if let Some(verb) = verb
+            && let Some(snapshot_files) = &snapshot_files
+            && let Ok(decoded) = decode_path(path)
+            && !snapshot_files.contains(&normalize(&decoded))
+        {
+            report(
+                severity(lint.not_in_snapshot),
+                path,
+                None,
+                format!("{} a file that wasn't in the last snapshot", verb),
+            );
+        }
 
-        if dry_run {
-            info!("DRY RUN: Would create new file: {}", file_path.display());
-            println!("\n--- New File: {} ---", file_path.display());
-            println!("{}", content);
-            return Ok(file_update.updates.len());
+        for (i, update) in file_update.updates.iter().enumerate() {
+            let deleted_lines = update.old_content.lines().count();
+            if !update.old_content.is_empty()
+                && update.new_content.trim().is_empty()
+                && deleted_lines >= lint.max_deleted_lines
+            {
+                report(
+                    severity(lint.large_deletion),
+                    path,
+                    Some(i + 1),
+                    format!(
+                        "deletes {} lines with nothing in their place",
+                        deleted_lines
+                    ),
+                );
+            }
+
+            if update.old_content != update.new_content
+                && update
+                    .old_content
+                    .lines()
+                    .map(str::trim_end)
+                    .eq(update.new_content.lines().map(str::trim_end))
+            {
+                report(
+                    severity(lint.whitespace_only),
+                    path,
+                    Some(i + 1),
+                    "only changes trailing whitespace".to_string(),
+                );
+            }
         }
+    }
 
-        fs::write(&file_path, &content)
-            .with_context(|| format!("Failed to create file: {}", file_path.display()))?;
+    findings
+}
 
-        info!("Created new file: {}", file_path.display());
-        return Ok(file_update.updates.len());
+/// Print lint findings, failing when any of them is an error
+fn report_lint(findings: &[LintFinding]) -> Result<()> {
+    if findings.is_empty() {
+        return Ok(());
     }
 
-    // Existing file update logic
-    if !file_path.exists() {
+    println!("🔍 Patch lint:");
+    for finding in findings {
+        let marker = match finding.severity {
+            LintSeverity::Error => "❌",
+            _ => "⚠️ ",
+        };
+        println!("  {} {}", marker, finding);
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == LintSeverity::Error)
+        .count();
+    if errors > 0 {
         return Err(anyhow::anyhow!(
-            "File does not exist: {}",
-            file_path.display()
+            "Patch failed lint with {} errors (use --lint warn to apply it anyway)",
+            errors
         ));
     }
+    Ok(())
+}
+
+/// Give the user's pre-patch command the parsed patch on stdin; a non-zero exit vetoes it
+pub async fn run_pre_patch_hook(command: &str, update_request: &UpdateRequest) -> Result<()> {
+    let json = serde_json::to_vec_pretty(update_request).context("Failed to serialize patch")?;
+    run_with_stdin(command, &json, false)
+        .await
+        .context("Patch vetoed by pre-patch hook")?;
+    info!("Pre-patch hook accepted the patch");
+    Ok(())
+}
+
+/// How far an update got
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateStatus {
+    /// Its `old_content` was found and the replacement worked out
+    Validated,
+    /// Written to disk along with the rest of its file
+    Applied,
+    /// Left out when asked with `--interactive`
+    Skipped,
+    Failed,
+}
+
+impl UpdateStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpdateStatus::Validated => "validated",
+            UpdateStatus::Applied => "applied",
+            UpdateStatus::Skipped => "skipped",
+            UpdateStatus::Failed => "failed",
+        }
+    }
+}
 
-    // Read current file content
-    let original_content = fs::read_to_string(&file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+/// `file=src/lib.rs update=2/5 status=applied`
+pub fn progress_line(path: &str, update: usize, total: usize, status: UpdateStatus) -> String {
+    format!(
+        "file={} update={}/{} status={}",
+        path,
+        update,
+        total,
+        status.label()
+    )
+}
 
-    let mut updated_content = original_content.clone();
-    let mut applied_updates = 0;
+/// Reports each update as it is validated and applied, as tracing events and optionally
+/// as `--porcelain` lines on stdout
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    porcelain: bool,
+}
 
-    // Apply updates in order
-    for (i, update) in file_update.updates.iter().enumerate() {
-        debug!(
-            "Applying update {}/{}: {}",
-            i + 1,
-            file_update.updates.len(),
-            update.description.as_deref().unwrap_or("no description")
-        );
+impl Progress {
+    pub fn new(porcelain: bool) -> Self {
+        Self { porcelain }
+    }
 
-        if !updated_content.contains(&update.old_content) {
+    fn report(&self, path: &str, update: usize, total: usize, status: UpdateStatus) {
+        if status == UpdateStatus::Validated {
+            debug!(
+                file = path,
+                update,
+                total,
+                status = status.label(),
+                "Update validated"
+            );
+        } else {
+            info!(
+                file = path,
+                update,
+                total,
+                status = status.label(),
+                "Update {}",
+                status.label()
+            );
+        }
+        if self.porcelain {
+            println!("{}", progress_line(path, update, total, status));
+        }
+    }
+}
+
+/// One file's new content, worked out before anything is written
+struct PreparedFile {
+    path: PathBuf,
+    /// Content on disk before the patch, for backups; `None` when the file is created or
+    /// an earlier entry of the same patch already changed it
+    on_disk: Option<String>,
+    content: String,
+    updates: usize,
+    /// 1-based indices of the updates applied, all of them unless some were declined
+    applied: Vec<usize>,
+    created: bool,
+    /// Removed rather than written
+    deleted: bool,
+    /// Where a renamed file is moved from; `path` is where it moves to
+    renamed_from: Option<PathBuf>,
+}
+
+impl PreparedFile {
+    /// Whether writing it would leave the tree as it is, as when every update was declined
+    fn changes_nothing(&self) -> bool {
+        self.applied.is_empty() && !self.deleted && self.renamed_from.is_none()
+    }
+}
+
+/// Apply one file's updates in memory, on top of what earlier entries of the same patch
+/// already staged for it
+async fn prepare_file_update(
+    vfs: &impl Vfs,
+    staged: &BTreeMap<PathBuf, Option<String>>,
+    attributes: &GitAttributes,
+    file_update: &FileUpdate,
+    fuzzy: Option<f64>,
+    mut approver: Option<&mut Approver<impl BufRead>>,
+    progress: Progress,
+) -> Result<PreparedFile> {
+    let file_path = decode_path(&file_update.path)?;
+
+    debug!("Processing file: {}", file_path.display());
+
+    let attributes = attributes.get(&file_path);
+    if attributes.binary == Some(true) {
+        return Err(anyhow::anyhow!(
+            "Refusing to patch a file marked binary in .gitattributes: {}",
+            file_path.display()
+        ));
+    }
+
+    // `None` when an earlier entry deleted or renamed the file
+    let earlier = staged.get(&file_path);
+    let exists = match earlier {
+        Some(earlier) => earlier.is_some(),
+        None => vfs.exists(&file_path).await,
+    };
+
+    let missing = || anyhow::anyhow!("File does not exist: {}", file_path.display());
+    let already_exists = || {
+        anyhow::anyhow!(
+            "Cannot create file - already exists: {}",
+            file_path.display()
+        )
+    };
+    if file_update.new_path.is_some() && file_update.action != Some(FileAction::Rename) {
+        return Err(anyhow::anyhow!(
+            "{}: new_path is only used with the rename action",
+            file_update.path
+        ));
+    }
+    let renamed_to = match file_update.action {
+        // A missing file is created from the updates with empty `old_content`, and later
+        // updates may edit what those added. In an existing file they append; a creation that
+        // must not be replayed onto an existing file says so with the create action.
+        None if !exists
+            && file_update
+                .updates
+                .first()
+                .is_some_and(|update| !update.old_content.is_empty()) =>
+        {
+            return Err(missing());
+        }
+        Some(FileAction::Create) if exists => return Err(already_exists()),
+        Some(FileAction::Modify | FileAction::Delete | FileAction::Rename) if !exists => {
+            return Err(missing());
+        }
+        Some(FileAction::Delete) if !file_update.updates.is_empty() => {
             return Err(anyhow::anyhow!(
-                "Old content not found in file. Expected content:\n{}",
-                update.old_content
+                "{}: a deleted file takes no updates",
+                file_update.path
             ));
         }
+        Some(FileAction::Rename) => {
+            let new_path = file_update
+                .new_path
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("{}: rename needs a new_path", file_update.path))?;
+            let new_path = decode_path(new_path)?;
+            let taken = match staged.get(&new_path) {
+                Some(earlier) => earlier.is_some(),
+                None => vfs.exists(&new_path).await,
+            };
+            if taken {
+                return Err(anyhow::anyhow!(
+                    "Cannot rename {} - {} already exists",
+                    file_path.display(),
+                    new_path.display()
+                ));
+            }
+            Some(new_path)
+        }
+        _ => None,
+    };
 
-        // Count occurrences to ensure we're not making ambiguous replacements
-        let occurrences = updated_content.matches(&update.old_content).count();
-        if occurrences > 1 {
-            warn!(
-                "Old content appears {} times in file, replacing all occurrences",
-                occurrences
-            );
+    let (original_content, on_disk) = match earlier {
+        Some(Some(earlier)) => (earlier.clone(), None),
+        None if exists => {
+            let content = vfs
+                .read_to_string(&file_path)
+                .await
+                .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+            (content.clone(), Some(content))
         }
+        _ => (String::new(), None),
+    };
 
-        // Replace the old content with new content
-        updated_content = updated_content.replace(&update.old_content, &update.new_content);
-        applied_updates += 1;
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let deleted = file_update.action == Some(FileAction::Delete);
+    if deleted || renamed_to.is_some() {
+        let (heading, diff) = match &renamed_to {
+            Some(new_path) => (
+                format!(
+                    "📦 Rename {} to {}",
+                    file_path.display(),
+                    new_path.display()
+                ),
+                String::new(),
+            ),
+            None => (
+                format!("🗑️  Delete {}", file_path.display()),
+                format_diff(&original_content, "", color),
+            ),
+        };
+        if let Some(approver) = approver.as_deref_mut()
+            && !approver.approve(&heading, &diff)?
+        {
+            // A declined deletion or move leaves the file, and its updates, out of the patch
+            return Ok(PreparedFile {
+                path: file_path,
+                on_disk: None,
+                content: original_content,
+                updates: 0,
+                applied: Vec::new(),
+                created: false,
+                deleted: false,
+                renamed_from: None,
+            });
+        }
+    }
+    if deleted {
+        return Ok(PreparedFile {
+            path: file_path,
+            on_disk,
+            content: String::new(),
+            updates: 0,
+            applied: Vec::new(),
+            created: false,
+            deleted: true,
+            renamed_from: None,
+        });
     }
 
+    // Updates are written with LF endings, so a declared line ending is matched and
+    // restored around them
+    let total = file_update.updates.len();
+    let mut content = match attributes.eol {
+        Some(_) => normalize_eol(&original_content),
+        None => original_content,
+    };
+    let mut applied = Vec::new();
+    for (i, update) in file_update.updates.iter().enumerate() {
+        if let Some(approver) = approver.as_deref_mut() {
+            let mut heading = format!("📝 {} (update {}/{})", file_update.path, i + 1, total);
+            if let Some(description) = &update.description {
+                heading.push_str(&format!(": {}", description));
+            }
+            if !approver.approve(
+                &heading,
+                &format_diff(&update.old_content, &update.new_content, color),
+            )? {
+                progress.report(&file_update.path, i + 1, total, UpdateStatus::Skipped);
+                // Everything after a declined creation edits the content it would have added
+                if !exists && i == 0 {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        match apply_update_fuzzy(&content, update, fuzzy) {
+            Ok((updated, confidence)) => {
+                if let Some(confidence) = confidence {
+                    warn!(
+                        "{} (update {}): old content matched with {:.0}% confidence, whitespace ignored",
+                        file_update.path,
+                        i + 1,
+                        confidence
+                    );
+                }
+                content = updated;
+                applied.push(i + 1);
+                progress.report(&file_update.path, i + 1, total, UpdateStatus::Validated);
+            }
+            Err(e) => {
+                progress.report(&file_update.path, i + 1, total, UpdateStatus::Failed);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(eol) = attributes.eol {
+        content = eol.apply(&content);
+    }
+
+    let (path, renamed_from) = match renamed_to {
+        Some(new_path) => (new_path, Some(file_path)),
+        None => (file_path, None),
+    };
+    Ok(PreparedFile {
+        path,
+        on_disk,
+        content,
+        updates: applied.len(),
+        applied,
+        created: !exists,
+        deleted: false,
+        renamed_from,
+    })
+}
+
+/// Write a prepared file, or in a dry run show what would be written
+async fn write_file_update(
+    vfs: &impl Vfs,
+    file: &PreparedFile,
+    file_update: &FileUpdate,
+    dry_run: bool,
+    create_backup: bool,
+    script: Option<&mut PatchScript>,
+) -> Result<()> {
+    let file_path = &file.path;
+
     if dry_run {
+        if let Some(script) = script {
+            if file.deleted {
+                script.delete(file_path);
+            } else if let Some(from) = &file.renamed_from {
+                script.rename(from, file_path, &file.content, file.updates);
+            } else if file.created {
+                script.create(file_path, &file.content);
+            } else {
+                script.update(file_path, &file.content, file.updates);
+            }
+        }
+
+        if file.deleted {
+            info!("DRY RUN: Would delete file: {}", file_path.display());
+            println!("\n--- Delete File: {} ---", file_path.display());
+            return Ok(());
+        }
+
+        if file.created {
+            info!("DRY RUN: Would create new file: {}", file_path.display());
+            println!("\n--- New File: {} ---", file_path.display());
+            println!("{}", file.content);
+            return Ok(());
+        }
+
         info!(
             "DRY RUN: Would apply {} updates to {}",
-            applied_updates,
+            file.updates,
             file_path.display()
         );
 
         // Show preview of changes
-        println!("\n--- File: {} ---", file_path.display());
+        match &file.renamed_from {
+            Some(from) => println!(
+                "\n--- Rename: {} -> {} ---",
+                from.display(),
+                file_path.display()
+            ),
+            None => println!("\n--- File: {} ---", file_path.display()),
+        }
         for (i, update) in file_update.updates.iter().enumerate() {
+            if !file.applied.contains(&(i + 1)) {
+                continue;
+            }
             println!("\n--- Update {} ---", i + 1);
             if let Some(desc) = &update.description {
                 println!("Description: {}", desc);
             }
-            println!("- OLD:\n{}", update.old_content);
-            println!("+ NEW:\n{}", update.new_content);
+            if update.old_content.is_empty() {
+                println!("+ APPEND:\n{}", update.new_content);
+            } else {
+                println!("- OLD:\n{}", update.old_content);
+                println!("+ NEW:\n{}", update.new_content);
+            }
         }
 
-        return Ok(applied_updates);
+        return Ok(());
+    }
+
+    if file.created {
+        // Create parent directories if they don't exist
+        if let Some(parent) = file_path.parent() {
+            vfs.create_dir_all(parent).await.with_context(|| {
+                format!(
+                    "Failed to create parent directories for: {}",
+                    file_path.display()
+                )
+            })?;
+        }
+
+        vfs.write(file_path, file.content.as_bytes())
+            .await
+            .with_context(|| format!("Failed to create file: {}", file_path.display()))?;
+
+        info!("Created new file: {}", file_path.display());
+        return Ok(());
     }
 
     // Create backup if requested
-    if create_backup {
-        let backup_path = format!("{}.backup", file_path.display());
-        fs::copy(&file_path, &backup_path)
-            .with_context(|| format!("Failed to create backup: {}", backup_path))?;
-        debug!("Created backup: {}", backup_path);
+    let original_path = file.renamed_from.as_ref().unwrap_or(file_path);
+    if create_backup && let Some(original_content) = &file.on_disk {
+        let mut backup_path = original_path.clone().into_os_string();
+        backup_path.push(".backup");
+        let backup_path = PathBuf::from(backup_path);
+        vfs.write(&backup_path, original_content.as_bytes())
+            .await
+            .with_context(|| format!("Failed to create backup: {}", backup_path.display()))?;
+        debug!("Created backup: {}", backup_path.display());
+    }
+
+    if file.deleted {
+        vfs.remove_file(file_path)
+            .await
+            .with_context(|| format!("Failed to delete file: {}", file_path.display()))?;
+        info!("Deleted file: {}", file_path.display());
+        return Ok(());
+    }
+
+    if file.renamed_from.is_some()
+        && let Some(parent) = file_path.parent()
+    {
+        vfs.create_dir_all(parent).await.with_context(|| {
+            format!(
+                "Failed to create parent directories for: {}",
+                file_path.display()
+            )
+        })?;
     }
 
     // Write updated content
-    fs::write(&file_path, &updated_content)
+    vfs.write(file_path, file.content.as_bytes())
+        .await
         .with_context(|| format!("Failed to write updated file: {}", file_path.display()))?;
 
-    Ok(applied_updates)
+    if let Some(from) = &file.renamed_from {
+        vfs.remove_file(from)
+            .await
+            .with_context(|| format!("Failed to remove renamed file: {}", from.display()))?;
+        info!("Renamed {} to {}", from.display(), file_path.display());
+    }
+
+    Ok(())
+}
+
+/// Apply updates in order to `original`, returning the new content and the number of updates
+/// applied. An update with empty `old_content` appends its `new_content`, or inserts it before
+/// its `line`.
+pub fn apply_updates(original: &str, updates: &[CodeUpdate]) -> Result<(String, usize)> {
+    let mut updated_content = original.to_string();
+
+    for (i, update) in updates.iter().enumerate() {
+        debug!(
+            "Applying update {}/{}: {}",
+            i + 1,
+            updates.len(),
+            update.description.as_deref().unwrap_or("no description")
+        );
+        updated_content = apply_update(&updated_content, update)?;
+    }
+
+    Ok((updated_content, updates.len()))
+}
+
+/// Apply a single update to `content`
+pub fn apply_update(content: &str, update: &CodeUpdate) -> Result<String> {
+    if update.old_content.is_empty() {
+        let at = match update.line {
+            Some(line) => line_start(content, line),
+            None => content.len(),
+        };
+        return Ok(splice(content, at, 0, &update.new_content));
+    }
+
+    if !content.contains(&update.old_content) {
+        return Err(anyhow::anyhow!(
+            "Old content not found in file. Expected content:\n{}",
+            update.old_content
+        ));
+    }
+
+    // With a line to go by, only the match starting nearest to it is replaced, as `git apply`
+    // does with a hunk whose context repeats
+    if let Some(line) = update.line {
+        let at = content
+            .match_indices(&update.old_content)
+            .map(|(at, _)| at)
+            .min_by_key(|&at| (content[..at].matches('\n').count() + 1).abs_diff(line))
+            .unwrap_or_default();
+        return Ok(splice(
+            content,
+            at,
+            update.old_content.len(),
+            &update.new_content,
+        ));
+    }
+
+    // Count occurrences to ensure we're not making ambiguous replacements
+    let occurrences = content.matches(&update.old_content).count();
+    if occurrences > 1 {
+        warn!(
+            "Old content appears {} times in file, replacing all occurrences",
+            occurrences
+        );
+    }
+
+    // Replace the old content with new content
+    Ok(content.replace(&update.old_content, &update.new_content))
+}
+
+/// Byte offset where the 1-based `line` of `content` starts, or the end for lines past it
+fn line_start(content: &str, line: usize) -> usize {
+    match line.checked_sub(2) {
+        Some(newlines) => content
+            .match_indices('\n')
+            .nth(newlines)
+            .map_or(content.len(), |(at, _)| at + 1),
+        None => 0,
+    }
+}
+
+/// `content` with the `len` bytes at `at` replaced by `new`
+fn splice(content: &str, at: usize, len: usize, new: &str) -> String {
+    format!("{}{}{}", &content[..at], new, &content[at + len..])
+}
+
+/// Apply a single update to `content`, falling back to a match that ignores whitespace and
+/// indentation when `old_content` isn't found as is and `min_confidence` is given. Returns
+/// the confidence of such a fallback match.
+pub fn apply_update_fuzzy(
+    content: &str,
+    update: &CodeUpdate,
+    min_confidence: Option<f64>,
+) -> Result<(String, Option<f64>)> {
+    if update.old_content.is_empty() || content.contains(&update.old_content) {
+        return Ok((apply_update(content, update)?, None));
+    }
+
+    let found = match min_confidence {
+        Some(_) => find_fuzzy(content, &update.old_content)?,
+        None => find_fuzzy(content, &update.old_content).ok().flatten(),
+    };
+    match (found, min_confidence) {
+        (Some(found), Some(min)) if found.confidence >= min => Ok((
+            found.replace(content, &update.old_content, &update.new_content),
+            Some(found.confidence),
+        )),
+        (Some(found), Some(min)) => Err(anyhow::anyhow!(
+            "Old content not found in file; the closest match ignoring whitespace has {:.0}% confidence, below --fuzzy {:.0}. Expected content:\n{}",
+            found.confidence,
+            min,
+            update.old_content
+        )),
+        (Some(found), None) => Err(anyhow::anyhow!(
+            "Old content not found in file, though it matches with {:.0}% confidence ignoring whitespace (pass --fuzzy to accept). Expected content:\n{}",
+            found.confidence,
+            update.old_content
+        )),
+        (None, _) => Ok((apply_update(content, update)?, None)),
+    }
+}
+
+/// Percentage of `original`'s lines that no longer appear in `updated`
+pub fn deleted_line_percentage(original: &str, updated: &str) -> f64 {
+    let total_lines = original.lines().count();
+    if total_lines == 0 {
+        return 0.0;
+    }
+
+    let (_, deleted_lines) = count_line_changes(original, updated);
+    deleted_lines as f64 * 100.0 / total_lines as f64
+}
+
+/// Reject patches that touch too many files or delete too much of an existing file
+pub async fn check_patch_safety(
+    vfs: &impl Vfs,
+    update_request: &UpdateRequest,
+    safety: &PatchSafety,
+) -> Result<()> {
+    if update_request.files.len() > safety.max_files {
+        return Err(anyhow::anyhow!(
+            "Patch touches {} files, more than the allowed {}",
+            update_request.files.len(),
+            safety.max_files
+        ));
+    }
+
+    for file_update in &update_request.files {
+        let file_path = decode_path(&file_update.path)?;
+        if !vfs
+            .metadata(&file_path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file)
+        {
+            continue;
+        }
+
+        let original_content = vfs
+            .read_to_string(&file_path)
+            .await
+            .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+
+        // Updates that don't apply are reported later by the regular patch flow
+        let updated_content = if file_update.action == Some(FileAction::Delete) {
+            String::new()
+        } else if let Ok((updated_content, _)) =
+            apply_updates(&original_content, &file_update.updates)
+        {
+            updated_content
+        } else {
+            continue;
+        };
+
+        let deleted_percent = deleted_line_percentage(&original_content, &updated_content);
+        if deleted_percent > safety.max_deleted_percent {
+            return Err(anyhow::anyhow!(
+                "Patch deletes {:.0}% of the lines in {}, more than the allowed {:.0}%",
+                deleted_percent,
+                file_path.display(),
+                safety.max_deleted_percent
+            ));
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,12 @@
+use anyhow::{Context, Result};
+
+use crate::cli::commands::patch::patch_schema;
+
+pub fn execute(patch: bool) -> Result<()> {
+    if patch {
+        let schema =
+            serde_json::to_string_pretty(&patch_schema()).context("Failed to serialize schema")?;
+        println!("{}", schema);
+    }
+    Ok(())
+}
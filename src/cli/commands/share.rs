@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::info;
+
+use crate::config::Settings;
+use crate::config::settings::ShareProviderKind;
+use crate::integrations::share::share;
+use crate::io::clipboard::ClipboardProvider;
+use crate::io::vfs::Vfs;
+
+pub async fn execute(
+    clipboard: &dyn ClipboardProvider,
+    vfs: &impl Vfs,
+    file: String,
+    provider: Option<ShareProviderKind>,
+    no_copy: bool,
+) -> Result<()> {
+    let (name, content) = if file == "-" {
+        let content = tokio::task::spawn_blocking(|| std::io::read_to_string(std::io::stdin()))
+            .await?
+            .context("Failed to read from stdin")?;
+        ("snapshot.md".to_string(), content)
+    } else {
+        let path = Path::new(&file);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "snapshot.md".to_string());
+        let content = vfs
+            .read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read snapshot: {}", file))?;
+        (name, content)
+    };
+
+    let settings = Settings::load()?;
+    let link = share(&settings.share, provider, &name, &content).await?;
+    println!("🔗 Shared: {}", link);
+
+    if !no_copy {
+        clipboard.copy(&link)?;
+        println!("📋 Link copied to clipboard");
+    }
+
+    info!("Shared {} ({} characters)", name, content.len());
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use tracing::{debug, info};
+
+use crate::cli::args::CollectArgs;
+use crate::core::complexity::Complexity;
+use crate::core::file_collector::collect_files_with;
+use crate::io::vfs::Vfs;
+use crate::utils::path_encoding::{encode_path, relative_to};
+
+pub async fn execute(vfs: &impl Vfs, args: CollectArgs) -> Result<()> {
+    let input_paths = args.input_paths()?;
+    if input_paths.is_empty() {
+        return Err(anyhow::anyhow!("No paths provided"));
+    }
+
+    let collect_options = args.collect_options(Vec::new())?;
+    let files = collect_files_with(vfs, &input_paths, &collect_options).await?;
+    let current_dir = std::env::current_dir().unwrap_or_default();
+
+    let mut rows = Vec::new();
+    for file in &files {
+        match vfs.read_text(&file.path).await {
+            Ok(content) => rows.push((
+                Complexity::measure(&content, file.language),
+                encode_path(&relative_to(&file.path, &current_dir)),
+            )),
+            Err(e) => debug!("Skipping {}: {}", file.path.display(), e),
+        }
+    }
+    rows.sort_by(|a, b| b.0.loc.cmp(&a.0.loc).then_with(|| a.1.cmp(&b.1)));
+
+    println!("{:>7} {:>9} {:>7}  path", "LOC", "functions", "nesting");
+    for (complexity, path) in &rows {
+        println!(
+            "{:>7} {:>9} {:>7}  {}",
+            complexity.loc, complexity.functions, complexity.max_nesting, path
+        );
+    }
+
+    info!(
+        "{} files, {} LOC, {} functions",
+        rows.len(),
+        rows.iter().map(|(c, _)| c.loc).sum::<usize>(),
+        rows.iter().map(|(c, _)| c.functions).sum::<usize>()
+    );
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use tracing::info;
+
+use crate::config::paths;
+use crate::core::patch_journal::{Restored, UndoJournal, forget_patched_files, undo_path};
+use crate::io::lock::lock_project_state;
+use crate::io::vfs::Vfs;
+
+pub async fn execute(vfs: &impl Vfs, dry_run: bool, force: bool) -> Result<()> {
+    let _lock = lock_project_state().await?;
+    let path = undo_path(&paths::history_dir()?);
+    let journal = UndoJournal::load(vfs, &path).await?;
+    if journal.files.is_empty() {
+        println!("Nothing to undo: no patch has been applied here since the last undo");
+        return Ok(());
+    }
+
+    let restored = journal.restore(vfs, dry_run, force).await?;
+    let verb = if dry_run { "Would undo" } else { "Undid" };
+    println!("↩️  {} the last patch: {}", verb, journal.analysis);
+    for file in &restored {
+        match file {
+            Restored::Reverted(path) => println!("  reverted {}", path.display()),
+            Restored::Removed(path) => println!("  removed {}", path.display()),
+        }
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    // Only the last patch can be undone, and now there is none to undo
+    UndoJournal::default().save(vfs, &path).await?;
+    let paths: Vec<_> = journal.files.iter().map(|file| file.path.clone()).collect();
+    forget_patched_files(vfs, &paths).await;
+
+    info!("Restored {} files", restored.len());
+    Ok(())
+}
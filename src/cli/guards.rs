@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// A soft condition a `--fail-*` flag turns into a failure, for CI jobs to gate on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Guard {
+    /// No files left to concatenate (`--fail-if-empty`)
+    Empty,
+    /// Output over `--fail-over-tokens` or `--fail-over-bytes`
+    OverLimit,
+    /// Likely-sensitive files found, included or not (`--fail-on-secrets`)
+    Secrets,
+    /// Files or values kept out of the output: likely-sensitive files, files under excluded
+    /// licenses, or anonymized values (`--fail-on-redactions`)
+    Redactions,
+}
+
+impl Guard {
+    /// Process exit code, distinct for each guard and from the 1 of other errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Guard::Empty => 3,
+            Guard::OverLimit => 4,
+            Guard::Secrets => 5,
+            Guard::Redactions => 6,
+        }
+    }
+}
+
+/// Error carrying the guard that failed, so `main` can exit with its code
+#[derive(Debug)]
+pub struct GuardFailure {
+    pub guard: Guard,
+    pub message: String,
+}
+
+impl GuardFailure {
+    pub fn new(guard: Guard, message: impl Into<String>) -> Self {
+        Self {
+            guard,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for GuardFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for GuardFailure {}
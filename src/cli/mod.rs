@@ -1,5 +1,6 @@
 pub mod args;
 pub mod commands;
+pub mod guards;
 
-pub use args::{Args, Commands};
+pub use args::{Args, CatArgs, CollectArgs, Commands};
 pub use clap::Parser;
@@ -1,5 +1,9 @@
+pub mod models;
+pub mod paths;
 pub mod patterns;
 pub mod prompt;
+pub mod settings;
 
 pub use patterns::{DEFAULT_EXCLUDE_PATTERNS, DEFAULT_INCLUDE_PATTERNS};
 pub use prompt::PROMPT;
+pub use settings::Settings;
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Pricing and context window of an LLM API model
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ModelSpec {
+    /// USD per million input tokens
+    pub input_per_million: f64,
+    /// Maximum number of tokens the model accepts
+    pub context_window: usize,
+}
+
+impl ModelSpec {
+    pub fn input_cost(&self, tokens: usize) -> f64 {
+        tokens as f64 * self.input_per_million / 1_000_000.0
+    }
+}
+
+/// Built-in list prices; override or extend them under `[models.<name>]` in the config
+const BUILTIN_MODELS: &[(&str, f64, usize)] = &[
+    ("gpt-4o", 2.50, 128_000),
+    ("gpt-4o-mini", 0.15, 128_000),
+    ("gpt-4.1", 2.00, 1_047_576),
+    ("gpt-4.1-mini", 0.40, 1_047_576),
+    ("o3", 2.00, 200_000),
+    ("claude-3.5", 3.00, 200_000),
+    ("claude-3.5-haiku", 0.80, 200_000),
+    ("claude-3-opus", 15.00, 200_000),
+    ("claude-sonnet-4", 3.00, 200_000),
+    ("claude-opus-4", 15.00, 200_000),
+    ("gemini-1.5-pro", 1.25, 2_000_000),
+    ("gemini-1.5-flash", 0.075, 1_000_000),
+    ("gemini-2.5-pro", 1.25, 1_048_576),
+];
+
+/// Merge the configured models over the built-in table
+pub fn model_table(configured: &BTreeMap<String, ModelSpec>) -> BTreeMap<String, ModelSpec> {
+    let mut table: BTreeMap<String, ModelSpec> = BUILTIN_MODELS
+        .iter()
+        .map(|&(name, input_per_million, context_window)| {
+            (
+                name.to_string(),
+                ModelSpec {
+                    input_per_million,
+                    context_window,
+                },
+            )
+        })
+        .collect();
+    table.extend(configured.iter().map(|(k, v)| (k.clone(), v.clone())));
+    table
+}
+
+pub fn resolve_model<'a>(
+    table: &'a BTreeMap<String, ModelSpec>,
+    name: &str,
+) -> Result<&'a ModelSpec> {
+    table.get(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown model '{}'. Known models: {}",
+            name,
+            table.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+/// Context window of a known model, or of a `<family>-<size>` spec such as `claude-200k`
+pub fn context_window_for(table: &BTreeMap<String, ModelSpec>, name: &str) -> Result<usize> {
+    if let Some(spec) = table.get(name) {
+        return Ok(spec.context_window);
+    }
+
+    name.rsplit_once('-')
+        .and_then(|(_, size)| parse_window_size(size))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown model '{}'. Use a known model ({}) or a size such as claude-200k",
+                name,
+                table.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+}
+
+fn parse_window_size(size: &str) -> Option<usize> {
+    let size = size.to_lowercase();
+    let (digits, multiplier) = if let Some(digits) = size.strip_suffix('k') {
+        (digits, 1_000)
+    } else if let Some(digits) = size.strip_suffix('m') {
+        (digits, 1_000_000)
+    } else {
+        (size.as_str(), 1)
+    };
+    digits.parse::<usize>().ok().map(|n| n * multiplier)
+}
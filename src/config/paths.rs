@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::path::PathBuf;
+
+/// Platform-specific locations for catnip's user-level state.
+///
+/// - Linux: `$XDG_CONFIG_HOME/catnip`, `$XDG_CACHE_HOME/catnip`, `$XDG_DATA_HOME/catnip`
+/// - macOS: `~/Library/Application Support/catnip`, `~/Library/Caches/catnip`
+/// - Windows: `%APPDATA%\catnip\config`, `%LOCALAPPDATA%\catnip\cache`, `%APPDATA%\catnip\data`
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("", "", "catnip").context("Could not determine the user's home directory")
+}
+
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().to_path_buf())
+}
+
+/// User-level config file, overridden by a project-level `catnip.toml`
+pub fn config_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+pub fn data_dir() -> Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().to_path_buf())
+}
+
+pub fn history_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join("history"))
+}
+
+/// Search indexes built by `catnip index`, one per project directory
+pub fn index_dir() -> Result<PathBuf> {
+    Ok(project_dirs()?.cache_dir().join("index"))
+}
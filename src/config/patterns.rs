@@ -1,3 +1,56 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Directories never descended into, whatever the patterns say, unless overridden by
+/// `collect.skip_directories` in the config or `--no-default-skips`
+pub const DEFAULT_SKIP_DIRECTORIES: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    ".bzr",
+    "node_modules",
+    "__pycache__",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".vscode",
+    ".idea",
+    "target",
+    "build",
+    "dist",
+    "out",
+];
+
+/// Dotfiles worth snapshotting even though no include pattern names them; extend with
+/// `--dotfiles-allow`. Files holding real environment values are never let in this way.
+pub const DEFAULT_DOTFILE_ALLOWLIST: &[&str] = &[
+    ".env.example",
+    ".env.sample",
+    ".env.template",
+    ".editorconfig",
+    ".nvmrc",
+    ".node-version",
+    ".python-version",
+    ".ruby-version",
+    ".tool-versions",
+    ".gitattributes",
+];
+
+/// Names that suggest a file holds keys or credentials; `cat` leaves matching files out
+/// unless `--allow-sensitive` is given
+pub const SENSITIVE_PATTERNS: &[&str] = &[
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "credentials*",
+    ".env",
+    ".netrc",
+];
+
 pub const DEFAULT_EXCLUDE_PATTERNS: &[&str] = &[
     // Compiled files and build artifacts
     "*.pyc",
@@ -129,6 +182,15 @@ pub const DEFAULT_EXCLUDE_PATTERNS: &[&str] = &[
     ".dockerignore",
 ];
 
+/// Media, archives, fonts and documents that `--binaries stub` lists even though the default
+/// excludes leave them out
+pub const BINARY_ASSET_PATTERNS: &[&str] = &[
+    "*.jpg", "*.jpeg", "*.png", "*.gif", "*.bmp", "*.svg", "*.webp", "*.ico", "*.tiff", "*.tif",
+    "*.psd", "*.ai", "*.sketch", "*.wav", "*.mp3", "*.mp4", "*.mov", "*.avi", "*.mkv", "*.flv",
+    "*.wmv", "*.m4a", "*.aac", "*.ogg", "*.flac", "*.webm", "*.zip", "*.tar", "*.gz", "*.rar",
+    "*.7z", "*.bz2", "*.xz", "*.tgz", "*.woff", "*.woff2", "*.ttf", "*.otf", "*.pdf",
+];
+
 pub const DEFAULT_INCLUDE_PATTERNS: &[&str] = &[
     // Programming languages
     "*.py",
@@ -245,3 +307,24 @@ pub const DEFAULT_INCLUDE_PATTERNS: &[&str] = &[
     "*.pgsql",
     "*.mysql",
 ];
+
+/// Patterns of an `--exclude-from`/`--include-from` file: one glob per line, skipping
+/// blank lines and `#` comments
+pub fn parse_pattern_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Project-level exclude patterns, read from the top of each input directory
+pub const CATNIPIGNORE_FILE: &str = ".catnipignore";
+
+/// Read a pattern list file given on the command line
+pub fn read_pattern_list(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read pattern file: {}", path.display()))?;
+    Ok(parse_pattern_list(&content))
+}
@@ -1,3 +1,9 @@
+use crate::core::snapshot::FileStatus;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
 pub const PROMPT: &str = r#"
 # Codebase Update Instructions
 You are an expert code reviewer. When updating this codebase, respond with JSON ONLY:
@@ -15,6 +21,16 @@ You are an expert code reviewer. When updating this codebase, respond with JSON
           "description": "Optional description"
         }
       ]
+    },
+    {
+      "path": "src/obsolete.rs",
+      "action": "delete"
+    },
+    {
+      "path": "src/utils.rs",
+      "action": "rename",
+      "new_path": "src/helpers/utils.rs",
+      "updates": []
     }
   ]
 }
@@ -32,6 +48,11 @@ You are an expert code reviewer. When updating this codebase, respond with JSON
 - Replaces exactly with `new_content`
 - If not found, update fails
 - Multiple occurrences = all replaced
+- Empty `old_content` appends `new_content` (creating the file if it doesn't exist)
+- `"action"` is optional: `modify`, `create`, `delete` or `rename`
+- Give new files `"action": "create"`, so they are never appended to an existing file
+- `"action": "delete"` removes an existing file and takes no updates
+- `"action": "rename"` moves a file to `new_path`; its updates edit the moved content
 
 ## Best Practices
 - Copy complete functions/methods vs fragments
@@ -69,3 +90,89 @@ You are an expert code reviewer. When updating this codebase, respond with JSON
 - Modify unprovided files
 - Mix unrelated changes
 "#;
+
+/// `## ` sections of `PROMPT` that can be dropped to make room for code, least useful first
+pub const OPTIONAL_PROMPT_SECTIONS: [&str; 3] = ["Examples", "Best Practices", "Workflow"];
+
+/// `prompt` without the `## heading` section, up to the next heading outside a code fence
+pub fn remove_prompt_section(prompt: &str, heading: &str) -> String {
+    let start = format!("## {}", heading);
+    let mut result = String::with_capacity(prompt.len());
+    let mut removing = false;
+    let mut in_fence = false;
+
+    for line in prompt.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && trimmed.starts_with('#') {
+            removing = trimmed == start;
+        }
+        if !removing {
+            result.push_str(line);
+        }
+    }
+    result
+}
+
+/// Separates the snapshot from the prompt when both go into one clipboard entry
+pub const PROMPT_MARKER: &str = "<!-- catnip: patch instructions -->";
+
+/// Where the prompt instructions go relative to the snapshot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PromptPosition {
+    /// Before the snapshot, for models that weight the start of the context more heavily
+    Start,
+    #[default]
+    End,
+}
+
+/// Join `content` and the prompt instructions, optionally with `PROMPT_MARKER` between them
+pub fn place_prompt(content: &str, position: PromptPosition, marker: bool) -> String {
+    place_prompt_with(content, PROMPT, position, marker)
+}
+
+/// Like `place_prompt`, with `prompt` in place of the built-in instructions
+pub fn place_prompt_with(
+    content: &str,
+    prompt: &str,
+    position: PromptPosition,
+    marker: bool,
+) -> String {
+    let separator = if marker {
+        format!("\n{}\n", PROMPT_MARKER)
+    } else {
+        "\n".to_string()
+    };
+    match position {
+        PromptPosition::Start => format!("{}{}{}", prompt, separator, content),
+        PromptPosition::End => format!("{}{}{}", content, separator, prompt),
+    }
+}
+
+/// Opening of a follow-up message: which files changed since the snapshot the model already
+/// has, so it knows everything else still holds
+pub fn delta_preamble(delta: &BTreeMap<PathBuf, FileStatus>) -> String {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let changed: Vec<_> = delta
+        .iter()
+        .filter(|(_, status)| **status != FileStatus::Unchanged)
+        .collect();
+    if changed.is_empty() {
+        return "Nothing changed since the context I gave you earlier.\n\n".to_string();
+    }
+
+    let mut preamble = String::from(
+        "The following files changed since the context I gave you earlier; everything else is unchanged.\n\n",
+    );
+    for (path, status) in changed {
+        let _ = writeln!(
+            preamble,
+            "- `{}` ({})",
+            encode_path(&relative_to(path, &current_dir)),
+            status.label()
+        );
+    }
+    preamble.push('\n');
+    preamble
+}
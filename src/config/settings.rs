@@ -0,0 +1,321 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Table;
+use tracing::{debug, warn};
+
+use crate::config::models::ModelSpec;
+use crate::config::paths;
+
+pub const CONFIG_FILE_NAME: &str = "catnip.toml";
+
+/// Settings that run commands or decide where tokens are sent, which a `catnip.toml` checked
+/// into an untrusted clone could abuse, so only the user-level config may set them
+const USER_ONLY_SETTINGS: &[&str] = &["transform", "hooks", "share"];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub collect: CollectSettings,
+    pub patch: PatchSettings,
+    pub hooks: HookSettings,
+    pub share: ShareSettings,
+    pub anonymize: AnonymizeSettings,
+    /// Extra or overridden entries for the model pricing table
+    pub models: BTreeMap<String, ModelSpec>,
+    /// Commands whose output replaces the content of matching files
+    #[serde(rename = "transform")]
+    pub transforms: Vec<TransformHook>,
+    /// Rendering options for files matching a pattern
+    #[serde(rename = "process")]
+    pub processing: Vec<ProcessingRule>,
+    /// Code fence tags to emit in place of the detected language names
+    pub fences: BTreeMap<String, String>,
+    /// Directories collected with their own patterns, in place of the paths when none are given
+    #[serde(rename = "root")]
+    pub roots: Vec<WorkspaceRoot>,
+    /// Share of the packing budget reserved for the files below each directory
+    pub budget: BTreeMap<PathBuf, BudgetPercent>,
+}
+
+/// A `[budget]` value: a percentage written as `"40%"` or `40`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "BudgetValue")]
+pub struct BudgetPercent(pub f64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BudgetValue {
+    Number(f64),
+    Text(String),
+}
+
+impl TryFrom<BudgetValue> for BudgetPercent {
+    type Error = String;
+
+    fn try_from(value: BudgetValue) -> Result<Self, Self::Error> {
+        let percent = match value {
+            BudgetValue::Number(percent) => percent,
+            BudgetValue::Text(text) => text
+                .trim()
+                .trim_end_matches('%')
+                .trim_end()
+                .parse()
+                .map_err(|_| format!("'{}' is not a percentage", text))?,
+        };
+        if percent > 0.0 && percent <= 100.0 {
+            Ok(Self(percent))
+        } else {
+            Err(format!("{}% is not between 0% and 100%", percent))
+        }
+    }
+}
+
+/// A `[[root]]` entry: a directory of a multi-root workspace, with patterns added to the
+/// command-line ones and a priority adjustment for files below it when packing
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct WorkspaceRoot {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// A `[[process]]` entry: options for files matching `pattern`, overriding the command-line
+/// flags; when several entries match, later ones win
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ProcessingRule {
+    pub pattern: String,
+    pub ignore_comments: Option<bool>,
+    pub ignore_docstrings: Option<bool>,
+}
+
+/// A `[[transform]]` entry: files matching `pattern` are piped through `command`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TransformHook {
+    pub pattern: String,
+    pub command: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CollectSettings {
+    /// Replaces the built-in list of directories that are never descended into
+    pub skip_directories: Option<Vec<String>>,
+    /// Files always included, whatever the patterns, and never trimmed to fit a model
+    pub pinned: Vec<PathBuf>,
+}
+
+/// Commands run around catnip commands
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    /// Run after each `cat`, with the rendered output on stdin and `CATNIP_OUTPUT` set to the
+    /// output file, if any
+    pub post_cat: Option<String>,
+    /// Run before `patch` touches any file, with the parsed patch JSON on stdin; a non-zero
+    /// exit refuses the patch
+    pub pre_patch: Option<String>,
+}
+
+/// Values `cat --anonymize` replaces with placeholders, the same value always getting the
+/// same placeholder
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AnonymizeSettings {
+    /// Replace email addresses
+    pub emails: bool,
+    /// Internal host names, replaced along with their subdomains, in URLs or anywhere else
+    pub hosts: Vec<String>,
+    /// Whole words to replace by label, e.g. `user = ["alice"]` or `codename = ["falcon"]`
+    pub words: BTreeMap<String, Vec<String>>,
+    /// Regular expressions whose matches are replaced, by label
+    pub patterns: BTreeMap<String, String>,
+}
+
+/// Where `catnip share` uploads snapshots
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareSettings {
+    pub provider: Option<ShareProviderKind>,
+    /// Pre-signed upload URL or paste service endpoint
+    pub url: Option<String>,
+    /// Link to print for pre-signed uploads instead of the upload URL without its signature
+    pub link: Option<String>,
+    /// Environment variable holding the access token (default: GITHUB_TOKEN for gists)
+    pub token_env: Option<String>,
+    /// Create public rather than secret gists
+    pub public: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareProviderKind {
+    /// PUT to a pre-signed URL, such as an S3 object's
+    Presigned,
+    /// POST to a paste service that answers with the link
+    Paste,
+    /// Create a GitHub gist
+    Gist,
+}
+
+impl std::fmt::Display for ShareProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Presigned => "presigned",
+            Self::Paste => "paste",
+            Self::Gist => "gist",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PatchSettings {
+    pub safety: PatchSafety,
+    pub lint: PatchLint,
+    /// Append a summary of every applied patch to this file
+    pub changelog: Option<String>,
+}
+
+/// Guards against hallucinated mega-patches pasted from the clipboard
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PatchSafety {
+    /// Maximum number of files a single patch may touch
+    pub max_files: usize,
+    /// Maximum percentage of an existing file's lines a patch may delete
+    pub max_deleted_percent: f64,
+}
+
+impl Default for PatchSafety {
+    fn default() -> Self {
+        Self {
+            max_files: 20,
+            max_deleted_percent: 50.0,
+        }
+    }
+}
+
+/// How a lint rule's findings are treated
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    /// Don't check
+    Off,
+    /// Report, then apply the patch anyway
+    #[default]
+    Warn,
+    /// Report and refuse to apply the patch
+    Error,
+}
+
+/// Checks for updates that are probably mistakes, run before a patch is applied
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PatchLint {
+    /// Updates deleting a large block with nothing in its place
+    pub large_deletion: LintSeverity,
+    /// Edits to files that weren't in the last `cat` snapshot
+    pub not_in_snapshot: LintSeverity,
+    /// Updates that only change trailing whitespace
+    pub whitespace_only: LintSeverity,
+    /// Lines an update must delete to count as a large deletion
+    pub max_deleted_lines: usize,
+}
+
+impl Default for PatchLint {
+    fn default() -> Self {
+        Self {
+            large_deletion: LintSeverity::Warn,
+            not_in_snapshot: LintSeverity::Warn,
+            whitespace_only: LintSeverity::Warn,
+            max_deleted_lines: 20,
+        }
+    }
+}
+
+impl Settings {
+    /// Load the user-level config, then overlay `catnip.toml` from the current directory
+    pub fn load() -> Result<Self> {
+        let user_config = match paths::config_file() {
+            Ok(user_config) => Some(user_config),
+            Err(e) => {
+                debug!("Skipping user config: {}", e);
+                None
+            }
+        };
+        Self::load_layers(user_config.as_deref(), Path::new(CONFIG_FILE_NAME))
+    }
+
+    /// Load `user_config`, then overlay `project_config` without its user-only settings
+    pub fn load_layers(user_config: Option<&Path>, project_config: &Path) -> Result<Self> {
+        let mut table = match user_config {
+            Some(user_config) => read_table(user_config)?,
+            None => Table::new(),
+        };
+        let mut project = read_table(project_config)?;
+        for setting in USER_ONLY_SETTINGS {
+            if remove_setting(&mut project, setting) {
+                warn!(
+                    "Ignoring `{}` in {}: it can only be set in the user config",
+                    setting,
+                    project_config.display()
+                );
+            }
+        }
+        merge_tables(&mut table, project);
+
+        Table::try_into(table).context("Invalid configuration")
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        Table::try_into(read_table(path)?)
+            .with_context(|| format!("Invalid configuration in {}", path.display()))
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    if !path.exists() {
+        debug!("No config file at {}", path.display());
+        return Ok(Table::new());
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+
+    content
+        .parse()
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))
+}
+
+/// Remove a setting given by its dotted path, returning whether it was there
+fn remove_setting(table: &mut Table, setting: &str) -> bool {
+    match setting.split_once('.') {
+        Some((key, rest)) => table
+            .get_mut(key)
+            .and_then(toml::Value::as_table_mut)
+            .is_some_and(|table| remove_setting(table, rest)),
+        None => table.remove(setting).is_some(),
+    }
+}
+
+/// Recursively merge `overlay` into `base`, with `overlay` winning on conflicts
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::config::settings::AnonymizeSettings;
+use crate::core::file_collector::CollectedFile;
+use crate::io::vfs::Vfs;
+use crate::utils::file_metadata::sha256_hex;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::debug;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+
+/// Default location of the anonymization map for the current working directory, outside
+/// the project so it never ends up in a snapshot
+pub fn anonymize_map_path(history_dir: &Path) -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let key = &sha256_hex(current_dir.display().to_string().as_bytes())[..16];
+    history_dir.join(format!("anonymize-{}.json", key))
+}
+
+/// Kind of value a rule replaces, which decides what its placeholders look like
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    /// `user1@example.invalid`, still an email address
+    Email,
+    /// `host1.example.invalid`, still a host name
+    Host,
+    /// `LABEL_1`
+    Label(String),
+}
+
+impl Kind {
+    fn placeholder(&self, n: usize) -> String {
+        match self {
+            Kind::Email => format!("user{}@example.invalid", n),
+            Kind::Host => format!("host{}.example.invalid", n),
+            Kind::Label(label) => format!("{}_{}", label.to_uppercase(), n),
+        }
+    }
+}
+
+/// Original values and the placeholders standing in for them. Saved between runs, so a
+/// value keeps its placeholder across snapshots and placeholders in a model's answer can
+/// be turned back into the real values.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AnonymizeMap {
+    /// Placeholder of each original value
+    pub placeholders: BTreeMap<String, String>,
+}
+
+impl AnonymizeMap {
+    /// Map saved at `path`, or an empty one when there is none yet
+    pub async fn load(vfs: &impl Vfs, path: &Path) -> Result<Self> {
+        if !vfs.exists(path).await {
+            return Ok(Self::default());
+        }
+
+        let content = vfs.read(path).await?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse anonymization map: {}", path.display()))
+    }
+
+    pub async fn save(&self, vfs: &impl Vfs, path: &Path) -> Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            vfs.create_dir_all(parent).await?;
+        }
+
+        let content =
+            serde_json::to_vec_pretty(self).context("Failed to serialize anonymization map")?;
+        vfs.write(path, &content).await
+    }
+
+    fn is_placeholder(&self, text: &str) -> bool {
+        self.placeholders
+            .values()
+            .any(|placeholder| placeholder == text)
+    }
+
+    /// Placeholder for `original`, the one it already has or the next free one of its kind
+    fn placeholder(&mut self, kind: &Kind, original: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(original) {
+            return placeholder.clone();
+        }
+
+        let n = (1..)
+            .find(|&n| {
+                let candidate = kind.placeholder(n);
+                !self.placeholders.values().any(|used| *used == candidate)
+            })
+            .unwrap_or_default();
+        let placeholder = kind.placeholder(n);
+        self.placeholders
+            .insert(original.to_string(), placeholder.clone());
+        placeholder
+    }
+
+    /// `text` with every placeholder turned back into its original value, and the number of
+    /// placeholders restored
+    pub fn restore(&self, text: &str) -> (String, usize) {
+        if self.placeholders.is_empty() {
+            return (text.to_string(), 0);
+        }
+
+        let originals: HashMap<&str, &str> = self
+            .placeholders
+            .iter()
+            .map(|(original, placeholder)| (placeholder.as_str(), original.as_str()))
+            .collect();
+        // Longest first, so USER_10 isn't read as USER_1 followed by a 0
+        let mut placeholders: Vec<&str> = originals.keys().copied().collect();
+        placeholders.sort_by_key(|placeholder| std::cmp::Reverse(placeholder.len()));
+        let alternation: Vec<String> = placeholders.iter().map(|p| regex::escape(p)).collect();
+        let regex = Regex::new(&format!(r"\b(?:{})\b", alternation.join("|")))
+            .expect("escaped placeholders form a valid regex");
+
+        let mut restored = 0;
+        let text = regex.replace_all(text, |captures: &regex::Captures| {
+            restored += 1;
+            originals[&captures[0]].to_string()
+        });
+        (text.into_owned(), restored)
+    }
+}
+
+/// The `[anonymize]` rules compiled into one regex, so every value is replaced in a single
+/// pass and placeholders are never rewritten by a later rule
+#[derive(Debug)]
+pub struct Anonymizer {
+    regex: Regex,
+    /// Kind of the rule behind each `r<i>` group of `regex`
+    kinds: Vec<Kind>,
+}
+
+impl Anonymizer {
+    /// `None` when the settings have nothing to replace
+    pub fn new(settings: &AnonymizeSettings) -> Result<Option<Self>> {
+        let mut rules = Vec::new();
+        if settings.emails {
+            rules.push((Kind::Email, EMAIL_PATTERN.to_string()));
+        }
+        if !settings.hosts.is_empty() {
+            rules.push((
+                Kind::Host,
+                format!(
+                    r"(?i)\b(?:[a-z0-9-]+\.)*(?:{})\b",
+                    alternation(&settings.hosts)
+                ),
+            ));
+        }
+        for (label, words) in &settings.words {
+            if !words.is_empty() {
+                rules.push((
+                    Kind::Label(label.clone()),
+                    format!(r"\b(?:{})\b", alternation(words)),
+                ));
+            }
+        }
+        for (label, pattern) in &settings.patterns {
+            Regex::new(pattern).with_context(|| {
+                format!("Invalid [anonymize.patterns] {} regex: {}", label, pattern)
+            })?;
+            rules.push((Kind::Label(label.clone()), pattern.clone()));
+        }
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let groups: Vec<String> = rules
+            .iter()
+            .enumerate()
+            .map(|(i, (_, pattern))| format!("(?P<r{}>{})", i, pattern))
+            .collect();
+        let regex = Regex::new(&groups.join("|")).context("Invalid [anonymize] rules")?;
+        Ok(Some(Self {
+            regex,
+            kinds: rules.into_iter().map(|(kind, _)| kind).collect(),
+        }))
+    }
+
+    /// `text` with every value the rules match replaced by its placeholder in `map`, and the
+    /// number of values replaced. Placeholders already in `text` are left alone, so
+    /// anonymizing twice changes nothing.
+    pub fn anonymize(&self, text: &str, map: &mut AnonymizeMap) -> (String, usize) {
+        let mut replaced = 0;
+        let anonymized = self.regex.replace_all(text, |captures: &regex::Captures| {
+            if map.is_placeholder(&captures[0]) {
+                return captures[0].to_string();
+            }
+            let kind = (0..self.kinds.len())
+                .find(|i| captures.name(&format!("r{}", i)).is_some())
+                .map(|i| &self.kinds[i])
+                .expect("one rule matched");
+            replaced += 1;
+            map.placeholder(kind, &captures[0])
+        });
+        (anonymized.into_owned(), replaced)
+    }
+
+    /// Anonymize each file's content, updating its metadata to the new content. Returns the
+    /// anonymized contents of the files that changed, by path, and the number of values
+    /// replaced.
+    pub async fn apply(
+        &self,
+        vfs: &impl Vfs,
+        files: &mut [CollectedFile],
+        map: &mut AnonymizeMap,
+    ) -> Result<(BTreeMap<PathBuf, Vec<u8>>, usize)> {
+        let mut anonymized = BTreeMap::new();
+        let mut total = 0;
+
+        for file in files.iter_mut() {
+            let Ok(content) = vfs.read_text(&file.path).await else {
+                // Unreadable files are reported when rendering
+                continue;
+            };
+            let (content, replaced) = self.anonymize(&content, map);
+            if replaced == 0 {
+                continue;
+            }
+
+            debug!("Anonymized {} values in {}", replaced, file.path.display());
+            total += replaced;
+            let content = content.into_bytes();
+            *file = CollectedFile::new(&file.path, &file.relative_path, &content, file.modified);
+            anonymized.insert(file.path.clone(), content);
+        }
+
+        Ok((anonymized, total))
+    }
+}
+
+/// An anonymizer and its map, shared by everything one `cat` run renders, so the paths in
+/// the structure, headings and manifest get the same placeholders as the file contents
+#[derive(Debug)]
+pub struct Anonymization {
+    anonymizer: Anonymizer,
+    map: Mutex<AnonymizeMap>,
+}
+
+impl Anonymization {
+    pub fn new(anonymizer: Anonymizer, map: AnonymizeMap) -> Self {
+        Self {
+            anonymizer,
+            map: Mutex::new(map),
+        }
+    }
+
+    /// `text` with every value the rules match replaced, adding new values to the map
+    pub fn conceal(&self, text: &str) -> String {
+        let mut map = self.map.lock().unwrap();
+        self.anonymizer.anonymize(text, &mut map).0
+    }
+
+    /// The map, with the values found while rendering
+    pub fn map(&self) -> AnonymizeMap {
+        self.map.lock().unwrap().clone()
+    }
+}
+
+/// Regex alternation matching any of `values` literally, longest first
+fn alternation(values: &[String]) -> String {
+    let mut values: Vec<&String> = values.iter().collect();
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    values
+        .iter()
+        .map(|value| regex::escape(value))
+        .collect::<Vec<_>>()
+        .join("|")
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use std::io::{BufRead, Write};
+
+/// An answer to "Apply this update?", as `git add -p` takes them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Answer {
+    /// Apply this update
+    Yes,
+    /// Leave this update out
+    No,
+    /// Apply this update and every later one without asking
+    All,
+    /// Leave this update and every later one out
+    Quit,
+}
+
+impl Answer {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Some(Answer::Yes),
+            "n" | "no" => Some(Answer::No),
+            "a" | "all" => Some(Answer::All),
+            "q" | "quit" => Some(Answer::Quit),
+            _ => None,
+        }
+    }
+}
+
+const HELP: &str = "y - apply this update
+n - leave this update out
+a - apply this update and all later ones
+q - leave this update and all later ones out";
+
+/// Asks whether to apply each update of a patch, until told to apply or skip the rest
+pub struct Approver<R> {
+    input: R,
+    /// Answer that now stands for every update, after `a` or `q`
+    settled: Option<bool>,
+}
+
+impl<R: BufRead> Approver<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            settled: None,
+        }
+    }
+
+    /// Show `diff` under `heading` and ask whether to apply it. End of input counts as `q`.
+    pub fn approve(&mut self, heading: &str, diff: &str) -> Result<bool> {
+        if let Some(settled) = self.settled {
+            return Ok(settled);
+        }
+
+        println!("\n{}", heading);
+        print!("{}", diff);
+        loop {
+            print!("Apply this update [y,n,a,q,?]? ");
+            std::io::stdout().flush().ok();
+
+            let mut line = String::new();
+            let read = self
+                .input
+                .read_line(&mut line)
+                .context("Failed to read answer")?;
+            let answer = if read == 0 {
+                println!();
+                Answer::Quit
+            } else if let Some(answer) = Answer::parse(&line) {
+                answer
+            } else {
+                println!("{}", HELP);
+                continue;
+            };
+
+            return Ok(match answer {
+                Answer::Yes => true,
+                Answer::No => false,
+                Answer::All => *self.settled.insert(true),
+                Answer::Quit => *self.settled.insert(false),
+            });
+        }
+    }
+}
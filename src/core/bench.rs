@@ -0,0 +1,153 @@
+use crate::config::patterns::{DEFAULT_EXCLUDE_PATTERNS, DEFAULT_INCLUDE_PATTERNS};
+use crate::core::content_processor::{RenderOptions, concatenate_files_with};
+use crate::core::file_collector::{CollectOptions, CollectedFile, collect_files_with};
+use crate::core::pattern_matcher::PatternMatcher;
+use crate::io::vfs::Vfs;
+use crate::utils::token_counter::count_tokens;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Pipeline phases in the order they run
+pub const PHASES: &[&str] = &["collect", "match", "read", "render", "tokenize"];
+
+/// Durations of each pipeline phase over several iterations
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    pub files: usize,
+    pub bytes: usize,
+    pub tokens: usize,
+    /// One duration per iteration, for each of `PHASES`
+    pub timings: Vec<Vec<Duration>>,
+}
+
+impl BenchReport {
+    /// Mean duration of a phase across iterations
+    pub fn mean(&self, phase: usize) -> Duration {
+        let samples = &self.timings[phase];
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+        samples.iter().sum::<Duration>() / samples.len() as u32
+    }
+
+    pub fn min(&self, phase: usize) -> Duration {
+        self.timings[phase]
+            .iter()
+            .min()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn format_table(&self) -> String {
+        let total: Duration = (0..PHASES.len()).map(|i| self.mean(i)).sum();
+        let mut table = format!(
+            "{:<10} {:>12} {:>12} {:>7}\n",
+            "phase", "mean", "min", "share"
+        );
+
+        for (i, phase) in PHASES.iter().enumerate() {
+            let mean = self.mean(i);
+            table.push_str(&format!(
+                "{:<10} {:>12} {:>12} {:>6.1}%\n",
+                phase,
+                format!("{:.2?}", mean),
+                format!("{:.2?}", self.min(i)),
+                if total.is_zero() {
+                    0.0
+                } else {
+                    mean.as_secs_f64() * 100.0 / total.as_secs_f64()
+                }
+            ));
+        }
+        table.push_str(&format!(
+            "{:<10} {:>12}\n",
+            "total",
+            format!("{:.2?}", total)
+        ));
+        table
+    }
+}
+
+/// Collect files below `paths` without printing the file tree
+pub async fn collect_phase(vfs: &impl Vfs, paths: &[PathBuf]) -> Result<Vec<CollectedFile>> {
+    let options = CollectOptions {
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    collect_files_with(vfs, paths, &options).await
+}
+
+/// Run the default include and exclude patterns against every file and its directories
+pub fn match_phase(files: &[CollectedFile]) -> usize {
+    let to_strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    let exclude = PatternMatcher::new(&to_strings(DEFAULT_EXCLUDE_PATTERNS));
+    let include = PatternMatcher::new(&to_strings(DEFAULT_INCLUDE_PATTERNS));
+
+    let mut matches = 0;
+    for file in files {
+        for ancestor in file.relative_path.ancestors().skip(1) {
+            matches += usize::from(exclude.matches_dir(ancestor));
+        }
+        matches += usize::from(include.matches_path(&file.relative_path));
+    }
+    matches
+}
+
+/// Read every file, returning the total number of bytes
+pub async fn read_phase(vfs: &impl Vfs, files: &[CollectedFile]) -> usize {
+    let mut bytes = 0;
+    for file in files {
+        if let Ok(content) = vfs.read(&file.path).await {
+            bytes += content.len();
+        }
+    }
+    bytes
+}
+
+/// Render the markdown snapshot without printing progress
+pub async fn render_phase(vfs: &impl Vfs, files: &[CollectedFile]) -> Result<String> {
+    let options = RenderOptions {
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    concatenate_files_with(vfs, files, &options).await
+}
+
+/// Time each pipeline phase on `paths` over `iterations` runs
+pub async fn bench_pipeline(
+    vfs: &impl Vfs,
+    paths: &[PathBuf],
+    iterations: usize,
+) -> Result<BenchReport> {
+    let mut report = BenchReport {
+        timings: vec![Vec::with_capacity(iterations); PHASES.len()],
+        ..BenchReport::default()
+    };
+
+    for _ in 0..iterations.max(1) {
+        let start = Instant::now();
+        let files = collect_phase(vfs, paths).await?;
+        report.timings[0].push(start.elapsed());
+
+        let start = Instant::now();
+        std::hint::black_box(match_phase(&files));
+        report.timings[1].push(start.elapsed());
+
+        let start = Instant::now();
+        report.bytes = read_phase(vfs, &files).await;
+        report.timings[2].push(start.elapsed());
+
+        let start = Instant::now();
+        let output = render_phase(vfs, &files).await?;
+        report.timings[3].push(start.elapsed());
+
+        let start = Instant::now();
+        report.tokens = count_tokens(&output);
+        report.timings[4].push(start.elapsed());
+
+        report.files = files.len();
+    }
+
+    Ok(report)
+}
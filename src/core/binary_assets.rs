@@ -0,0 +1,177 @@
+use crate::core::content_processor::OutputFormat;
+use crate::utils::file_metadata::sha256_hex;
+use crate::utils::filetype::magic_mime;
+use crate::utils::html::escape_html;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// What becomes of binary files in a snapshot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BinaryMode {
+    /// Leave them out without a trace
+    #[default]
+    Skip,
+    /// List each one's path, size, type and hash under a "Binary Assets" heading
+    Stub,
+}
+
+/// Structure annotation of binary files listed as stubs
+pub const BINARY_ANNOTATION: &str = "binary";
+
+/// Length of the sha256 hex prefix shown for each asset, as for file metadata
+const HASH_PREFIX_LEN: usize = 12;
+
+/// A binary file found during collection, described instead of included
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BinaryAsset {
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Path relative to the current directory, as shown in the structure
+    #[serde(rename = "path")]
+    pub display_path: String,
+    pub size: u64,
+    pub mime: &'static str,
+    /// `None` for files over `--max-size-mb`, which aren't read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl BinaryAsset {
+    /// One-line description: `image/png · 5120 bytes · sha256 0123456789ab`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} · {} bytes", self.mime, self.size);
+        if let Some(sha256) = &self.sha256 {
+            summary.push_str(&format!(" · sha256 {}", &sha256[..HASH_PREFIX_LEN]));
+        }
+        summary
+    }
+}
+
+/// Binary files found below the input directories. Clones share the same record, so
+/// collection can fill it in while rendering reads it afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct BinaryAssets {
+    assets: Arc<Mutex<Vec<BinaryAsset>>>,
+}
+
+impl BinaryAssets {
+    pub fn record(&self, path: &Path, size: u64, content: Option<&[u8]>) {
+        let mut assets = self.assets.lock().unwrap();
+        if assets.iter().any(|asset| asset.path == path) {
+            return;
+        }
+
+        debug!("Listing binary asset {}", path.display());
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        assets.push(BinaryAsset {
+            path: path.to_path_buf(),
+            display_path: encode_path(&relative_to(path, &current_dir)),
+            size,
+            mime: guess_mime(path, content.unwrap_or_default()),
+            sha256: content.map(sha256_hex),
+        });
+    }
+
+    /// Recorded assets, ordered by path
+    pub fn assets(&self) -> Vec<BinaryAsset> {
+        let mut assets = self.assets.lock().unwrap().clone();
+        assets.sort_by(|a, b| a.path.cmp(&b.path));
+        assets
+    }
+
+    /// "Binary Assets" section listing each recorded asset, or a `<binary_assets>` element in
+    /// XML; empty when there are none
+    pub fn section(&self, format: OutputFormat) -> String {
+        let assets = self.assets();
+        if assets.is_empty() {
+            return String::new();
+        }
+
+        if format == OutputFormat::Xml {
+            let mut section = String::from("<binary_assets>\n");
+            for asset in assets {
+                section.push_str(&format!(
+                    "<asset path=\"{}\" mime=\"{}\" size=\"{}\"",
+                    escape_html(&asset.display_path),
+                    asset.mime,
+                    asset.size
+                ));
+                if let Some(sha256) = &asset.sha256 {
+                    section.push_str(&format!(" sha256=\"{}\"", sha256));
+                }
+                section.push_str("/>\n");
+            }
+            section.push_str("</binary_assets>\n\n");
+            return section;
+        }
+
+        let mut section = String::from(
+            "# Binary Assets\n\n*These binary files exist but their contents are not included:*\n\n",
+        );
+        for asset in assets {
+            section.push_str(&format!(
+                "- `{}`: {}\n",
+                asset.display_path,
+                asset.summary()
+            ));
+        }
+        section.push('\n');
+        section
+    }
+}
+
+/// MIME type of a binary file, from its extension or failing that its first bytes
+pub fn guess_mime(path: &Path, content: &[u8]) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    if let Some(mime) = extension.as_deref().and_then(mime_from_extension) {
+        return mime;
+    }
+
+    magic_mime(content).unwrap_or("application/octet-stream")
+}
+
+/// MIME type registered for a lowercase file extension
+fn mime_from_extension(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ico" => "image/vnd.microsoft.icon",
+        "tif" | "tiff" => "image/tiff",
+        "psd" => "image/vnd.adobe.photoshop",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "avi" => "video/x-msvideo",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "7z" => "application/x-7z-compressed",
+        "rar" => "application/vnd.rar",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "wasm" => "application/wasm",
+        _ => return None,
+    })
+}
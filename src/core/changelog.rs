@@ -0,0 +1,69 @@
+use crate::io::vfs::Vfs;
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Default changelog written next to the patched code
+pub const DEFAULT_CHANGELOG: &str = "CHANGES.catnip.md";
+
+const CHANGELOG_HEADER: &str = "# Changes applied with catnip\n";
+
+/// A file an applied patch created or updated
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedFile {
+    pub path: PathBuf,
+    pub updates: usize,
+    pub created: bool,
+    pub deleted: bool,
+    /// Where a renamed file was moved from
+    pub renamed_from: Option<PathBuf>,
+}
+
+/// Changelog section for one applied patch
+pub fn format_entry(analysis: &str, applied_at: SystemTime, files: &[ChangedFile]) -> String {
+    let mut entry = String::new();
+    let _ = writeln!(
+        entry,
+        "\n## {}\n",
+        humantime::format_rfc3339_seconds(applied_at)
+    );
+    if !analysis.trim().is_empty() {
+        let _ = writeln!(entry, "{}\n", analysis.trim());
+    }
+    for file in files {
+        let mut what = if file.deleted {
+            "deleted".to_string()
+        } else if file.created {
+            "created".to_string()
+        } else if file.updates == 1 {
+            "1 update".to_string()
+        } else {
+            format!("{} updates", file.updates)
+        };
+        if let Some(from) = &file.renamed_from {
+            what = match file.updates {
+                0 => format!("renamed from `{}`", from.display()),
+                _ => format!("renamed from `{}`, {}", from.display(), what),
+            };
+        }
+        let _ = writeln!(entry, "- `{}` ({})", file.path.display(), what);
+    }
+    entry
+}
+
+/// Append `entry` to the changelog at `path`, starting the file if there is none yet
+pub async fn append_changelog(vfs: &impl Vfs, path: &Path, entry: &str) -> Result<()> {
+    let mut content = if vfs.exists(path).await {
+        vfs.read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read changelog: {}", path.display()))?
+    } else {
+        CHANGELOG_HEADER.to_string()
+    };
+
+    content.push_str(entry);
+    vfs.write(path, content.as_bytes())
+        .await
+        .with_context(|| format!("Failed to write changelog: {}", path.display()))
+}
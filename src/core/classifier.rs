@@ -0,0 +1,277 @@
+use crate::core::rust_cfg::blank_literals;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Directory names that conventionally hold tests, benchmarks or fixtures
+const TEST_DIRECTORIES: &[&str] = &[
+    "test",
+    "tests",
+    "__tests__",
+    "__mocks__",
+    "spec",
+    "specs",
+    "bench",
+    "benches",
+    "benchmarks",
+    "fixtures",
+    "testdata",
+    "test_data",
+];
+
+pub fn is_test_directory(name: &str) -> bool {
+    TEST_DIRECTORIES.contains(&name)
+}
+
+/// Whether a path is test, bench or fixture code by naming convention
+pub fn is_test_path(path: &Path) -> bool {
+    let in_test_directory = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .filter_map(|component| component.as_os_str().to_str())
+        .any(is_test_directory);
+    if in_test_directory {
+        return true;
+    }
+
+    let Some(filename) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let (stem, extension) = filename.rsplit_once('.').unwrap_or((filename, ""));
+
+    match extension {
+        // foo_test.go
+        "go" => stem.ends_with("_test"),
+        // test_foo.py, foo_test.py, conftest.py
+        "py" => stem.starts_with("test_") || stem.ends_with("_test") || stem == "conftest",
+        // foo.test.ts, foo.spec.js
+        "js" | "jsx" | "ts" | "tsx" | "mjs" => stem.ends_with(".test") || stem.ends_with(".spec"),
+        // FooTest.java, FooTests.kt
+        "java" | "kt" | "scala" | "cs" => stem.ends_with("Test") || stem.ends_with("Tests"),
+        // foo_spec.rb, foo_test.rb
+        "rb" => stem.ends_with("_spec") || stem.ends_with("_test"),
+        // foo_test.rs
+        "rs" => stem.ends_with("_test") || stem.ends_with("_tests"),
+        _ => false,
+    }
+}
+
+/// Name of the module a test file exercises by naming convention: `parser` for
+/// `parser_test.go`, `test_parser.py`, `parser.spec.ts` or `ParserTest.java`
+fn tested_module(path: &Path) -> Option<String> {
+    let filename = path.file_name()?.to_str()?;
+    let stem = filename.rsplit_once('.').map_or(filename, |(stem, _)| stem);
+    let stem = stem.strip_prefix("test_").unwrap_or(stem);
+    let stem = [
+        "_tests", "_test", "_spec", ".test", ".spec", "Tests", "Test",
+    ]
+    .iter()
+    .find_map(|suffix| stem.strip_suffix(suffix))
+    .unwrap_or(stem);
+    (!stem.is_empty()).then(|| stem.to_lowercase())
+}
+
+/// Name a source file is imported by: its stem, or its directory's for `mod.rs`,
+/// `index.ts` and `__init__.py`
+fn module_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let name = match stem {
+        "mod" | "index" | "__init__" => path.parent()?.file_name()?.to_str()?,
+        _ => stem,
+    };
+    Some(name.to_lowercase())
+}
+
+/// Keywords that introduce an import, ahead of the path it imports
+const IMPORT_KEYWORDS: &[&str] = &[
+    "use", "pub", "import", "from", "include", "require", "mod", "extern", "type", "typeof",
+    "export", "default", "static",
+];
+
+/// Keywords inside an import path, which never name a module of the project
+const PATH_KEYWORDS: &[&str] = &["as", "crate", "super", "self"];
+
+/// Standard library roots, which don't name a module of the project when a path starts
+/// with them
+const STD_ROOTS: &[&str] = &["std", "core", "alloc", "java", "javax"];
+
+/// Words of an import line that may name a module of the project
+fn import_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut at_root = true;
+    for word in line
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+    {
+        if IMPORT_KEYWORDS.contains(&word.as_str()) {
+            continue;
+        }
+        let is_root = std::mem::replace(&mut at_root, false);
+        if PATH_KEYWORDS.contains(&word.as_str()) || (is_root && STD_ROOTS.contains(&word.as_str()))
+        {
+            continue;
+        }
+        words.push(word);
+    }
+    words
+}
+
+/// Words on the lines of `content` that import other modules
+fn imported_names(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| {
+            [
+                "use ", "pub use ", "import ", "from ", "#include", "require ",
+            ]
+            .iter()
+            .any(|keyword| line.starts_with(keyword))
+                || line.contains("require(")
+        })
+        .flat_map(import_words)
+}
+
+/// Which of `files`, as relative paths and contents, `--tests-only` keeps: test files,
+/// sources with inline `#[cfg(test)]` modules, and the code under test, which a test file
+/// names by its own name or in its imports
+pub fn select_tests_and_tested(files: &[(&Path, &str)]) -> Vec<bool> {
+    let mut tested: HashSet<String> = HashSet::new();
+    for (path, content) in files.iter().filter(|(path, _)| is_test_path(path)) {
+        tested.extend(tested_module(path));
+        tested.extend(imported_names(content));
+    }
+
+    files
+        .iter()
+        .map(|(path, content)| {
+            is_test_path(path)
+                || (path.extension().is_some_and(|extension| extension == "rs")
+                    && !split_rust_test_modules(content).1.is_empty())
+                || module_name(path).is_some_and(|name| tested.contains(&name))
+        })
+        .collect()
+}
+
+/// File names that are always machine-generated
+const GENERATED_FILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "go.sum",
+    "flake.lock",
+];
+
+/// Markers that code generators conventionally put in a file's header
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "auto-generated",
+    "autogenerated",
+    "automatically generated",
+    "code generated by",
+];
+
+/// Whether a file is machine-generated: lockfiles, minified bundles or files whose
+/// header carries a generator marker
+pub fn is_generated_file(path: &Path, content: &str) -> bool {
+    let Some(filename) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if GENERATED_FILE_NAMES.contains(&filename)
+        || filename.contains(".min.")
+        || filename.ends_with(".pb.go")
+        || filename.ends_with("_pb2.py")
+    {
+        return true;
+    }
+
+    content.lines().take(5).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+    })
+}
+
+/// Which part of a codebase a snapshot should cover
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TestFilter {
+    #[default]
+    All,
+    /// Drop test code entirely
+    ExcludeTests,
+    /// Keep test code in full, reducing the code under test to an outline and leaving out
+    /// everything else
+    OnlyTests,
+}
+
+/// Remove inline test modules (`#[cfg(test)] mod tests { ... }`) from source code
+pub fn strip_test_code(content: &str, language: &str) -> String {
+    match language {
+        "rust" => split_rust_test_modules(content).0,
+        _ => content.to_string(),
+    }
+}
+
+/// Extract inline test modules from source code, dropping everything else
+pub fn extract_test_code(content: &str, language: &str) -> String {
+    match language {
+        "rust" => split_rust_test_modules(content).1,
+        _ => String::new(),
+    }
+}
+
+/// Split Rust source into its production code and its `#[cfg(test)]` modules. Attributes
+/// and braces are looked for outside comments and string and char literals.
+fn split_rust_test_modules(content: &str) -> (String, String) {
+    let mut production = String::with_capacity(content.len());
+    let mut tests = String::new();
+    let blanked = blank_literals(content);
+    // Blanking keeps every line break, so each line pairs with its blanked code
+    let mut lines = content.lines().zip(blanked.lines()).peekable();
+
+    while let Some((line, code)) = lines.next() {
+        if code.trim() != "#[cfg(test)]" {
+            production.push_str(line);
+            production.push('\n');
+            continue;
+        }
+
+        // Only treat the attribute as a test module when it guards a module with a body
+        let is_module = lines.peek().is_some_and(|(_, next)| {
+            let next = next.trim_start();
+            (next.starts_with("mod ") || next.starts_with("pub mod ")) && next.contains('{')
+        });
+        if !is_module {
+            production.push_str(line);
+            production.push('\n');
+            continue;
+        }
+
+        tests.push_str(line);
+        tests.push('\n');
+
+        let mut depth = 0usize;
+        for (module_line, module_code) in lines.by_ref() {
+            tests.push_str(module_line);
+            tests.push('\n');
+
+            depth += module_code.matches('{').count();
+            depth = depth.saturating_sub(module_code.matches('}').count());
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    if !content.ends_with('\n') {
+        production.pop();
+    }
+
+    (production, tests)
+}
@@ -0,0 +1,69 @@
+use crate::utils::text_processing::extract_outline;
+use regex::Regex;
+use serde::Serialize;
+
+/// Rough size and shape of one file, for pointing a model at the modules that need the
+/// most attention
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Complexity {
+    /// Non-blank lines
+    pub loc: usize,
+    /// Functions and methods in the file's outline
+    pub functions: usize,
+    /// Deepest indentation level of any line, in the file's own indent width
+    pub max_nesting: usize,
+}
+
+impl Complexity {
+    pub fn measure(content: &str, language: &str) -> Self {
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        // Files without an outline grammar come back whole, and have no functions to count
+        let outline = extract_outline(content, language);
+        let functions = if outline == content {
+            0
+        } else {
+            // Java-style methods have no keyword, just a parameter list not following a type keyword
+            let keyword = Regex::new(r"\b(fn|def|func|function|fun)\b").unwrap();
+            let type_keyword =
+                Regex::new(r"\b(struct|class|enum|interface|trait|object|type)\b").unwrap();
+            outline
+                .lines()
+                .filter(|line| {
+                    keyword.is_match(line) || (line.contains('(') && !type_keyword.is_match(line))
+                })
+                .count()
+        };
+
+        let indents: Vec<usize> = lines.iter().map(|line| indent_width(line)).collect();
+        let unit = indents
+            .iter()
+            .copied()
+            .filter(|&w| w > 0)
+            .min()
+            .unwrap_or(1);
+        let max_nesting = indents.iter().map(|w| w / unit).max().unwrap_or(0);
+
+        Self {
+            loc: lines.len(),
+            functions,
+            max_nesting,
+        }
+    }
+
+    /// Heading annotation, e.g. `120 LOC, 8 functions, nesting 3`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} LOC, {} functions, nesting {}",
+            self.loc, self.functions, self.max_nesting
+        )
+    }
+}
+
+/// Leading whitespace width, a tab counting as four spaces
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
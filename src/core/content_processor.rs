@@ -1,81 +1,890 @@
-use crate::core::structure_generator::generate_directory_structure;
-use crate::utils::language_detection::get_language_from_extension;
-use crate::utils::text_processing::remove_comments_and_docstrings;
-use anyhow::Result;
-use std::path::PathBuf;
-use tokio::fs;
-use tracing::{debug, instrument, warn};
-
-#[instrument(skip(files))]
+use crate::config::settings::ProcessingRule;
+use crate::core::anonymizer::Anonymization;
+use crate::core::binary_assets::{BINARY_ANNOTATION, BinaryAsset, BinaryAssets};
+use crate::core::classifier::{
+    TestFilter, extract_test_code, is_generated_file, is_test_path, strip_test_code,
+};
+use crate::core::complexity::Complexity;
+use crate::core::dir_summary::{DirectorySummary, summarize_directories};
+use crate::core::file_collector::CollectedFile;
+use crate::core::io_errors::IoErrors;
+use crate::core::licenses::LicenseNotice;
+use crate::core::manifest::Manifest;
+use crate::core::packer::{Representation, summarize};
+use crate::core::pattern_matcher::{PatternMatcher, PatternSource};
+use crate::core::rust_cfg::RustCfg;
+use crate::core::snapshot::FileStatus;
+use crate::core::structure_generator::{
+    StructureNode, generate_structure_tree, write_annotated_structure,
+};
+use crate::core::warnings::{WarningKind, Warnings};
+use crate::integrations::git::{
+    NOT_MATERIALIZED, NotMaterialized, SubmoduleMode, Submodules, normalize_eol,
+};
+use crate::io::encryption::EncryptionTarget;
+use crate::io::vfs::{TokioFs, Vfs};
+use crate::utils::file_metadata::FileMetadata;
+use crate::utils::html::escape_html;
+use crate::utils::language_detection::{
+    LanguageCategory, get_language_category, get_language_from_extension,
+};
+use crate::utils::path_encoding::{encode_path, relative_to};
+use crate::utils::text_processing::{extract_outline, remove_comments_and_docstrings};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{Instrument, debug, instrument, trace_span};
+
+/// How file headings are arranged in the rendered document
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// One `##` heading per file
+    #[default]
+    Flat,
+    /// `##` headings per top-level directory with `###` file headings beneath
+    Grouped,
+}
+
+/// What files are grouped by in the `grouped` layout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// Top-level directory
+    #[default]
+    Directory,
+    /// Language, with code first, then configuration, then documentation
+    Language,
+}
+
+/// Location markers added to the rendered document
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Anchors {
+    #[default]
+    None,
+    /// `path:line` headings and chunk markers an editor or terminal can jump to
+    Editor,
+}
+
+/// Markup the rendered document is written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Headings and fenced code blocks
+    #[default]
+    Markdown,
+    /// A `<documents>` element with one `<document path=...>` per file, as Claude's prompting
+    /// guides suggest; contents are left unescaped, like the documents the guides show
+    Xml,
+    /// One JSON document with the structure tree and each file's language and contents, for
+    /// feeding other tools
+    Json,
+}
+
+/// Write an italic remark in markdown, or a `<note>` element in XML
+fn write_note(result: &mut String, format: OutputFormat, note: &str) {
+    let _ = match format {
+        OutputFormat::Xml => writeln!(result, "<note>{}</note>\n", escape_html(note)),
+        _ => writeln!(result, "*{}*\n", note),
+    };
+}
+
+/// Lines per fenced chunk when files are rendered with editor anchors
+pub const ANCHOR_CHUNK_LINES: usize = 100;
+
+/// Write `content` as fenced chunks of `ANCHOR_CHUNK_LINES` lines, each after the first
+/// preceded by its `path:line` marker
+fn write_anchored_chunks(result: &mut String, path: &str, fence: &str, content: &str) {
+    let lines: Vec<&str> = content.lines().collect();
+    for (i, chunk) in lines.chunks(ANCHOR_CHUNK_LINES).enumerate() {
+        if i > 0 {
+            let _ = writeln!(result, "`{}:{}`\n", path, i * ANCHOR_CHUNK_LINES + 1);
+        }
+        let _ = writeln!(result, "```{}", fence);
+        result.push_str(&chunk.join("\n"));
+        result.push_str("\n```\n\n");
+    }
+}
+
+/// Heading and sort key of the group a file belongs to
+fn group_of(relative_path: &Path, group_by: GroupBy) -> (LanguageCategory, String) {
+    match group_by {
+        GroupBy::Directory => (LanguageCategory::Code, top_level_group(relative_path)),
+        GroupBy::Language => {
+            let language = get_language_from_extension(relative_path);
+            let category = get_language_category(language);
+            let name = match category {
+                LanguageCategory::Code => language.to_string(),
+                LanguageCategory::Config => "configuration".to_string(),
+                LanguageCategory::Docs => "documentation".to_string(),
+            };
+            (category, name)
+        }
+    }
+}
+
+/// Top-level directory a file is grouped under, `.` for files at the root
+fn top_level_group(relative_path: &Path) -> String {
+    match relative_path.parent().and_then(|p| p.components().next()) {
+        Some(component) => component.as_os_str().to_string_lossy().to_string(),
+        None => ".".to_string(),
+    }
+}
+
+/// One of several outputs a snapshot is split into to stay under a token limit. Every part
+/// has the whole structure tree, but only its own files' contents.
+#[derive(Clone, Debug, Default)]
+pub struct Part {
+    /// 1-based
+    pub index: usize,
+    pub total: usize,
+    pub files: HashSet<PathBuf>,
+}
+
+impl Part {
+    /// Sections about the snapshot as a whole, such as caveats, close the last part only
+    pub fn is_last(&self) -> bool {
+        self.index == self.total
+    }
+}
+
+/// Where part `index` of a split snapshot written to `output` goes, e.g. `snapshot.part2.md`
+pub fn part_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{}.part{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.part{}", stem, index),
+    };
+    output.with_file_name(name)
+}
+
+/// Options controlling how collected files are rendered
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    pub output_file: Option<String>,
+    /// Encrypt what is written to `output_file` for this recipient
+    pub encrypt: Option<EncryptionTarget>,
+    pub ignore_comments: bool,
+    pub ignore_docstrings: bool,
+    pub test_filter: TestFilter,
+    /// Drop Rust code behind `#[cfg(...)]` attributes this configuration turns off
+    pub rust_cfg: Option<RustCfg>,
+    pub file_meta: bool,
+    /// Note each file's line count, functions and nesting depth below its heading
+    pub complexity: bool,
+    /// Status of each file relative to a previous snapshot; only changed files are rendered
+    pub delta: Option<BTreeMap<PathBuf, FileStatus>>,
+    /// Representation chosen for each file to fit a context window; missing files render in full
+    pub packing: Option<BTreeMap<PathBuf, Representation>>,
+    /// Front-load a README excerpt or synthesized summary for each directory
+    pub dir_summaries: bool,
+    pub layout: Layout,
+    pub group_by: GroupBy,
+    pub io_errors: IoErrors,
+    /// Per-pattern overrides of `ignore_comments` and `ignore_docstrings`
+    pub processing: Vec<ProcessingRule>,
+    /// Fence tag to use for a detected language, when not the language name itself
+    pub fence_aliases: BTreeMap<String, String>,
+    /// Files left out or cut down; unreadable files found while rendering are added
+    pub warnings: Warnings,
+    /// End with a section listing the recorded warnings
+    pub caveats: bool,
+    /// Submodules found during collection, rendered as `submodule_mode` says
+    pub submodules: Submodules,
+    pub submodule_mode: SubmoduleMode,
+    /// Files a sparse checkout left off disk, listed in the structure
+    pub not_materialized: NotMaterialized,
+    /// Binary files listed in the structure and a closing section instead of included
+    pub binary_assets: BinaryAssets,
+    /// Licenses of the rendered files, listed in a closing section
+    pub license_notice: Option<LicenseNotice>,
+    /// Embedded run manifest, appended after everything else
+    pub manifest: Option<String>,
+    /// `--anonymize`: replace the values it matches anywhere in the output, paths included
+    pub anonymization: Option<Arc<Anonymization>>,
+    pub anchors: Anchors,
+    pub format: OutputFormat,
+    /// Render only this part's files
+    pub part: Option<Part>,
+    /// Don't print progress to stdout
+    pub quiet: bool,
+}
+
+/// Comment and docstring stripping resolved for one file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stripping {
+    comments: bool,
+    docstrings: bool,
+}
+
+/// `[[process]]` rules compiled for matching
+struct ProcessingRules<'a> {
+    rules: Vec<(PatternMatcher, &'a ProcessingRule)>,
+}
+
+impl<'a> ProcessingRules<'a> {
+    fn new(rules: &'a [ProcessingRule]) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .map(|rule| {
+                    let matcher = PatternMatcher::builder()
+                        .add(&rule.pattern, PatternSource::Config)
+                        .build();
+                    (matcher, rule)
+                })
+                .collect(),
+        }
+    }
+
+    /// Stripping for `relative_path`, starting from the command-line flags
+    fn resolve(&self, relative_path: &Path, options: &RenderOptions) -> Stripping {
+        let mut stripping = Stripping {
+            comments: options.ignore_comments,
+            docstrings: options.ignore_docstrings,
+        };
+        for (matcher, rule) in &self.rules {
+            if matcher.matches_path(relative_path) {
+                stripping.comments = rule.ignore_comments.unwrap_or(stripping.comments);
+                stripping.docstrings = rule.ignore_docstrings.unwrap_or(stripping.docstrings);
+            }
+        }
+        stripping
+    }
+}
+
 pub async fn concatenate_files(
     files: &[PathBuf],
     output_file: Option<&str>,
     ignore_comments: bool,
     ignore_docstrings: bool,
 ) -> Result<String> {
-    println!("\n🔨 Processing {} files...", files.len());
-    let mut result = String::new();
-
-    // Generate directory structure
-    result.push_str("# Project Structure\n\n");
-    result.push_str("```\n");
-    let structure = generate_directory_structure(files);
-    for line in structure {
-        result.push_str(&line);
+    let options = RenderOptions {
+        output_file: output_file.map(str::to_string),
+        ignore_comments,
+        ignore_docstrings,
+        ..RenderOptions::default()
+    };
+
+    let mut collected = Vec::new();
+    for file in files {
+        match CollectedFile::load(&TokioFs, file).await {
+            Ok(file) => collected.push(file),
+            // Unreadable files are still listed, with the error in place of their content
+            Err(_) => collected.push(CollectedFile::new(file, file, &[], None)),
+        }
+    }
+    concatenate_files_with(&TokioFs, &collected, &options).await
+}
+
+#[instrument(name = "render", skip(vfs, files, options))]
+pub async fn concatenate_files_with(
+    vfs: &impl Vfs,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+) -> Result<String> {
+    if !options.quiet {
+        println!("\n🔨 Processing {} files...", files.len());
+    }
+
+    // Generate directory structure, including removed files when rendering a delta
+    let mut tree_files = CollectedFile::paths(files);
+    let mut annotations = BTreeMap::new();
+    if let Some(delta) = &options.delta {
+        for (path, status) in delta {
+            if *status == FileStatus::Removed {
+                tree_files.push(path.clone());
+            }
+            annotations.insert(path.clone(), status.label().to_string());
+        }
+    }
+    for path in options.not_materialized.files() {
+        annotations.insert(path.clone(), NOT_MATERIALIZED.to_string());
+        tree_files.push(path);
+    }
+    for asset in options.binary_assets.assets() {
+        annotations.insert(asset.path.clone(), BINARY_ANNOTATION.to_string());
+        tree_files.push(asset.path);
+    }
+
+    let result = match options.format {
+        OutputFormat::Json => render_json(vfs, files, options, &tree_files, &annotations).await?,
+        OutputFormat::Markdown | OutputFormat::Xml => {
+            render_text(vfs, files, options, &tree_files, &annotations).await?
+        }
+    };
+    let result = match &options.anonymization {
+        Some(anonymization) => anonymization.conceal(&result),
+        None => result,
+    };
+
+    if !options.quiet {
+        println!("\n📝 Total content: {} characters", result.len());
+    }
+
+    if let Some(output_path) = &options.output_file {
+        match &options.encrypt {
+            Some(target) => {
+                let encrypted = target.encrypt(result.as_bytes())?;
+                vfs.write(Path::new(output_path), &encrypted).await?;
+                println!(
+                    "🔒 Output encrypted for {} and written to: {}",
+                    target, output_path
+                );
+            }
+            None => {
+                vfs.write(Path::new(output_path), result.as_bytes()).await?;
+                println!("💾 Output written to: {}", output_path);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// A file read and processed for rendering, whatever the output format
+struct PreparedFile {
+    encoded_path: String,
+    language: &'static str,
+    representation: Representation,
+    metadata: Option<FileMetadata>,
+    complexity: Option<Complexity>,
+    body: FileBody,
+}
+
+enum FileBody {
+    /// One-line description standing in for the content
+    Summary(String),
+    Content {
+        content: String,
+        /// Still line for line the file on disk, so editor chunk markers hold
+        unchanged: bool,
+    },
+}
+
+/// Read and process one file, or `None` when it is left out or can't be read
+async fn prepare_file(
+    vfs: &impl Vfs,
+    file: &CollectedFile,
+    options: &RenderOptions,
+    processing: &ProcessingRules<'_>,
+    current_dir: &Path,
+) -> Result<Option<PreparedFile>> {
+    let file_path = &file.path;
+    let relative_path = &relative_to(file_path, current_dir);
+
+    if let Some(delta) = &options.delta
+        && !delta
+            .get(file_path)
+            .is_some_and(|status| status.is_changed())
+    {
+        return Ok(None);
+    }
+    if let Some(part) = &options.part
+        && !part.files.contains(file_path)
+    {
+        return Ok(None);
+    }
+
+    let mut representation = options
+        .packing
+        .as_ref()
+        .and_then(|packing| packing.get(file_path).copied())
+        .unwrap_or(Representation::Full);
+    if representation == Representation::Full
+        && options.submodule_mode == SubmoduleMode::Outline
+        && options.submodules.contains(file_path)
+    {
+        representation = Representation::Outline;
+    }
+    if representation == Representation::Omitted {
+        debug!("Omitting {} to fit the context window", file_path.display());
+        return Ok(None);
+    }
+
+    // Unreadable files are listed together at the end rather than as stubs in the body
+    let content = match vfs
+        .read_text(file_path)
+        .instrument(trace_span!("read"))
+        .await
+    {
+        Ok(content) => content,
+        Err(e) => {
+            options.io_errors.skip(file_path, &e)?;
+            options
+                .warnings
+                .push(WarningKind::Unreadable, relative_path, e.to_string());
+            if !options.quiet {
+                println!("  ✗ {} - Error: {}", relative_path.display(), e);
+            }
+            return Ok(None);
+        }
+    };
+
+    let language = file.language;
+    // Text with a declared line ending is shown the way git stores it
+    let content = match file.eol {
+        Some(_) => normalize_eol(&content),
+        None => content,
+    };
+    // Chunk markers only hold while the lines are the ones on disk
+    let original = (options.format == OutputFormat::Markdown && options.anchors == Anchors::Editor)
+        .then(|| content.clone());
+
+    let metadata = options.file_meta.then(|| FileMetadata {
+        size: file.size,
+        lines: content.lines().count(),
+        modified: file.modified,
+        sha256: file.sha256.clone(),
+    });
+    let complexity = options
+        .complexity
+        .then(|| Complexity::measure(&content, language));
+    let prepared = |body| PreparedFile {
+        encoded_path: encode_path(relative_path),
+        language,
+        representation,
+        metadata,
+        complexity,
+        body,
+    };
+
+    let content = trace_span!("strip").in_scope(|| match options.test_filter {
+        TestFilter::All => content,
+        TestFilter::ExcludeTests => strip_test_code(&content, language),
+        TestFilter::OnlyTests if is_test_path(relative_path) => content,
+        TestFilter::OnlyTests => {
+            let outline = extract_outline(&strip_test_code(&content, language), language);
+            let tests = extract_test_code(&content, language);
+            if tests.is_empty() {
+                outline
+            } else {
+                format!("{}\n\n{}", outline, tests)
+            }
+        }
+    });
+    let content = match &options.rust_cfg {
+        Some(cfg) if language == "rust" => {
+            let cfg = cfg.for_file(vfs, file_path).await;
+            trace_span!("strip").in_scope(|| cfg.strip(&content))
+        }
+        _ => content,
+    };
+    if representation == Representation::Summary {
+        let generated = is_generated_file(file_path, &content);
+        if !options.quiet {
+            println!("  ✓ {} (summary)", relative_path.display());
+        }
+        return Ok(Some(prepared(FileBody::Summary(summarize(
+            &content, generated,
+        )))));
+    }
+    let content = representation
+        .render(&content, language, false)
+        .unwrap_or_default();
+
+    let stripping = processing.resolve(relative_path, options);
+    let processed_content = trace_span!("strip").in_scope(|| {
+        remove_comments_and_docstrings(&content, language, stripping.comments, stripping.docstrings)
+    });
+
+    if !options.quiet {
+        println!(
+            "  ✓ {} ({} chars, {})",
+            relative_path.display(),
+            processed_content.len(),
+            language
+        );
+    }
+    debug!(
+        "Added file: {} ({} chars)",
+        relative_path.display(),
+        processed_content.len()
+    );
+
+    Ok(Some(prepared(FileBody::Content {
+        unchanged: original.as_ref() == Some(&processed_content),
+        content: processed_content,
+    })))
+}
+
+/// Remarks on what the snapshot leaves out, shown before the file contents
+fn snapshot_notes(options: &RenderOptions, current_dir: &Path) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    if let Some(part) = &options.part {
+        notes.push(format!(
+            "Part {} of {}: the structure lists every file, but only some of their contents are in this part.",
+            part.index, part.total
+        ));
+    }
+
+    if let Some(delta) = &options.delta {
+        let changed = delta.values().filter(|status| status.is_changed()).count();
+        notes.push(format!(
+            "Only the {} files changed since the previous snapshot are shown.",
+            changed
+        ));
+    }
+
+    if let Some(packing) = &options.packing {
+        let reduced = packing
+            .values()
+            .filter(|r| **r != Representation::Full)
+            .count();
+        if reduced > 0 {
+            notes.push(format!(
+                "{} files are minified, outlined, summarized or omitted to fit the context window.",
+                reduced
+            ));
+        }
+    }
+
+    if options.submodule_mode == SubmoduleMode::Skip {
+        let skipped: Vec<_> = options
+            .submodules
+            .dirs()
+            .iter()
+            .map(|dir| format!("`{}`", encode_path(&relative_to(dir, current_dir))))
+            .collect();
+        if !skipped.is_empty() {
+            notes.push(format!(
+                "Git submodules left out of this snapshot: {}.",
+                skipped.join(", ")
+            ));
+        }
+    }
+
+    notes
+}
+
+/// Render as markdown or XML
+async fn render_text(
+    vfs: &impl Vfs,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+    tree_files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+) -> Result<String> {
+    // Room for every file's content plus its heading and fence, so big snapshots don't reallocate
+    let capacity = files
+        .iter()
+        .map(|file| file.size as usize + file.path.as_os_str().len() + 32)
+        .sum::<usize>()
+        + 1024;
+    let mut result = String::with_capacity(capacity);
+
+    let format = options.format;
+    result.push_str(match format {
+        OutputFormat::Xml => "<project_structure>\n",
+        _ => "# Project Structure\n\n```\n",
+    });
+    if format == OutputFormat::Xml {
+        let mut tree = String::new();
+        write_annotated_structure(&mut tree, tree_files, annotations);
+        result.push_str(&escape_html(&tree));
+    } else {
+        write_annotated_structure(&mut result, tree_files, annotations);
+    }
+    result.push_str(match format {
+        OutputFormat::Xml => "</project_structure>\n\n",
+        _ => "```\n\n",
+    });
+
+    if options.dir_summaries {
+        result.push_str(match format {
+            OutputFormat::Xml => "<directory_summaries>\n",
+            _ => "# Directory Summaries\n\n",
+        });
+        for summary in summarize_directories(vfs, &CollectedFile::paths(files)).await {
+            let path = summary.display_path();
+            let _ = match format {
+                OutputFormat::Xml => writeln!(
+                    result,
+                    "- {}/: {}",
+                    escape_html(&path),
+                    escape_html(&summary.text)
+                ),
+                _ => writeln!(result, "- **{}/**: {}", path, summary.text),
+            };
+        }
+        if format == OutputFormat::Xml {
+            result.push_str("</directory_summaries>\n");
+        }
         result.push('\n');
     }
-    result.push_str("```\n\n");
 
     // Add file contents
-    result.push_str("# File Contents\n\n");
+    result.push_str(match format {
+        OutputFormat::Xml => "<documents>\n",
+        _ => "# File Contents\n\n",
+    });
 
     let current_dir = std::env::current_dir().unwrap_or_default();
+    for note in snapshot_notes(options, &current_dir) {
+        write_note(&mut result, format, &note);
+    }
 
-    for file_path in files {
-        let relative_path = file_path.strip_prefix(&current_dir).unwrap_or(file_path);
+    // Keep each group's files together, preserving their order within the group
+    let mut ordered: Vec<(Option<(LanguageCategory, String)>, &CollectedFile)> = files
+        .iter()
+        .map(|file| {
+            let relative_path = &relative_to(&file.path, &current_dir);
+            let group = match options.layout {
+                Layout::Flat => None,
+                Layout::Grouped => Some(group_of(relative_path, options.group_by)),
+            };
+            (group, file)
+        })
+        .collect();
+    ordered.sort_by(|a, b| a.0.cmp(&b.0));
 
-        result.push_str(&format!("## {}\n\n", relative_path.display()));
+    let processing = ProcessingRules::new(&options.processing);
 
-        match fs::read_to_string(file_path).await {
-            Ok(content) => {
-                let language = get_language_from_extension(file_path);
-                let processed_content = remove_comments_and_docstrings(
-                    &content,
-                    language,
-                    ignore_comments,
-                    ignore_docstrings,
-                );
+    let file_heading = if options.layout == Layout::Flat {
+        "##"
+    } else {
+        "###"
+    };
+    let mut current_group = None;
+    let mut index = 0;
 
-                result.push_str(&format!("```{}\n", language));
-                result.push_str(&processed_content);
-                result.push_str("\n```\n\n");
+    for (group, file) in ordered {
+        let Some(file) = prepare_file(vfs, file, options, &processing, &current_dir).await? else {
+            continue;
+        };
 
-                println!(
-                    "  ✓ {} ({} chars, {})",
-                    relative_path.display(),
-                    processed_content.len(),
-                    language
-                );
-                debug!(
-                    "Added file: {} ({} chars)",
-                    relative_path.display(),
-                    processed_content.len()
+        if format == OutputFormat::Markdown && group.is_some() && group != current_group {
+            let name = group
+                .as_ref()
+                .map(|(_, name)| name.as_str())
+                .unwrap_or_default();
+            let _ = match options.group_by {
+                GroupBy::Directory => writeln!(result, "## {}/\n", name),
+                GroupBy::Language => writeln!(result, "## {}\n", name),
+            };
+            current_group = group;
+        }
+
+        let encoded_path = &file.encoded_path;
+        index += 1;
+        let _ = match (format, options.anchors) {
+            (OutputFormat::Xml, _) => writeln!(
+                result,
+                "<document index=\"{}\" path=\"{}\" language=\"{}\">",
+                index,
+                escape_html(encoded_path),
+                file.language
+            ),
+            (_, Anchors::None) => writeln!(result, "{} {}\n", file_heading, encoded_path),
+            (_, Anchors::Editor) => writeln!(result, "{} {}:1\n", file_heading, encoded_path),
+        };
+
+        if let Some(metadata) = &file.metadata {
+            let _ = match format {
+                OutputFormat::Xml => {
+                    writeln!(result, "<metadata>{}</metadata>", metadata.summary())
+                }
+                _ => writeln!(result, "*{}*\n", metadata.summary()),
+            };
+        }
+
+        if let Some(complexity) = &file.complexity {
+            let _ = match format {
+                OutputFormat::Xml => {
+                    writeln!(result, "<complexity>{}</complexity>", complexity.summary())
+                }
+                _ => writeln!(result, "*{}*\n", complexity.summary()),
+            };
+        }
+
+        let fence = options
+            .fence_aliases
+            .get(file.language)
+            .map_or(file.language, String::as_str);
+        match (&file.body, format) {
+            (FileBody::Summary(summary), OutputFormat::Xml) => {
+                let _ = writeln!(
+                    result,
+                    "<summary>{}</summary>\n</document>",
+                    escape_html(summary)
                 );
             }
-            Err(e) => {
-                println!("  ✗ {} - Error: {}", relative_path.display(), e);
-                warn!("Could not read file {}: {}", file_path.display(), e);
-                result.push_str(&format!("*Error reading file: {}*\n\n", e));
+            (FileBody::Summary(summary), _) => {
+                let _ = writeln!(result, "*{}*\n", summary);
+            }
+            (FileBody::Content { content, .. }, OutputFormat::Xml) => {
+                result.push_str("<document_content>\n");
+                result.push_str(content);
+                result.push_str("\n</document_content>\n</document>\n");
+            }
+            (FileBody::Content { content, unchanged }, _) => {
+                if *unchanged {
+                    write_anchored_chunks(&mut result, encoded_path, fence, content);
+                } else {
+                    let _ = writeln!(result, "```{}", fence);
+                    result.push_str(content);
+                    result.push_str("\n```\n\n");
+                }
             }
         }
     }
 
-    println!("\n📝 Total content: {} characters", result.len());
+    if format == OutputFormat::Xml {
+        result.push_str("</documents>\n\n");
+    }
+
+    if options.part.as_ref().is_some_and(|part| !part.is_last()) {
+        return Ok(result);
+    }
 
-    if let Some(output_path) = output_file {
-        fs::write(output_path, &result).await?;
-        println!("💾 Output written to: {}", output_path);
+    result.push_str(&options.binary_assets.section(format));
+    if let Some(notice) = &options.license_notice {
+        result.push_str(&notice.section(format));
+    }
+    result.push_str(&options.io_errors.unavailable_section(format));
+    if options.caveats && !options.warnings.is_empty() {
+        result.push_str(&options.warnings.caveats_section(format));
+    }
+    if let Some(manifest) = &options.manifest {
+        result.push_str(manifest);
     }
 
     Ok(result)
 }
+
+/// `--format json` document
+#[derive(Serialize)]
+struct JsonSnapshot {
+    structure: StructureNode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    directory_summaries: Vec<DirectorySummary>,
+    files: Vec<JsonFile>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    binary_assets: Vec<BinaryAsset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license_notice: Option<LicenseNotice>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unavailable: Vec<JsonUnavailable>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    caveats: Vec<JsonCaveat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest: Option<Manifest>,
+}
+
+#[derive(Serialize)]
+struct JsonFile {
+    path: String,
+    language: &'static str,
+    /// `full`, or how the file was cut down to fit the context window
+    representation: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<FileMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    complexity: Option<Complexity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonUnavailable {
+    path: PathBuf,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct JsonCaveat {
+    path: PathBuf,
+    kind: &'static str,
+    message: String,
+}
+
+/// Render as a single JSON document, for tools rather than models
+async fn render_json(
+    vfs: &impl Vfs,
+    files: &[CollectedFile],
+    options: &RenderOptions,
+    tree_files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+) -> Result<String> {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let processing = ProcessingRules::new(&options.processing);
+
+    let mut json_files = Vec::new();
+    for file in files {
+        let Some(file) = prepare_file(vfs, file, options, &processing, &current_dir).await? else {
+            continue;
+        };
+        let (summary, content) = match file.body {
+            FileBody::Summary(summary) => (Some(summary), None),
+            FileBody::Content { content, .. } => (None, Some(content)),
+        };
+        json_files.push(JsonFile {
+            path: file.encoded_path,
+            language: file.language,
+            representation: file.representation.label(),
+            metadata: file.metadata,
+            complexity: file.complexity,
+            summary,
+            content,
+        });
+    }
+
+    let directory_summaries = if options.dir_summaries {
+        summarize_directories(vfs, &CollectedFile::paths(files)).await
+    } else {
+        Vec::new()
+    };
+    // Like the closing sections of markdown, these only go in the last part
+    let closing = options.part.as_ref().is_none_or(Part::is_last);
+    let caveats = if options.caveats && closing {
+        options
+            .warnings
+            .all()
+            .into_iter()
+            .map(|warning| JsonCaveat {
+                path: warning.path,
+                kind: warning.kind.label(),
+                message: warning.message,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let snapshot = JsonSnapshot {
+        structure: generate_structure_tree(tree_files, annotations, &BTreeMap::new()),
+        notes: snapshot_notes(options, &current_dir),
+        directory_summaries,
+        files: json_files,
+        binary_assets: options
+            .binary_assets
+            .assets()
+            .into_iter()
+            .filter(|_| closing)
+            .collect(),
+        license_notice: options.license_notice.clone().filter(|_| closing),
+        unavailable: options
+            .io_errors
+            .skipped()
+            .into_iter()
+            .filter(|_| closing)
+            .map(|skipped| JsonUnavailable {
+                reason: skipped.short_reason(),
+                path: relative_to(&skipped.path, &current_dir),
+            })
+            .collect(),
+        caveats,
+        manifest: options
+            .manifest
+            .as_deref()
+            .filter(|_| closing)
+            .and_then(Manifest::extract)
+            .transpose()?,
+    };
+    serde_json::to_string_pretty(&snapshot).context("Failed to serialize snapshot")
+}
@@ -0,0 +1,153 @@
+use crate::io::vfs::Vfs;
+use crate::utils::path_encoding::relative_to;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const README_NAMES: &[&str] = &[
+    "README.md",
+    "README",
+    "README.txt",
+    "README.rst",
+    "readme.md",
+];
+
+/// Longest README excerpt kept per directory, in characters
+const MAX_EXCERPT_CHARS: usize = 300;
+
+/// Number of file names listed in a synthesized summary
+const MAX_LISTED_FILES: usize = 8;
+
+/// Navigational summary of one directory of the snapshot
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DirectorySummary {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+impl DirectorySummary {
+    /// Path as shown in a snapshot, `.` for the project root
+    pub fn display_path(&self) -> String {
+        if self.path.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            self.path.display().to_string()
+        }
+    }
+}
+
+/// Purpose of a directory guessed from conventional names
+pub fn directory_purpose(name: &str) -> Option<&'static str> {
+    Some(match name.to_lowercase().as_str() {
+        "src" | "lib" | "source" => "Source code",
+        "test" | "tests" | "spec" | "specs" | "__tests__" => "Tests",
+        "bench" | "benches" | "benchmarks" => "Benchmarks",
+        "fixtures" | "testdata" | "test_data" => "Test fixtures",
+        "doc" | "docs" | "documentation" => "Documentation",
+        "example" | "examples" | "samples" => "Examples",
+        "script" | "scripts" | "tools" => "Scripts and tooling",
+        "bin" | "cmd" => "Executable entry points",
+        "cli" | "commands" => "Command-line interface",
+        "config" | "configs" | "conf" | "settings" => "Configuration",
+        "api" | "routes" | "handlers" | "controllers" => "API and request handling",
+        "model" | "models" | "entities" | "schema" | "schemas" => "Data models",
+        "migrations" => "Database migrations",
+        "util" | "utils" | "helpers" | "common" | "shared" => "Shared utilities",
+        "core" | "domain" => "Core logic",
+        "io" => "Input/output",
+        "components" | "views" | "pages" | "templates" | "ui" => "User interface",
+        "assets" | "static" | "public" | "resources" => "Static assets",
+        ".github" | "ci" => "CI configuration",
+        _ => return None,
+    })
+}
+
+/// First prose paragraph of a README, skipping headings, badges and HTML
+pub fn readme_excerpt(content: &str) -> Option<String> {
+    let paragraph: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| is_readme_decoration(line))
+        .take_while(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    if paragraph.is_empty() {
+        return None;
+    }
+
+    let excerpt = paragraph.join(" ");
+    if excerpt.chars().count() <= MAX_EXCERPT_CHARS {
+        return Some(excerpt);
+    }
+    let truncated: String = excerpt.chars().take(MAX_EXCERPT_CHARS).collect();
+    Some(format!("{}…", truncated.trim_end()))
+}
+
+fn is_readme_decoration(line: &str) -> bool {
+    line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("[![")
+        || line.starts_with("![")
+        || line.starts_with('<')
+        || line.chars().all(|c| c == '=' || c == '-')
+}
+
+/// Summarize every directory holding snapshot files, from its README when present or
+/// from its name and contents otherwise
+pub async fn summarize_directories(vfs: &impl Vfs, files: &[PathBuf]) -> Vec<DirectorySummary> {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+
+    let mut directories: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for file in files {
+        let relative = &relative_to(file, &current_dir);
+        let parent = relative.parent().unwrap_or(Path::new("")).to_path_buf();
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        directories.entry(parent).or_default().push(name);
+    }
+
+    let mut summaries = Vec::new();
+    for (directory, mut names) in directories {
+        names.sort();
+
+        let mut readme = None;
+        for candidate in README_NAMES {
+            if let Ok(content) = vfs.read_to_string(&directory.join(candidate)).await {
+                readme = readme_excerpt(&content);
+                break;
+            }
+        }
+
+        let text = readme.unwrap_or_else(|| synthesize_summary(&directory, &names));
+        summaries.push(DirectorySummary {
+            path: directory,
+            text,
+        });
+    }
+
+    summaries
+}
+
+fn synthesize_summary(directory: &Path, names: &[String]) -> String {
+    let purpose = directory
+        .file_name()
+        .and_then(|n| n.to_str())
+        .and_then(directory_purpose);
+
+    let mut listed = names
+        .iter()
+        .take(MAX_LISTED_FILES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > MAX_LISTED_FILES {
+        listed.push_str(&format!(" (+{} more)", names.len() - MAX_LISTED_FILES));
+    }
+
+    match purpose {
+        Some(purpose) => format!("{}. Files: {}", purpose, listed),
+        None => format!("Files: {}", listed),
+    }
+}
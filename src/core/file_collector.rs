@@ -1,156 +1,1028 @@
-use crate::config::patterns::{DEFAULT_EXCLUDE_PATTERNS, DEFAULT_INCLUDE_PATTERNS};
-use crate::core::pattern_matcher::PatternMatcher;
+use crate::config::patterns::{
+    BINARY_ASSET_PATTERNS, CATNIPIGNORE_FILE, DEFAULT_DOTFILE_ALLOWLIST, DEFAULT_EXCLUDE_PATTERNS,
+    DEFAULT_INCLUDE_PATTERNS, DEFAULT_SKIP_DIRECTORIES, parse_pattern_list,
+};
+use crate::config::settings::WorkspaceRoot;
+use crate::core::binary_assets::{BinaryAssets, BinaryMode};
+use crate::core::classifier::{
+    TestFilter, is_test_directory, is_test_path, select_tests_and_tested,
+};
+use crate::core::filter_expr::{FileFacts, FilterExpr};
+use crate::core::ignore_files::IgnoreRules;
+use crate::core::io_errors::IoErrors;
+use crate::core::packer::{PINNED_PRIORITY, file_priority};
+use crate::core::pattern_matcher::{PatternMatcher, PatternMatcherBuilder, PatternSource};
+use crate::core::pruned_dirs::{PrunedDirs, tree_size};
+use crate::integrations::git::{
+    Eol, GitAttributes, NotMaterialized, SUBMODULE_REASON, SubmoduleMode, Submodules,
+    is_sparse_checkout, sparse_files, submodule_paths,
+};
+use crate::io::vfs::{TokioFs, Vfs};
+use crate::utils::file_metadata::sha256_hex;
+use crate::utils::filetype;
+use crate::utils::language_detection::get_language_from_extension;
+use crate::utils::path_encoding::{relative_to, strip_verbatim_prefix};
+use crate::utils::token_counter::estimate_tokens;
 use anyhow::Result;
-use std::collections::BTreeMap;
-use std::path::{Path, PathBuf};
-use tokio::fs;
-use tracing::{debug, info, instrument};
-use walkdir::{DirEntry, WalkDir};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tracing::{Instrument, debug, info, instrument, trace_span};
 
-pub fn is_binary_file(content: &[u8]) -> bool {
-    let check_len = content.len().min(1024);
-    content[..check_len].contains(&0)
+/// A synthetic file injected into the snapshot as if it were part of the tree
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualEntry {
+    pub name: PathBuf,
+    pub content: String,
 }
 
-fn should_skip_directory(entry: &DirEntry, exclude_matcher: &PatternMatcher) -> bool {
-    let path = entry.path();
+impl VirtualEntry {
+    /// Parse a `name=path-or-literal` spec; the value is read from disk when it names an existing file
+    pub async fn resolve(vfs: &impl Vfs, spec: &str) -> Result<Self> {
+        let (name, value) = spec.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid virtual file '{}', expected name=path-or-literal",
+                spec
+            )
+        })?;
 
-    // Quick checks for common directories to skip
-    if let Some(
-        ".git" | ".svn" | ".hg" | ".bzr" | "node_modules" | "__pycache__" | ".mypy_cache"
-        | ".pytest_cache" | ".vscode" | ".idea" | "target" | "build" | "dist" | "out",
-    ) = path.file_name().and_then(|n| n.to_str())
-    {
-        return true;
+        if name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Virtual file '{}' has an empty name", spec));
+        }
+
+        let value_path = Path::new(value);
+        let content = if vfs
+            .metadata(value_path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file)
+        {
+            vfs.read_to_string(value_path).await?
+        } else {
+            value.to_string()
+        };
+
+        Ok(Self {
+            name: PathBuf::from(name.trim()),
+            content,
+        })
     }
 
-    exclude_matcher.matches_path(path)
+    pub fn collected(&self) -> CollectedFile {
+        CollectedFile::new(&self.name, &self.name, self.content.as_bytes(), None)
+    }
+}
+
+pub fn is_binary_file(content: &[u8]) -> bool {
+    filetype::is_binary(content)
 }
 
-fn should_include_file(
+/// Why to prune the directory at `path`, if it should be; `relative_path` is what patterns
+/// are matched against
+fn should_skip_directory(
     path: &Path,
+    relative_path: &Path,
+    skip_directories: &[String],
     exclude_matcher: &PatternMatcher,
-    include_matcher: &PatternMatcher,
+) -> Option<String> {
+    // Quick checks for common directories to skip
+    if let Some(name) = path.file_name().and_then(|n| n.to_str())
+        && skip_directories.iter().any(|skip| skip == name)
+    {
+        return Some("directory skip list".to_string());
+    }
+
+    exclude_matcher
+        .match_with_dir_reason(relative_path)
+        .map(|info| format!("excluded by {}", info))
+}
+
+/// Filters shared by every file of one collection
+struct FileFilter<'a> {
+    /// Default and command-line excludes, which a `.catnipignore` adds to
+    exclude_patterns: PatternMatcherBuilder,
+    exclude_matcher: PatternMatcher,
+    include_matcher: PatternMatcher,
+    /// Dotfiles let in even though no include pattern matches them
+    dotfile_matcher: PatternMatcher,
+    /// Media, archives and fonts listed as binary assets, with `--binaries stub`
+    asset_matcher: Option<PatternMatcher>,
+    binary_assets: &'a BinaryAssets,
     max_size_bytes: u64,
-) -> bool {
-    // Quick exclusion check
-    if exclude_matcher.matches_path(path) {
-        return false;
+    /// `--filter` expression files must also satisfy
+    expression: Option<&'a FilterExpr>,
+    io_errors: &'a IoErrors,
+}
+
+impl<'a> FileFilter<'a> {
+    fn new(options: &'a CollectOptions) -> Self {
+        let additional_excludes = &options.excludes;
+        let additional_includes = &options.includes;
+
+        // Build pattern matchers, remembering where each pattern came from. Listing binary
+        // assets lets in the ones the defaults exclude.
+        let stub_binaries = options.binaries == BinaryMode::Stub;
+        let exclude_patterns = PatternMatcher::builder()
+            .add_all(
+                DEFAULT_EXCLUDE_PATTERNS
+                    .iter()
+                    .copied()
+                    .filter(|pattern| !stub_binaries || !BINARY_ASSET_PATTERNS.contains(pattern)),
+                PatternSource::Default,
+            )
+            .add_all(additional_excludes.iter().cloned(), PatternSource::Cli);
+
+        let include_matcher = if additional_includes.is_empty() {
+            PatternMatcher::builder()
+                .add_all(
+                    DEFAULT_INCLUDE_PATTERNS.iter().copied(),
+                    PatternSource::Default,
+                )
+                .build()
+        } else {
+            PatternMatcher::builder()
+                .add_all(additional_includes.iter().cloned(), PatternSource::Cli)
+                .build()
+        };
+
+        // Custom includes replace the default allowlist too, so only explicitly allowed dotfiles get in
+        let mut dotfile_matcher = PatternMatcher::builder();
+        if additional_includes.is_empty() {
+            dotfile_matcher = dotfile_matcher.add_all(
+                DEFAULT_DOTFILE_ALLOWLIST.iter().copied(),
+                PatternSource::Default,
+            );
+        }
+        let dotfile_matcher = dotfile_matcher
+            .add_all(options.dotfiles_allow.iter().cloned(), PatternSource::Cli)
+            .build();
+
+        debug!(
+            "Using {} exclude patterns",
+            DEFAULT_EXCLUDE_PATTERNS.len() + additional_excludes.len()
+        );
+        debug!(
+            "Using {} include patterns",
+            if additional_includes.is_empty() {
+                DEFAULT_INCLUDE_PATTERNS.len()
+            } else {
+                additional_includes.len()
+            }
+        );
+
+        Self {
+            exclude_matcher: exclude_patterns.clone().build(),
+            exclude_patterns,
+            include_matcher,
+            dotfile_matcher,
+            asset_matcher: stub_binaries.then(|| {
+                PatternMatcher::builder()
+                    .add_all(
+                        BINARY_ASSET_PATTERNS.iter().copied(),
+                        PatternSource::Default,
+                    )
+                    .build()
+            }),
+            binary_assets: &options.binary_assets,
+            max_size_bytes: options.max_size_mb * 1024 * 1024,
+            expression: options.filter.as_ref(),
+            io_errors: &options.io_errors,
+        }
     }
 
-    // Quick inclusion check
-    if !include_matcher.matches_path(path) {
-        return false;
+    /// Add the patterns of an input directory's `.catnipignore` to the default and
+    /// command-line excludes, replacing the previous directory's
+    fn use_catnipignore(&mut self, patterns: Vec<String>) {
+        self.exclude_matcher = self
+            .exclude_patterns
+            .clone()
+            .add_all(patterns, PatternSource::Catnipignore)
+            .build();
     }
 
-    // Size and binary checks
-    if let Ok(metadata) = std::fs::metadata(path) {
-        metadata.len() <= max_size_bytes && metadata.len() > 0
-    } else {
-        false
+    /// The file at `path` with its metadata, if it passes the filters; `relative_path` is what
+    /// patterns are matched against
+    #[instrument(level = "trace", name = "filter", skip_all)]
+    async fn collect(
+        &self,
+        vfs: &impl Vfs,
+        path: &Path,
+        relative_path: &Path,
+        attributes: &GitAttributes,
+    ) -> Result<Option<CollectedFile>> {
+        if !self.matches_patterns(relative_path) {
+            return Ok(None);
+        }
+
+        // Size check
+        let metadata = match vfs.metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.io_errors.skip(path, e)?;
+                return Ok(None);
+            }
+        };
+        let asset = self.is_asset(relative_path);
+        if metadata.len == 0 {
+            return Ok(None);
+        }
+        if metadata.len > self.max_size_bytes {
+            if asset {
+                self.binary_assets.record(path, metadata.len, None);
+            }
+            return Ok(None);
+        }
+
+        // Binary check, hashing the content while it's at hand. `.gitattributes` has the
+        // final say, as it does for git.
+        let attributes = attributes.get(relative_path);
+        match vfs.read(path).await {
+            Ok(content)
+                if asset
+                    || attributes
+                        .binary
+                        .unwrap_or_else(|| is_binary_file(&content)) =>
+            {
+                if self.asset_matcher.is_some() {
+                    self.binary_assets
+                        .record(path, metadata.len, Some(&content));
+                }
+                Ok(None)
+            }
+            Ok(content) => {
+                let mut file = CollectedFile::new(path, relative_path, &content, metadata.modified);
+                file.eol = attributes.eol;
+                if let Some(expression) = self.expression
+                    && !expression.matches(&FileFacts::new(&file, &content))
+                {
+                    debug!(
+                        "Filtered out {}: {}",
+                        relative_path.display(),
+                        expression.source()
+                    );
+                    return Ok(None);
+                }
+                Ok(Some(file))
+            }
+            Err(e) => {
+                self.io_errors.skip(path, e)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Whether `relative_path` passes the exclude and include patterns
+    fn matches_patterns(&self, relative_path: &Path) -> bool {
+        !self.exclude_matcher.matches_path(relative_path)
+            && (self.include_matcher.matches_path(relative_path)
+                || self.allows_dotfile(relative_path)
+                || self.is_asset(relative_path))
+    }
+
+    /// Whether `relative_path` is a binary asset to list, whatever its content
+    fn is_asset(&self, relative_path: &Path) -> bool {
+        self.asset_matcher
+            .as_ref()
+            .is_some_and(|matcher| matcher.matches_path(relative_path))
+    }
+
+    /// Dotfiles on the allowlist, as long as they don't hold real environment values
+    fn allows_dotfile(&self, relative_path: &Path) -> bool {
+        let Some(name) = relative_path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        name.starts_with('.')
+            && !is_env_file(name)
+            && self.dotfile_matcher.matches_path(relative_path)
     }
 }
 
-async fn is_text_file(path: &Path) -> bool {
-    match fs::read(path).await {
-        Ok(content) => !is_binary_file(&content),
-        Err(_) => false,
+/// Whether `name` is a `.env` file holding real values rather than an example of one
+pub fn is_env_file(name: &str) -> bool {
+    match name.strip_prefix(".env") {
+        Some("") => true,
+        Some(suffix) => match suffix.strip_prefix('.') {
+            Some(kind) => !matches!(kind, "example" | "sample" | "template" | "dist"),
+            None => false,
+        },
+        None => false,
     }
 }
 
-#[instrument(skip(additional_excludes, additional_includes))]
+/// A file that passed the collection filters, with the metadata gathered while checking it
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectedFile {
+    pub path: PathBuf,
+    /// Path relative to the input directory it was found under
+    pub relative_path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub language: &'static str,
+    pub sha256: String,
+    /// Line ending `.gitattributes` declares, making the file text git stores with LF
+    pub eol: Option<Eol>,
+}
+
+impl CollectedFile {
+    pub fn new(
+        path: &Path,
+        relative_path: &Path,
+        content: &[u8],
+        modified: Option<SystemTime>,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            relative_path: relative_path.to_path_buf(),
+            size: content.len() as u64,
+            modified,
+            language: get_language_from_extension(path),
+            sha256: sha256_hex(content),
+            eol: None,
+        }
+    }
+
+    /// Collect a single file without any filtering, e.g. for a path named by the caller
+    pub async fn load(vfs: &impl Vfs, path: &Path) -> Result<Self> {
+        let content = vfs.read(path).await?;
+        let modified = vfs
+            .metadata(path)
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified);
+        let relative_path = Path::new(path.file_name().unwrap_or(path.as_os_str()));
+        Ok(Self::new(path, relative_path, &content, modified))
+    }
+
+    /// Collect each of `paths` without any filtering
+    pub async fn load_all(vfs: &impl Vfs, paths: &[PathBuf]) -> Result<Vec<Self>> {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            files.push(Self::load(vfs, path).await?);
+        }
+        Ok(files)
+    }
+
+    pub fn paths(files: &[CollectedFile]) -> Vec<PathBuf> {
+        files.iter().map(|file| file.path.clone()).collect()
+    }
+}
+
+/// Options controlling which files end up in a snapshot
+#[derive(Clone, Debug)]
+pub struct CollectOptions {
+    pub excludes: Vec<String>,
+    pub includes: Vec<String>,
+    /// Extra dotfile patterns let in alongside the default dotfile allowlist
+    pub dotfiles_allow: Vec<String>,
+    pub max_size_mb: u64,
+    pub virtual_entries: Vec<VirtualEntry>,
+    pub test_filter: TestFilter,
+    pub io_errors: IoErrors,
+    /// Directory names never descended into below the input directories
+    pub skip_directories: Vec<String>,
+    /// Directories the walk skipped, filled in during collection
+    pub pruned: PrunedDirs,
+    /// List every pruned directory, with the size of skipped top-level trees
+    pub prune_report: bool,
+    /// Honor `.gitignore`, `.ignore` and `.rgignore` files below the input directories
+    pub ignore_files: bool,
+    /// Files always collected, whatever the patterns, skip list or ignore files say
+    pub pinned: Vec<PathBuf>,
+    /// Workspace roots whose own patterns apply when they are among the input paths
+    pub roots: Vec<WorkspaceRoot>,
+    /// What to do with the git submodules listed in an input directory's `.gitmodules`
+    pub submodule_mode: SubmoduleMode,
+    /// Submodule directories found, filled in during collection
+    pub submodules: Submodules,
+    /// Files a sparse checkout left off disk, filled in during collection
+    pub not_materialized: NotMaterialized,
+    /// Don't print the collected file tree
+    pub quiet: bool,
+    /// `--filter` expression over file metadata that collected files must satisfy
+    pub filter: Option<FilterExpr>,
+    pub binaries: BinaryMode,
+    /// Binary files found with `binaries` set to stub, filled in during collection
+    pub binary_assets: BinaryAssets,
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self {
+            excludes: Vec::new(),
+            includes: Vec::new(),
+            dotfiles_allow: Vec::new(),
+            max_size_mb: 10,
+            virtual_entries: Vec::new(),
+            test_filter: TestFilter::default(),
+            io_errors: IoErrors::default(),
+            skip_directories: DEFAULT_SKIP_DIRECTORIES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            pruned: PrunedDirs::default(),
+            prune_report: false,
+            ignore_files: true,
+            pinned: Vec::new(),
+            roots: Vec::new(),
+            submodule_mode: SubmoduleMode::default(),
+            submodules: Submodules::default(),
+            not_materialized: NotMaterialized::default(),
+            quiet: false,
+            filter: None,
+            binaries: BinaryMode::default(),
+            binary_assets: BinaryAssets::default(),
+        }
+    }
+}
+
+impl CollectOptions {
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        let path = without_cur_dir(path);
+        self.pinned
+            .iter()
+            .any(|pinned| without_cur_dir(pinned) == path)
+    }
+
+    /// The workspace root `path` lies in, if any
+    pub fn root_of(&self, path: &Path) -> Option<&WorkspaceRoot> {
+        let path = without_cur_dir(path);
+        self.roots
+            .iter()
+            .find(|root| path.starts_with(without_cur_dir(&root.path)))
+    }
+
+    /// Packing priority of `path`: pinned files first, otherwise its own priority
+    /// adjusted by its workspace root's
+    pub fn priority(&self, path: &Path) -> u32 {
+        if self.is_pinned(path) {
+            return PINNED_PRIORITY;
+        }
+
+        let priority = file_priority(path);
+        match self.root_of(path) {
+            Some(root) => priority
+                .saturating_add_signed(root.priority)
+                .min(PINNED_PRIORITY - 1),
+            None => priority,
+        }
+    }
+
+    /// These options with a workspace root's patterns added
+    fn for_root(&self, root: &WorkspaceRoot) -> Self {
+        let mut options = self.clone();
+        options.excludes.extend(root.exclude.iter().cloned());
+        options.includes.extend(root.include.iter().cloned());
+        options
+    }
+}
+
+/// `path` without `.` components, so `./README.md` and `README.md` compare equal
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
 pub async fn collect_files(
     paths: &[PathBuf],
     additional_excludes: &[String],
     additional_includes: &[String],
     max_size_mb: u64,
-) -> Result<Vec<PathBuf>> {
-    let max_size_bytes = max_size_mb * 1024 * 1024;
+) -> Result<Vec<CollectedFile>> {
+    let options = CollectOptions {
+        excludes: additional_excludes.to_vec(),
+        includes: additional_includes.to_vec(),
+        max_size_mb,
+        ..CollectOptions::default()
+    };
+    collect_files_with(&TokioFs, paths, &options).await
+}
 
-    // Build pattern matchers
-    let mut exclude_patterns = DEFAULT_EXCLUDE_PATTERNS.to_vec();
-    exclude_patterns.extend(additional_excludes.iter().map(|s| s.as_str()));
-    let exclude_patterns: Vec<String> = exclude_patterns.iter().map(|s| s.to_string()).collect();
+#[instrument(skip(vfs, options))]
+pub async fn collect_files_with(
+    vfs: &impl Vfs,
+    paths: &[PathBuf],
+    options: &CollectOptions,
+) -> Result<Vec<CollectedFile>> {
+    let mut all_files = Vec::new();
+    walk_files(vfs, paths, options, |file| all_files.push(file)).await?;
+    if options.test_filter == TestFilter::OnlyTests {
+        retain_tests_and_tested(vfs, &mut all_files).await;
+    }
 
-    let include_patterns: Vec<String> = if additional_includes.is_empty() {
-        DEFAULT_INCLUDE_PATTERNS
-            .iter()
-            .map(|s| s.to_string())
-            .collect()
-    } else {
-        additional_includes.to_vec()
+    let walked: HashSet<PathBuf> = all_files
+        .iter()
+        .map(|file| without_cur_dir(&file.path))
+        .collect();
+    all_files.extend(collect_pinned(vfs, options, &walked).await?);
+
+    // Virtual entries are explicitly requested, so they bypass the filters
+    for entry in &options.virtual_entries {
+        if !all_files.iter().any(|file| file.path == entry.name) {
+            all_files.push(entry.collected());
+        }
+    }
+
+    info!("Found {} files after filtering", all_files.len());
+
+    if !all_files.is_empty() && !options.quiet {
+        println!("\n📁 Files to be included:");
+        print_file_tree(&all_files);
+        println!();
+    }
+
+    if !options.quiet {
+        print_pruned_dirs(vfs, &options.pruned, options.prune_report).await;
+    }
+
+    Ok(all_files)
+}
+
+/// Keep the test files among `files` and the code they test, for `--tests-only`. Unreadable
+/// files are kept, to be reported when rendering.
+async fn retain_tests_and_tested(vfs: &impl Vfs, files: &mut Vec<CollectedFile>) {
+    let mut contents = Vec::with_capacity(files.len());
+    for file in files.iter() {
+        contents.push(vfs.read_text(&file.path).await.ok());
+    }
+    let candidates: Vec<(&Path, &str)> = files
+        .iter()
+        .zip(&contents)
+        .map(|(file, content)| {
+            (
+                file.relative_path.as_path(),
+                content.as_deref().unwrap_or(""),
+            )
+        })
+        .collect();
+    let keep = select_tests_and_tested(&candidates);
+    let mut keep = keep
+        .into_iter()
+        .zip(&contents)
+        .map(|(keep, content)| keep || content.is_none());
+    files.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Pinned files the walk didn't find, given the paths it did without `.` components.
+/// Pinned files are always wanted, so they bypass the filters too.
+async fn collect_pinned(
+    vfs: &impl Vfs,
+    options: &CollectOptions,
+    walked: &HashSet<PathBuf>,
+) -> Result<Vec<CollectedFile>> {
+    let mut pinned_files = Vec::new();
+    for pinned in &options.pinned {
+        let pinned_path = without_cur_dir(pinned);
+        if walked.contains(&pinned_path) {
+            continue;
+        }
+        match vfs.read(&pinned_path).await {
+            Ok(content) => {
+                let modified = vfs
+                    .metadata(&pinned_path)
+                    .await
+                    .ok()
+                    .and_then(|metadata| metadata.modified);
+                pinned_files.push(CollectedFile::new(
+                    &pinned_path,
+                    &pinned_path,
+                    &content,
+                    modified,
+                ));
+            }
+            Err(e) => options.io_errors.skip(&pinned_path, e)?,
+        }
+    }
+    Ok(pinned_files)
+}
+
+/// Collect files as a stream, yielding each one as soon as the walk finds it so
+/// rendering can start before the walk of a huge tree finishes. Nothing is printed.
+pub fn collect_files_stream<'a>(
+    vfs: &'a impl Vfs,
+    paths: &'a [PathBuf],
+    options: &'a CollectOptions,
+) -> impl Stream<Item = Result<CollectedFile>> + 'a {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    let producer = async move {
+        let mut seen = HashSet::new();
+        if options.test_filter == TestFilter::OnlyTests {
+            // Which files are under test is only known once every test file is found
+            let mut walked = Vec::new();
+            walk_files(vfs, paths, options, |file| walked.push(file)).await?;
+            retain_tests_and_tested(vfs, &mut walked).await;
+            for file in walked {
+                seen.insert(file.path.clone());
+                let _ = sender.send(file);
+            }
+        } else {
+            walk_files(vfs, paths, options, |file| {
+                seen.insert(file.path.clone());
+                let _ = sender.send(file);
+            })
+            .await?;
+        }
+
+        let walked: HashSet<PathBuf> = seen.iter().map(|path| without_cur_dir(path)).collect();
+        for file in collect_pinned(vfs, options, &walked).await? {
+            seen.insert(file.path.clone());
+            let _ = sender.send(file);
+        }
+        for entry in &options.virtual_entries {
+            if !seen.contains(&entry.name) {
+                let _ = sender.send(entry.collected());
+            }
+        }
+        Ok(())
     };
 
-    let exclude_matcher = PatternMatcher::new(&exclude_patterns);
-    let include_matcher = PatternMatcher::new(&include_patterns);
+    CollectStream {
+        producer: Some(Box::pin(producer)),
+        receiver,
+        error: None,
+    }
+}
 
-    debug!("Using {} exclude patterns", exclude_patterns.len());
-    debug!("Using {} include patterns", include_patterns.len());
+/// Drives the walk while handing out the files it has found so far
+struct CollectStream<'a> {
+    producer: Option<Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>>,
+    receiver: mpsc::UnboundedReceiver<CollectedFile>,
+    /// Walk error, reported once the files found before it have been yielded
+    error: Option<anyhow::Error>,
+}
 
-    let mut all_files = Vec::new();
+impl Stream for CollectStream<'_> {
+    type Item = Result<CollectedFile>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(producer) = self.producer.as_mut()
+            && let Poll::Ready(result) = producer.as_mut().poll(cx)
+        {
+            self.producer = None;
+            self.error = result.err();
+        }
+
+        match self.receiver.poll_recv(cx) {
+            Poll::Ready(Some(file)) => Poll::Ready(Some(Ok(file))),
+            Poll::Ready(None) => Poll::Ready(self.error.take().map(Err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Walk the input paths, passing each file that passes the filters to `emit`. Input paths
+/// that are workspace roots are walked with the root's patterns added.
+async fn walk_files(
+    vfs: &impl Vfs,
+    paths: &[PathBuf],
+    options: &CollectOptions,
+    mut emit: impl FnMut(CollectedFile),
+) -> Result<()> {
+    if options.roots.is_empty() {
+        return walk_paths(vfs, paths, options, emit).await;
+    }
 
     for path in paths {
-        if path.is_file() {
-            if should_include_file(path, &exclude_matcher, &include_matcher, max_size_bytes)
-                && is_text_file(path).await
-            {
-                all_files.push(path.clone());
-            }
-        } else if path.is_dir() {
-            for entry in WalkDir::new(path)
-                .into_iter()
-                .filter_entry(|e| {
-                    if e.path().is_dir() {
-                        !should_skip_directory(e, &exclude_matcher)
-                    } else {
+        let root = options
+            .roots
+            .iter()
+            .find(|root| without_cur_dir(&root.path) == without_cur_dir(path));
+        match root {
+            Some(root) => {
+                debug!("Collecting workspace root {}", root.path.display());
+                walk_paths(
+                    vfs,
+                    std::slice::from_ref(path),
+                    &options.for_root(root),
+                    &mut emit,
+                )
+                .await?
+            }
+            None => walk_paths(vfs, std::slice::from_ref(path), options, &mut emit).await?,
+        }
+    }
+    Ok(())
+}
+
+async fn walk_paths(
+    vfs: &impl Vfs,
+    paths: &[PathBuf],
+    options: &CollectOptions,
+    mut emit: impl FnMut(CollectedFile),
+) -> Result<()> {
+    // Source files are still needed in tests-only mode to outline the code under test
+    let exclude_tests = options.test_filter == TestFilter::ExcludeTests;
+    let io_errors = &options.io_errors;
+    let mut filter = FileFilter::new(options);
+
+    for path in paths {
+        // Extended-length Windows paths would never match their plain form elsewhere
+        let path = &strip_verbatim_prefix(path);
+        let metadata = match vfs.metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                io_errors.skip(path, e)?;
+                continue;
+            }
+        };
+
+        // A `.catnipignore` at the top of an input directory adds to the exclude patterns
+        let catnipignore = if metadata.is_dir {
+            read_catnipignore(vfs, path).await.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        filter.use_catnipignore(catnipignore);
+
+        if metadata.is_file {
+            let filename = Path::new(path.file_name().unwrap_or_default());
+            if exclude_tests && is_test_path(filename) {
+                debug!("Excluding test file: {}", path.display());
+                continue;
+            }
+
+            // An explicitly named file is its own root, so only its name is matched
+            let attributes = GitAttributes::load(vfs, path.parent().unwrap_or(Path::new(""))).await;
+            if let Some(file) = filter.collect(vfs, path, filename, &attributes).await? {
+                emit(file);
+            }
+        } else if metadata.is_dir {
+            let attributes = GitAttributes::load(vfs, path).await;
+            let submodules = submodule_paths(vfs, path).await;
+            for submodule in &submodules {
+                options.submodules.record(path.join(submodule));
+            }
+            // The walk already leaves out what these ignore, where it can; they are checked
+            // again so pruned directories get their reason and every filesystem agrees
+            let mut ignore_rules = IgnoreRules::new(options.ignore_files);
+            if options.ignore_files {
+                ignore_rules.load_repository(vfs, path).await;
+            }
+            ignore_rules.load_dir(vfs, path, Path::new("")).await;
+
+            // The walk runs on blocking threads, so the callback owns copies of what it checks
+            let input = path.clone();
+            let skip_directories = options.skip_directories.clone();
+            let exclude_matcher = filter.exclude_matcher.clone();
+            let submodule_mode = options.submodule_mode;
+            let prune_rules = ignore_rules.clone();
+            let pruned = options.pruned.clone();
+            let prune = move |dir: &Path| {
+                // The input directory itself was asked for by name
+                if dir == input.as_path() {
+                    return false;
+                }
+
+                let relative_path = &relative_to(dir, &input);
+                let reason =
+                    should_skip_directory(dir, relative_path, &skip_directories, &exclude_matcher)
+                        .or_else(|| {
+                            (exclude_tests
+                                && dir
+                                    .file_name()
+                                    .and_then(|n| n.to_str())
+                                    .is_some_and(is_test_directory))
+                            .then(|| "test directory".to_string())
+                        })
+                        .or_else(|| {
+                            (submodule_mode == SubmoduleMode::Skip
+                                && submodules
+                                    .iter()
+                                    .any(|submodule| submodule == relative_path))
+                            .then(|| SUBMODULE_REASON.to_string())
+                        })
+                        .or_else(|| {
+                            prune_rules
+                                .matched(relative_path, true)
+                                .map(|info| format!("ignored by {}", info))
+                        });
+                match reason {
+                    Some(reason) => {
+                        pruned.record(dir, relative_path, reason);
                         true
                     }
-                })
-                .filter_map(|e| e.ok())
-            {
-                let entry_path = entry.path();
-
-                if entry_path.is_file()
-                    && should_include_file(
-                        entry_path,
-                        &exclude_matcher,
-                        &include_matcher,
-                        max_size_bytes,
-                    )
-                    && is_text_file(entry_path).await
+                    None => false,
+                }
+            };
+
+            let walk = vfs
+                // A prune report names the ignore file rule behind each skipped directory,
+                // which a walk leaving them out itself couldn't tell
+                .walk(
+                    path,
+                    Arc::new(prune),
+                    options.ignore_files && !options.prune_report,
+                )
+                .instrument(trace_span!("walk"))
+                .await?;
+            for (error_path, reason) in &walk.errors {
+                io_errors.skip(error_path, reason)?;
+            }
+            // Ignore files deeper down can only filter files, the walk being over by now
+            ignore_rules.load_nested(vfs, path, &walk.files).await;
+
+            for entry_path in walk.files {
+                let relative_path = &relative_to(&entry_path, path);
+                if exclude_tests && is_test_path(relative_path) {
+                    debug!("Excluding test file: {}", entry_path.display());
+                    continue;
+                }
+                if let Some(info) = ignore_rules.matched(relative_path, false) {
+                    debug!("Ignoring {}: {}", entry_path.display(), info);
+                    continue;
+                }
+
+                if let Some(file) = filter
+                    .collect(vfs, &entry_path, relative_path, &attributes)
+                    .await?
                 {
-                    all_files.push(entry_path.to_path_buf());
+                    emit(file);
+                }
+            }
+
+            // Files a sparse checkout left out are noted where the filters would have let
+            // them in, rather than silently missing from the structure
+            if is_sparse_checkout(vfs, path).await {
+                for relative_path in sparse_files(path).await {
+                    let file_path = path.join(&relative_path);
+                    let filtered_dir = relative_path.ancestors().skip(1).any(|dir| {
+                        !dir.as_os_str().is_empty()
+                            && (should_skip_directory(
+                                &path.join(dir),
+                                dir,
+                                &options.skip_directories,
+                                &filter.exclude_matcher,
+                            )
+                            .is_some()
+                                || ignore_rules.matched(dir, true).is_some())
+                    });
+                    if filtered_dir
+                        || (exclude_tests && is_test_path(&relative_path))
+                        || ignore_rules.matched(&relative_path, false).is_some()
+                        || !filter.matches_patterns(&relative_path)
+                        || vfs.exists(&file_path).await
+                    {
+                        continue;
+                    }
+                    debug!("Not materialized: {}", file_path.display());
+                    options.not_materialized.record(file_path);
                 }
             }
         }
     }
 
-    info!("Found {} files after filtering", all_files.len());
+    Ok(())
+}
 
-    if !all_files.is_empty() {
-        println!("\n📁 Files to be included:");
-        print_file_tree(&all_files);
-        println!();
+/// Why collecting `paths` with `options` came up empty: the input paths that don't exist
+/// or hold no files, then how many files each filtering stage dropped, the stage that
+/// dropped the most first. `options` must be the ones of the empty collection, so its
+/// pruned directories are known.
+pub async fn diagnose_empty(
+    vfs: &impl Vfs,
+    paths: &[PathBuf],
+    options: &CollectOptions,
+) -> Vec<String> {
+    let mut findings = Vec::new();
+    let mut dropped: BTreeMap<String, usize> = BTreeMap::new();
+    let mut extensions: BTreeMap<String, usize> = BTreeMap::new();
+    let exclude_tests = options.test_filter == TestFilter::ExcludeTests;
+    let pruned = options.pruned.dirs();
+    let mut filter = FileFilter::new(options);
+
+    for path in paths {
+        let path = &strip_verbatim_prefix(path);
+        let Ok(metadata) = vfs.metadata(path).await else {
+            findings.push(format!("{} does not exist", path.display()));
+            continue;
+        };
+
+        let (root, files) = if metadata.is_dir {
+            filter.use_catnipignore(read_catnipignore(vfs, path).await.unwrap_or_default());
+            let files = vfs
+                .walk(path, Arc::new(|_: &Path| false), false)
+                .await
+                .map(|walk| walk.files)
+                .unwrap_or_default();
+            (path.clone(), files)
+        } else {
+            filter.use_catnipignore(Vec::new());
+            let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            (parent, vec![path.clone()])
+        };
+        if files.is_empty() {
+            findings.push(format!("{} holds no files", path.display()));
+            continue;
+        }
+
+        let mut ignore_rules = IgnoreRules::new(options.ignore_files);
+        if metadata.is_dir {
+            if options.ignore_files {
+                ignore_rules.load_repository(vfs, &root).await;
+            }
+            ignore_rules.load_dir(vfs, &root, Path::new("")).await;
+            ignore_rules.load_nested(vfs, &root, &files).await;
+        }
+
+        for file in files {
+            let relative_path = &relative_to(&file, &root);
+            let reason = if let Some(dir) = pruned.iter().find(|dir| file.starts_with(&dir.path)) {
+                format!("in a skipped directory ({})", dir.reason)
+            } else if exclude_tests && is_test_path(relative_path) {
+                "are tests, left out by --no-tests".to_string()
+            } else if let Some(info) = ignore_rules.matched(relative_path, false) {
+                format!("ignored by {}", info)
+            } else if let Some(info) = filter.exclude_matcher.match_with_reason(relative_path) {
+                format!("excluded by {}", info)
+            } else if !filter.matches_patterns(relative_path) {
+                if let Some(extension) = file.extension().and_then(|e| e.to_str()) {
+                    *extensions.entry(format!("*.{}", extension)).or_default() += 1;
+                }
+                "not matched by any include pattern".to_string()
+            } else if let Some(expression) = &options.filter
+                && let Ok(content) = vfs.read(&file).await
+                && !content.is_empty()
+                && !is_binary_file(&content)
+                && !expression.matches(&FileFacts::new(
+                    &CollectedFile::new(&file, relative_path, &content, None),
+                    &content,
+                ))
+            {
+                format!("rejected by --filter '{}'", expression.source())
+            } else {
+                "are empty, binary, unreadable or over --max-size-mb".to_string()
+            };
+            *dropped.entry(reason).or_default() += 1;
+        }
     }
 
-    Ok(all_files)
+    let mut dropped: Vec<_> = dropped.into_iter().collect();
+    dropped.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (reason, count) in dropped {
+        findings.push(format!("{} file(s) {}", count, reason));
+    }
+
+    // The extensions the include patterns missed hint at the pattern that was meant
+    if !extensions.is_empty() {
+        let mut extensions: Vec<_> = extensions.into_iter().collect();
+        extensions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let common: Vec<_> = extensions
+            .iter()
+            .take(3)
+            .map(|(extension, count)| format!("{} ({})", extension, count))
+            .collect();
+        findings.push(format!(
+            "Most common extensions no include pattern matched: {}",
+            common.join(", ")
+        ));
+    }
+    findings
+}
+
+/// Patterns of the `.catnipignore` at the top of `dir`, if there is one
+async fn read_catnipignore(vfs: &impl Vfs, dir: &Path) -> Option<Vec<String>> {
+    let path = dir.join(CATNIPIGNORE_FILE);
+    let content = vfs.read_to_string(&path).await.ok()?;
+    debug!("Using {}", path.display());
+    Some(parse_pattern_list(&content))
+}
+
+/// Files estimated above this many tokens are flagged in the preview tree
+pub const HOT_FILE_TOKENS: usize = 5_000;
+/// Directories estimated above this many tokens are flagged in the preview tree
+pub const HOT_DIR_TOKENS: usize = 25_000;
+
+/// Print `files` as a tree, relative to the current directory
+pub fn print_file_tree(files: &[CollectedFile]) {
+    for line in file_tree_lines(files) {
+        println!("{}", line);
+    }
 }
 
-fn print_file_tree(files: &[PathBuf]) {
+/// Lines of the preview tree, with a token estimate for each file and directory and a 🔥
+/// on the ones big enough to eat a noticeable share of a context window
+pub fn file_tree_lines(files: &[CollectedFile]) -> Vec<String> {
     let current_dir = std::env::current_dir().unwrap_or_default();
     let mut tree = BTreeMap::new();
 
     // Build tree structure
     for file in files {
-        let relative_path = file.strip_prefix(&current_dir).unwrap_or(file);
-        add_file_to_tree(&mut tree, relative_path);
+        let relative_path = &relative_to(&file.path, &current_dir);
+        add_file_to_tree(&mut tree, relative_path, estimate_tokens(file.size));
     }
 
-    // Print tree
-    print_tree_recursive(&tree, "", true);
+    let mut lines = Vec::new();
+    tree_lines_recursive(&tree, "", &mut lines);
+    lines
 }
 
-fn add_file_to_tree(tree: &mut BTreeMap<String, TreeNode>, path: &Path) {
+fn add_file_to_tree(tree: &mut BTreeMap<String, TreeNode>, path: &Path, tokens: usize) {
     let components: Vec<_> = path.components().collect();
     if components.is_empty() {
         return;
@@ -163,7 +1035,7 @@ fn add_file_to_tree(tree: &mut BTreeMap<String, TreeNode>, path: &Path) {
         let is_file = i == components.len() - 1;
 
         if is_file {
-            current.insert(name, TreeNode::File);
+            current.insert(name, TreeNode::File(tokens));
             break;
         }
 
@@ -175,39 +1047,106 @@ fn add_file_to_tree(tree: &mut BTreeMap<String, TreeNode>, path: &Path) {
             TreeNode::Directory(subtree) => {
                 current = subtree;
             }
-            TreeNode::File => break,
+            TreeNode::File(_) => break,
         }
     }
 }
 
 #[derive(Debug)]
 enum TreeNode {
-    File,
+    /// Estimated tokens of the file
+    File(usize),
     Directory(BTreeMap<String, TreeNode>),
 }
 
-fn print_tree_recursive(tree: &BTreeMap<String, TreeNode>, prefix: &str, is_root: bool) {
+impl TreeNode {
+    fn tokens(&self) -> usize {
+        match self {
+            TreeNode::File(tokens) => *tokens,
+            TreeNode::Directory(subtree) => subtree.values().map(TreeNode::tokens).sum(),
+        }
+    }
+}
+
+/// `~1.2k tokens`, with a 🔥 once past `hot`
+fn token_label(tokens: usize, hot: usize) -> String {
+    let amount = if tokens < 1_000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1_000.0)
+    };
+    let heat = if tokens > hot { " 🔥" } else { "" };
+    format!("(~{} tokens){}", amount, heat)
+}
+
+fn tree_lines_recursive(tree: &BTreeMap<String, TreeNode>, prefix: &str, lines: &mut Vec<String>) {
     let items: Vec<_> = tree.iter().collect();
 
     for (i, (name, node)) in items.iter().enumerate() {
         let is_last = i == items.len() - 1;
-        let connector = if is_root {
-            if is_last { "└── " } else { "├── " }
-        } else if is_last {
-            "└── "
-        } else {
-            "├── "
-        };
+        let connector = if is_last { "└── " } else { "├── " };
 
         match node {
-            TreeNode::File => {
-                println!("{}{}📄 {}", prefix, connector, name);
+            TreeNode::File(tokens) => {
+                lines.push(format!(
+                    "{}{}📄 {} {}",
+                    prefix,
+                    connector,
+                    name,
+                    token_label(*tokens, HOT_FILE_TOKENS)
+                ));
             }
             TreeNode::Directory(subtree) => {
-                println!("{}{}📁 {}/", prefix, connector, name);
+                lines.push(format!(
+                    "{}{}📁 {}/ {}",
+                    prefix,
+                    connector,
+                    name,
+                    token_label(node.tokens(), HOT_DIR_TOKENS)
+                ));
                 let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
-                print_tree_recursive(subtree, &new_prefix, false);
+                tree_lines_recursive(subtree, &new_prefix, lines);
             }
         }
     }
 }
+
+/// Summarize pruned directories so over-eager skips don't go unnoticed
+async fn print_pruned_dirs(vfs: &impl Vfs, pruned: &PrunedDirs, report: bool) {
+    let dirs = pruned.dirs();
+    if dirs.is_empty() {
+        return;
+    }
+
+    let top_level = pruned.top_level();
+    if !report {
+        let names: Vec<_> = top_level
+            .iter()
+            .map(|dir| format!("{}/", dir.relative_path.display()))
+            .collect();
+        println!(
+            "🚫 Skipped {} directories (top-level: {}); use --prune-report for details\n",
+            dirs.len(),
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        );
+        return;
+    }
+
+    println!("🚫 Skipped {} directories:", dirs.len());
+    for dir in &top_level {
+        println!(
+            "  {}/ ({}) - {}",
+            dir.relative_path.display(),
+            tree_size(vfs, &dir.path).await,
+            dir.reason
+        );
+    }
+    for dir in dirs.iter().filter(|dir| !dir.is_top_level()) {
+        println!("  {}/ - {}", dir.relative_path.display(), dir.reason);
+    }
+    println!();
+}
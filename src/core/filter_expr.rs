@@ -0,0 +1,484 @@
+use crate::core::file_collector::CollectedFile;
+use crate::core::pattern_matcher::PatternMatcher;
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+
+/// What a `--filter` expression can ask about a file
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileFacts {
+    /// Path relative to the input directory, with `/` separators
+    pub path: String,
+    pub name: String,
+    /// Extension without the dot, empty when there is none
+    pub ext: String,
+    pub lang: String,
+    pub size: u64,
+    pub lines: usize,
+}
+
+impl FileFacts {
+    pub fn new(file: &CollectedFile, content: &[u8]) -> Self {
+        let path = &file.relative_path;
+        let text = |part: Option<&std::ffi::OsStr>| {
+            part.map(|part| part.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            path: path.to_string_lossy().replace('\\', "/"),
+            name: text(path.file_name()),
+            ext: text(path.extension()),
+            lang: file.language.to_string(),
+            size: file.size,
+            lines: content.split(|&byte| byte == b'\n').count()
+                - usize::from(content.is_empty() || content.ends_with(b"\n")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Str,
+    Num,
+    Bool,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Str => "string",
+            Type::Num => "number",
+            Type::Bool => "boolean",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Path,
+    Name,
+    Ext,
+    Lang,
+    Size,
+    Lines,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "path" => Field::Path,
+            "name" => Field::Name,
+            "ext" => Field::Ext,
+            "lang" | "language" => Field::Lang,
+            "size" => Field::Size,
+            "lines" => Field::Lines,
+            _ => return None,
+        })
+    }
+
+    fn value(&self, facts: &FileFacts) -> Value {
+        match self {
+            Field::Path => Value::Str(facts.path.clone()),
+            Field::Name => Value::Str(facts.name.clone()),
+            Field::Ext => Value::Str(facts.ext.clone()),
+            Field::Lang => Value::Str(facts.lang.clone()),
+            Field::Size => Value::Num(facts.size as f64),
+            Field::Lines => Value::Num(facts.lines as f64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// Glob match, with the same syntax as `--include`
+    Matches,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+    Call(Box<Expr>, Method, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+}
+
+/// Operators, longest first so `<=` isn't read as `<`
+const OPERATORS: [&str; 14] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", ".", ",", "=",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while let Some(ch) = rest.chars().next() {
+        if ch == '"' || ch == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, c)) if c == ch => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => return Err(anyhow::anyhow!("Unterminated string in filter")),
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => return Err(anyhow::anyhow!("Unterminated string in filter")),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end..];
+        } else if ch.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '_')
+                .unwrap_or(rest.len());
+            let number = rest[..end].replace('_', "");
+            let value = number
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid number in filter: {}", &rest[..end]))?;
+            tokens.push(Token::Num(value));
+            rest = &rest[end..];
+        } else if ch.is_alphabetic() || ch == '_' {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) else {
+                return Err(anyhow::anyhow!("Unexpected '{}' in filter", ch));
+            };
+            if *op == "=" {
+                return Err(anyhow::anyhow!("Use '==' to compare in filters, not '='"));
+            }
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, lowest precedence first: `||`, `&&`, `!`, comparisons
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(next)) if *next == op) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Expected '{}' in filter, found {}",
+                op,
+                self.describe_next()
+            ))
+        }
+    }
+
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            Some(Token::Ident(name)) => format!("'{}'", name),
+            Some(Token::Str(value)) => format!("\"{}\"", value),
+            Some(Token::Num(value)) => value.to_string(),
+            Some(Token::Op(op)) => format!("'{}'", op),
+            None => "the end".to_string(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.term()?;
+        let comparison = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ]
+        .into_iter()
+        .find(|(op, _)| self.eat(op));
+        match comparison {
+            Some((_, comparison)) => Ok(Expr::Compare(
+                Box::new(left),
+                comparison,
+                Box::new(self.term()?),
+            )),
+            None => Ok(left),
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr = match self.peek().cloned() {
+            Some(Token::Str(value)) => Expr::Literal(Value::Str(value)),
+            Some(Token::Num(value)) => Expr::Literal(Value::Num(value)),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                _ => Expr::Field(Field::parse(&name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown field '{}' in filter; expected path, name, ext, lang, size or lines",
+                        name
+                    )
+                })?),
+            },
+            Some(Token::Op("(")) => {
+                self.position += 1;
+                let expr = self.or()?;
+                self.expect(")")?;
+                return self.methods(expr);
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Expected a value in filter, found {}",
+                    self.describe_next()
+                ));
+            }
+        };
+        self.position += 1;
+        expr = self.methods(expr)?;
+        Ok(expr)
+    }
+
+    /// `.method(arg)` calls following a value
+    fn methods(&mut self, mut expr: Expr) -> Result<Expr> {
+        while self.eat(".") {
+            let method = match self.peek() {
+                Some(Token::Ident(name)) => match name.as_str() {
+                    "contains" => Method::Contains,
+                    "starts_with" => Method::StartsWith,
+                    "ends_with" => Method::EndsWith,
+                    "matches" => Method::Matches,
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Unknown method '{}' in filter; expected contains, starts_with, ends_with or matches",
+                            name
+                        ));
+                    }
+                },
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Expected a method name in filter, found {}",
+                        self.describe_next()
+                    ));
+                }
+            };
+            self.position += 1;
+            self.expect("(")?;
+            let argument = self.or()?;
+            self.expect(")")?;
+            expr = Expr::Call(Box::new(expr), method, Box::new(argument));
+        }
+        Ok(expr)
+    }
+}
+
+impl Expr {
+    /// Type of the expression, or why its operands don't fit together
+    fn check(&self) -> Result<Type> {
+        let expect = |expr: &Expr, expected: Type, context: &str| -> Result<()> {
+            let actual = expr.check()?;
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "{} expects a {}, not a {}",
+                    context,
+                    expected,
+                    actual
+                ))
+            }
+        };
+
+        match self {
+            Expr::Literal(Value::Str(_)) => Ok(Type::Str),
+            Expr::Literal(Value::Num(_)) => Ok(Type::Num),
+            Expr::Literal(Value::Bool(_)) => Ok(Type::Bool),
+            Expr::Field(Field::Size | Field::Lines) => Ok(Type::Num),
+            Expr::Field(_) => Ok(Type::Str),
+            Expr::Not(inner) => expect(inner, Type::Bool, "'!'").map(|()| Type::Bool),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                expect(left, Type::Bool, "'&&' and '||'")?;
+                expect(right, Type::Bool, "'&&' and '||'")?;
+                Ok(Type::Bool)
+            }
+            Expr::Compare(left, comparison, right) => {
+                let left_type = left.check()?;
+                expect(right, left_type, "Comparing")?;
+                if !matches!(comparison, Comparison::Eq | Comparison::Ne) && left_type != Type::Num
+                {
+                    return Err(anyhow::anyhow!("Only numbers can be ordered in filters"));
+                }
+                Ok(Type::Bool)
+            }
+            Expr::Call(receiver, _, argument) => {
+                expect(receiver, Type::Str, "A method")?;
+                expect(argument, Type::Str, "A method argument")?;
+                Ok(Type::Bool)
+            }
+        }
+    }
+
+    fn eval(&self, facts: &FileFacts) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(field) => field.value(facts),
+            Expr::Not(inner) => Value::Bool(!inner.eval(facts).is_true()),
+            Expr::And(left, right) => {
+                Value::Bool(left.eval(facts).is_true() && right.eval(facts).is_true())
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(left.eval(facts).is_true() || right.eval(facts).is_true())
+            }
+            Expr::Compare(left, comparison, right) => {
+                let (left, right) = (left.eval(facts), right.eval(facts));
+                let ordering = match (&left, &right) {
+                    (Value::Num(a), Value::Num(b)) => a.partial_cmp(b),
+                    _ => None,
+                };
+                Value::Bool(match comparison {
+                    Comparison::Eq => left == right,
+                    Comparison::Ne => left != right,
+                    Comparison::Lt => ordering.is_some_and(|o| o.is_lt()),
+                    Comparison::Le => ordering.is_some_and(|o| o.is_le()),
+                    Comparison::Gt => ordering.is_some_and(|o| o.is_gt()),
+                    Comparison::Ge => ordering.is_some_and(|o| o.is_ge()),
+                })
+            }
+            Expr::Call(receiver, method, argument) => {
+                let (Value::Str(receiver), Value::Str(argument)) =
+                    (receiver.eval(facts), argument.eval(facts))
+                else {
+                    return Value::Bool(false);
+                };
+                Value::Bool(match method {
+                    Method::Contains => receiver.contains(&argument),
+                    Method::StartsWith => receiver.starts_with(&argument),
+                    Method::EndsWith => receiver.ends_with(&argument),
+                    Method::Matches => {
+                        PatternMatcher::new(&[argument]).matches_path(Path::new(&receiver))
+                    }
+                })
+            }
+        }
+    }
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        *self == Value::Bool(true)
+    }
+}
+
+/// A parsed `--filter` expression over a file's path, name, ext, lang, size and lines, e.g.
+/// `lang == "rust" && lines < 2000 && !path.contains("generated")`
+#[derive(Clone, Debug)]
+pub struct FilterExpr {
+    source: String,
+    expr: Expr,
+}
+
+impl FilterExpr {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(anyhow::anyhow!(
+                "Unexpected {} in filter",
+                parser.describe_next()
+            ));
+        }
+        if expr.check()? != Type::Bool {
+            return Err(anyhow::anyhow!(
+                "Filter must be a condition, such as lines < 2000"
+            ));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// The expression as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, facts: &FileFacts) -> bool {
+        self.expr.eval(facts).is_true()
+    }
+}
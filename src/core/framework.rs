@@ -0,0 +1,259 @@
+use crate::config::patterns::DEFAULT_INCLUDE_PATTERNS;
+use crate::core::file_collector::{CollectOptions, CollectedFile};
+use crate::core::pattern_matcher::{PatternMatcher, PatternSource};
+use crate::io::vfs::Vfs;
+use std::path::Path;
+use tracing::debug;
+
+/// Web frameworks with a curated snapshot profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Framework {
+    /// Next.js: config, middleware, then `app/` layouts, pages and route handlers
+    Nextjs,
+    /// Vite: config, entry points, then routes, pages and components
+    Vite,
+    /// Django: settings, URLs, then models, views and templates
+    Django,
+    /// Rails: Gemfile, routes and config, then models, controllers and views
+    Rails,
+}
+
+/// Files to add to, leave out of and put first in a framework project's snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameworkProfile {
+    /// Patterns collected on top of the default include patterns
+    pub includes: &'static [&'static str],
+    /// Build output, generated code and runtime state
+    pub excludes: &'static [&'static str],
+    /// Files matching each pattern come before those matching the next, and files matching
+    /// none come last, each group in collection order
+    pub order: &'static [&'static str],
+}
+
+const NEXTJS: FrameworkProfile = FrameworkProfile {
+    includes: &["*.cjs", "*.mts", "*.mdx"],
+    excludes: &[
+        ".next/",
+        "out/",
+        ".vercel/",
+        ".turbo/",
+        "coverage/",
+        "next-env.d.ts",
+        "*.tsbuildinfo",
+    ],
+    order: &[
+        "/package.json",
+        "next.config.*",
+        "/tsconfig.json",
+        "middleware.*",
+        "**/app/**/layout.*",
+        "**/app/**/page.*",
+        "**/app/**/route.*",
+        "**/app/**",
+        "**/pages/**",
+        "**/components/**",
+        "**/lib/**",
+        "**/styles/**",
+    ],
+};
+
+const VITE: FrameworkProfile = FrameworkProfile {
+    includes: &["*.cjs", "*.mts"],
+    excludes: &["dist/", ".vite/", "coverage/", "*.tsbuildinfo"],
+    order: &[
+        "/package.json",
+        "vite.config.*",
+        "/tsconfig*.json",
+        "/index.html",
+        "src/main.*",
+        "src/App.*",
+        "**/{router,routes}/**",
+        "**/{pages,views}/**",
+        "**/components/**",
+        "**/{store,stores}/**",
+    ],
+};
+
+const DJANGO: FrameworkProfile = FrameworkProfile {
+    includes: &["Pipfile", "*.jinja", "*.j2"],
+    excludes: &[
+        "staticfiles/",
+        "media/",
+        "htmlcov/",
+        "**/migrations/**",
+        "*.sqlite3",
+    ],
+    order: &[
+        "/manage.py",
+        "/pyproject.toml",
+        "/requirements*.txt",
+        "settings.py",
+        "**/settings/**",
+        "urls.py",
+        "models.py",
+        "**/models/**",
+        "views.py",
+        "**/views/**",
+        "{serializers,forms,admin}.py",
+        "**/templates/**",
+    ],
+};
+
+const RAILS: FrameworkProfile = FrameworkProfile {
+    includes: &[
+        "Gemfile",
+        "Rakefile",
+        "config.ru",
+        "*.rake",
+        "*.erb",
+        "*.haml",
+        "*.slim",
+        "*.jbuilder",
+    ],
+    excludes: &[
+        "tmp/",
+        "log/",
+        "storage/",
+        "coverage/",
+        "/public/assets/",
+        "/public/packs/",
+        "/vendor/bundle/",
+        "/db/migrate/",
+    ],
+    order: &[
+        "/Gemfile",
+        "/config/routes.rb",
+        "/config/application.rb",
+        "/config/**",
+        "/db/schema.rb",
+        "/app/models/**",
+        "/app/controllers/**",
+        "/app/views/**",
+        "/app/**",
+        "/lib/**",
+    ],
+};
+
+impl Framework {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Framework::Nextjs => "Next.js",
+            Framework::Vite => "Vite",
+            Framework::Django => "Django",
+            Framework::Rails => "Rails",
+        }
+    }
+
+    pub fn profile(&self) -> FrameworkProfile {
+        match self {
+            Framework::Nextjs => NEXTJS,
+            Framework::Vite => VITE,
+            Framework::Django => DJANGO,
+            Framework::Rails => RAILS,
+        }
+    }
+}
+
+impl FrameworkProfile {
+    /// Add the profile's excludes to `options`, and its includes along with the defaults
+    /// unless `--include` already replaced them
+    pub fn apply(&self, options: &mut CollectOptions) {
+        options
+            .excludes
+            .extend(self.excludes.iter().map(|pattern| pattern.to_string()));
+        if options.includes.is_empty() {
+            options.includes = DEFAULT_INCLUDE_PATTERNS
+                .iter()
+                .chain(self.includes)
+                .map(|pattern| pattern.to_string())
+                .collect();
+        }
+    }
+
+    /// Reorder `files` by the profile's `order`, keeping collection order within a group
+    pub fn order_files(&self, files: &mut [CollectedFile]) {
+        let matchers: Vec<PatternMatcher> = self
+            .order
+            .iter()
+            .map(|pattern| {
+                PatternMatcher::builder()
+                    .add(*pattern, PatternSource::Default)
+                    .build()
+            })
+            .collect();
+        files.sort_by_cached_key(|file| {
+            matchers
+                .iter()
+                .position(|matcher| matcher.matches_path(&file.relative_path))
+                .unwrap_or(matchers.len())
+        });
+    }
+}
+
+/// `dependencies` or `devDependencies` of the `package.json` in `dir` include `name`
+async fn has_npm_dependency(vfs: &impl Vfs, dir: &Path, name: &str) -> bool {
+    let Ok(content) = vfs.read_to_string(&dir.join("package.json")).await else {
+        return false;
+    };
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(&content) else {
+        return false;
+    };
+    ["dependencies", "devDependencies"]
+        .iter()
+        .any(|key| package.get(key).and_then(|deps| deps.get(name)).is_some())
+}
+
+/// Whether any of `names` exists in `dir`
+async fn has_any(vfs: &impl Vfs, dir: &Path, names: &[&str]) -> bool {
+    for name in names {
+        if vfs.exists(&dir.join(name)).await {
+            return true;
+        }
+    }
+    false
+}
+
+/// Framework of the project in `dir`, told by its config files and dependencies
+pub async fn detect_framework(vfs: &impl Vfs, dir: &Path) -> Option<Framework> {
+    let next_configs = [
+        "next.config.js",
+        "next.config.mjs",
+        "next.config.ts",
+        "next.config.cjs",
+    ];
+    if has_any(vfs, dir, &next_configs).await || has_npm_dependency(vfs, dir, "next").await {
+        return Some(Framework::Nextjs);
+    }
+
+    if vfs.exists(&dir.join("config/routes.rb")).await
+        && (vfs.exists(&dir.join("bin/rails")).await
+            || vfs
+                .read_to_string(&dir.join("Gemfile"))
+                .await
+                .is_ok_and(|gemfile| gemfile.contains("rails")))
+    {
+        return Some(Framework::Rails);
+    }
+
+    if vfs
+        .read_to_string(&dir.join("manage.py"))
+        .await
+        .is_ok_and(|manage| manage.contains("DJANGO_SETTINGS_MODULE"))
+    {
+        return Some(Framework::Django);
+    }
+
+    let vite_configs = [
+        "vite.config.js",
+        "vite.config.mjs",
+        "vite.config.ts",
+        "vite.config.mts",
+        "vite.config.cjs",
+    ];
+    if has_any(vfs, dir, &vite_configs).await || has_npm_dependency(vfs, dir, "vite").await {
+        return Some(Framework::Vite);
+    }
+
+    debug!("No framework recognized in {}", dir.display());
+    None
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+
+/// Where an update's `old_content` was found when only whitespace kept it from matching
+/// exactly, and how to fit its `new_content` in
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    /// Byte range of the matched lines in the content, without the last line's newline
+    pub start: usize,
+    pub end: usize,
+    /// How closely the lines matched, from 0 to 100
+    pub confidence: f64,
+    shift: Shift,
+}
+
+/// Indentation the file has on top of the expected lines, or lacks compared to them
+#[derive(Clone, Debug, PartialEq)]
+enum Shift {
+    None,
+    Add(String),
+    Remove(String),
+}
+
+impl Shift {
+    fn between(actual: &str, expected: &str) -> Option<Self> {
+        let indent = |line: &str| line[..line.len() - line.trim_start().len()].to_string();
+        let (actual, expected) = (indent(actual), indent(expected));
+        if actual == expected {
+            Some(Shift::None)
+        } else if let Some(extra) = actual.strip_suffix(expected.as_str()) {
+            Some(Shift::Add(extra.to_string()))
+        } else {
+            expected
+                .strip_suffix(actual.as_str())
+                .map(|missing| Shift::Remove(missing.to_string()))
+        }
+    }
+
+    fn apply(&self, line: &str) -> String {
+        match self {
+            Shift::Add(extra) if !line.trim().is_empty() => format!("{}{}", extra, line),
+            Shift::Remove(missing) => line.strip_prefix(missing.as_str()).unwrap_or(line).into(),
+            _ => line.to_string(),
+        }
+    }
+}
+
+/// How well `actual` stands in for `expected`: 100 when identical, less the more whitespace
+/// differs, `None` when the text itself differs. Indentation costs less when the whole
+/// block is off by the same amount, since the replacement can be re-indented to match.
+fn line_score(actual: &str, expected: &str, consistent_indent: bool) -> Option<f64> {
+    if actual == expected {
+        Some(100.0)
+    } else if actual.trim_end() == expected.trim_end() {
+        Some(95.0)
+    } else if actual.trim() == expected.trim() {
+        Some(if consistent_indent { 90.0 } else { 80.0 })
+    } else if actual.split_whitespace().eq(expected.split_whitespace()) {
+        Some(70.0)
+    } else {
+        None
+    }
+}
+
+/// Non-blank lines of `text` with the number of blank lines trimmed from its start and end
+fn trimmed_lines(text: &str) -> (Vec<&str>, usize, usize) {
+    let lines: Vec<&str> = text.lines().collect();
+    let leading = lines
+        .iter()
+        .take_while(|line| line.trim().is_empty())
+        .count();
+    let trailing = lines[leading..]
+        .iter()
+        .rev()
+        .take_while(|line| line.trim().is_empty())
+        .count();
+    (
+        lines[leading..lines.len() - trailing].to_vec(),
+        leading,
+        trailing,
+    )
+}
+
+/// Find `old` in `content` line by line, tolerating differences in trailing whitespace,
+/// indentation and runs of spaces. Of the places that match, the closest wins; an error
+/// when several match equally well.
+pub fn find_fuzzy(content: &str, old: &str) -> Result<Option<FuzzyMatch>> {
+    let (expected, _, _) = trimmed_lines(old);
+    if expected.is_empty() {
+        return Ok(None);
+    }
+
+    // Each line of the content with its byte offset, newlines left out
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        lines.push((offset, line.trim_end_matches(['\n', '\r'])));
+        offset += line.len();
+    }
+
+    let mut best: Vec<FuzzyMatch> = Vec::new();
+    for window in lines.windows(expected.len()) {
+        // Re-indenting the replacement only makes sense when every line is off the same way
+        let shifts: Vec<Option<Shift>> = window
+            .iter()
+            .zip(&expected)
+            .filter(|((_, actual), _)| !actual.trim().is_empty())
+            .map(|((_, actual), expected)| Shift::between(actual, expected))
+            .collect();
+        let consistent = shifts.windows(2).all(|pair| pair[0] == pair[1]);
+        let shift = match shifts.first() {
+            Some(Some(shift)) if consistent => shift.clone(),
+            _ => Shift::None,
+        };
+        let Some(score) = window
+            .iter()
+            .zip(&expected)
+            .map(|((_, actual), expected)| line_score(actual, expected, consistent))
+            .sum::<Option<f64>>()
+        else {
+            continue;
+        };
+
+        let (start, _) = window[0];
+        let (last_start, last) = window[window.len() - 1];
+        let candidate = FuzzyMatch {
+            start,
+            end: last_start + last.len(),
+            confidence: score / expected.len() as f64,
+            shift,
+        };
+        match best.first() {
+            Some(current) if current.confidence > candidate.confidence => {}
+            Some(current) if current.confidence == candidate.confidence => best.push(candidate),
+            _ => best = vec![candidate],
+        }
+    }
+
+    if best.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "Old content matches {} places equally well with whitespace ignored",
+            best.len()
+        ));
+    }
+    Ok(best.pop())
+}
+
+impl FuzzyMatch {
+    /// `content` with the matched lines replaced by `new`, re-indented to fit. Blank lines
+    /// trimmed from the edges of `old` are trimmed from `new` too.
+    pub fn replace(&self, content: &str, old: &str, new: &str) -> String {
+        let (_, leading, trailing) = trimmed_lines(old);
+        let mut lines: Vec<&str> = new.lines().collect();
+        let skip = lines
+            .iter()
+            .take(leading)
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        lines.drain(..skip);
+        for _ in 0..trailing {
+            if lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+        }
+
+        let replacement: Vec<String> = lines.iter().map(|line| self.shift.apply(line)).collect();
+        let mut end = self.end;
+        // Nothing left in place of the lines, so their line break goes too
+        if replacement.is_empty() {
+            end = content[end..]
+                .find('\n')
+                .map_or(content.len(), |newline| end + newline + 1);
+        }
+        format!(
+            "{}{}{}",
+            &content[..self.start],
+            replacement.join("\n"),
+            &content[end..]
+        )
+    }
+}
@@ -0,0 +1,228 @@
+use crate::config::patterns::CATNIPIGNORE_FILE;
+use crate::core::pattern_matcher::{MatchInfo, PatternSource};
+use crate::io::vfs::Vfs;
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Component, Path, PathBuf};
+use tracing::debug;
+
+/// Ignore files honored during collection, the way ripgrep reads them. Later names take
+/// precedence over earlier ones in the same directory.
+pub const IGNORE_FILE_NAMES: [&str; 3] = [".gitignore", ".ignore", ".rgignore"];
+
+/// Source reported for a rule read from the ignore file named `name`
+fn pattern_source(name: &str) -> PatternSource {
+    match name {
+        ".gitignore" => PatternSource::Gitignore,
+        CATNIPIGNORE_FILE => PatternSource::Catnipignore,
+        "exclude" => PatternSource::GitExclude,
+        _ => PatternSource::IgnoreFile,
+    }
+}
+
+/// Ignore rules from one directory's ignore files
+#[derive(Clone, Debug)]
+struct IgnoreDir {
+    /// Directory holding the ignore files, relative to the input directory
+    dir: PathBuf,
+    /// For a directory above the input directory, the input directory relative to it
+    above: Option<PathBuf>,
+    /// Rules are checked from the highest rank down: `.git/info/exclude`, then directories
+    /// above the input directory, then the ones in it, shallowest first within each
+    rank: (u8, usize),
+    /// The directory's ignore files parsed together, so the last matching line decides
+    rules: Gitignore,
+}
+
+/// Lines of the ignore files at `paths`, parsed the way git and ripgrep do, if any exist
+async fn read_ignore_files(vfs: &impl Vfs, paths: &[PathBuf]) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new("");
+    let mut found = false;
+
+    for path in paths {
+        let Ok(content) = vfs.read_to_string(path).await else {
+            continue;
+        };
+        debug!("Using ignore file {}", path.display());
+        found = true;
+        for line in content.lines() {
+            if let Err(e) = builder.add_line(Some(path.clone()), line) {
+                debug!("Skipping line of {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    if !found {
+        return None;
+    }
+    builder
+        .build()
+        .inspect_err(|e| debug!("Failed to build ignore rules: {}", e))
+        .ok()
+}
+
+/// Rules from the ignore files found below one input directory, matched with the same
+/// parser the walk uses. Each directory's rules apply to paths relative to it; the deepest
+/// directory with a matching rule decides, and within a directory the last matching line
+/// does, so a `!` pattern re-includes what an earlier one ignored.
+#[derive(Clone, Debug)]
+pub struct IgnoreRules {
+    dirs: Vec<IgnoreDir>,
+    /// Ignore file names read in each directory
+    names: Vec<&'static str>,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl IgnoreRules {
+    /// Rules from `.catnipignore` files, and from the other ignore files too when
+    /// `ignore_files` is set
+    pub fn new(ignore_files: bool) -> Self {
+        let mut names = Vec::new();
+        if ignore_files {
+            names.extend(IGNORE_FILE_NAMES);
+        }
+        names.push(CATNIPIGNORE_FILE);
+        Self {
+            dirs: Vec::new(),
+            names,
+        }
+    }
+
+    fn push(&mut self, ignore_dir: IgnoreDir) {
+        self.dirs.push(ignore_dir);
+        self.dirs.sort_by_key(|ignore_dir| ignore_dir.rank);
+    }
+
+    fn ignore_files_in(&self, dir: &Path) -> Vec<PathBuf> {
+        self.names.iter().map(|name| dir.join(name)).collect()
+    }
+
+    /// Read the ignore files in `root/dir`, skipping the ones that are missing or unreadable
+    pub async fn load_dir(&mut self, vfs: &impl Vfs, root: &Path, dir: &Path) {
+        let paths = self.ignore_files_in(&root.join(dir));
+        if let Some(rules) = read_ignore_files(vfs, &paths).await {
+            self.push(IgnoreDir {
+                dir: dir.to_path_buf(),
+                above: None,
+                rank: (2, dir.components().count()),
+                rules,
+            });
+        }
+    }
+
+    /// Load the rules git applies to `input_dir` from outside it: the ignore files of the
+    /// directories between it and the root of its repository, and the repository's
+    /// `.git/info/exclude`. Nothing is loaded outside a repository.
+    pub async fn load_repository(&mut self, vfs: &impl Vfs, input_dir: &Path) {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let input_dir: PathBuf = current_dir
+            .join(input_dir)
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+
+        let mut repository = None;
+        for ancestor in input_dir.ancestors() {
+            if vfs.exists(&ancestor.join(".git")).await {
+                repository = Some(ancestor.to_path_buf());
+                break;
+            }
+        }
+        let Some(repository) = repository else {
+            return;
+        };
+
+        for ancestor in input_dir
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(&repository))
+        {
+            let paths = self.ignore_files_in(ancestor);
+            if let Some(rules) = read_ignore_files(vfs, &paths).await {
+                self.push(IgnoreDir {
+                    dir: PathBuf::new(),
+                    above: input_dir.strip_prefix(ancestor).ok().map(Path::to_path_buf),
+                    rank: (1, ancestor.components().count()),
+                    rules,
+                });
+            }
+        }
+
+        let exclude = repository.join(".git/info/exclude");
+        if let Some(rules) = read_ignore_files(vfs, std::slice::from_ref(&exclude)).await {
+            self.push(IgnoreDir {
+                dir: PathBuf::new(),
+                above: input_dir
+                    .strip_prefix(&repository)
+                    .ok()
+                    .map(Path::to_path_buf),
+                rank: (0, 0),
+                rules,
+            });
+        }
+    }
+
+    /// Load the ignore files of every directory below the input that holds one of `files`
+    pub async fn load_nested(&mut self, vfs: &impl Vfs, root: &Path, files: &[PathBuf]) {
+        let mut dirs: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| {
+                file.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| self.names.contains(&name))
+            })
+            .filter_map(|file| {
+                file.strip_prefix(root)
+                    .ok()?
+                    .parent()
+                    .map(Path::to_path_buf)
+            })
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        for dir in dirs {
+            self.load_dir(vfs, root, &dir).await;
+        }
+    }
+
+    /// The rule ignoring `relative_path`, a directory when `is_dir` is set
+    pub fn matched(&self, relative_path: &Path, is_dir: bool) -> Option<MatchInfo> {
+        for ignore_dir in self.dirs.iter().rev() {
+            let path = match &ignore_dir.above {
+                Some(input_dir) => input_dir.join(relative_path),
+                None => match relative_path.strip_prefix(&ignore_dir.dir) {
+                    Ok(path) => path.to_path_buf(),
+                    Err(_) => continue,
+                },
+            };
+            // Paths outside the input directory aren't the rules' business
+            if path.as_os_str().is_empty() || path.has_root() {
+                continue;
+            }
+
+            match ignore_dir.rules.matched_path_or_any_parents(&path, is_dir) {
+                Match::None => {}
+                Match::Whitelist(_) => return None,
+                Match::Ignore(glob) => {
+                    let name = glob
+                        .from()
+                        .and_then(Path::file_name)
+                        .and_then(|name| name.to_str())
+                        .unwrap_or_default();
+                    return Some(MatchInfo {
+                        pattern: glob.original().to_string(),
+                        source: pattern_source(name),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
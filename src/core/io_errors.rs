@@ -0,0 +1,122 @@
+use crate::core::content_processor::OutputFormat;
+use crate::utils::html::escape_html;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use anyhow::Result;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// A file left out of the snapshot because it could not be read
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl SkippedFile {
+    /// The reason without the path it repeats or the byte offsets that would keep files
+    /// failing the same way from being listed together
+    pub fn short_reason(&self) -> String {
+        let prefix = format!("Failed to read {}: ", self.path.display());
+        let reason = self.reason.strip_prefix(&prefix).unwrap_or(&self.reason);
+        match reason.split_once(": ") {
+            Some(("invalid UTF-8", _)) => "invalid UTF-8".to_string(),
+            _ => reason.to_string(),
+        }
+    }
+}
+
+/// What to do about unreadable files: fail in strict mode, otherwise warn and keep a
+/// record. Clones share the same record, so one instance can follow a run through
+/// collection and rendering.
+#[derive(Clone, Debug, Default)]
+pub struct IoErrors {
+    strict: bool,
+    skipped: Arc<Mutex<Vec<SkippedFile>>>,
+}
+
+impl IoErrors {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            ..Self::default()
+        }
+    }
+
+    pub fn skip(&self, path: &Path, reason: impl Display) -> Result<()> {
+        if self.strict {
+            return Err(anyhow::anyhow!(
+                "Could not read {}: {} (--strict-io)",
+                path.display(),
+                reason
+            ));
+        }
+
+        warn!("Skipping {}: {}", path.display(), reason);
+        self.skipped.lock().unwrap().push(SkippedFile {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+        Ok(())
+    }
+
+    pub fn skipped(&self) -> Vec<SkippedFile> {
+        self.skipped.lock().unwrap().clone()
+    }
+
+    /// "Unavailable Files" section listing every skipped file, with files that failed for
+    /// the same reason collapsed into one line, or an `<unavailable_files>` element in XML;
+    /// empty when nothing was skipped
+    pub fn unavailable_section(&self, format: OutputFormat) -> String {
+        let skipped = self.skipped();
+        if skipped.is_empty() {
+            return String::new();
+        }
+
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        if format == OutputFormat::Xml {
+            let mut section = String::from("<unavailable_files>\n");
+            for file in skipped {
+                section.push_str(&format!(
+                    "<file path=\"{}\" reason=\"{}\"/>\n",
+                    escape_html(&encode_path(&relative_to(&file.path, &current_dir))),
+                    escape_html(&file.short_reason())
+                ));
+            }
+            section.push_str("</unavailable_files>\n\n");
+            return section;
+        }
+
+        let mut reasons: Vec<(String, Vec<String>)> = Vec::new();
+        for file in skipped {
+            let path = format!("`{}`", encode_path(&relative_to(&file.path, &current_dir)));
+            let reason = file.short_reason();
+            match reasons.iter_mut().find(|(known, _)| *known == reason) {
+                Some((_, paths)) => {
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+                None => reasons.push((reason, vec![path])),
+            }
+        }
+
+        let mut section = String::from(
+            "# Unavailable Files\n\n*These files could not be read and are not included:*\n\n",
+        );
+        for (reason, paths) in reasons {
+            section.push_str(&format!("- {}: {}\n", paths.join(", "), reason));
+        }
+        section.push('\n');
+        section
+    }
+
+    /// One line per skipped file, for `--error-report`
+    pub fn report(&self) -> String {
+        self.skipped()
+            .iter()
+            .map(|skipped| format!("{}\t{}\n", skipped.path.display(), skipped.reason))
+            .collect()
+    }
+}
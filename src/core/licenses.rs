@@ -0,0 +1,387 @@
+use crate::core::content_processor::OutputFormat;
+use crate::core::file_collector::CollectedFile;
+use crate::io::vfs::Vfs;
+use crate::utils::html::escape_html;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Source of the licenses files declare in their own header
+const HEADER_SOURCE: &str = "SPDX headers";
+
+/// Lines at the top of a file searched for an `SPDX-License-Identifier` header
+const HEADER_LINES: usize = 20;
+
+/// License files looked for in each directory, covering a whole subtree
+const LICENSE_FILES: &[&str] = &[
+    "LICENSE",
+    "LICENSE.md",
+    "LICENSE.txt",
+    "LICENCE",
+    "LICENCE.md",
+    "LICENCE.txt",
+    "COPYING",
+    "COPYING.md",
+    "COPYING.txt",
+    "LICENSE-MIT",
+    "LICENSE-APACHE",
+    "UNLICENSE",
+];
+
+/// Phrases identifying common license texts, most specific first, since the GPL family
+/// texts mention each other
+const LICENSE_TEXTS: &[(&[&str], &str)] = &[
+    (&["GNU AFFERO GENERAL PUBLIC LICENSE"], "AGPL-3.0"),
+    (
+        &["GNU LESSER GENERAL PUBLIC LICENSE", "Version 3"],
+        "LGPL-3.0",
+    ),
+    (&["GNU LESSER GENERAL PUBLIC LICENSE"], "LGPL-2.1"),
+    (&["GNU GENERAL PUBLIC LICENSE", "Version 3"], "GPL-3.0"),
+    (&["GNU GENERAL PUBLIC LICENSE", "Version 2"], "GPL-2.0"),
+    (&["Mozilla Public License Version 2.0"], "MPL-2.0"),
+    (&["Apache License", "Version 2.0"], "Apache-2.0"),
+    (&["Boost Software License - Version 1.0"], "BSL-1.0"),
+    (&["CC0 1.0 Universal"], "CC0-1.0"),
+    (
+        &["This is free and unencumbered software released into the public domain"],
+        "Unlicense",
+    ),
+    (&["Permission is hereby granted, free of charge"], "MIT"),
+    (
+        &["Permission to use, copy, modify, and/or distribute this software"],
+        "ISC",
+    ),
+    (
+        &[
+            "Redistribution and use in source and binary forms",
+            "Neither the name",
+        ],
+        "BSD-3-Clause",
+    ),
+    (
+        &["Redistribution and use in source and binary forms"],
+        "BSD-2-Clause",
+    ),
+];
+
+/// A collected file's license and where it was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileLicense {
+    /// SPDX license expression, such as `MIT OR Apache-2.0`
+    pub expression: String,
+    /// The file itself for an SPDX header, otherwise the license file or package manifest
+    /// covering its directory
+    pub source: PathBuf,
+}
+
+/// Expression of an `SPDX-License-Identifier:` header near the top of `content`
+pub fn spdx_header(content: &str) -> Option<String> {
+    content.lines().take(HEADER_LINES).find_map(|line| {
+        let (_, rest) = line.split_once("SPDX-License-Identifier:")?;
+        let expression = rest
+            .trim()
+            .trim_end_matches("*/")
+            .trim_end_matches("-->")
+            .trim_end_matches('"')
+            .trim();
+        (!expression.is_empty()).then(|| expression.to_string())
+    })
+}
+
+/// SPDX identifier of a license text, recognized by phrases every copy of it contains
+pub fn identify_license_text(text: &str) -> Option<&'static str> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    LICENSE_TEXTS
+        .iter()
+        .find(|(phrases, _)| phrases.iter().all(|phrase| text.contains(phrase)))
+        .map(|(_, id)| *id)
+}
+
+/// `license` field of a package manifest: `Cargo.toml`, `package.json` or `pyproject.toml`
+fn manifest_license(name: &str, content: &str) -> Option<String> {
+    let license = match name {
+        "Cargo.toml" => toml::from_str::<toml::Value>(content)
+            .ok()?
+            .get("package")?
+            .get("license")?
+            .as_str()?
+            .to_string(),
+        "package.json" => serde_json::from_str::<serde_json::Value>(content)
+            .ok()?
+            .get("license")?
+            .as_str()?
+            .to_string(),
+        "pyproject.toml" => toml::from_str::<toml::Value>(content)
+            .ok()?
+            .get("project")?
+            .get("license")?
+            .as_str()?
+            .to_string(),
+        _ => return None,
+    };
+    Some(license).filter(|license| !license.trim().is_empty())
+}
+
+/// License declared in `dir` by a package manifest, or failing that by its license files.
+/// Several license files, as in `LICENSE-MIT` and `LICENSE-APACHE`, offer a choice.
+async fn directory_license(vfs: &impl Vfs, dir: &Path) -> Option<FileLicense> {
+    for name in ["Cargo.toml", "package.json", "pyproject.toml"] {
+        let path = dir.join(name);
+        if let Ok(content) = vfs.read_to_string(&path).await
+            && let Some(expression) = manifest_license(name, &content)
+        {
+            return Some(FileLicense {
+                expression,
+                source: path,
+            });
+        }
+    }
+
+    let mut ids = BTreeSet::new();
+    let mut source = None;
+    for name in LICENSE_FILES {
+        let path = dir.join(name);
+        let Ok(content) = vfs.read_to_string(&path).await else {
+            continue;
+        };
+        if let Some(id) =
+            spdx_header(&content).or_else(|| identify_license_text(&content).map(str::to_string))
+        {
+            debug!("{} is {}", path.display(), id);
+            ids.insert(id);
+            source.get_or_insert(path);
+        }
+    }
+    Some(FileLicense {
+        expression: ids.into_iter().collect::<Vec<_>>().join(" OR "),
+        source: source?,
+    })
+}
+
+/// Highest directory whose license can cover `file`: the current directory for files under
+/// it, otherwise the input directory the file was collected from
+fn license_root(file: &CollectedFile, current_dir: &Path) -> PathBuf {
+    if file.path.is_relative() || file.path.starts_with(current_dir) {
+        return current_dir.to_path_buf();
+    }
+    file.path
+        .ancestors()
+        .nth(file.relative_path.components().count())
+        .unwrap_or(&file.path)
+        .to_path_buf()
+}
+
+/// License of each collected file: its own SPDX header, otherwise the nearest enclosing
+/// directory's manifest or license files, up to the current directory, or the input
+/// directory for files outside it. Files with no license found are left out. Only the
+/// project's own licenses are found, not those of its dependencies.
+pub async fn detect_licenses(
+    vfs: &impl Vfs,
+    files: &[CollectedFile],
+) -> BTreeMap<PathBuf, FileLicense> {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let mut directories: HashMap<PathBuf, Option<FileLicense>> = HashMap::new();
+    let mut licenses = BTreeMap::new();
+
+    for file in files {
+        if let Ok(content) = vfs.read_text(&file.path).await
+            && let Some(expression) = spdx_header(&content)
+        {
+            licenses.insert(
+                file.path.clone(),
+                FileLicense {
+                    expression,
+                    source: file.path.clone(),
+                },
+            );
+            continue;
+        }
+
+        let root = license_root(file, &current_dir);
+        let mut dir = file.path.parent();
+        while let Some(current) = dir {
+            if !directories.contains_key(current) {
+                let license = directory_license(vfs, current).await;
+                directories.insert(current.to_path_buf(), license);
+            }
+            if let Some(license) = &directories[current] {
+                licenses.insert(file.path.clone(), license.clone());
+                break;
+            }
+            if current == root {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    licenses
+}
+
+/// Licenses whose files `--exclude-license` leaves out
+#[derive(Clone, Debug, Default)]
+pub struct LicenseFilter {
+    excluded: Vec<String>,
+}
+
+impl LicenseFilter {
+    /// `ids` are SPDX identifiers in any case; `gpl-3.0` also covers `GPL-3.0-only`,
+    /// `GPL-3.0-or-later` and `GPL-3.0+`
+    pub fn new(ids: &[String]) -> Self {
+        Self {
+            excluded: ids.iter().map(|id| id.trim().to_lowercase()).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.excluded.is_empty()
+    }
+
+    fn excludes_id(&self, id: &str) -> bool {
+        let id = id.to_lowercase();
+        self.excluded.iter().any(|excluded| {
+            id == *excluded
+                || id
+                    .strip_prefix(excluded.as_str())
+                    .is_some_and(|suffix| matches!(suffix, "-only" | "-or-later" | "+"))
+        })
+    }
+
+    /// Whether `expression` only allows excluded licenses: every `OR` alternative has an
+    /// excluded license among its `AND` terms
+    pub fn excludes(&self, expression: &str) -> bool {
+        let tokens = expression.replace(['(', ')'], " ");
+        let mut alternatives = vec![false];
+        let mut tokens = tokens.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token.to_uppercase().as_str() {
+                "OR" => alternatives.push(false),
+                "AND" => {}
+                // License exceptions only grant more
+                "WITH" => {
+                    tokens.next();
+                }
+                _ => {
+                    if self.excludes_id(token) {
+                        *alternatives.last_mut().unwrap() = true;
+                    }
+                }
+            }
+        }
+        !self.is_empty() && alternatives.into_iter().all(|excluded| excluded)
+    }
+}
+
+/// One license of a snapshot's license notice
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LicenseEntry {
+    pub license: String,
+    pub files: usize,
+    /// License files and manifests it was found in, relative to the current directory, and
+    /// `SPDX headers` when files declare it themselves
+    pub sources: Vec<String>,
+}
+
+/// What the license notice covers, and what it doesn't
+const NOTICE_SCOPE: &str = "Licenses of the files in this snapshot, from SPDX headers, license files and package manifests. The licenses of dependencies are not covered.";
+
+/// Licenses of the files in a snapshot, for organizations that must account for what
+/// code is shared
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LicenseNotice {
+    pub licenses: Vec<LicenseEntry>,
+    /// Files no license was found for
+    pub unlicensed: usize,
+}
+
+impl LicenseNotice {
+    /// Notice for `files`, with the licenses `detect_licenses` found
+    pub fn new(licenses: &BTreeMap<PathBuf, FileLicense>, files: &[PathBuf]) -> Self {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let mut entries: BTreeMap<&str, (usize, BTreeSet<String>)> = BTreeMap::new();
+        let mut unlicensed = 0;
+        for file in files {
+            let Some(license) = licenses.get(file) else {
+                unlicensed += 1;
+                continue;
+            };
+            let source = if license.source == *file {
+                HEADER_SOURCE.to_string()
+            } else {
+                encode_path(&relative_to(&license.source, &current_dir))
+            };
+            let entry = entries.entry(&license.expression).or_default();
+            entry.0 += 1;
+            entry.1.insert(source);
+        }
+
+        Self {
+            licenses: entries
+                .into_iter()
+                .map(|(license, (files, sources))| LicenseEntry {
+                    license: license.to_string(),
+                    files,
+                    sources: sources.into_iter().collect(),
+                })
+                .collect(),
+            unlicensed,
+        }
+    }
+
+    /// "License Notice" section listing each license with its file count and sources, or a
+    /// `<license_notice>` element in XML
+    pub fn section(&self, format: OutputFormat) -> String {
+        if format == OutputFormat::Xml {
+            return self.xml_section();
+        }
+
+        let mut section = format!("# License Notice\n\n*{}*\n\n", NOTICE_SCOPE);
+        for entry in &self.licenses {
+            let sources: Vec<String> = entry
+                .sources
+                .iter()
+                .map(|source| match source.as_str() {
+                    HEADER_SOURCE => source.clone(),
+                    _ => format!("`{}`", source),
+                })
+                .collect();
+            let _ = writeln!(
+                section,
+                "- `{}`: {} files ({})",
+                entry.license,
+                entry.files,
+                sources.join(", ")
+            );
+        }
+        if self.unlicensed > 0 {
+            let _ = writeln!(section, "- No license found: {} files", self.unlicensed);
+        }
+        section.push('\n');
+        section
+    }
+
+    fn xml_section(&self) -> String {
+        let mut section = format!("<license_notice>\n<note>{}</note>\n", NOTICE_SCOPE);
+        for entry in &self.licenses {
+            let _ = writeln!(
+                section,
+                "<license expression=\"{}\" files=\"{}\">",
+                escape_html(&entry.license),
+                entry.files
+            );
+            for source in &entry.sources {
+                let _ = writeln!(section, "<source>{}</source>", escape_html(source));
+            }
+            section.push_str("</license>\n");
+        }
+        if self.unlicensed > 0 {
+            let _ = writeln!(section, "<unlicensed files=\"{}\"/>", self.unlicensed);
+        }
+        section.push_str("</license_notice>\n\n");
+        section
+    }
+}
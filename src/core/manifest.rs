@@ -0,0 +1,200 @@
+use crate::config::settings::{ProcessingRule, TransformHook, WorkspaceRoot};
+use crate::core::content_processor::RenderOptions;
+use crate::core::file_collector::CollectOptions;
+use crate::core::packer::Representation;
+use crate::core::snapshot::Snapshot;
+use crate::utils::path_encoding::relative_to;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Opens the manifest comment embedded at the end of a snapshot
+pub const MANIFEST_MARKER: &str = "<!-- catnip manifest";
+
+/// Everything that went into one `cat` run, so the snapshot can be regenerated later or
+/// audited for what was sent. Nothing time-dependent is recorded, so regenerating an
+/// unchanged tree reproduces the same manifest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub catnip_version: String,
+    /// Command line of the run
+    pub command: Vec<String>,
+    /// Left out under `--anonymize`, with the paths made relative to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    pub paths: Vec<PathBuf>,
+    pub collect: CollectManifest,
+    pub render: RenderManifest,
+    pub transforms: Vec<TransformHook>,
+    /// SHA-256 of each file as sent, after transforms
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+/// Collection options after the config and command line were merged
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CollectManifest {
+    pub excludes: Vec<String>,
+    pub includes: Vec<String>,
+    pub dotfiles_allow: Vec<String>,
+    pub max_size_mb: u64,
+    pub test_filter: String,
+    pub skip_directories: Vec<String>,
+    pub ignore_files: bool,
+    pub pinned: Vec<PathBuf>,
+    pub roots: Vec<WorkspaceRoot>,
+    pub submodules: String,
+    #[serde(default)]
+    pub binaries: String,
+    /// `--filter` expression, as written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RustCfgManifest {
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub no_default_features: bool,
+}
+
+/// Rendering options, with the representation `--fit-model` chose for each reduced file
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RenderManifest {
+    pub ignore_comments: bool,
+    pub ignore_docstrings: bool,
+    pub file_meta: bool,
+    pub complexity: bool,
+    pub dir_summaries: bool,
+    pub layout: String,
+    pub group_by: String,
+    pub anchors: String,
+    pub format: String,
+    pub processing: Vec<ProcessingRule>,
+    pub fence_aliases: BTreeMap<String, String>,
+    /// Only the files changed since a previous snapshot were rendered
+    pub delta: bool,
+    /// Features and target triple of `--rust-cfg`, when Rust code was filtered by `cfg`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_cfg: Option<RustCfgManifest>,
+    pub reduced: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+    pub fn new(
+        command: Vec<String>,
+        paths: &[PathBuf],
+        collect: &CollectOptions,
+        render: &RenderOptions,
+        transforms: &[TransformHook],
+        snapshot: &Snapshot,
+    ) -> Self {
+        let working_dir = std::env::current_dir().unwrap_or_default();
+        let anonymized = render.anonymization.is_some();
+        let recorded = |path: &Path| {
+            if anonymized {
+                relative_to(path, &working_dir)
+            } else {
+                path.to_path_buf()
+            }
+        };
+        // The executable's location and absolute arguments would name the user's directories
+        let command = if anonymized {
+            command
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| {
+                    let path = Path::new(arg);
+                    match path.file_name() {
+                        Some(name) if i == 0 => name.to_string_lossy().into_owned(),
+                        _ if path.is_absolute() => recorded(path).display().to_string(),
+                        _ => arg.clone(),
+                    }
+                })
+                .collect()
+        } else {
+            command
+        };
+        Self {
+            catnip_version: env!("CARGO_PKG_VERSION").to_string(),
+            command,
+            working_dir: (!anonymized).then(|| working_dir.clone()),
+            paths: paths.iter().map(|path| recorded(path)).collect(),
+            collect: CollectManifest {
+                excludes: collect.excludes.clone(),
+                includes: collect.includes.clone(),
+                dotfiles_allow: collect.dotfiles_allow.clone(),
+                max_size_mb: collect.max_size_mb,
+                test_filter: format!("{:?}", collect.test_filter),
+                skip_directories: collect.skip_directories.clone(),
+                ignore_files: collect.ignore_files,
+                pinned: collect.pinned.clone(),
+                roots: collect.roots.clone(),
+                submodules: format!("{:?}", collect.submodule_mode),
+                binaries: format!("{:?}", collect.binaries),
+                filter: collect
+                    .filter
+                    .as_ref()
+                    .map(|filter| filter.source().to_string()),
+            },
+            render: RenderManifest {
+                ignore_comments: render.ignore_comments,
+                ignore_docstrings: render.ignore_docstrings,
+                file_meta: render.file_meta,
+                complexity: render.complexity,
+                dir_summaries: render.dir_summaries,
+                layout: format!("{:?}", render.layout),
+                group_by: format!("{:?}", render.group_by),
+                anchors: format!("{:?}", render.anchors),
+                format: format!("{:?}", render.format),
+                processing: render.processing.clone(),
+                fence_aliases: render.fence_aliases.clone(),
+                delta: render.delta.is_some(),
+                rust_cfg: render.rust_cfg.as_ref().map(|cfg| RustCfgManifest {
+                    features: cfg.features.iter().cloned().collect(),
+                    target: cfg.target.as_ref().map(|target| target.triple.clone()),
+                    no_default_features: cfg.no_default_features,
+                }),
+                reduced: render
+                    .packing
+                    .iter()
+                    .flatten()
+                    .filter(|(_, representation)| **representation != Representation::Full)
+                    .map(|(path, representation)| {
+                        (recorded(path), representation.label().to_string())
+                    })
+                    .collect(),
+            },
+            transforms: transforms.to_vec(),
+            files: snapshot
+                .files
+                .iter()
+                .map(|(path, entry)| (recorded(path), entry.sha256.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize manifest")
+    }
+
+    /// The manifest as an HTML comment, invisible in rendered markdown
+    pub fn embedded(&self) -> Result<String> {
+        Ok(format!("{}\n{}\n-->\n", MANIFEST_MARKER, self.to_json()?))
+    }
+
+    /// Read back the manifest embedded in a snapshot
+    pub fn extract(snapshot: &str) -> Option<Result<Self>> {
+        let start = snapshot.rfind(MANIFEST_MARKER)? + MANIFEST_MARKER.len();
+        let json = &snapshot[start..];
+        let json = &json[..json.find("-->")?];
+        Some(serde_json::from_str(json).context("Failed to parse embedded manifest"))
+    }
+}
+
+/// `snapshot.md` → `snapshot.manifest.json`, next to the output file
+pub fn manifest_path(output: &Path) -> PathBuf {
+    output.with_extension("manifest.json")
+}
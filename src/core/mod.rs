@@ -1,4 +1,32 @@
+pub mod anonymizer;
+pub mod approval;
+pub mod bench;
+pub mod binary_assets;
+pub mod changelog;
+pub mod classifier;
+pub mod complexity;
 pub mod content_processor;
+pub mod dir_summary;
 pub mod file_collector;
+pub mod filter_expr;
+pub mod framework;
+pub mod fuzzy_match;
+pub mod ignore_files;
+pub mod io_errors;
+pub mod licenses;
+pub mod manifest;
+pub mod packer;
+pub mod patch_journal;
+pub mod patch_script;
 pub mod pattern_matcher;
+pub mod pruned_dirs;
+pub mod refine;
+pub mod relevance;
+pub mod rust_cfg;
+pub mod search_index;
+pub mod sensitive;
+pub mod snapshot;
 pub mod structure_generator;
+pub mod transform;
+pub mod unified_diff;
+pub mod warnings;
@@ -0,0 +1,408 @@
+use crate::config::prompt::{OPTIONAL_PROMPT_SECTIONS, remove_prompt_section};
+use crate::core::classifier::{is_generated_file, is_test_path};
+use crate::core::content_processor::OutputFormat;
+use crate::utils::language_detection::get_language_from_extension;
+use crate::utils::text_processing::{extract_outline, minify};
+use crate::utils::token_counter::count_tokens;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tracing::debug;
+
+/// How much of a file is rendered, from most to least detailed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Representation {
+    Full,
+    Minified,
+    Outline,
+    Summary,
+    Omitted,
+}
+
+impl Representation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Representation::Full => "full",
+            Representation::Minified => "minified",
+            Representation::Outline => "outline",
+            Representation::Summary => "summary",
+            Representation::Omitted => "omitted",
+        }
+    }
+
+    /// Render `content` at this level; `None` when the file is left out
+    pub fn render(&self, content: &str, language: &str, generated: bool) -> Option<String> {
+        match self {
+            Representation::Full => Some(content.to_string()),
+            Representation::Minified => Some(minify(content, language)),
+            Representation::Outline => Some(extract_outline(content, language)),
+            Representation::Summary => Some(summarize(content, generated)),
+            Representation::Omitted => None,
+        }
+    }
+}
+
+/// One-line stand-in for a file's content
+pub fn summarize(content: &str, generated: bool) -> String {
+    format!(
+        "{}{} lines, {} bytes",
+        if generated { "Generated file, " } else { "" },
+        content.lines().count(),
+        content.len()
+    )
+}
+
+/// How important a file is for understanding the project; higher is kept in more detail
+pub fn file_priority(path: &Path) -> u32 {
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let stem = filename.split('.').next().unwrap_or_default();
+
+    let base: u32 = match filename {
+        _ if stem.eq_ignore_ascii_case("readme") => 100,
+        "Cargo.toml" | "package.json" | "pyproject.toml" | "go.mod" | "pom.xml"
+        | "build.gradle" | "Gemfile" | "setup.py" => 95,
+        _ if matches!(stem, "main" | "lib" | "mod" | "index" | "app" | "__init__") => 90,
+        _ if is_test_path(path) => 20,
+        _ if filename.ends_with(".md") => 40,
+        _ => 60,
+    };
+
+    // Files closer to the root tend to be more central
+    let depth = path.components().count().saturating_sub(1) as u32;
+    base.saturating_sub(depth.min(5) * 2)
+}
+
+/// Priority class of pinned files, which are never degraded to fit a budget
+pub const PINNED_PRIORITY: u32 = u32::MAX;
+
+/// Per-file representation chosen to fit a token budget
+#[derive(Clone, Debug)]
+pub struct PackingPlan {
+    pub budget: usize,
+    pub total_tokens: usize,
+    pub decisions: BTreeMap<PathBuf, Representation>,
+    pub tokens: BTreeMap<PathBuf, usize>,
+    /// Tokens the files would take before any of them is degraded
+    pub required_tokens: usize,
+}
+
+impl PackingPlan {
+    pub fn count(&self, representation: Representation) -> usize {
+        self.decisions
+            .values()
+            .filter(|r| **r == representation)
+            .count()
+    }
+
+    pub fn fits(&self) -> bool {
+        self.total_tokens <= self.budget
+    }
+}
+
+struct Candidate {
+    path: PathBuf,
+    priority: u32,
+    level: Representation,
+    /// Tokens of each representation, indexed by `Representation as usize`
+    costs: [usize; 5],
+}
+
+/// Pick the representation of each file that keeps as many files as possible within
+/// `budget` tokens. Generated files start as summaries; then, one level at a time and
+/// least important files first, files are minified, outlined, summarized and finally
+/// omitted until everything fits.
+pub fn plan_packing(files: &[(PathBuf, String)], budget: usize) -> PackingPlan {
+    plan_packing_with(files, budget, file_priority)
+}
+
+/// Like `plan_packing`, with each file's priority given by `priority`; files with
+/// `PINNED_PRIORITY` are always rendered in full
+pub fn plan_packing_with(
+    files: &[(PathBuf, String)],
+    budget: usize,
+    priority: impl Fn(&Path) -> u32,
+) -> PackingPlan {
+    let mut candidates: Vec<Candidate> = files
+        .iter()
+        .map(|(path, content)| {
+            let language = get_language_from_extension(path);
+            let generated = is_generated_file(path, content);
+            // Heading and code fence around each rendered file
+            let overhead = count_tokens(&format!(
+                "## {}\n\n```{}\n\n```\n\n",
+                path.display(),
+                language
+            ));
+
+            let mut costs = [0; 5];
+            for level in [
+                Representation::Full,
+                Representation::Minified,
+                Representation::Outline,
+                Representation::Summary,
+            ] {
+                let rendered = level
+                    .render(content, language, generated)
+                    .unwrap_or_default();
+                costs[level as usize] = overhead + count_tokens(&rendered);
+            }
+
+            let priority = priority(path);
+            Candidate {
+                path: path.clone(),
+                priority,
+                level: if generated && priority != PINNED_PRIORITY {
+                    Representation::Summary
+                } else {
+                    Representation::Full
+                },
+                costs,
+            }
+        })
+        .collect();
+
+    // Least important files are degraded first; ties go to the larger file
+    candidates.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then(b.costs[0].cmp(&a.costs[0]))
+            .then(a.path.cmp(&b.path))
+    });
+
+    let mut total: usize = candidates.iter().map(|c| c.costs[c.level as usize]).sum();
+    let required_tokens = total;
+
+    for target in [
+        Representation::Minified,
+        Representation::Outline,
+        Representation::Summary,
+        Representation::Omitted,
+    ] {
+        for candidate in candidates.iter_mut() {
+            if total <= budget {
+                break;
+            }
+            if candidate.level >= target || candidate.priority == PINNED_PRIORITY {
+                continue;
+            }
+
+            let saved = candidate.costs[candidate.level as usize]
+                .saturating_sub(candidate.costs[target as usize]);
+            // Only degrade when it actually helps, except as a last resort
+            if saved > 0 || target == Representation::Omitted {
+                total -= candidate.costs[candidate.level as usize];
+                total += candidate.costs[target as usize];
+                candidate.level = target;
+            }
+        }
+    }
+
+    PackingPlan {
+        budget,
+        total_tokens: total,
+        decisions: candidates
+            .iter()
+            .map(|c| (c.path.clone(), c.level))
+            .collect(),
+        tokens: candidates
+            .iter()
+            .map(|c| (c.path.clone(), c.costs[c.level as usize]))
+            .collect(),
+        required_tokens,
+    }
+}
+
+/// Share of the packing budget set aside for the files below a directory, from a
+/// `[budget]` entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetShare {
+    pub dir: PathBuf,
+    /// Percentage of the budget, above 0 and at most 100
+    pub percent: f64,
+}
+
+/// `path` without `.` components, so `./src` and `src` compare equal
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Index of the share covering `path`, the deepest directory winning
+fn share_of(path: &Path, shares: &[BudgetShare]) -> Option<usize> {
+    let path = without_cur_dir(path);
+    shares
+        .iter()
+        .enumerate()
+        .map(|(index, share)| (index, without_cur_dir(&share.dir)))
+        .filter(|(_, dir)| path.starts_with(dir))
+        .max_by_key(|(_, dir)| dir.components().count())
+        .map(|(index, _)| index)
+}
+
+/// Like `plan_packing_with`, with `budget` split between the directories of `shares` and
+/// the files outside them, which get whatever percentage is left. Each group is packed
+/// within its own allotment, so a large directory can't crowd out a small one; tokens a
+/// group doesn't need go to the others in proportion to their shares. Shares adding up to
+/// more than 100% are scaled down.
+pub fn plan_packing_shared(
+    files: &[(PathBuf, String)],
+    budget: usize,
+    priority: impl Fn(&Path) -> u32,
+    shares: &[BudgetShare],
+) -> PackingPlan {
+    if shares.is_empty() {
+        return plan_packing_with(files, budget, priority);
+    }
+
+    // One group per share, then one for the files outside all of them
+    let mut groups = vec![Vec::new(); shares.len() + 1];
+    for file in files {
+        let group = share_of(&file.0, shares).unwrap_or(shares.len());
+        groups[group].push(file.clone());
+    }
+    let mut weights: Vec<f64> = shares.iter().map(|share| share.percent).collect();
+    weights.push((100.0 - weights.iter().sum::<f64>()).max(0.0));
+
+    let needs: Vec<usize> = groups
+        .iter()
+        .map(|group| plan_packing_with(group, usize::MAX, &priority).required_tokens)
+        .collect();
+
+    // Hand out the budget by weight, settling the groups that need less than their
+    // allotment first so their surplus is shared among the rest
+    let mut allotments = vec![0; groups.len()];
+    let mut remaining = budget;
+    let mut open: Vec<usize> = (0..groups.len()).filter(|&g| needs[g] > 0).collect();
+    while !open.is_empty() {
+        let mut total_weight: f64 = open.iter().map(|&g| weights[g]).sum();
+        if total_weight <= 0.0 {
+            // Nothing claims the rest, so it's split evenly
+            open.iter().for_each(|&g| weights[g] = 1.0);
+            total_weight = open.len() as f64;
+        }
+        let offer = |g: usize| (remaining as f64 * weights[g] / total_weight) as usize;
+
+        let settled: Vec<usize> = open
+            .iter()
+            .copied()
+            .filter(|&g| needs[g] <= offer(g))
+            .collect();
+        if settled.is_empty() {
+            for &g in &open {
+                allotments[g] = offer(g);
+            }
+            break;
+        }
+        for &g in &settled {
+            allotments[g] = needs[g];
+            remaining -= needs[g];
+        }
+        open.retain(|g| !settled.contains(g));
+    }
+
+    let mut plan = PackingPlan {
+        budget,
+        total_tokens: 0,
+        decisions: BTreeMap::new(),
+        tokens: BTreeMap::new(),
+        required_tokens: 0,
+    };
+    for (group, allotment) in groups.iter().zip(allotments) {
+        if group.is_empty() {
+            continue;
+        }
+        let group_plan = plan_packing_with(group, allotment, &priority);
+        plan.total_tokens += group_plan.total_tokens;
+        plan.required_tokens += group_plan.required_tokens;
+        plan.decisions.extend(group_plan.decisions);
+        plan.tokens.extend(group_plan.tokens);
+    }
+    plan
+}
+
+/// Like `plan_packing_shared`, for files sent along with `prompt`. Optional prompt sections
+/// are dropped, least useful first, before any file is degraded; returns the plan and the
+/// prompt to send.
+pub fn plan_packing_with_prompt(
+    files: &[(PathBuf, String)],
+    budget: usize,
+    priority: impl Fn(&Path) -> u32,
+    shares: &[BudgetShare],
+    prompt: &str,
+) -> (PackingPlan, String) {
+    let mut prompt = prompt.to_string();
+    let mut plan = plan_packing_shared(
+        files,
+        budget.saturating_sub(count_tokens(&prompt)),
+        &priority,
+        shares,
+    );
+
+    let mut trimmed = false;
+    for section in OPTIONAL_PROMPT_SECTIONS {
+        if plan.required_tokens + count_tokens(&prompt) <= budget {
+            break;
+        }
+        debug!(
+            "Dropping the {} section of the prompt to fit the budget",
+            section
+        );
+        prompt = remove_prompt_section(&prompt, section);
+        trimmed = true;
+    }
+
+    if trimmed {
+        plan = plan_packing_shared(
+            files,
+            budget.saturating_sub(count_tokens(&prompt)),
+            &priority,
+            shares,
+        );
+    }
+    (plan, prompt)
+}
+
+/// Group `files`, in order, into parts of at most `budget` tokens each, counting each file's
+/// heading and fence, or its `<document>` element in XML. A file bigger than the budget gets
+/// a part of its own.
+pub fn split_into_parts(
+    files: &[(PathBuf, String)],
+    budget: usize,
+    format: OutputFormat,
+) -> Vec<Vec<PathBuf>> {
+    let mut parts: Vec<Vec<PathBuf>> = Vec::new();
+    let mut current = Vec::new();
+    let mut used = 0;
+
+    for (path, content) in files {
+        let language = get_language_from_extension(path);
+        let cost = count_tokens(&match format {
+            OutputFormat::Xml => format!(
+                "<document index=\"1\" path=\"{}\" language=\"{}\">\n<document_content>\n{}\n</document_content>\n</document>\n",
+                path.display(),
+                language,
+                content
+            ),
+            _ => format!(
+                "## {}\n\n```{}\n{}\n```\n\n",
+                path.display(),
+                language,
+                content
+            ),
+        });
+        if !current.is_empty() && used + cost > budget {
+            parts.push(std::mem::take(&mut current));
+            used = 0;
+        }
+        current.push(path.clone());
+        used += cost;
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
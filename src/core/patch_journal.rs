@@ -0,0 +1,323 @@
+use crate::io::vfs::Vfs;
+use crate::utils::file_metadata::sha256_hex;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// Hashes of the files `catnip patch` wrote since the last `cat`, so later external
+/// edits that would break follow-up patches from the same conversation can be spotted
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PatchJournal {
+    pub files: BTreeMap<PathBuf, PatchedFile>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PatchedFile {
+    pub sha256: String,
+    pub patched_at: SystemTime,
+}
+
+/// Journal at `path`, or an empty one when there is none yet
+async fn load_json<T: DeserializeOwned + Default>(vfs: &impl Vfs, path: &Path) -> Result<T> {
+    if !vfs.exists(path).await {
+        return Ok(T::default());
+    }
+
+    let content = vfs.read(path).await?;
+    serde_json::from_slice(&content)
+        .with_context(|| format!("Failed to parse patch journal: {}", path.display()))
+}
+
+async fn save_json(vfs: &impl Vfs, path: &Path, journal: &impl Serialize) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        vfs.create_dir_all(parent).await?;
+    }
+
+    let content = serde_json::to_vec(journal).context("Failed to serialize patch journal")?;
+    vfs.write(path, &content).await
+}
+
+impl PatchJournal {
+    pub async fn load(vfs: &impl Vfs, path: &Path) -> Result<Self> {
+        load_json(vfs, path).await
+    }
+
+    pub async fn save(&self, vfs: &impl Vfs, path: &Path) -> Result<()> {
+        save_json(vfs, path, self).await
+    }
+
+    pub fn record(&mut self, path: &Path, content: &[u8]) {
+        self.files.insert(
+            path.to_path_buf(),
+            PatchedFile {
+                sha256: sha256_hex(content),
+                patched_at: SystemTime::now(),
+            },
+        );
+    }
+
+    /// Patched files that were changed or removed after catnip wrote them
+    pub async fn drifted(&self, vfs: &impl Vfs) -> Vec<PathBuf> {
+        let mut drifted = Vec::new();
+
+        for (path, patched) in &self.files {
+            let unchanged = vfs
+                .read(path)
+                .await
+                .is_ok_and(|content| sha256_hex(&content) == patched.sha256);
+            if !unchanged {
+                drifted.push(path.clone());
+            }
+        }
+
+        drifted
+    }
+}
+
+/// Key of the current working directory in journal file names
+fn directory_key() -> String {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    sha256_hex(current_dir.display().to_string().as_bytes())[..16].to_string()
+}
+
+/// Location of the patch journal for the current working directory
+pub fn journal_path(history_dir: &Path) -> PathBuf {
+    history_dir.join(format!("patches-{}.json", directory_key()))
+}
+
+/// Location of the undo journal for the current working directory
+pub fn undo_path(history_dir: &Path) -> PathBuf {
+    history_dir.join(format!("undo-{}.json", directory_key()))
+}
+
+/// Add freshly patched files to the journal, warning rather than failing on errors
+pub async fn record_patched_files(vfs: &impl Vfs, files: &[PathBuf]) {
+    let path = match crate::config::paths::history_dir() {
+        Ok(dir) => journal_path(&dir),
+        Err(e) => {
+            warn!("Not recording patched files: {}", e);
+            return;
+        }
+    };
+
+    let mut journal = PatchJournal::load(vfs, &path).await.unwrap_or_else(|e| {
+        warn!("Starting a new patch journal: {}", e);
+        PatchJournal::default()
+    });
+    for file in files {
+        match vfs.read(file).await {
+            Ok(content) => journal.record(file, &content),
+            Err(e) => debug!("Not journaling {}: {}", file.display(), e),
+        }
+    }
+
+    if let Err(e) = journal.save(vfs, &path).await {
+        warn!("Failed to record patch journal {}: {}", path.display(), e);
+    }
+}
+
+/// Forget patched files once a new snapshot has been taken, warning rather than failing
+pub async fn clear_patch_journal(vfs: &impl Vfs) {
+    let Ok(history_dir) = crate::config::paths::history_dir() else {
+        return;
+    };
+
+    let path = journal_path(&history_dir);
+    if vfs.exists(&path).await
+        && let Err(e) = PatchJournal::default().save(vfs, &path).await
+    {
+        warn!("Failed to clear patch journal {}: {}", path.display(), e);
+    }
+}
+
+/// Stop tracking `files`, once `undo` has put them back as they were before the patch
+pub async fn forget_patched_files(vfs: &impl Vfs, files: &[PathBuf]) {
+    let Ok(history_dir) = crate::config::paths::history_dir() else {
+        return;
+    };
+
+    let path = journal_path(&history_dir);
+    let Ok(mut journal) = PatchJournal::load(vfs, &path).await else {
+        return;
+    };
+    if !files.iter().any(|file| journal.files.contains_key(file)) {
+        return;
+    }
+    for file in files {
+        journal.files.remove(file);
+    }
+    if let Err(e) = journal.save(vfs, &path).await {
+        warn!("Failed to update patch journal {}: {}", path.display(), e);
+    }
+}
+
+/// Files as they were before the last `catnip patch` in a directory, so `catnip undo` can
+/// put them all back
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UndoJournal {
+    /// Analysis of the patch, to tell the user what is being undone
+    pub analysis: String,
+    pub patched_at: Option<SystemTime>,
+    pub files: Vec<PreImage>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PreImage {
+    pub path: PathBuf,
+    /// Content before the patch; `None` for a file the patch created
+    pub before: Option<String>,
+    /// Hash of what the patch wrote, to spot later edits that undoing would throw away;
+    /// `None` for a file the patch deleted or renamed away
+    pub sha256: Option<String>,
+}
+
+/// What `UndoJournal::restore` did, or would do, to one file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Restored {
+    /// Written back with its content from before the patch
+    Reverted(PathBuf),
+    /// Removed, since the patch created it
+    Removed(PathBuf),
+}
+
+impl UndoJournal {
+    pub fn new(analysis: &str) -> Self {
+        Self {
+            analysis: analysis.to_string(),
+            patched_at: Some(SystemTime::now()),
+            files: Vec::new(),
+        }
+    }
+
+    pub async fn load(vfs: &impl Vfs, path: &Path) -> Result<Self> {
+        load_json(vfs, path).await
+    }
+
+    pub async fn save(&self, vfs: &impl Vfs, path: &Path) -> Result<()> {
+        save_json(vfs, path, self).await
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    /// Note that the patch wrote `content` to `path`, which held `before` (`None` when the
+    /// patch created it). A path written twice keeps its first pre-image.
+    pub fn record(&mut self, path: &Path, before: Option<&str>, content: &str) {
+        self.set(path, before, Some(sha256_hex(content.as_bytes())));
+    }
+
+    /// Note that the patch removed `path`, which held `before`, by deleting or renaming it
+    pub fn record_removed(&mut self, path: &Path, before: Option<&str>) {
+        self.set(path, before, None);
+    }
+
+    fn set(&mut self, path: &Path, before: Option<&str>, sha256: Option<String>) {
+        match self.files.iter_mut().find(|file| file.path == path) {
+            Some(file) => file.sha256 = sha256,
+            None => self.files.push(PreImage {
+                path: path.to_path_buf(),
+                before: before.map(str::to_string),
+                sha256,
+            }),
+        }
+    }
+
+    /// Files changed or removed since the patch wrote them, whose edits undoing would lose
+    pub async fn drifted(&self, vfs: &impl Vfs) -> Vec<PathBuf> {
+        let mut drifted = Vec::new();
+        for file in &self.files {
+            let unchanged = match &file.sha256 {
+                Some(sha256) => vfs
+                    .read(&file.path)
+                    .await
+                    .is_ok_and(|content| sha256_hex(&content) == *sha256),
+                None => !vfs.exists(&file.path).await,
+            };
+            if !unchanged {
+                drifted.push(file.path.clone());
+            }
+        }
+        drifted
+    }
+
+    /// Put every file back as it was before the patch, all or nothing: when one can't be
+    /// restored, those already restored get the content they had again. Refuses files
+    /// edited since the patch unless `force` is set.
+    pub async fn restore(
+        &self,
+        vfs: &impl Vfs,
+        dry_run: bool,
+        force: bool,
+    ) -> Result<Vec<Restored>> {
+        let drifted = self.drifted(vfs).await;
+        if !drifted.is_empty() && !force {
+            let list: Vec<String> = drifted
+                .iter()
+                .map(|path| format!("  {}", path.display()))
+                .collect();
+            return Err(anyhow::anyhow!(
+                "These files changed after the patch, and undoing it would lose those edits (use --force to undo anyway):\n{}",
+                list.join("\n")
+            ));
+        }
+
+        let restored: Vec<Restored> = self
+            .files
+            .iter()
+            .map(|file| match file.before {
+                Some(_) => Restored::Reverted(file.path.clone()),
+                None => Restored::Removed(file.path.clone()),
+            })
+            .collect();
+        if dry_run {
+            return Ok(restored);
+        }
+
+        let mut current = Vec::new();
+        for file in &self.files {
+            current.push(vfs.read(&file.path).await.ok());
+        }
+
+        for (i, file) in self.files.iter().enumerate() {
+            let result = match &file.before {
+                Some(before) => vfs.write(&file.path, before.as_bytes()).await,
+                None if current[i].is_none() => Ok(()),
+                None => vfs.remove_file(&file.path).await,
+            };
+            if let Err(e) = result {
+                for (file, content) in self.files[..i].iter().zip(&current) {
+                    let rollback = match content {
+                        Some(content) => vfs.write(&file.path, content).await,
+                        None if !vfs.exists(&file.path).await => Ok(()),
+                        None => vfs.remove_file(&file.path).await,
+                    };
+                    if let Err(rollback) = rollback {
+                        warn!("Failed to roll back {}: {}", file.path.display(), rollback);
+                    }
+                }
+                return Err(e.context("Nothing was undone"));
+            }
+        }
+        Ok(restored)
+    }
+}
+
+/// Keep `journal` as what `catnip undo` reverts, warning rather than failing on errors
+pub async fn record_undo(vfs: &impl Vfs, journal: &UndoJournal) {
+    let path = match crate::config::paths::history_dir() {
+        Ok(dir) => undo_path(&dir),
+        Err(e) => {
+            warn!("Not recording the patch for undo: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = journal.save(vfs, &path).await {
+        warn!("Failed to record undo journal {}: {}", path.display(), e);
+    }
+}
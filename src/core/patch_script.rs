@@ -0,0 +1,131 @@
+use std::fmt::Write;
+use std::path::Path;
+
+/// Shell script reproducing a dry-run patch, for reviewers who'd rather apply changes with
+/// standard tooling than with catnip
+#[derive(Debug, Default)]
+pub struct PatchScript {
+    analysis: String,
+    body: String,
+}
+
+impl PatchScript {
+    pub fn new(analysis: &str) -> Self {
+        Self {
+            analysis: analysis.to_string(),
+            body: String::new(),
+        }
+    }
+
+    /// Create `path` with `content`, refusing to overwrite an existing file
+    pub fn create(&mut self, path: &Path, content: &str) {
+        let quoted = shell_quote(&path.display().to_string());
+        let _ = writeln!(self.body, "\n# Create {}", path.display());
+        let _ = writeln!(
+            self.body,
+            "[ -e {0} ] && {{ echo \"Refusing to overwrite \"{0} >&2; exit 1; }}",
+            quoted
+        );
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            let _ = writeln!(
+                self.body,
+                "mkdir -p {}",
+                shell_quote(&parent.display().to_string())
+            );
+        }
+        self.write_content(&quoted, content);
+    }
+
+    /// Replace the content of `path`, which the patch changes in `updates` places
+    pub fn update(&mut self, path: &Path, content: &str, updates: usize) {
+        let quoted = shell_quote(&path.display().to_string());
+        let _ = writeln!(
+            self.body,
+            "\n# Update {} ({} updates)",
+            path.display(),
+            updates
+        );
+        let _ = writeln!(
+            self.body,
+            "[ -f {0} ] || {{ echo \"Missing \"{0} >&2; exit 1; }}",
+            quoted
+        );
+        self.write_content(&quoted, content);
+    }
+
+    /// Remove `path`, which must exist
+    pub fn delete(&mut self, path: &Path) {
+        let quoted = shell_quote(&path.display().to_string());
+        let _ = writeln!(self.body, "\n# Delete {}", path.display());
+        let _ = writeln!(self.body, "rm {}", quoted);
+    }
+
+    /// Move `from` to `to`, refusing to overwrite, and give it `content`, which the patch
+    /// changes in `updates` places
+    pub fn rename(&mut self, from: &Path, to: &Path, content: &str, updates: usize) {
+        let quoted_from = shell_quote(&from.display().to_string());
+        let quoted_to = shell_quote(&to.display().to_string());
+        let _ = writeln!(
+            self.body,
+            "\n# Rename {} to {} ({} updates)",
+            from.display(),
+            to.display(),
+            updates
+        );
+        let _ = writeln!(
+            self.body,
+            "[ -e {0} ] && {{ echo \"Refusing to overwrite \"{0} >&2; exit 1; }}",
+            quoted_to
+        );
+        if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+            let _ = writeln!(
+                self.body,
+                "mkdir -p {}",
+                shell_quote(&parent.display().to_string())
+            );
+        }
+        let _ = writeln!(self.body, "mv {} {}", quoted_from, quoted_to);
+        if updates > 0 {
+            self.write_content(&quoted_to, content);
+        }
+    }
+
+    /// Heredocs read best but always end in a newline, so content without one is printed instead
+    fn write_content(&mut self, quoted_path: &str, content: &str) {
+        if content.is_empty() || !content.ends_with('\n') {
+            let _ = writeln!(
+                self.body,
+                "printf '%s' {} > {}",
+                shell_quote(content),
+                quoted_path
+            );
+            return;
+        }
+
+        let mut delimiter = "CATNIP_EOF".to_string();
+        while content.lines().any(|line| line == delimiter) {
+            delimiter.push('_');
+        }
+        let _ = write!(
+            self.body,
+            "cat > {} <<'{}'\n{}{}\n",
+            quoted_path, delimiter, content, delimiter
+        );
+    }
+
+    pub fn render(&self) -> String {
+        let mut script =
+            String::from("#!/bin/sh\n# Generated by `catnip patch --dry-run --emit-script`\n");
+        for line in self.analysis.lines() {
+            let _ = writeln!(script, "# {}", line);
+        }
+        script.push_str("set -e\n");
+        script.push_str(&self.body);
+        script
+    }
+}
+
+/// Quote `value` as a single POSIX shell word
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
@@ -1,103 +1,254 @@
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path};
 use tracing::{debug, instrument};
 
-#[derive(Debug)]
+/// Where a pattern came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternSource {
+    Default,
+    Config,
+    Cli,
+    Gitignore,
+    /// `.ignore` or `.rgignore`
+    IgnoreFile,
+    Gitattributes,
+    /// A repository's `.git/info/exclude`
+    GitExclude,
+    /// A `.catnipignore` at the top of an input directory
+    Catnipignore,
+}
+
+impl PatternSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PatternSource::Default => "default",
+            PatternSource::Config => "config",
+            PatternSource::Cli => "command line",
+            PatternSource::Gitignore => "gitignore",
+            PatternSource::IgnoreFile => "ignore file",
+            PatternSource::Gitattributes => "gitattributes",
+            PatternSource::GitExclude => "git exclude",
+            PatternSource::Catnipignore => "catnipignore",
+        }
+    }
+}
+
+impl fmt::Display for PatternSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// The pattern that matched a path and where it came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchInfo {
+    pub pattern: String,
+    pub source: PatternSource,
+}
+
+impl fmt::Display for MatchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' ({})", self.pattern, self.source)
+    }
+}
+
+/// Matches paths relative to a collection root with gitignore-style anchoring:
+///
+/// - a pattern without a `/` (`*.rs`, `target`) matches a file or directory name at any depth
+/// - a pattern with a leading or inner `/` (`/Cargo.toml`, `src/*.rs`) matches from the root
+/// - a trailing `/` (`build/`) only matches directories
+/// - `**/` matches zero or more directories, and a trailing `/**` everything inside
+/// - `{a,b}` matches any of its comma-separated alternatives (`*.{rs,toml}`, `src/{cli,core}/**`)
+///
+/// A pattern matching a directory also matches everything below it.
+#[derive(Clone, Debug)]
 pub struct PatternMatcher {
-    // Fast lookups for exact matches
-    exact_filenames: HashSet<String>,
-    exact_extensions: HashSet<String>,
-    exact_directories: HashSet<String>,
+    // Fast lookups for unanchored patterns without wildcards, keyed by (name, directory only)
+    exact_names: HashMap<(String, bool), MatchInfo>,
+    exact_extensions: HashMap<(String, bool), MatchInfo>,
 
-    // Simple patterns that need more complex matching
+    // Patterns that need more complex matching
     glob_patterns: Vec<GlobPattern>,
 }
 
-#[derive(Debug)]
+/// Collects patterns along with their sources; later patterns take precedence when
+/// reporting which pattern matched
+#[derive(Clone, Debug, Default)]
+pub struct PatternMatcherBuilder {
+    patterns: Vec<MatchInfo>,
+}
+
+impl PatternMatcherBuilder {
+    pub fn add(mut self, pattern: impl Into<String>, source: PatternSource) -> Self {
+        self.patterns.push(MatchInfo {
+            pattern: pattern.into(),
+            source,
+        });
+        self
+    }
+
+    pub fn add_all<I, S>(mut self, patterns: I, source: PatternSource) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for pattern in patterns {
+            self = self.add(pattern, source);
+        }
+        self
+    }
+
+    pub fn build(self) -> PatternMatcher {
+        let mut matcher = PatternMatcher {
+            exact_names: HashMap::new(),
+            exact_extensions: HashMap::new(),
+            glob_patterns: Vec::new(),
+        };
+
+        for info in self.patterns {
+            matcher.categorize_pattern(info);
+        }
+
+        debug!(
+            "PatternMatcher created: {} exact names, {} extensions, {} globs",
+            matcher.exact_names.len(),
+            matcher.exact_extensions.len(),
+            matcher.glob_patterns.len()
+        );
+
+        matcher
+    }
+}
+
+/// Every pattern `pattern` stands for once its `{a,b}` alternations are expanded, nested
+/// ones included. Braces without a matching `}` or a top-level `,` are taken literally.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let mut depth = 0;
+    let mut open = None;
+    let mut commas = Vec::new();
+
+    for (index, ch) in pattern.char_indices() {
+        match ch {
+            '{' => {
+                if depth == 0 {
+                    open = Some(index);
+                    commas.clear();
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => commas.push(index),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    continue;
+                }
+                let start = open.unwrap_or_default();
+                if commas.is_empty() {
+                    continue;
+                }
+
+                let (prefix, suffix) = (&pattern[..start], &pattern[index + 1..]);
+                let mut bounds = vec![start];
+                bounds.extend(&commas);
+                bounds.push(index);
+                return bounds
+                    .windows(2)
+                    .flat_map(|bound| {
+                        let alternative = &pattern[bound[0] + 1..bound[1]];
+                        expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    vec![pattern.to_string()]
+}
+
+#[derive(Clone, Debug)]
 struct GlobPattern {
     parts: Vec<GlobPart>,
+    info: MatchInfo,
+    /// Matched against the path from the root rather than against single names
+    anchored: bool,
+    dir_only: bool,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum GlobPart {
     Literal(String),
     Star,       // *
     DoubleStar, // **
+    DirStar,    // **/
     Question,   // ?
 }
 
 impl PatternMatcher {
+    /// Matcher over built-in patterns; use [`PatternMatcher::builder`] to track other sources
     pub fn new(patterns: &[String]) -> Self {
-        let mut exact_filenames = HashSet::new();
-        let mut exact_extensions = HashSet::new();
-        let mut exact_directories = HashSet::new();
-        let mut glob_patterns = Vec::new();
-
-        for pattern in patterns {
-            Self::categorize_pattern(
-                pattern.trim(),
-                &mut exact_filenames,
-                &mut exact_extensions,
-                &mut exact_directories,
-                &mut glob_patterns,
-            );
-        }
+        Self::builder()
+            .add_all(patterns.iter().cloned(), PatternSource::Default)
+            .build()
+    }
 
-        debug!(
-            "PatternMatcher created: {} exact filenames, {} extensions, {} directories, {} globs",
-            exact_filenames.len(),
-            exact_extensions.len(),
-            exact_directories.len(),
-            glob_patterns.len()
-        );
+    pub fn builder() -> PatternMatcherBuilder {
+        PatternMatcherBuilder::default()
+    }
 
-        Self {
-            exact_filenames,
-            exact_extensions,
-            exact_directories,
-            glob_patterns,
+    fn categorize_pattern(&mut self, mut info: MatchInfo) {
+        info.pattern = info.pattern.trim().to_string();
+        // Each alternative is categorized on its own, so `*.{rs,toml}` still gets the fast
+        // extension lookups, while matches report the pattern as written
+        for pattern in expand_braces(&info.pattern) {
+            self.categorize_alternative(&pattern, info.clone());
         }
     }
 
-    fn categorize_pattern(
-        pattern: &str,
-        exact_filenames: &mut HashSet<String>,
-        exact_extensions: &mut HashSet<String>,
-        exact_directories: &mut HashSet<String>,
-        glob_patterns: &mut Vec<GlobPattern>,
-    ) {
-        // Extension patterns (*.rs, *.py, etc.)
-        if let Some(ext) = pattern.strip_prefix("*.")
-            && !ext.contains('*')
-            && !ext.contains('?')
-            && !ext.contains('/')
-        {
-            exact_extensions.insert(ext.to_string());
+    fn categorize_alternative(&mut self, pattern: &str, info: MatchInfo) {
+        let dir_only = pattern.len() > 1 && pattern.ends_with('/');
+        let pattern = pattern
+            .strip_suffix('/')
+            .filter(|_| dir_only)
+            .unwrap_or(pattern);
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        if pattern.is_empty() {
             return;
         }
 
-        // Exact filename patterns (Cargo.toml, main.rs, etc.)
-        if !pattern.contains('*') && !pattern.contains('?') && !pattern.contains('/') {
-            exact_filenames.insert(pattern.to_string());
-            return;
-        }
+        let has_wildcard = pattern.contains(['*', '?']);
 
-        // Simple directory patterns - handle both "dir" and "dir/*" as the same
-        let clean_pattern = pattern.strip_suffix("/*").unwrap_or(pattern);
-        if !clean_pattern.contains('*')
-            && !clean_pattern.contains('?')
-            && !clean_pattern.contains('/')
-            && !clean_pattern.contains('.')
-        {
-            exact_directories.insert(clean_pattern.to_string());
-            return;
+        if !anchored {
+            // Extension patterns (*.rs, *.py, etc.)
+            if let Some(ext) = pattern.strip_prefix("*.")
+                && !ext.contains(['*', '?'])
+            {
+                self.exact_extensions
+                    .insert((ext.to_string(), dir_only), info);
+                return;
+            }
+
+            // Exact name patterns (Cargo.toml, target, etc.)
+            if !has_wildcard {
+                self.exact_names
+                    .insert((pattern.to_string(), dir_only), info);
+                return;
+            }
         }
 
         // Everything else becomes a glob pattern
-        glob_patterns.push(Self::parse_glob_pattern(pattern));
+        let parts = Self::parse_glob_pattern(pattern);
+        self.glob_patterns.push(GlobPattern {
+            parts,
+            info,
+            anchored,
+            dir_only,
+        });
     }
 
-    fn parse_glob_pattern(pattern: &str) -> GlobPattern {
+    fn parse_glob_pattern(pattern: &str) -> Vec<GlobPart> {
         let mut parts = Vec::new();
         let mut current_literal = String::new();
         let mut chars = pattern.chars().peekable();
@@ -107,11 +258,18 @@ impl PatternMatcher {
                 '*' => {
                     if chars.peek() == Some(&'*') {
                         chars.next(); // consume second *
+                        let at_dir_start =
+                            current_literal.is_empty() || current_literal.ends_with('/');
                         if !current_literal.is_empty() {
                             parts.push(GlobPart::Literal(current_literal.clone()));
                             current_literal.clear();
                         }
-                        parts.push(GlobPart::DoubleStar);
+                        if at_dir_start && chars.peek() == Some(&'/') {
+                            chars.next(); // consume /
+                            parts.push(GlobPart::DirStar);
+                        } else {
+                            parts.push(GlobPart::DoubleStar);
+                        }
                     } else {
                         if !current_literal.is_empty() {
                             parts.push(GlobPart::Literal(current_literal.clone()));
@@ -135,86 +293,122 @@ impl PatternMatcher {
             parts.push(GlobPart::Literal(current_literal));
         }
 
-        GlobPattern { parts }
+        parts
     }
 
-    #[instrument(skip(self))]
+    /// Whether a file at `path`, relative to the collection root, matches
     pub fn matches_path(&self, path: &Path) -> bool {
-        let filename = path
-            .file_name()
-            .map(|n| n.to_string_lossy())
-            .unwrap_or_default();
-
-        // Exact filename check
-        if self.exact_filenames.contains(filename.as_ref()) {
-            debug!("Exact filename match: {}", filename);
-            return true;
-        }
+        self.match_with_reason(path).is_some()
+    }
 
-        // Exact extension check
-        if let Some(ext) = path.extension().and_then(|e| e.to_str())
-            && self.exact_extensions.contains(ext)
-        {
-            debug!("Extension match: .{}", ext);
-            return true;
-        }
+    /// Whether a directory at `path`, relative to the collection root, matches
+    pub fn matches_dir(&self, path: &Path) -> bool {
+        self.match_with_dir_reason(path).is_some()
+    }
+
+    /// The pattern that matches a directory at `path`, if any
+    pub fn match_with_dir_reason(&self, path: &Path) -> Option<MatchInfo> {
+        self.match_entry(path, true)
+    }
+
+    /// The pattern that matches a file at `path`, if any, checking exact matches before globs
+    pub fn match_with_reason(&self, path: &Path) -> Option<MatchInfo> {
+        self.match_entry(path, false)
+    }
+
+    #[instrument(skip(self))]
+    fn match_entry(&self, path: &Path, is_dir: bool) -> Option<MatchInfo> {
+        let names: Vec<_> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        // Every name but the last is a directory containing the path
+        let name_is_dir = |index: usize| is_dir || index + 1 < names.len();
 
-        // Exact directory check - check if any path component matches
-        for component in path.components() {
-            if let Some(dir_name) = component.as_os_str().to_str()
-                && self.exact_directories.contains(dir_name)
+        // Exact name check, against every component
+        for (index, name) in names.iter().enumerate() {
+            let key = (name.to_string(), name_is_dir(index));
+            if let Some(info) = self
+                .exact_names
+                .get(&key)
+                .or_else(|| self.exact_names.get(&(key.0, false)))
             {
-                debug!("Directory match: {}", dir_name);
-                return true;
+                debug!("Exact name match: {}", name);
+                return Some(info.clone());
+            }
+        }
+
+        // Exact extension check, against every component
+        for (index, name) in names.iter().enumerate() {
+            if let Some((_, ext)) = name.rsplit_once('.') {
+                let key = (ext.to_string(), name_is_dir(index));
+                if let Some(info) = self
+                    .exact_extensions
+                    .get(&key)
+                    .or_else(|| self.exact_extensions.get(&(key.0, false)))
+                {
+                    debug!("Extension match: .{}", ext);
+                    return Some(info.clone());
+                }
             }
         }
 
         // Glob pattern matching (only if no fast matches)
-        let path_str = path.to_string_lossy();
-        self.glob_patterns
-            .iter()
-            .any(|glob| Self::matches_glob(&path_str, glob))
+        let mut prefix = String::new();
+        for (index, name) in names.iter().enumerate() {
+            if index > 0 {
+                prefix.push('/');
+            }
+            prefix.push_str(name);
+
+            let found = self.glob_patterns.iter().find(|glob| {
+                (!glob.dir_only || name_is_dir(index))
+                    && Self::matches_glob(if glob.anchored { &prefix } else { name }, glob)
+            });
+            if let Some(glob) = found {
+                return Some(glob.info.clone());
+            }
+        }
+
+        None
     }
 
     fn matches_glob(path: &str, glob: &GlobPattern) -> bool {
         Self::match_parts(path, &glob.parts, 0, 0)
     }
 
+    /// Match `parts` against `path[path_pos..]`; `path_pos` is always on a char boundary,
+    /// so wildcards consume whole characters however many bytes they take
     fn match_parts(path: &str, parts: &[GlobPart], path_pos: usize, part_idx: usize) -> bool {
         // If we've consumed all parts
         if part_idx >= parts.len() {
             return path_pos == path.len();
         }
 
+        let rest = &path[path_pos..];
+
         // If we've consumed all of the path but have parts left
-        if path_pos >= path.len() {
+        if rest.is_empty() {
             // Only OK if all remaining parts are stars
             return parts[part_idx..]
                 .iter()
-                .all(|p| matches!(p, GlobPart::Star | GlobPart::DoubleStar));
+                .all(|p| matches!(p, GlobPart::Star | GlobPart::DoubleStar | GlobPart::DirStar));
         }
 
         match &parts[part_idx] {
             GlobPart::Literal(lit) => {
-                if path[path_pos..].starts_with(lit) {
-                    Self::match_parts(path, parts, path_pos + lit.len(), part_idx + 1)
-                } else {
-                    false
-                }
+                rest.starts_with(lit.as_str())
+                    && Self::match_parts(path, parts, path_pos + lit.len(), part_idx + 1)
             }
-            GlobPart::Question => {
-                let next_char_boundary = path[path_pos..]
-                    .char_indices()
-                    .nth(1)
-                    .map(|(i, _)| path_pos + i)
-                    .unwrap_or(path.len());
-
-                if path_pos < path.len() && !path.chars().nth(path_pos).unwrap_or('\0').eq(&'/') {
-                    Self::match_parts(path, parts, next_char_boundary, part_idx + 1)
-                } else {
-                    false
+            GlobPart::Question => match rest.chars().next() {
+                Some(ch) if ch != '/' => {
+                    Self::match_parts(path, parts, path_pos + ch.len_utf8(), part_idx + 1)
                 }
-            }
+                _ => false,
+            },
             GlobPart::Star => {
                 // Try matching zero characters
                 if Self::match_parts(path, parts, path_pos, part_idx + 1) {
@@ -222,11 +416,12 @@ impl PatternMatcher {
                 }
 
                 // Try matching one or more characters (but not path separator)
-                for i in path_pos + 1..=path.len() {
-                    if path[path_pos..i].contains('/') {
+                for (offset, ch) in rest.char_indices() {
+                    if ch == '/' {
                         break;
                     }
-                    if Self::match_parts(path, parts, i, part_idx + 1) {
+                    let end = path_pos + offset + ch.len_utf8();
+                    if Self::match_parts(path, parts, end, part_idx + 1) {
                         return true;
                     }
                 }
@@ -239,12 +434,20 @@ impl PatternMatcher {
                 }
 
                 // Try matching one or more characters (including path separator)
-                for i in path_pos + 1..=path.len() {
-                    if Self::match_parts(path, parts, i, part_idx + 1) {
-                        return true;
-                    }
+                rest.char_indices().any(|(offset, ch)| {
+                    Self::match_parts(path, parts, path_pos + offset + ch.len_utf8(), part_idx + 1)
+                })
+            }
+            GlobPart::DirStar => {
+                // Try matching zero directories
+                if Self::match_parts(path, parts, path_pos, part_idx + 1) {
+                    return true;
                 }
-                false
+
+                // Try matching one or more whole directories
+                rest.match_indices('/').any(|(offset, _)| {
+                    Self::match_parts(path, parts, path_pos + offset + 1, part_idx + 1)
+                })
             }
         }
     }
@@ -0,0 +1,95 @@
+use crate::io::vfs::Vfs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// A directory the walk did not descend into
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrunedDir {
+    pub path: PathBuf,
+    /// Path relative to the input directory it was found under
+    pub relative_path: PathBuf,
+    pub reason: String,
+}
+
+impl PrunedDir {
+    /// Directly below an input directory, so a whole top-level tree was skipped
+    pub fn is_top_level(&self) -> bool {
+        self.relative_path.components().count() == 1
+    }
+}
+
+/// Record of pruned directories. Clones share the same record, so the walk's prune
+/// callback can fill it in while collection reads it afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct PrunedDirs {
+    dirs: Arc<Mutex<Vec<PrunedDir>>>,
+}
+
+impl PrunedDirs {
+    pub fn record(&self, path: &Path, relative_path: &Path, reason: impl Into<String>) {
+        let mut dirs = self.dirs.lock().unwrap();
+        // Walks may ask about the same directory more than once
+        if dirs.iter().any(|dir| dir.path == path) {
+            return;
+        }
+
+        let reason = reason.into();
+        debug!("Pruning {}: {}", path.display(), reason);
+        dirs.push(PrunedDir {
+            path: path.to_path_buf(),
+            relative_path: relative_path.to_path_buf(),
+            reason,
+        });
+    }
+
+    pub fn dirs(&self) -> Vec<PrunedDir> {
+        self.dirs.lock().unwrap().clone()
+    }
+
+    pub fn top_level(&self) -> Vec<PrunedDir> {
+        self.dirs()
+            .into_iter()
+            .filter(PrunedDir::is_top_level)
+            .collect()
+    }
+}
+
+/// Number of files and total bytes below a directory
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TreeSize {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl std::fmt::Display for TreeSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+        let mut size = self.bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            write!(f, "{} files, {} B", self.files, self.bytes)
+        } else {
+            write!(f, "{} files, {:.1} {}", self.files, size, UNITS[unit])
+        }
+    }
+}
+
+/// Size of everything below `dir`, unreadable entries counting as empty
+pub async fn tree_size(vfs: &impl Vfs, dir: &Path) -> TreeSize {
+    let mut size = TreeSize::default();
+    let Ok(walk) = vfs.walk(dir, Arc::new(|_: &Path| false), false).await else {
+        return size;
+    };
+
+    for file in walk.files {
+        size.files += 1;
+        size.bytes += vfs.metadata(&file).await.map(|m| m.len).unwrap_or(0);
+    }
+    size
+}
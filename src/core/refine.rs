@@ -0,0 +1,96 @@
+use crate::core::file_collector::{CollectedFile, print_file_tree};
+use crate::core::pattern_matcher::{PatternMatcher, PatternSource};
+use crate::io::vfs::Vfs;
+use crate::utils::token_counter::count_tokens;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// Drop the files whose path below their input directory matches `pattern`, returning
+/// the paths that were dropped
+pub fn exclude_matching(files: &mut Vec<CollectedFile>, pattern: &str) -> Vec<PathBuf> {
+    let matcher = PatternMatcher::builder()
+        .add(pattern, PatternSource::Cli)
+        .build();
+
+    let mut excluded = Vec::new();
+    files.retain(|file| {
+        let matched = matcher.matches_path(&file.relative_path);
+        if matched {
+            excluded.push(file.path.clone());
+        }
+        !matched
+    });
+    excluded
+}
+
+/// Token counts of each file, read once so the estimate updates instantly while refining
+pub async fn token_counts(vfs: &impl Vfs, files: &[CollectedFile]) -> BTreeMap<PathBuf, usize> {
+    let mut counts = BTreeMap::new();
+    for file in files {
+        let tokens = vfs
+            .read_text(&file.path)
+            .await
+            .map(|content| count_tokens(&content))
+            .unwrap_or(0);
+        counts.insert(file.path.clone(), tokens);
+    }
+    counts
+}
+
+/// Let the user type extra exclude globs, one per line, reprinting the tree and token
+/// estimate after each; an empty line or end of input accepts the current selection.
+/// Returns the globs that were applied.
+pub async fn refine_files(
+    vfs: &impl Vfs,
+    files: &mut Vec<CollectedFile>,
+    input: &mut impl BufRead,
+) -> Result<Vec<String>> {
+    let counts = token_counts(vfs, files).await;
+    let estimate = |files: &[CollectedFile]| -> usize {
+        files
+            .iter()
+            .map(|file| counts.get(&file.path).copied().unwrap_or(0))
+            .sum()
+    };
+
+    let mut patterns = Vec::new();
+    println!(
+        "🔎 {} files, ~{} tokens. Type a glob to exclude, or press Enter to continue.",
+        files.len(),
+        estimate(files)
+    );
+
+    loop {
+        print!("exclude> ");
+        std::io::stdout().flush().ok();
+
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .context("Failed to read exclude pattern")?;
+        let pattern = line.trim();
+        if read == 0 || pattern.is_empty() {
+            break;
+        }
+
+        let excluded = exclude_matching(files, pattern);
+        if excluded.is_empty() {
+            println!("No files match {}", pattern);
+            continue;
+        }
+        patterns.push(pattern.to_string());
+
+        println!("\n📁 Files to be included:");
+        print_file_tree(files);
+        println!(
+            "\n➖ Excluded {} files; {} files, ~{} tokens remain",
+            excluded.len(),
+            files.len(),
+            estimate(files)
+        );
+    }
+
+    Ok(patterns)
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+/// Files kept by `--query` when `--top` isn't given
+pub const DEFAULT_QUERY_TOP: usize = 20;
+
+/// Lowercased words of a query, split on anything that isn't a letter, digit or underscore
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in query
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+    {
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+/// Files relevant to `query`, most relevant first, by a TF-IDF score over substring
+/// matches of its terms. A term in a file's path counts more than one in its content.
+/// Files matching no term are left out.
+pub fn rank_by_relevance(files: &[(PathBuf, String)], query: &str) -> Vec<(PathBuf, f64)> {
+    let terms = query_terms(query);
+    let documents: Vec<(String, String)> = files
+        .iter()
+        .map(|(path, content)| {
+            (
+                path.to_string_lossy().to_lowercase(),
+                content.to_lowercase(),
+            )
+        })
+        .collect();
+
+    // Rarer terms say more about a file, so they weigh more
+    let idf: Vec<f64> = terms
+        .iter()
+        .map(|term| {
+            let containing = documents
+                .iter()
+                .filter(|(path, content)| path.contains(term) || content.contains(term))
+                .count();
+            ((documents.len() as f64 + 1.0) / (containing as f64 + 1.0)).ln() + 1.0
+        })
+        .collect();
+
+    let mut ranked: Vec<(PathBuf, f64)> = files
+        .iter()
+        .zip(&documents)
+        .map(|((file, _), (path, content))| {
+            let score = terms
+                .iter()
+                .zip(&idf)
+                .map(|(term, idf)| {
+                    let occurrences = content.matches(term.as_str()).count();
+                    // Repeats help, but with diminishing returns, so long files don't win by size
+                    let in_content = if occurrences > 0 {
+                        1.0 + (occurrences as f64).ln()
+                    } else {
+                        0.0
+                    };
+                    let in_path = if path.contains(term) { 2.0 } else { 0.0 };
+                    (in_content + in_path) * idf
+                })
+                .sum::<f64>();
+            (file.clone(), score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
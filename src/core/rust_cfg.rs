@@ -0,0 +1,517 @@
+use crate::io::vfs::Vfs;
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Platform a target triple such as `x86_64-unknown-linux-gnu` builds for, in the terms
+/// `cfg(target_*)` predicates use
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub triple: String,
+    pub arch: String,
+    pub vendor: String,
+    pub os: String,
+    pub env: String,
+    pub family: Vec<&'static str>,
+    pub pointer_width: u8,
+    pub endian: &'static str,
+}
+
+/// Vendors that can stand second in a three-part triple, where otherwise the OS does
+const VENDORS: &[&str] = &[
+    "unknown", "pc", "apple", "sun", "nvidia", "fortanix", "uwp", "wrs", "sony", "nintendo",
+];
+
+impl Target {
+    pub fn parse(triple: &str) -> Result<Self> {
+        let parts: Vec<&str> = triple.trim().split('-').collect();
+        let (arch, vendor, os, env) = match parts.as_slice() {
+            [arch, vendor, os, env] => (*arch, *vendor, *os, *env),
+            [arch, vendor, os] if VENDORS.contains(vendor) => (*arch, *vendor, *os, ""),
+            [arch, os, env] => (*arch, "unknown", *os, *env),
+            [arch, os] => (*arch, "unknown", *os, ""),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid target triple '{}'; expected e.g. x86_64-unknown-linux-gnu",
+                    triple
+                ));
+            }
+        };
+
+        let arch = match arch {
+            "i386" | "i586" | "i686" => "x86",
+            "arm64" => "aarch64",
+            "riscv64gc" | "riscv64imac" => "riscv64",
+            "powerpc64le" => "powerpc64",
+            "mipsel" => "mips",
+            "mips64el" => "mips64",
+            arch if arch.starts_with("thumb") || arch.starts_with("armv") => "arm",
+            arch if arch.starts_with("riscv32") => "riscv32",
+            arch => arch,
+        };
+        let os = match (os, env) {
+            ("darwin", _) => "macos",
+            ("linux", "android" | "androideabi") => "android",
+            (os, _) => os,
+        };
+        let env = match env {
+            env if env.starts_with("gnu") => "gnu",
+            env if env.starts_with("musl") => "musl",
+            "msvc" => "msvc",
+            "sgx" => "sgx",
+            _ => "",
+        };
+        let family = match os {
+            "windows" => vec!["windows"],
+            _ if arch.starts_with("wasm") => vec!["wasm"],
+            "none" | "unknown" | "uefi" => vec![],
+            _ => vec!["unix"],
+        };
+        let pointer_width = match arch {
+            "x86_64" | "aarch64" | "riscv64" | "powerpc64" | "mips64" | "s390x" | "sparc64"
+            | "loongarch64" | "wasm64" => 64,
+            _ => 32,
+        };
+        let big_endian = matches!(arch, "s390x" | "sparc64" | "powerpc" | "mips" | "mips64")
+            && !parts[0].ends_with("le")
+            && !parts[0].ends_with("el");
+
+        Ok(Self {
+            triple: triple.trim().to_string(),
+            arch: arch.to_string(),
+            vendor: vendor.to_string(),
+            os: os.to_string(),
+            env: env.to_string(),
+            family,
+            pointer_width,
+            endian: if big_endian { "big" } else { "little" },
+        })
+    }
+}
+
+/// Build configuration `#[cfg(...)]` attributes are evaluated against: tests are off, only
+/// the listed features are on, and platform predicates follow `target` when there is one.
+/// Predicates it can't settle, such as `debug_assertions`, leave their code in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RustCfg {
+    pub features: BTreeSet<String>,
+    pub target: Option<Target>,
+    /// Leave the crate's `default` features off, as `cargo --no-default-features` does
+    pub no_default_features: bool,
+}
+
+impl RustCfg {
+    /// This configuration for the crate `path` belongs to: `features` and, unless
+    /// `no_default_features`, the `default` ones of the nearest `Cargo.toml`, each with the
+    /// features it turns on in turn
+    pub async fn for_file(&self, vfs: &impl Vfs, path: &Path) -> Self {
+        for dir in path.ancestors().skip(1) {
+            if let Ok(manifest) = vfs.read_to_string(&dir.join("Cargo.toml")).await {
+                return self.for_manifest(&manifest);
+            }
+        }
+        self.clone()
+    }
+
+    /// This configuration for the crate whose `Cargo.toml` reads `manifest`
+    pub fn for_manifest(&self, manifest: &str) -> Self {
+        let table = toml::from_str::<toml::Table>(manifest)
+            .ok()
+            .and_then(|manifest| manifest.get("features")?.as_table().cloned())
+            .unwrap_or_default();
+
+        let mut pending: Vec<String> = self.features.iter().cloned().collect();
+        if !self.no_default_features {
+            pending.push("default".to_string());
+        }
+        let mut features = BTreeSet::new();
+        while let Some(feature) = pending.pop() {
+            if !features.insert(feature.clone()) {
+                continue;
+            }
+            let enables = table.get(&feature).and_then(|value| value.as_array());
+            // `dep:name` and `name/feature` turn on dependencies, not features of this crate
+            pending.extend(
+                enables
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|value| value.as_str())
+                    .filter(|name| !name.starts_with("dep:") && !name.contains('/'))
+                    .map(str::to_string),
+            );
+        }
+        features.remove("default");
+
+        Self {
+            features,
+            ..self.clone()
+        }
+    }
+
+    /// Value of a single `name` or `name = "value"` predicate, `None` when unknown
+    fn predicate(&self, name: &str, value: Option<&str>) -> Option<bool> {
+        let target = self.target.as_ref();
+        match (name, value) {
+            ("test", None) => Some(false),
+            ("feature", Some(feature)) => Some(self.features.contains(feature)),
+            ("unix", None) => target.map(|t| t.family.contains(&"unix")),
+            ("windows", None) => target.map(|t| t.family.contains(&"windows")),
+            ("target_os", Some(v)) => target.map(|t| t.os == v),
+            ("target_arch", Some(v)) => target.map(|t| t.arch == v),
+            ("target_env", Some(v)) => target.map(|t| t.env == v),
+            ("target_vendor", Some(v)) => target.map(|t| t.vendor == v),
+            ("target_family", Some(v)) => target.map(|t| t.family.contains(&v)),
+            ("target_pointer_width", Some(v)) => target.map(|t| t.pointer_width.to_string() == v),
+            ("target_endian", Some(v)) => target.map(|t| t.endian == v),
+            _ => None,
+        }
+    }
+
+    /// Value of the predicate inside `cfg(...)`, `None` when it depends on something
+    /// unknown or doesn't parse
+    pub fn evaluate(&self, predicate: &str) -> Option<bool> {
+        let mut parser = Parser {
+            input: predicate,
+            pos: 0,
+        };
+        let value = parser.predicate(self)?;
+        parser.skip_whitespace();
+        if parser.pos == predicate.len() {
+            value
+        } else {
+            None
+        }
+    }
+
+    /// `content` without the items behind `#[cfg(...)]` attributes that are off, along with
+    /// their doc comments and other attributes. A file whose `#![cfg(...)]` is off is
+    /// compiled out entirely, leaving nothing.
+    pub fn strip(&self, content: &str) -> String {
+        let lines: Vec<&str> = content.lines().collect();
+        // Attributes and items are found in the code outside comments and literals, while
+        // what is kept and the predicates evaluated come from the original lines
+        let blanked = blank_literals(content);
+        let code: Vec<&str> = blanked.lines().collect();
+        let mut kept: Vec<&str> = Vec::with_capacity(lines.len());
+        let mut i = 0;
+
+        while i < lines.len() {
+            let trimmed = code[i].trim_start();
+            let inner = trimmed.starts_with("#![cfg(");
+            if !inner && !trimmed.starts_with("#[cfg(") {
+                kept.push(lines[i]);
+                i += 1;
+                continue;
+            }
+
+            let (attribute, end, rest) = read_attribute(&lines, &code, i);
+            let predicate = attribute
+                .trim_start_matches('#')
+                .trim_start_matches('!')
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .trim()
+                .strip_prefix("cfg(")
+                .and_then(|predicate| predicate.strip_suffix(')'));
+            if predicate.and_then(|predicate| self.evaluate(predicate)) != Some(false) {
+                kept.extend(&lines[i..=end]);
+                i = end + 1;
+                continue;
+            }
+            if inner {
+                return String::new();
+            }
+
+            // Doc comments and attributes above the `cfg` belong to the same item
+            while kept.last().is_some_and(|line| {
+                let line = line.trim_start();
+                line.starts_with("///") || line.starts_with("#[")
+            }) {
+                kept.pop();
+            }
+            i = skip_item(&code, end + 1, rest);
+            // Don't leave two blank lines where the item was
+            if kept.last().is_some_and(|line| line.trim().is_empty())
+                && lines.get(i).is_some_and(|line| line.trim().is_empty())
+            {
+                i += 1;
+            }
+        }
+
+        let mut stripped = kept.join("\n");
+        if content.ends_with('\n') && !stripped.is_empty() {
+            stripped.push('\n');
+        }
+        stripped
+    }
+}
+
+/// `content` with comments and the insides of string and char literals blanked out byte
+/// for byte, keeping line breaks, so brackets and `//` in them aren't taken for code
+pub(crate) fn blank_literals(content: &str) -> String {
+    let bytes = content.as_bytes();
+    let mut blanked = bytes.to_vec();
+    let mut blank = |from: usize, to: usize| {
+        for byte in &mut blanked[from..to.min(bytes.len())] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let word_start = i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_');
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |n| i + n);
+                blank(i, end);
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // Block comments nest
+                let mut depth = 0;
+                let mut end = i;
+                while end < bytes.len() {
+                    if bytes[end..].starts_with(b"/*") {
+                        depth += 1;
+                        end += 2;
+                    } else if bytes[end..].starts_with(b"*/") {
+                        depth -= 1;
+                        end += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        end += 1;
+                    }
+                }
+                blank(i, end);
+                i = end;
+            }
+            b'"' => {
+                let mut end = i + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                blank(i + 1, end);
+                i = end + 1;
+            }
+            b'\'' => {
+                // A char literal, or a lifetime or label when no closing quote follows
+                let end = if bytes.get(i + 1) == Some(&b'\\') {
+                    bytes
+                        .get(i + 3..)
+                        .and_then(|rest| rest.iter().position(|&b| b == b'\''))
+                        .map(|n| i + 3 + n)
+                } else {
+                    content[i + 1..]
+                        .chars()
+                        .next()
+                        .map(|c| i + 1 + c.len_utf8())
+                        .filter(|&end| bytes.get(end) == Some(&b'\''))
+                };
+                match end {
+                    Some(end) => {
+                        blank(i + 1, end);
+                        i = end + 1;
+                    }
+                    None => i += 1,
+                }
+            }
+            b'b' | b'c' | b'r' if word_start => {
+                // `r"..."`, `br#"..."#` and the like, or the prefix of `b"..."` and `b'x'`
+                let mut start = i;
+                if bytes[start] != b'r' {
+                    start += 1;
+                }
+                let hashes = if bytes.get(start) == Some(&b'r') {
+                    bytes[start + 1..]
+                        .iter()
+                        .take_while(|&&b| b == b'#')
+                        .count()
+                } else {
+                    0
+                };
+                let quote = start + 1 + hashes;
+                if bytes.get(start) == Some(&b'r') && bytes.get(quote) == Some(&b'"') {
+                    let closing = format!("\"{}", "#".repeat(hashes));
+                    let end = content[quote + 1..]
+                        .find(&closing)
+                        .map_or(bytes.len(), |n| quote + 1 + n);
+                    blank(quote + 1, end);
+                    i = end + closing.len();
+                } else if start > i && matches!(bytes.get(start), Some(b'"' | b'\'')) {
+                    i = start;
+                } else {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    String::from_utf8(blanked).expect("only whole characters are blanked")
+}
+
+/// The attribute starting on line `start`, the line it ends on, and the code after it on
+/// that line. `code` is `lines` as `blank_literals` leaves them.
+fn read_attribute<'a>(lines: &[&str], code: &[&'a str], start: usize) -> (String, usize, &'a str) {
+    let mut attribute = String::new();
+    let mut depth = 0i32;
+    for (i, code_line) in code.iter().enumerate().skip(start) {
+        let indent = if i == start {
+            code_line.len() - code_line.trim_start().len()
+        } else {
+            0
+        };
+        for (at, c) in code_line.char_indices().skip_while(|&(at, _)| at < indent) {
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 && c == ']' {
+                attribute.push_str(&lines[i][indent..=at]);
+                return (attribute, i, &code_line[at + 1..]);
+            }
+        }
+        attribute.push_str(lines[i].trim());
+        attribute.push(' ');
+    }
+    (attribute, code.len() - 1, "")
+}
+
+/// Index of the first line after the item that starts with `rest` and continues from line
+/// `start`: any further attributes, then everything up to a closing `;`, `,` or brace at
+/// the item's own nesting level. `lines` are as `blank_literals` leaves them.
+fn skip_item(lines: &[&str], start: usize, rest: &str) -> usize {
+    let mut depth = 0i32;
+    let mut opened = false;
+    let mut in_attributes = true;
+
+    let mut consume = |line: &str, depth: &mut i32| -> Option<bool> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Some(false);
+        }
+        let mut next = *depth;
+        for c in line.chars() {
+            match c {
+                '{' | '[' | '(' => {
+                    next += 1;
+                    opened |= c == '{';
+                }
+                '}' | ']' | ')' => next -= 1,
+                _ => {}
+            }
+        }
+        // A closing brace of the enclosing block is not part of the item
+        if next < 0 {
+            return None;
+        }
+        *depth = next;
+        let attribute = in_attributes && trimmed.starts_with("#[");
+        in_attributes = attribute;
+        Some(
+            !attribute && next == 0 && (opened || trimmed.ends_with(';') || trimmed.ends_with(',')),
+        )
+    };
+
+    if !rest.trim().is_empty() {
+        match consume(rest, &mut depth) {
+            Some(true) | None => return start,
+            Some(false) => {}
+        }
+    }
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        match consume(line, &mut depth) {
+            Some(true) => return i + 1,
+            Some(false) => {}
+            None => return i,
+        }
+    }
+    lines.len()
+}
+
+/// Recursive descent over `name`, `name = "value"`, and `all`/`any`/`not(...)`
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.input[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&str> {
+        self.skip_whitespace();
+        let rest = &self.input[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return None;
+        }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    fn string(&mut self) -> Option<&str> {
+        if !self.eat('"') {
+            return None;
+        }
+        let rest = &self.input[self.pos..];
+        let len = rest.find('"')?;
+        self.pos += len + 1;
+        Some(&rest[..len])
+    }
+
+    /// `None` when the predicate doesn't parse, otherwise its value, itself `None` when
+    /// unknown
+    fn predicate(&mut self, cfg: &RustCfg) -> Option<Option<bool>> {
+        let name = self.identifier()?.to_string();
+        if self.eat('(') {
+            let mut values = Vec::new();
+            while !self.eat(')') {
+                values.push(self.predicate(cfg)?);
+                if !self.eat(',') {
+                    if !self.eat(')') {
+                        return None;
+                    }
+                    break;
+                }
+            }
+            return match name.as_str() {
+                "all" if values.contains(&Some(false)) => Some(Some(false)),
+                "all" => Some(values.iter().all(|v| *v == Some(true)).then_some(true)),
+                "any" if values.contains(&Some(true)) => Some(Some(true)),
+                "any" => Some(values.iter().all(|v| *v == Some(false)).then_some(false)),
+                "not" if values.len() == 1 => Some(values[0].map(|v| !v)),
+                _ => None,
+            };
+        }
+
+        let value = if self.eat('=') {
+            Some(self.string()?.to_string())
+        } else {
+            None
+        };
+        Some(cfg.predicate(&name, value.as_deref()))
+    }
+}
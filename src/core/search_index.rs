@@ -0,0 +1,167 @@
+use crate::utils::file_metadata::sha256_hex;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, STORED, STRING, Schema, TEXT, Value};
+use tantivy::{Index, IndexWriter, TantivyDocument, Term, doc};
+use tracing::debug;
+
+/// Memory the index writer may use before flushing a segment
+const WRITER_MEMORY_BYTES: usize = 50_000_000;
+
+/// Content hashes of the indexed files, kept next to the index so updates only touch
+/// what changed
+const HASHES_FILE: &str = "files.json";
+
+/// What an index update did
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// A persistent full-text index of a project's files, for ranking them against a query
+pub struct SearchIndex {
+    dir: PathBuf,
+    index: Index,
+    path: Field,
+    name: Field,
+    content: Field,
+}
+
+impl SearchIndex {
+    /// Open the index in `dir`, creating an empty one if there is none
+    pub fn open_or_create(dir: &Path) -> Result<Self> {
+        let mut builder = Schema::builder();
+        // `path` is matched exactly to replace a file's document; `name` is searched
+        let path = builder.add_text_field("path", STRING | STORED);
+        let name = builder.add_text_field("name", TEXT);
+        let content = builder.add_text_field("content", TEXT);
+        let schema = builder.build();
+
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create index directory: {}", dir.display()))?;
+        let directory = MmapDirectory::open(dir)
+            .with_context(|| format!("Failed to open index: {}", dir.display()))?;
+        let index = Index::open_or_create(directory, schema)
+            .with_context(|| format!("Failed to open index: {}", dir.display()))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index,
+            path,
+            name,
+            content,
+        })
+    }
+
+    /// Whether `catnip index` has been run for `dir`
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(HASHES_FILE).exists()
+    }
+
+    fn hashes(&self) -> BTreeMap<String, String> {
+        fs::read(self.dir.join(HASHES_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Index `files`, given as (path, content), skipping those unchanged since they were last
+    /// indexed. With `remove_missing`, indexed files not among them are dropped.
+    pub fn update(&self, files: &[(String, String)], remove_missing: bool) -> Result<IndexUpdate> {
+        let previous = self.hashes();
+        let mut hashes = if remove_missing {
+            BTreeMap::new()
+        } else {
+            previous.clone()
+        };
+        let mut update = IndexUpdate::default();
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY_BYTES)?;
+
+        for (path, content) in files {
+            let hash = sha256_hex(content.as_bytes());
+            match previous.get(path) {
+                Some(previous) if *previous == hash => update.unchanged += 1,
+                seen => {
+                    if seen.is_some() {
+                        writer.delete_term(Term::from_field_text(self.path, path));
+                        update.updated += 1;
+                    } else {
+                        update.added += 1;
+                    }
+                    writer.add_document(doc!(
+                        self.path => path.as_str(),
+                        self.name => path.as_str(),
+                        self.content => content.as_str(),
+                    ))?;
+                }
+            }
+            hashes.insert(path.clone(), hash);
+        }
+
+        let seen: HashSet<&String> = files.iter().map(|(path, _)| path).collect();
+        for path in previous
+            .keys()
+            .filter(|path| remove_missing && !seen.contains(path))
+        {
+            writer.delete_term(Term::from_field_text(self.path, path));
+            update.removed += 1;
+        }
+
+        writer.commit().context("Failed to write the index")?;
+        fs::write(self.dir.join(HASHES_FILE), serde_json::to_vec(&hashes)?)
+            .context("Failed to write the index")?;
+        debug!("Updated index {}: {:?}", self.dir.display(), update);
+        Ok(update)
+    }
+
+    /// Number of indexed files
+    pub fn file_count(&self) -> Result<usize> {
+        Ok(self.index.reader()?.searcher().num_docs() as usize)
+    }
+
+    /// Indexed paths matching `query`, best first, with their BM25 scores. Matches in a
+    /// file's path count double.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<(String, f32)>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let mut parser = QueryParser::for_index(&self.index, vec![self.name, self.content]);
+        parser.set_field_boost(self.name, 2.0);
+        // Code words such as `foo::bar` aren't query syntax, so parse errors are ignored
+        let (query, _) = parser.parse_query_lenient(query);
+
+        let mut results = Vec::new();
+        for (score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(path) = document.get_first(self.path).and_then(|v| v.as_str()) {
+                results.push((path.to_string(), score));
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// How a file is identified in the index: its path relative to the current directory
+pub fn index_key(path: &Path) -> String {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let relative: PathBuf = relative_to(path, &current_dir)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    encode_path(&relative)
+}
+
+/// Location of the search index for the current working directory
+pub fn index_path(index_dir: &Path) -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let key = &sha256_hex(current_dir.display().to_string().as_bytes())[..16];
+    index_dir.join(key)
+}
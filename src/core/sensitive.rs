@@ -0,0 +1,30 @@
+use crate::config::patterns::SENSITIVE_PATTERNS;
+use crate::core::file_collector::CollectedFile;
+use crate::core::pattern_matcher::{MatchInfo, PatternMatcher, PatternSource};
+use std::path::PathBuf;
+
+/// A collected file whose name suggests it holds secrets
+#[derive(Clone, Debug, PartialEq)]
+pub struct SensitiveFile {
+    pub path: PathBuf,
+    pub matched: MatchInfo,
+}
+
+/// Collected files matching the sensitive patterns, in collection order
+pub fn find_sensitive(files: &[CollectedFile]) -> Vec<SensitiveFile> {
+    let matcher = PatternMatcher::builder()
+        .add_all(SENSITIVE_PATTERNS.iter().copied(), PatternSource::Default)
+        .build();
+
+    files
+        .iter()
+        .filter_map(|file| {
+            matcher
+                .match_with_reason(&file.relative_path)
+                .map(|matched| SensitiveFile {
+                    path: file.path.clone(),
+                    matched,
+                })
+        })
+        .collect()
+}
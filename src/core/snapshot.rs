@@ -0,0 +1,286 @@
+use crate::core::file_collector::CollectOptions;
+use crate::io::vfs::Vfs;
+use crate::utils::file_metadata::sha256_hex;
+use crate::utils::text_processing::count_line_changes;
+use crate::utils::token_counter::count_tokens;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// The files and contents captured by a `cat` run
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub created: SystemTime,
+    pub files: BTreeMap<PathBuf, SnapshotEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SnapshotEntry {
+    pub sha256: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileChange {
+    Added {
+        path: PathBuf,
+        lines: usize,
+    },
+    Removed {
+        path: PathBuf,
+        lines: usize,
+    },
+    Modified {
+        path: PathBuf,
+        added: usize,
+        removed: usize,
+    },
+}
+
+impl FileChange {
+    pub fn path(&self) -> &Path {
+        match self {
+            FileChange::Added { path, .. }
+            | FileChange::Removed { path, .. }
+            | FileChange::Modified { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileChange::Added { path, lines } => write!(f, "A  {} (+{})", path.display(), lines),
+            FileChange::Removed { path, lines } => {
+                write!(f, "D  {} (-{})", path.display(), lines)
+            }
+            FileChange::Modified {
+                path,
+                added,
+                removed,
+            } => write!(f, "M  {} (+{} -{})", path.display(), added, removed),
+        }
+    }
+}
+
+/// How a file compares to a previous snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Unchanged,
+    Removed,
+}
+
+impl FileStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileStatus::Added => "added",
+            FileStatus::Modified => "modified",
+            FileStatus::Unchanged => "unchanged",
+            FileStatus::Removed => "removed",
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        matches!(self, FileStatus::Added | FileStatus::Modified)
+    }
+}
+
+impl Snapshot {
+    /// Tokens of each file's content, largest first
+    pub fn token_counts(&self) -> Vec<(PathBuf, usize)> {
+        let mut counts: Vec<(PathBuf, usize)> = self
+            .files
+            .iter()
+            .map(|(path, entry)| (path.clone(), count_tokens(&entry.content)))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Capture the current contents of `files`; unreadable files are left out
+    pub async fn capture(vfs: &impl Vfs, files: &[PathBuf]) -> Self {
+        let mut entries = BTreeMap::new();
+
+        for file in files {
+            match vfs.read_text(file).await {
+                Ok(content) => {
+                    entries.insert(
+                        file.clone(),
+                        SnapshotEntry {
+                            sha256: sha256_hex(content.as_bytes()),
+                            content,
+                        },
+                    );
+                }
+                Err(e) => debug!("Leaving {} out of snapshot: {}", file.display(), e),
+            }
+        }
+
+        Self {
+            created: SystemTime::now(),
+            files: entries,
+        }
+    }
+
+    /// List the changes needed to go from `previous` to `self`
+    pub fn changes_since(&self, previous: &Snapshot) -> Vec<FileChange> {
+        let mut changes = Vec::new();
+
+        for (path, entry) in &self.files {
+            match previous.files.get(path) {
+                None => changes.push(FileChange::Added {
+                    path: path.clone(),
+                    lines: entry.content.lines().count(),
+                }),
+                Some(old) if old.sha256 != entry.sha256 => {
+                    let (added, removed) = count_line_changes(&old.content, &entry.content);
+                    changes.push(FileChange::Modified {
+                        path: path.clone(),
+                        added,
+                        removed,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (path, entry) in &previous.files {
+            if !self.files.contains_key(path) {
+                changes.push(FileChange::Removed {
+                    path: path.clone(),
+                    lines: entry.content.lines().count(),
+                });
+            }
+        }
+
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+
+    /// Status of every file in either snapshot relative to `previous`
+    pub fn statuses_since(&self, previous: &Snapshot) -> BTreeMap<PathBuf, FileStatus> {
+        let mut statuses: BTreeMap<PathBuf, FileStatus> = self
+            .files
+            .iter()
+            .map(|(path, entry)| {
+                let status = match previous.files.get(path) {
+                    None => FileStatus::Added,
+                    Some(old) if old.sha256 != entry.sha256 => FileStatus::Modified,
+                    Some(_) => FileStatus::Unchanged,
+                };
+                (path.clone(), status)
+            })
+            .collect();
+
+        for path in previous.files.keys() {
+            statuses.entry(path.clone()).or_insert(FileStatus::Removed);
+        }
+
+        statuses
+    }
+
+    pub async fn load(vfs: &impl Vfs, path: &Path) -> Result<Option<Self>> {
+        if !vfs.exists(path).await {
+            return Ok(None);
+        }
+
+        let content = vfs.read(path).await?;
+        let snapshot = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse snapshot: {}", path.display()))?;
+        Ok(Some(snapshot))
+    }
+
+    pub async fn save(&self, vfs: &impl Vfs, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            vfs.create_dir_all(parent).await?;
+        }
+
+        let content = serde_json::to_vec(self).context("Failed to serialize snapshot")?;
+        vfs.write(path, &content).await
+    }
+}
+
+/// Identify a collection profile by the working directory, paths and filters it was run with
+pub fn profile_key(paths: &[PathBuf], options: &CollectOptions) -> String {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+
+    let mut paths: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+    paths.sort();
+
+    let mut profile = format!(
+        "{}\n{}\n{}\n{}\n{}\n{:?}",
+        current_dir.display(),
+        paths.join("\n"),
+        options.excludes.join("\n"),
+        options.includes.join("\n"),
+        options.max_size_mb,
+        options.test_filter
+    );
+    // Only appended when set, so profiles recorded before `--filter` existed keep their key
+    if let Some(filter) = &options.filter {
+        profile.push_str(&format!("\n{}", filter.source()));
+    }
+
+    sha256_hex(profile.as_bytes())[..16].to_string()
+}
+
+/// Location of the latest snapshot for a profile in the history directory
+pub fn snapshot_path(history_dir: &Path, profile: &str) -> PathBuf {
+    history_dir.join(format!("{}.json", profile))
+}
+
+/// Record the snapshot as the latest for its profile, warning rather than failing on errors
+pub async fn record_snapshot(vfs: &impl Vfs, snapshot: &Snapshot, profile: &str) {
+    let history_dir = match crate::config::paths::history_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("Not recording snapshot: {}", e);
+            return;
+        }
+    };
+
+    let path = snapshot_path(&history_dir, profile);
+    match snapshot.save(vfs, &path).await {
+        Ok(()) => debug!("Recorded snapshot: {}", path.display()),
+        Err(e) => warn!("Failed to record snapshot {}: {}", path.display(), e),
+    }
+
+    let latest = latest_profile_path(&history_dir);
+    if let Err(e) = vfs.write(&latest, profile.as_bytes()).await {
+        warn!(
+            "Failed to record latest snapshot {}: {}",
+            latest.display(),
+            e
+        );
+    }
+}
+
+/// Location of the file naming the profile of the latest `cat` in the current working directory
+pub fn latest_profile_path(history_dir: &Path) -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let key = &sha256_hex(current_dir.display().to_string().as_bytes())[..16];
+    history_dir.join(format!("latest-{}", key))
+}
+
+/// The snapshot of the latest `cat` in the current working directory, whatever its paths
+/// and filters, if one was recorded and can still be read
+pub async fn load_latest_snapshot(vfs: &impl Vfs) -> Option<Snapshot> {
+    let history_dir = crate::config::paths::history_dir().ok()?;
+    let profile = vfs
+        .read_to_string(&latest_profile_path(&history_dir))
+        .await
+        .ok()?;
+
+    match Snapshot::load(vfs, &snapshot_path(&history_dir, profile.trim())).await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            warn!("Ignoring latest snapshot: {}", e);
+            None
+        }
+    }
+}
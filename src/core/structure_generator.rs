@@ -1,45 +1,123 @@
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::utils::file_metadata::FileMetadata;
+use crate::utils::path_encoding::{encode_path, relative_to};
+
 #[derive(Debug)]
 enum TreeNode {
-    File,
+    File {
+        file: PathBuf,
+        annotation: Option<String>,
+    },
     Directory(BTreeMap<String, TreeNode>),
 }
 
+/// Serializable form of the structure tree, for tools that build their own view of it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StructureNode {
+    File {
+        name: String,
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        annotation: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metadata: Option<FileMetadata>,
+    },
+    Directory {
+        name: String,
+        path: String,
+        children: Vec<StructureNode>,
+    },
+}
+
 pub fn generate_directory_structure(files: &[PathBuf]) -> Vec<String> {
-    let mut structure = Vec::new();
+    generate_annotated_structure(files, &BTreeMap::new())
+}
+
+/// Generate the structure tree, appending `[annotation]` to files that have one
+pub fn generate_annotated_structure(
+    files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+) -> Vec<String> {
+    let mut structure = String::new();
+    write_annotated_structure(&mut structure, files, annotations);
+    structure.lines().map(str::to_string).collect()
+}
+
+/// Write the annotated structure tree into `out`, one newline-terminated line per entry
+pub fn write_annotated_structure(
+    out: &mut String,
+    files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+) {
+    let root = build_tree(files, annotations);
+
+    // Generate structure recursively, growing and shrinking a single prefix buffer
+    write_tree_lines(&root, out, &mut String::new());
+}
+
+/// Generate the structure tree rooted at `.`, attaching each file's metadata when known
+pub fn generate_structure_tree(
+    files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+    metadata: &BTreeMap<PathBuf, FileMetadata>,
+) -> StructureNode {
+    let root = build_tree(files, annotations);
+
+    StructureNode::Directory {
+        name: ".".to_string(),
+        path: ".".to_string(),
+        children: build_structure_nodes(&root, Path::new(""), metadata),
+    }
+}
+
+fn build_tree(
+    files: &[PathBuf],
+    annotations: &BTreeMap<PathBuf, String>,
+) -> BTreeMap<String, TreeNode> {
     let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
 
     // Build tree structure
     let mut root = BTreeMap::new();
 
     for file in files {
-        let relative_path = file.strip_prefix(&current_dir).unwrap_or(file);
-
-        add_to_tree(&mut root, relative_path);
+        let relative_path = &relative_to(file, &current_dir);
+
+        add_to_tree(
+            &mut root,
+            relative_path,
+            file,
+            annotations.get(file).cloned(),
+        );
     }
 
-    // Generate structure recursively
-    build_tree_lines(&root, &mut structure, "");
-
-    structure
+    root
 }
 
-fn add_to_tree(tree: &mut BTreeMap<String, TreeNode>, path: &Path) {
+fn add_to_tree(
+    tree: &mut BTreeMap<String, TreeNode>,
+    path: &Path,
+    file: &Path,
+    annotation: Option<String>,
+) {
     let components: Vec<_> = path.components().collect();
 
     if components.is_empty() {
         return;
     }
 
-    add_components_to_tree(tree, &components, 0);
+    add_components_to_tree(tree, &components, 0, file, annotation);
 }
 
 fn add_components_to_tree(
     tree: &mut BTreeMap<String, TreeNode>,
     components: &[std::path::Component],
     index: usize,
+    file: &Path,
+    annotation: Option<String>,
 ) {
     if index >= components.len() {
         return;
@@ -49,7 +127,13 @@ fn add_components_to_tree(
 
     if index == components.len() - 1 {
         // This is a file
-        tree.insert(component_name, TreeNode::File);
+        tree.insert(
+            component_name,
+            TreeNode::File {
+                file: file.to_path_buf(),
+                annotation,
+            },
+        );
     } else {
         // This is a directory
         let entry = tree
@@ -57,23 +141,60 @@ fn add_components_to_tree(
             .or_insert_with(|| TreeNode::Directory(BTreeMap::new()));
 
         if let TreeNode::Directory(subtree) = entry {
-            add_components_to_tree(subtree, components, index + 1);
+            add_components_to_tree(subtree, components, index + 1, file, annotation);
         }
     }
 }
 
-fn build_tree_lines(tree: &BTreeMap<String, TreeNode>, lines: &mut Vec<String>, prefix: &str) {
-    let items: Vec<_> = tree.iter().collect();
-
-    for (i, (name, node)) in items.iter().enumerate() {
-        let is_last = i == items.len() - 1;
+fn write_tree_lines(tree: &BTreeMap<String, TreeNode>, out: &mut String, prefix: &mut String) {
+    for (i, (name, node)) in tree.iter().enumerate() {
+        let is_last = i == tree.len() - 1;
         let connector = if is_last { "└── " } else { "├── " };
 
-        lines.push(format!("{}{}{}", prefix, connector, name));
+        out.push_str(prefix);
+        out.push_str(connector);
+        out.push_str(name);
+        if let TreeNode::File {
+            annotation: Some(annotation),
+            ..
+        } = node
+        {
+            out.push_str(" [");
+            out.push_str(annotation);
+            out.push(']');
+        }
+        out.push('\n');
 
         if let TreeNode::Directory(subtree) = node {
-            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
-            build_tree_lines(subtree, lines, &new_prefix);
+            let len = prefix.len();
+            prefix.push_str(if is_last { "    " } else { "│   " });
+            write_tree_lines(subtree, out, prefix);
+            prefix.truncate(len);
         }
     }
 }
+
+fn build_structure_nodes(
+    tree: &BTreeMap<String, TreeNode>,
+    parent: &Path,
+    metadata: &BTreeMap<PathBuf, FileMetadata>,
+) -> Vec<StructureNode> {
+    tree.iter()
+        .map(|(name, node)| {
+            let path = parent.join(name);
+            match node {
+                TreeNode::File { file, annotation } => StructureNode::File {
+                    name: name.clone(),
+                    path: encode_path(&path),
+                    annotation: annotation.clone(),
+                    metadata: metadata.get(file).cloned(),
+                },
+                TreeNode::Directory(subtree) => StructureNode::Directory {
+                    name: name.clone(),
+                    path: encode_path(&path),
+                    children: build_structure_nodes(subtree, &path, metadata),
+                },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,70 @@
+use crate::config::settings::TransformHook;
+use crate::core::file_collector::CollectedFile;
+use crate::core::pattern_matcher::{PatternMatcher, PatternSource};
+use crate::io::shell::run_with_stdin;
+use crate::io::vfs::Vfs;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Transform hooks with their patterns compiled, applied in config order
+pub struct Transforms {
+    hooks: Vec<(PatternMatcher, String)>,
+}
+
+impl Transforms {
+    pub fn new(hooks: &[TransformHook]) -> Self {
+        Self {
+            hooks: hooks
+                .iter()
+                .map(|hook| {
+                    let matcher = PatternMatcher::builder()
+                        .add(&hook.pattern, PatternSource::Config)
+                        .build();
+                    (matcher, hook.command.clone())
+                })
+                .collect(),
+        }
+    }
+
+    /// Pipe each matching file through its hooks, updating its metadata to the new content.
+    /// Returns the transformed contents by path; a failing hook leaves the content unchanged.
+    pub async fn apply(
+        &self,
+        vfs: &impl Vfs,
+        files: &mut [CollectedFile],
+    ) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let mut transformed = BTreeMap::new();
+
+        for file in files.iter_mut() {
+            let commands: Vec<_> = self
+                .hooks
+                .iter()
+                .filter(|(matcher, _)| matcher.matches_path(&file.relative_path))
+                .map(|(_, command)| command)
+                .collect();
+            if commands.is_empty() {
+                continue;
+            }
+
+            let mut content = vfs.read(&file.path).await?;
+            for command in commands {
+                match run_transform(command, &content).await {
+                    Ok(output) => content = output,
+                    Err(e) => warn!("Transform of {} failed: {:#}", file.path.display(), e),
+                }
+            }
+
+            *file = CollectedFile::new(&file.path, &file.relative_path, &content, file.modified);
+            transformed.insert(file.path.clone(), content);
+        }
+
+        Ok(transformed)
+    }
+}
+
+/// Run `command` through the shell with `input` on stdin, returning its stdout
+pub async fn run_transform(command: &str, input: &[u8]) -> Result<Vec<u8>> {
+    run_with_stdin(command, input, true).await
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+
+/// The changes a unified diff makes to one file
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffFile {
+    /// Path with any `a/` or `b/` prefix removed
+    pub path: String,
+    /// The diff creates the file, its old side being `/dev/null`
+    pub created: bool,
+    /// The diff deletes the file, its new side being `/dev/null`
+    pub deleted: bool,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// One `@@` hunk: the lines it expects, context included, and what replaces them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffHunk {
+    pub old: String,
+    pub new: String,
+    /// Line the hunk starts at once the hunks before it are applied, from its `+` range; for
+    /// a hunk that only removes lines, the line after the ones it keeps
+    pub line: Option<usize>,
+}
+
+/// Whether `text` reads as a unified diff rather than a JSON patch
+pub fn looks_like_unified_diff(text: &str) -> bool {
+    !text.trim_start().starts_with('{')
+        && text.lines().any(|line| line.starts_with("+++ "))
+        && text.lines().any(|line| line.starts_with("@@"))
+}
+
+/// Path of a `---`/`+++` line, without a trailing timestamp or the `prefix` git adds;
+/// `None` for `/dev/null`
+fn diff_path(rest: &str, prefix: &str) -> Option<String> {
+    let path = rest.split('\t').next().unwrap_or(rest).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// Parse the files of a unified diff, as `diff -u` or `git diff` write them. Hunks are
+/// located by their content, their line numbers only picking the nearest of several matches
+/// and placing pure insertions, so the ones models write with wrong or missing counts still
+/// apply; anything outside the hunks, such as `diff --git` and `index` lines or code fences,
+/// is skipped.
+pub fn parse_unified_diff(text: &str) -> Result<Vec<DiffFile>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<DiffFile> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(old) = line.strip_prefix("--- ")
+            && let Some(new) = lines.get(i + 1).and_then(|next| next.strip_prefix("+++ "))
+        {
+            let (old, new) = (diff_path(old, "a/"), diff_path(new, "b/"));
+            let (created, deleted) = (old.is_none(), new.is_none());
+            let Some(path) = new.or(old) else {
+                return Err(anyhow::anyhow!(
+                    "Both sides of a diff are /dev/null: {}",
+                    line
+                ));
+            };
+            files.push(DiffFile {
+                path,
+                created,
+                deleted,
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            let Some(file) = files.last_mut() else {
+                return Err(anyhow::anyhow!(
+                    "Hunk before any '---'/'+++' file header: {}",
+                    line
+                ));
+            };
+            let (mut hunk, next) = parse_hunk(&lines, i + 1);
+            hunk.line = hunk_line(line);
+            file.hunks.push(hunk);
+            i = next;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("No files found in the diff"));
+    }
+    // A deletion's hunks only list the lines that go, so it may have none
+    if let Some(file) = files
+        .iter()
+        .find(|file| file.hunks.is_empty() && !file.deleted)
+    {
+        return Err(anyhow::anyhow!("No hunks for {} in the diff", file.path));
+    }
+    Ok(files)
+}
+
+/// Where a hunk applies, from the `+start,count` range of its `@@ -l,c +l,c @@` header: the
+/// new side's first line, or the line after `start` when the new side is empty
+fn hunk_line(header: &str) -> Option<usize> {
+    let range = header
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (range.parse().ok()?, 1),
+    };
+    Some(if count == 0 { start + 1 } else { start })
+}
+
+/// Whether `lines[index]` starts another hunk or file rather than continuing a hunk
+fn starts_section(lines: &[&str], index: usize) -> bool {
+    let line = lines[index];
+    line.starts_with("@@")
+        || line.starts_with("diff ")
+        || (line.starts_with("--- ")
+            && lines
+                .get(index + 1)
+                .is_some_and(|next| next.starts_with("+++ ")))
+}
+
+/// Read the hunk body starting at `start`, returning it and the index of the line after it
+fn parse_hunk(lines: &[&str], start: usize) -> (DiffHunk, usize) {
+    let mut hunk = DiffHunk::default();
+    // Which sides the previous line went to, for a `\ No newline at end of file` marker
+    let mut last = (false, false);
+    let mut end = start;
+
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        if starts_section(lines, index) {
+            break;
+        }
+
+        let (old, new) = match line.chars().next() {
+            Some(' ') => (true, true),
+            Some('-') => (true, false),
+            Some('+') => (false, true),
+            Some('\\') => {
+                if last.0 {
+                    hunk.old.pop();
+                }
+                if last.1 {
+                    hunk.new.pop();
+                }
+                end = index + 1;
+                continue;
+            }
+            // Blank context lines lose their leading space in pasted diffs; trailing blank
+            // lines are separators instead, so they only count once more of the hunk follows
+            None => {
+                let next = (index + 1..lines.len()).find(|&next| !lines[next].is_empty());
+                let continues = next.is_some_and(|next| {
+                    !starts_section(lines, next) && lines[next].starts_with([' ', '-', '+', '\\'])
+                });
+                if !continues {
+                    break;
+                }
+                (true, true)
+            }
+            Some(_) => break,
+        };
+
+        let content = line.get(1..).unwrap_or("");
+        if old {
+            hunk.old.push_str(content);
+            hunk.old.push('\n');
+        }
+        if new {
+            hunk.new.push_str(content);
+            hunk.new.push('\n');
+        }
+        last = (old, new);
+        end = index + 1;
+    }
+
+    (hunk, end)
+}
+
+/// ANSI escapes for removed lines, added lines, and the reset after either
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// `old` and `new` as diff lines, `-` for removed, `+` for added and ` ` for kept, along
+/// their longest common subsequence of lines; colored with ANSI escapes when `color` is set
+pub fn format_diff(old: &str, new: &str, color: bool) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let mut line = |sign: char, text: &str| {
+        let (start, end) = match (color, sign) {
+            (true, '-') => (RED, RESET),
+            (true, '+') => (GREEN, RESET),
+            _ => ("", ""),
+        };
+        diff.push_str(&format!("{}{}{}{}\n", start, sign, text, end));
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            line(' ', old[i]);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            line('-', old[i]);
+            i += 1;
+        } else {
+            line('+', new[j]);
+            j += 1;
+        }
+    }
+    diff
+}
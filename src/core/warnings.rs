@@ -0,0 +1,100 @@
+use crate::core::content_processor::OutputFormat;
+use crate::utils::html::escape_html;
+use crate::utils::path_encoding::{encode_path, relative_to};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Why part of the project is missing from, or incomplete in, a snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningKind {
+    /// Could not be read or was not valid UTF-8
+    Unreadable,
+    /// Left out because it looks like it holds secrets
+    Redacted,
+    /// Minified, outlined, summarized or omitted to fit the context window
+    Reduced,
+    /// Left out because of its license, by `--exclude-license`
+    Licensed,
+}
+
+impl WarningKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WarningKind::Unreadable => "unreadable",
+            WarningKind::Redacted => "redacted",
+            WarningKind::Reduced => "reduced",
+            WarningKind::Licensed => "license",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Everything a run left out or cut down, kept apart from the rendered content. Clones
+/// share the same record, so each stage of a run can add to it.
+#[derive(Clone, Debug, Default)]
+pub struct Warnings {
+    warnings: Arc<Mutex<Vec<Warning>>>,
+}
+
+impl Warnings {
+    pub fn push(&self, kind: WarningKind, path: &Path, message: impl Into<String>) {
+        self.warnings.lock().unwrap().push(Warning {
+            kind,
+            path: path.to_path_buf(),
+            message: message.into(),
+        });
+    }
+
+    /// Recorded warnings, grouped by kind in the order they were added
+    pub fn all(&self) -> Vec<Warning> {
+        let mut warnings = self.warnings.lock().unwrap().clone();
+        warnings.sort_by_key(|warning| warning.kind);
+        warnings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.lock().unwrap().is_empty()
+    }
+
+    /// Closing section telling the reader of a snapshot what it can't see, as markdown or a
+    /// `<caveats>` element
+    pub fn caveats_section(&self, format: OutputFormat) -> String {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let display_path = |path: &Path| encode_path(&relative_to(path, &current_dir));
+        if format == OutputFormat::Xml {
+            let mut section = String::from("<caveats>\n");
+            for warning in self.all() {
+                let _ = writeln!(
+                    section,
+                    "<caveat path=\"{}\" kind=\"{}\">{}</caveat>",
+                    escape_html(&display_path(&warning.path)),
+                    warning.kind.label(),
+                    escape_html(&warning.message)
+                );
+            }
+            section.push_str("</caveats>\n\n");
+            return section;
+        }
+
+        let mut section = String::from("# Caveats\n\n");
+        section.push_str("*These files are missing or incomplete in this snapshot:*\n\n");
+        for warning in self.all() {
+            let _ = writeln!(
+                section,
+                "- `{}` ({}): {}",
+                display_path(&warning.path),
+                warning.kind.label(),
+                warning.message
+            );
+        }
+        section.push('\n');
+        section
+    }
+}
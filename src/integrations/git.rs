@@ -0,0 +1,534 @@
+use crate::core::pattern_matcher::{PatternMatcher, PatternSource};
+use crate::io::shell::run_shell;
+use crate::io::vfs::{Prune, Vfs, VfsMetadata, Walk};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+use tracing::debug;
+
+/// Line ending `.gitattributes` asks a path to be checked out with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    Crlf,
+}
+
+impl Eol {
+    /// `content` with every line ending turned into this one
+    pub fn apply(&self, content: &str) -> String {
+        let content = normalize_eol(content);
+        match self {
+            Eol::Lf => content,
+            Eol::Crlf => content.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// `content` with CRLF line endings turned into LF, the way git stores text
+pub fn normalize_eol(content: &str) -> String {
+    content.replace("\r\n", "\n")
+}
+
+/// What `.gitattributes` says about one path; `None` leaves the decision to catnip's own checks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathAttributes {
+    /// Set by `binary` or `-text`, cleared by `text` or `eol`
+    pub binary: Option<bool>,
+    pub eol: Option<Eol>,
+}
+
+#[derive(Debug)]
+struct AttributeRule {
+    matcher: PatternMatcher,
+    attributes: PathAttributes,
+}
+
+/// Rules of a `.gitattributes` file. Later lines override earlier ones attribute by
+/// attribute, as in git.
+#[derive(Debug, Default)]
+pub struct GitAttributes {
+    rules: Vec<AttributeRule>,
+}
+
+impl GitAttributes {
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let mut attributes = PathAttributes::default();
+            for attribute in fields {
+                match attribute {
+                    "binary" | "-text" => attributes.binary = Some(true),
+                    "text" => attributes.binary = Some(false),
+                    "eol=lf" | "eol=crlf" => {
+                        attributes.eol = Some(if attribute == "eol=lf" {
+                            Eol::Lf
+                        } else {
+                            Eol::Crlf
+                        });
+                        attributes.binary.get_or_insert(false);
+                    }
+                    // `text=auto` and attributes like `diff` or `linguist-generated`
+                    _ => {}
+                }
+            }
+
+            if attributes != PathAttributes::default() {
+                rules.push(AttributeRule {
+                    matcher: PatternMatcher::builder()
+                        .add(pattern, PatternSource::Gitattributes)
+                        .build(),
+                    attributes,
+                });
+            }
+        }
+
+        Self { rules }
+    }
+
+    /// The `.gitattributes` file in `dir`, or no rules if it is missing or unreadable
+    pub async fn load(vfs: &impl Vfs, dir: &Path) -> Self {
+        let path = dir.join(".gitattributes");
+        match vfs.read_to_string(&path).await {
+            Ok(content) => {
+                debug!("Using attributes file {}", path.display());
+                Self::parse(&content)
+            }
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Attributes of `relative_path`, relative to the directory of the `.gitattributes` file
+    pub fn get(&self, relative_path: &Path) -> PathAttributes {
+        let relative_path = relative_path.strip_prefix(".").unwrap_or(relative_path);
+        let mut attributes = PathAttributes::default();
+
+        for rule in &self.rules {
+            if !rule.matcher.matches_path(relative_path) {
+                continue;
+            }
+            if rule.attributes.binary.is_some() {
+                attributes.binary = rule.attributes.binary;
+            }
+            if rule.attributes.eol.is_some() {
+                attributes.eol = rule.attributes.eol;
+            }
+        }
+
+        attributes
+    }
+}
+
+/// How the contents of git submodules end up in a snapshot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SubmoduleMode {
+    /// Leave them out, with a note naming each one
+    Skip,
+    /// Walk them like any other directory
+    #[default]
+    Include,
+    /// Include only an outline of their files
+    Outline,
+}
+
+/// Reason recorded for submodule directories the walk skipped
+pub const SUBMODULE_REASON: &str = "git submodule";
+
+/// Submodule paths listed in a `.gitmodules` file, relative to its directory
+pub fn parse_gitmodules(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| PathBuf::from(value.trim()))
+        })
+        .collect()
+}
+
+/// Submodules declared in `dir/.gitmodules`, relative to `dir`
+pub async fn submodule_paths(vfs: &impl Vfs, dir: &Path) -> Vec<PathBuf> {
+    match vfs.read_to_string(&dir.join(".gitmodules")).await {
+        Ok(content) => parse_gitmodules(&content),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Submodule directories found below the input directories. Clones share the same record,
+/// so the walk can fill it in while rendering reads it afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct Submodules {
+    dirs: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl Submodules {
+    pub fn record(&self, dir: PathBuf) {
+        let mut dirs = self.dirs.lock().unwrap();
+        if !dirs.contains(&dir) {
+            debug!("Found submodule {}", dir.display());
+            dirs.push(dir);
+        }
+    }
+
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        self.dirs.lock().unwrap().clone()
+    }
+
+    /// Whether `path` lies in one of the submodules
+    pub fn contains(&self, path: &Path) -> bool {
+        self.dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|dir| path.starts_with(dir))
+    }
+}
+
+/// Structure annotation of files a sparse checkout left off disk
+pub const NOT_MATERIALIZED: &str = "not materialized";
+
+/// Whether `dir` lies in a sparse checkout, judging by the `.git/info/sparse-checkout`
+/// of the nearest repository above it
+pub async fn is_sparse_checkout(vfs: &impl Vfs, dir: &Path) -> bool {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    for ancestor in dir.ancestors().chain(current_dir.ancestors()) {
+        let git_dir = ancestor.join(".git");
+        if vfs.exists(&git_dir).await {
+            return vfs.exists(&git_dir.join("info/sparse-checkout")).await;
+        }
+    }
+    false
+}
+
+/// Paths marked skip-worktree (`S`) in `git ls-files -t -z` output
+pub fn parse_skip_worktree(output: &str) -> Vec<PathBuf> {
+    output
+        .split('\0')
+        .filter_map(|entry| entry.strip_prefix("S "))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Files in the index below `dir` that the sparse checkout left out, relative to `dir`.
+/// Nothing is returned when git can't be run there.
+pub async fn sparse_files(dir: &Path) -> Vec<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["ls-files", "-t", "-z"])
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_skip_worktree(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            debug!(
+                "git ls-files failed in {}: {}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Vec::new()
+        }
+        Err(e) => {
+            debug!("Could not run git in {}: {}", dir.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// Files a sparse checkout left off disk that the filters would otherwise have collected.
+/// Clones share the same record, so the walk can fill it in while rendering reads it.
+#[derive(Clone, Debug, Default)]
+pub struct NotMaterialized {
+    files: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl NotMaterialized {
+    pub fn record(&self, path: PathBuf) {
+        let mut files = self.files.lock().unwrap();
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.files.lock().unwrap().clone();
+        files.sort();
+        files
+    }
+}
+
+/// Blob entries of `git ls-tree -r -z` output, as (object id, path); submodule commits and
+/// other non-blob entries are left out
+pub fn parse_ls_tree(output: &str) -> Vec<(String, PathBuf)> {
+    output
+        .split('\0')
+        .filter_map(|entry| {
+            let (info, path) = entry.split_once('\t')?;
+            let mut fields = info.split_whitespace();
+            let (_mode, kind, oid) = (fields.next()?, fields.next()?, fields.next()?);
+            (kind == "blob").then(|| (oid.to_string(), PathBuf::from(path)))
+        })
+        .collect()
+}
+
+/// Contents of the objects in `git cat-file --batch` output, in request order
+pub fn parse_cat_file_batch(mut output: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut contents = Vec::new();
+
+    while !output.is_empty() {
+        let header_end = output
+            .iter()
+            .position(|&b| b == b'\n')
+            .context("Truncated git cat-file output")?;
+        let header = String::from_utf8_lossy(&output[..header_end]);
+        let size: usize = header
+            .rsplit(' ')
+            .next()
+            .and_then(|size| size.parse().ok())
+            .with_context(|| format!("Unexpected git cat-file header: {}", header))?;
+
+        let start = header_end + 1;
+        let content = output
+            .get(start..start + size)
+            .context("Truncated git cat-file output")?;
+        contents.push(content.to_vec());
+        // Each object is followed by a newline
+        output = output.get(start + size + 1..).unwrap_or_default();
+    }
+
+    Ok(contents)
+}
+
+/// Run `git` with `args` in the current directory, returning its stdout
+async fn git(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut cmd = Command::new("git");
+    cmd.args(args);
+    run_shell(cmd, &format!("git {}", args.join(" ")), input, true).await
+}
+
+/// The files of a git revision, read through git's object database rather than a checkout.
+/// Reads, metadata and walks see the revision's tree below the current directory; writes
+/// such as output files and history go to `base`.
+#[derive(Debug)]
+pub struct GitRevisionFs<'a, V: Vfs> {
+    base: &'a V,
+    /// Contents keyed by path relative to the current directory
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl<'a, V: Vfs> GitRevisionFs<'a, V> {
+    /// Load every blob of `revision` below the current directory
+    pub async fn load(base: &'a V, revision: &str) -> Result<Self> {
+        let listing = git(&["ls-tree", "-r", "-z", revision], &[]).await?;
+        let entries = parse_ls_tree(&String::from_utf8_lossy(&listing));
+
+        let request: String = entries
+            .iter()
+            .map(|(oid, _)| format!("{}\n", oid))
+            .collect();
+        let output = git(&["cat-file", "--batch"], request.as_bytes()).await?;
+        let contents = parse_cat_file_batch(&output)?;
+
+        debug!("Loaded {} files from {}", contents.len(), revision);
+        Ok(Self::new(
+            base,
+            entries
+                .into_iter()
+                .map(|(_, path)| path)
+                .zip(contents)
+                .collect(),
+        ))
+    }
+
+    pub fn new(base: &'a V, files: BTreeMap<PathBuf, Vec<u8>>) -> Self {
+        Self { base, files }
+    }
+
+    /// `path` as the key it has in `files`
+    fn key(path: &Path) -> PathBuf {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let path = path.strip_prefix(&current_dir).unwrap_or(path);
+        path.components()
+            .filter(|component| *component != Component::CurDir)
+            .collect()
+    }
+
+    fn not_found(path: &Path) -> anyhow::Error {
+        anyhow::anyhow!("No such file in the revision: {}", path.display())
+    }
+}
+
+impl<V: Vfs> Vfs for GitRevisionFs<'_, V> {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.files
+            .get(&Self::key(path))
+            .cloned()
+            .ok_or_else(|| Self::not_found(path))
+    }
+
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.base.write(path, content).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.base.remove_file(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        let key = Self::key(path);
+        if let Some(content) = self.files.get(&key) {
+            return Ok(VfsMetadata {
+                len: content.len() as u64,
+                is_file: true,
+                is_dir: false,
+                modified: None,
+            });
+        }
+
+        if self.files.keys().any(|file| file.starts_with(&key)) {
+            return Ok(VfsMetadata {
+                len: 0,
+                is_file: false,
+                is_dir: true,
+                modified: None,
+            });
+        }
+
+        Err(Self::not_found(path))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.base.create_dir_all(path).await
+    }
+
+    /// Ignore files are left to the collector
+    async fn walk(&self, root: &Path, prune: Prune, _ignore_files: bool) -> Result<Walk> {
+        let key = Self::key(root);
+
+        // Paths are reported below `root` as given, like a walk of the working tree
+        let files = self
+            .files
+            .keys()
+            .filter_map(|file| file.strip_prefix(&key).ok())
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .filter(|relative| {
+                // Outermost first, like a real walk that never visits a pruned directory's children
+                let dirs: Vec<_> = relative
+                    .ancestors()
+                    .skip(1)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .collect();
+                dirs.iter().rev().all(|dir| !prune(&root.join(dir)))
+            })
+            .map(|relative| root.join(relative))
+            .collect();
+        Ok(Walk {
+            files,
+            errors: Vec::new(),
+        })
+    }
+}
+
+/// Branch `patch --onto` commits to, e.g. `catnip/onto-release-1.2-1700000000`
+pub fn onto_branch_name(reference: &str, stamp: u64) -> String {
+    let reference: String = reference
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("catnip/onto-{}-{}", reference.trim_matches('-'), stamp)
+}
+
+/// A temporary `git worktree` checked out on a new branch, for changing another ref without
+/// touching the current checkout
+#[derive(Debug)]
+pub struct Worktree {
+    pub path: PathBuf,
+    pub branch: String,
+    /// Ref the branch was started from
+    pub base: String,
+    /// The current directory relative to the top of the repository, where paths relative to it
+    /// start in the worktree too
+    pub prefix: PathBuf,
+}
+
+impl Worktree {
+    /// Check out a new branch at `reference` in a worktree under the temp directory
+    pub async fn add(reference: &str) -> Result<Self> {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let branch = onto_branch_name(reference, stamp);
+        let path = std::env::temp_dir().join(branch.replace('/', "-"));
+        let prefix = git(&["rev-parse", "--show-prefix"], &[]).await?;
+        let prefix = PathBuf::from(String::from_utf8_lossy(&prefix).trim());
+
+        let path_arg = path.to_string_lossy();
+        git(
+            &["worktree", "add", "-q", "-b", &branch, &path_arg, reference],
+            &[],
+        )
+        .await
+        .with_context(|| format!("Failed to create a worktree at {}", reference))?;
+
+        debug!("Created worktree {} on {}", path.display(), branch);
+        Ok(Self {
+            path,
+            branch,
+            base: reference.to_string(),
+            prefix,
+        })
+    }
+
+    /// Run `git` inside the worktree
+    async fn git(&self, args: &[&str]) -> Result<Vec<u8>> {
+        let path = self.path.to_string_lossy();
+        let args: Vec<&str> = ["-C", &path]
+            .into_iter()
+            .chain(args.iter().copied())
+            .collect();
+        git(&args, &[]).await
+    }
+
+    /// Commit every change in the worktree to its branch
+    pub async fn commit(&self, message: &str) -> Result<()> {
+        self.git(&["add", "-A"]).await?;
+        self.git(&["commit", "-q", "-m", message]).await?;
+        Ok(())
+    }
+
+    /// `git diff --stat` of the branch against the ref it started from
+    pub async fn diff_stat(&self) -> Result<String> {
+        let range = format!("{}..HEAD", self.base);
+        let stat = self.git(&["diff", "--stat", &range]).await?;
+        Ok(String::from_utf8_lossy(&stat).into_owned())
+    }
+
+    /// Remove the worktree, keeping its branch unless `delete_branch`
+    pub async fn remove(self, delete_branch: bool) -> Result<()> {
+        let path = self.path.to_string_lossy();
+        git(&["worktree", "remove", "--force", &path], &[]).await?;
+        if delete_branch {
+            git(&["branch", "-q", "-D", &self.branch], &[]).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::settings::{ShareProviderKind, ShareSettings};
+use crate::io::remote::http_client;
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use tracing::debug;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// A destination rendered snapshots can be uploaded to
+pub trait ShareProvider: Send + Sync {
+    /// Upload `content` as a document called `name`, returning the link to share
+    fn upload(&self, name: &str, content: &str) -> impl Future<Output = Result<String>> + Send;
+}
+
+/// A pre-signed PUT URL, such as one generated for an S3 object
+pub struct PresignedUrl {
+    pub url: String,
+    /// Link to hand out instead of the upload URL stripped of its signature
+    pub link: Option<String>,
+}
+
+impl ShareProvider for PresignedUrl {
+    async fn upload(&self, _name: &str, content: &str) -> Result<String> {
+        http_client()?
+            .put(&self.url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "text/markdown; charset=utf-8",
+            )
+            .body(content.to_string())
+            .send()
+            .await
+            .context("Failed to upload to pre-signed URL")?
+            .error_for_status()
+            .context("Failed to upload to pre-signed URL")?;
+
+        Ok(self
+            .link
+            .clone()
+            .unwrap_or_else(|| presigned_link(&self.url)))
+    }
+}
+
+/// A paste service that takes the document as the POST body and answers with its link
+pub struct PasteService {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+impl ShareProvider for PasteService {
+    async fn upload(&self, name: &str, content: &str) -> Result<String> {
+        let mut request = http_client()?
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .header("X-Filename", name)
+            .body(content.to_string());
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let body = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to upload to {}", self.url))?
+            .error_for_status()
+            .with_context(|| format!("Failed to upload to {}", self.url))?
+            .text()
+            .await?;
+        paste_link(&body)
+    }
+}
+
+/// A GitHub gist, secret unless `public` is set
+pub struct Gist {
+    pub token: String,
+    pub public: bool,
+}
+
+impl ShareProvider for Gist {
+    async fn upload(&self, name: &str, content: &str) -> Result<String> {
+        let response: Value = http_client()?
+            .post(format!("{}/gists", GITHUB_API_URL))
+            .bearer_auth(&self.token)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&gist_payload(name, content, self.public))
+            .send()
+            .await
+            .context("Failed to create gist")?
+            .error_for_status()
+            .context("Failed to create gist")?
+            .json()
+            .await
+            .context("Failed to parse GitHub response")?;
+        gist_link(&response)
+    }
+}
+
+/// Upload URL without its query string, which holds the signature and shouldn't be shared
+pub fn presigned_link(url: &str) -> String {
+    url.split(['?', '#']).next().unwrap_or(url).to_string()
+}
+
+/// The link a paste service answered with, the first non-empty line of its response
+pub fn paste_link(body: &str) -> Result<String> {
+    body.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Paste service returned no link"))
+}
+
+pub fn gist_payload(name: &str, content: &str, public: bool) -> Value {
+    json!({
+        "description": "catnip snapshot",
+        "public": public,
+        "files": { name: { "content": content } },
+    })
+}
+
+pub fn gist_link(response: &Value) -> Result<String> {
+    response["html_url"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("GitHub response has no gist link"))
+}
+
+/// Upload with the provider configured under `[share]`, or `kind` when given
+pub async fn share(
+    settings: &ShareSettings,
+    kind: Option<ShareProviderKind>,
+    name: &str,
+    content: &str,
+) -> Result<String> {
+    let kind = kind
+        .or(settings.provider)
+        .context("No share provider configured; set [share] provider in the config")?;
+    let token = settings
+        .token_env
+        .as_deref()
+        .or(match kind {
+            ShareProviderKind::Gist => Some("GITHUB_TOKEN"),
+            _ => None,
+        })
+        .and_then(|var| std::env::var(var).ok());
+    let url = || {
+        settings
+            .url
+            .clone()
+            .with_context(|| format!("The {} share provider needs [share] url", kind))
+    };
+    debug!("Sharing {} ({} bytes) via {}", name, content.len(), kind);
+
+    match kind {
+        ShareProviderKind::Presigned => {
+            PresignedUrl {
+                url: url()?,
+                link: settings.link.clone(),
+            }
+            .upload(name, content)
+            .await
+        }
+        ShareProviderKind::Paste => {
+            PasteService { url: url()?, token }
+                .upload(name, content)
+                .await
+        }
+        ShareProviderKind::Gist => {
+            Gist {
+                token: token.context("Sharing to a gist needs a GitHub token in $GITHUB_TOKEN")?,
+                public: settings.public,
+            }
+            .upload(name, content)
+            .await
+        }
+    }
+}
@@ -1,13 +1,37 @@
 use anyhow::Result;
 use std::process::Command;
+use std::sync::Mutex;
 use tracing::{debug, info};
 
-#[derive(Debug)]
+/// How long the rich clipboard is served on Linux, where clipboard content
+/// only lives as long as the process that owns it
+#[cfg(target_os = "linux")]
+const LINUX_RICH_CLIPBOARD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A source and sink for clipboard content
+pub trait ClipboardProvider: Send + Sync {
+    fn copy(&self, content: &str) -> Result<()>;
+    fn read(&self) -> Result<String>;
+
+    /// Copy an HTML flavor alongside the plain-text one; backends without rich
+    /// clipboard support only copy the plain text
+    fn copy_html(&self, html: &str, text: &str) -> Result<()> {
+        debug!(
+            "Rich clipboard not supported, copying {} bytes of HTML as text",
+            html.len()
+        );
+        self.copy(text)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 enum ClipboardType {
     Wayland,
     X11,
     MacOS,
     Windows,
+    Wsl,
+    Termux,
     Unsupported,
 }
 
@@ -20,6 +44,16 @@ fn detect_clipboard_system() -> ClipboardType {
         return ClipboardType::MacOS;
     }
 
+    // WSL can reach the Windows clipboard without any X11/Wayland tooling
+    if is_wsl() {
+        return ClipboardType::Wsl;
+    }
+
+    // Termux on Android exposes the system clipboard through termux-api
+    if std::env::var("TERMUX_VERSION").is_ok() && command_exists("termux-clipboard-set") {
+        return ClipboardType::Termux;
+    }
+
     // For Linux/Unix systems
     if std::env::var("WAYLAND_DISPLAY").is_ok() && command_exists("wl-copy") {
         return ClipboardType::Wayland;
@@ -32,6 +66,13 @@ fn detect_clipboard_system() -> ClipboardType {
     ClipboardType::Unsupported
 }
 
+fn is_wsl() -> bool {
+    cfg!(target_os = "linux")
+        && std::fs::read_to_string("/proc/version")
+            .map(|version| version.to_lowercase().contains("microsoft"))
+            .unwrap_or(false)
+}
+
 fn command_exists(cmd: &str) -> bool {
     Command::new("which")
         .arg(cmd)
@@ -40,95 +81,238 @@ fn command_exists(cmd: &str) -> bool {
         .unwrap_or(false)
 }
 
-async fn copy_to_clipboard_native(content: &str) -> Result<()> {
-    let clipboard_type = detect_clipboard_system();
-    debug!("Detected clipboard system: {:?}", clipboard_type);
+/// Clipboard backed by the platform's command-line clipboard tools
+#[derive(Debug)]
+pub struct SystemClipboard;
+
+impl SystemClipboard {
+    fn copy_command(clipboard_type: ClipboardType) -> Result<(&'static str, Vec<&'static str>)> {
+        Ok(match clipboard_type {
+            ClipboardType::Wayland => ("wl-copy", vec![]),
+            ClipboardType::X11 => ("xclip", vec!["-selection", "clipboard"]),
+            ClipboardType::MacOS => ("pbcopy", vec![]),
+            ClipboardType::Windows => ("clip", vec![]),
+            ClipboardType::Wsl => ("clip.exe", vec![]),
+            ClipboardType::Termux => ("termux-clipboard-set", vec![]),
+            ClipboardType::Unsupported => {
+                return Err(anyhow::anyhow!(
+                    "No supported clipboard system found. Install:\n\
+                    - Wayland: wl-clipboard\n\
+                    - X11: xclip\n\
+                    - Termux: termux-api\n\
+                    - Or use --output to save to file"
+                ));
+            }
+        })
+    }
+
+    fn paste_command(clipboard_type: ClipboardType) -> Result<(&'static str, Vec<&'static str>)> {
+        Ok(match clipboard_type {
+            ClipboardType::Wayland => ("wl-paste", vec![]),
+            ClipboardType::X11 => ("xclip", vec!["-selection", "clipboard", "-o"]),
+            ClipboardType::MacOS => ("pbpaste", vec![]),
+            ClipboardType::Windows => ("powershell", vec!["-command", "Get-Clipboard"]),
+            ClipboardType::Wsl => (
+                "powershell.exe",
+                vec!["-NoProfile", "-NonInteractive", "-command", "Get-Clipboard"],
+            ),
+            ClipboardType::Termux => ("termux-clipboard-get", vec![]),
+            ClipboardType::Unsupported => {
+                return Err(anyhow::anyhow!(
+                    "No supported clipboard system found. Install:\n\
+                    - Wayland: wl-clipboard\n\
+                    - X11: xclip\n\
+                    - Termux: termux-api\n\
+                    - Or provide a JSON file path"
+                ));
+            }
+        })
+    }
+}
+
+impl ClipboardProvider for SystemClipboard {
+    fn copy(&self, content: &str) -> Result<()> {
+        debug!("Copying {} characters to clipboard", content.len());
+        let clipboard_type = detect_clipboard_system();
+        debug!("Detected clipboard system: {:?}", clipboard_type);
+
+        let (cmd, args) = Self::copy_command(clipboard_type)?;
+
+        let mut child = Command::new(cmd)
+            .args(&args)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn {}: {}", cmd, e))?;
+
+        if let Some(stdin) = child.stdin.as_mut() {
+            use std::io::Write;
+            stdin
+                .write_all(content.as_bytes())
+                .map_err(|e| anyhow::anyhow!("Failed to write to {} stdin: {}", cmd, e))?;
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| anyhow::anyhow!("Failed to wait for {}: {}", cmd, e))?;
 
-    let (cmd, args): (&str, Vec<&str>) = match clipboard_type {
-        ClipboardType::Wayland => ("wl-copy", vec![]),
-        ClipboardType::X11 => ("xclip", vec!["-selection", "clipboard"]),
-        ClipboardType::MacOS => ("pbcopy", vec![]),
-        ClipboardType::Windows => ("clip", vec![]),
-        ClipboardType::Unsupported => {
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} failed with status: {}", cmd, status));
+        }
+
+        info!("Content copied to clipboard using {}", cmd);
+        println!("Content copied to clipboard");
+        Ok(())
+    }
+
+    fn read(&self) -> Result<String> {
+        let clipboard_type = detect_clipboard_system();
+        debug!("Reading from clipboard using: {:?}", clipboard_type);
+
+        let (cmd, args) = Self::paste_command(clipboard_type)?;
+
+        let output = Command::new(cmd)
+            .args(&args)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", cmd, e))?;
+
+        if !output.status.success() {
             return Err(anyhow::anyhow!(
-                "No supported clipboard system found. Install:\n\
-                - Wayland: wl-clipboard\n\
-                - X11: xclip\n\
-                - Or use --output to save to file"
+                "{} failed with status: {}",
+                cmd,
+                output.status
             ));
         }
-    };
 
-    let mut child = Command::new(cmd)
-        .args(&args)
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| anyhow::anyhow!("Failed to spawn {}: {}", cmd, e))?;
+        let mut content = String::from_utf8(output.stdout)
+            .map_err(|e| anyhow::anyhow!("Invalid UTF-8 in clipboard content: {}", e))?;
 
-    if let Some(stdin) = child.stdin.as_mut() {
-        use std::io::Write;
-        stdin
-            .write_all(content.as_bytes())
-            .map_err(|e| anyhow::anyhow!("Failed to write to {} stdin: {}", cmd, e))?;
+        // powershell.exe emits CRLF line endings when called from inside WSL
+        if matches!(clipboard_type, ClipboardType::Wsl) {
+            content = content.replace("\r\n", "\n");
+        }
+
+        if content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Clipboard is empty"));
+        }
+
+        info!("Read {} characters from clipboard", content.len());
+        Ok(content)
+    }
+
+    fn copy_html(&self, html: &str, text: &str) -> Result<()> {
+        // The command-line tools only carry a single flavor, so defer to arboard
+        ArboardClipboard.copy_html(html, text)
+    }
+}
+
+/// Clipboard backed by the `arboard` crate's native platform integrations
+#[derive(Debug)]
+pub struct ArboardClipboard;
+
+impl ArboardClipboard {
+    fn open() -> Result<arboard::Clipboard> {
+        arboard::Clipboard::new().map_err(|e| anyhow::anyhow!("Failed to open clipboard: {}", e))
+    }
+}
+
+impl ClipboardProvider for ArboardClipboard {
+    fn copy(&self, content: &str) -> Result<()> {
+        Self::open()?
+            .set_text(content)
+            .map_err(|e| anyhow::anyhow!("Failed to copy to clipboard: {}", e))?;
+
+        info!("Content copied to clipboard using arboard");
+        println!("Content copied to clipboard");
+        Ok(())
     }
 
-    let status = child
-        .wait()
-        .map_err(|e| anyhow::anyhow!("Failed to wait for {}: {}", cmd, e))?;
+    fn read(&self) -> Result<String> {
+        let content = Self::open()?
+            .get_text()
+            .map_err(|e| anyhow::anyhow!("Failed to read from clipboard: {}", e))?;
+
+        if content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Clipboard is empty"));
+        }
 
-    if !status.success() {
-        return Err(anyhow::anyhow!("{} failed with status: {}", cmd, status));
+        info!("Read {} characters from clipboard", content.len());
+        Ok(content)
     }
 
-    info!("Content copied to clipboard using {}", cmd);
-    println!("Content copied to clipboard");
-    Ok(())
+    fn copy_html(&self, html: &str, text: &str) -> Result<()> {
+        let mut clipboard = Self::open()?;
+
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::SetExtLinux;
+            println!(
+                "Serving rich clipboard content for {}s, paste it now",
+                LINUX_RICH_CLIPBOARD_TIMEOUT.as_secs()
+            );
+            clipboard
+                .set()
+                .wait_until(std::time::Instant::now() + LINUX_RICH_CLIPBOARD_TIMEOUT)
+                .html(html, Some(text))
+                .map_err(|e| anyhow::anyhow!("Failed to copy HTML to clipboard: {}", e))?;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        clipboard
+            .set_html(html, Some(text))
+            .map_err(|e| anyhow::anyhow!("Failed to copy HTML to clipboard: {}", e))?;
+
+        info!("Content copied to clipboard as text and HTML using arboard");
+        println!("Content copied to clipboard");
+        Ok(())
+    }
 }
 
-pub async fn copy_to_clipboard(content: &str) -> Result<()> {
-    debug!("Copying {} characters to clipboard", content.len());
-    copy_to_clipboard_native(content).await
+/// In-memory clipboard for tests and headless environments
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    content: Mutex<String>,
+    html: Mutex<Option<String>>,
 }
 
-pub async fn read_from_clipboard() -> Result<String> {
-    let clipboard_type = detect_clipboard_system();
-    debug!("Reading from clipboard using: {:?}", clipboard_type);
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    let (cmd, args): (&str, Vec<&str>) = match clipboard_type {
-        ClipboardType::Wayland => ("wl-paste", vec![]),
-        ClipboardType::X11 => ("xclip", vec!["-selection", "clipboard", "-o"]),
-        ClipboardType::MacOS => ("pbpaste", vec![]),
-        ClipboardType::Windows => ("powershell", vec!["-command", "Get-Clipboard"]),
-        ClipboardType::Unsupported => {
-            return Err(anyhow::anyhow!(
-                "No supported clipboard system found. Install:\n\
-                - Wayland: wl-clipboard\n\
-                - X11: xclip\n\
-                - Or provide a JSON file path"
-            ));
+    pub fn with_content(content: &str) -> Self {
+        Self {
+            content: Mutex::new(content.to_string()),
+            html: Mutex::new(None),
         }
-    };
+    }
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", cmd, e))?;
+    pub fn content(&self) -> String {
+        self.content.lock().unwrap().clone()
+    }
 
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "{} failed with status: {}",
-            cmd,
-            output.status
-        ));
+    pub fn html(&self) -> Option<String> {
+        self.html.lock().unwrap().clone()
     }
+}
 
-    let content = String::from_utf8(output.stdout)
-        .map_err(|e| anyhow::anyhow!("Invalid UTF-8 in clipboard content: {}", e))?;
+impl ClipboardProvider for MemoryClipboard {
+    fn copy(&self, content: &str) -> Result<()> {
+        *self.content.lock().unwrap() = content.to_string();
+        *self.html.lock().unwrap() = None;
+        Ok(())
+    }
 
-    if content.trim().is_empty() {
-        return Err(anyhow::anyhow!("Clipboard is empty"));
+    fn copy_html(&self, html: &str, text: &str) -> Result<()> {
+        *self.content.lock().unwrap() = text.to_string();
+        *self.html.lock().unwrap() = Some(html.to_string());
+        Ok(())
     }
 
-    info!("Read {} characters from clipboard", content.len());
-    Ok(content)
+    fn read(&self) -> Result<String> {
+        let content = self.content();
+        if content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Clipboard is empty"));
+        }
+        Ok(content)
+    }
 }
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// First line of every file in the age format
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+/// Who a snapshot is encrypted for, from `--encrypt age:<recipient>`
+#[derive(Clone, Debug)]
+pub enum EncryptionTarget {
+    /// An age X25519 public key (`age1...`), as `age-keygen` and `rage-keygen` print
+    Age(age::x25519::Recipient),
+}
+
+impl EncryptionTarget {
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.split_once(':') {
+            Some(("age", recipient)) => age::x25519::Recipient::from_str(recipient.trim())
+                .map(EncryptionTarget::Age)
+                .map_err(|e| anyhow::anyhow!("Invalid age recipient '{}': {}", recipient, e)),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported encryption '{}'; expected age:<recipient>",
+                spec
+            )),
+        }
+    }
+
+    /// `plaintext` encrypted in the age format, readable with the recipient's key only
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            EncryptionTarget::Age(recipient) => age::encrypt(recipient, plaintext)
+                .map_err(|e| anyhow::anyhow!("Failed to encrypt: {}", e)),
+        }
+    }
+}
+
+impl fmt::Display for EncryptionTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionTarget::Age(recipient) => write!(f, "age:{}", recipient),
+        }
+    }
+}
+
+/// Whether `content` is in the age format
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(AGE_HEADER)
+}
+
+/// `content` decrypted with one of the keys in the age identity file at `identity`
+pub fn decrypt(content: &[u8], identity: &Path) -> Result<Vec<u8>> {
+    let identities = age::IdentityFile::from_file(identity.to_string_lossy().into_owned())
+        .and_then(|file| {
+            file.into_identities()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+        .with_context(|| format!("Failed to read identity file {}", identity.display()))?;
+
+    let decryptor =
+        age::Decryptor::new(content).map_err(|e| anyhow::anyhow!("Failed to decrypt: {}", e))?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))
+        .map_err(|e| anyhow::anyhow!("Failed to decrypt: {}", e))?;
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .context("Failed to decrypt")?;
+    Ok(plaintext)
+}
+
+/// `content` as text, decrypted first when it is in the age format. `name` describes the
+/// content in errors.
+pub fn decrypt_text(content: Vec<u8>, identity: Option<&Path>, name: &str) -> Result<String> {
+    let content = match identity {
+        _ if !is_encrypted(&content) => content,
+        Some(identity) => {
+            decrypt(&content, identity).with_context(|| format!("Failed to read {}", name))?
+        }
+        None => {
+            return Err(anyhow::anyhow!(
+                "{} is encrypted; pass --identity with the age key file to decrypt it",
+                name
+            ));
+        }
+    };
+    String::from_utf8(content).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", name, e))
+}
@@ -1,6 +1,8 @@
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
 
 pub async fn read_file_safe(path: &Path) -> Result<String> {
     fs::read_to_string(path)
@@ -8,8 +10,70 @@ pub async fn read_file_safe(path: &Path) -> Result<String> {
         .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
 }
 
-pub async fn write_file_safe(path: &Path, content: &str) -> Result<()> {
-    fs::write(path, content)
+/// Resolve the file a write to `path` would end up in, refusing symlinks unless
+/// `follow_symlinks` is set
+pub async fn resolve_write_target(path: &Path, follow_symlinks: bool) -> Result<PathBuf> {
+    let is_symlink = fs::symlink_metadata(path)
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+        .is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if !is_symlink {
+        return Ok(path.to_path_buf());
+    }
+
+    let link = fs::read_link(path)
+        .await
+        .with_context(|| format!("Failed to read symlink {}", path.display()))?;
+    let target = match path.parent() {
+        Some(parent) if link.is_relative() => parent.join(&link),
+        _ => link,
+    };
+
+    if !follow_symlinks {
+        return Err(anyhow::anyhow!(
+            "Refusing to write {}: it is a symlink to {} (use --follow-symlinks to write through it)",
+            path.display(),
+            target.display()
+        ));
+    }
+
+    debug!(
+        "Following symlink {} -> {}",
+        path.display(),
+        target.display()
+    );
+    Ok(target)
+}
+
+/// Write `content` atomically: it goes to a temporary file next to the target, which is
+/// then renamed over it, so readers never see a truncated file
+pub async fn write_file_safe(path: &Path, content: &[u8], follow_symlinks: bool) -> Result<()> {
+    let target = resolve_write_target(path, follow_symlinks).await?;
+
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Cannot write to {}: not a file path", target.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = target.with_file_name(temp_name);
+
+    let result = async {
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(content).await?;
+        file.sync_all().await?;
+        drop(file);
+        // Keep e.g. the executable bit of a file being replaced
+        if let Ok(metadata) = fs::metadata(&target).await {
+            fs::set_permissions(&temp_path, metadata.permissions()).await?;
+        }
+        fs::rename(&temp_path, &target).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(anyhow::anyhow!("Failed to write {}: {}", path.display(), e));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,99 @@
+use crate::utils::file_metadata::sha256_hex;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// How long to wait for another run to release a project's state lock
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cleared by `--no-lock`
+static LOCKING: AtomicBool = AtomicBool::new(true);
+
+/// Let state writes go ahead without the lock, for `--no-lock`
+pub fn disable_locking() {
+    LOCKING.store(false, Ordering::Relaxed);
+}
+
+/// Location of the state lock for the current working directory
+pub fn lock_path(history_dir: &Path) -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let key = &sha256_hex(current_dir.display().to_string().as_bytes())[..16];
+    history_dir.join(format!("lock-{}", key))
+}
+
+/// Exclusive hold on a project's snapshot history, patch journals and anonymization map.
+/// An OS advisory lock on the lock file, released on drop or whenever the process exits,
+/// so a run that is killed never leaves the project locked.
+#[derive(Debug)]
+pub struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    /// Lock the file at `path`, waiting up to `timeout` for another run to release it
+    pub async fn acquire(path: &Path, timeout: Duration) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock {}", path.display()))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let holder = fs::read_to_string(path).unwrap_or_default();
+                    return Err(anyhow::anyhow!(
+                        "Another catnip run (pid {}) holds the lock at {}; wait for it to finish or use --no-lock",
+                        holder.trim(),
+                        path.display()
+                    ));
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(anyhow::anyhow!("Failed to lock {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        // The pid is only for the error message of a run left waiting
+        let _ = file
+            .set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| writeln!(file, "{}", std::process::id()));
+        debug!("Acquired state lock: {}", path.display());
+        Ok(Self { _file: file })
+    }
+}
+
+/// Lock the current project's state for the writes that follow, until the guard is
+/// dropped. `None` with `--no-lock`, or when there is nowhere to put the lock.
+pub async fn lock_project_state() -> Result<Option<StateLock>> {
+    if !LOCKING.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    match crate::config::paths::history_dir() {
+        Ok(history_dir) => Ok(Some(
+            StateLock::acquire(&lock_path(&history_dir), LOCK_TIMEOUT).await?,
+        )),
+        Err(e) => {
+            warn!("Writing state without a lock: {}", e);
+            Ok(None)
+        }
+    }
+}
@@ -1,2 +1,8 @@
 pub mod clipboard;
+pub mod encryption;
 pub mod file_operations;
+pub mod lock;
+pub mod opener;
+pub mod remote;
+pub mod shell;
+pub mod vfs;
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+use tracing::{debug, info};
+
+fn is_html(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"))
+}
+
+fn platform_open_command() -> Vec<String> {
+    let command: &[&str] = if cfg!(target_os = "macos") {
+        &["open"]
+    } else if cfg!(target_os = "windows") {
+        // The empty argument is the window title `start` expects before the path
+        &["cmd", "/C", "start", ""]
+    } else {
+        &["xdg-open"]
+    };
+    command.iter().map(|s| s.to_string()).collect()
+}
+
+/// Pick the command used to open `path`: `$BROWSER` for HTML, `$EDITOR` for anything
+/// else, falling back to the platform default handler
+pub fn opener_command(path: &Path, editor: Option<&str>, browser: Option<&str>) -> Vec<String> {
+    let preferred = if is_html(path) { browser } else { editor };
+
+    let mut command = match preferred.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(custom) => custom.split_whitespace().map(str::to_string).collect(),
+        None => platform_open_command(),
+    };
+    command.push(path.display().to_string());
+    command
+}
+
+/// Open a file with the user's editor, browser or the platform default handler
+pub fn open_path(path: &Path) -> Result<()> {
+    let editor = std::env::var("EDITOR").ok();
+    let browser = std::env::var("BROWSER").ok();
+    let command = opener_command(path, editor.as_deref(), browser.as_deref());
+    debug!("Opening {} with {:?}", path.display(), command);
+
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "{} exited with {} while opening {}",
+            command[0],
+            status,
+            path.display()
+        ));
+    }
+
+    info!("Opened {}", path.display());
+    Ok(())
+}
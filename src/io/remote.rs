@@ -0,0 +1,93 @@
+use anyhow::{Context, Result, anyhow};
+use std::time::Duration;
+use tracing::debug;
+
+/// Largest patch document fetched from a URL
+pub const MAX_PATCH_BYTES: usize = 10 * 1024 * 1024;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether a `patch` argument names a remote document rather than a local file
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+/// Accept JSON and the plain text raw gist and paste services serve; anything else, such as
+/// an HTML login page, isn't a patch
+pub fn check_content_type(content_type: Option<&str>) -> Result<()> {
+    let Some(content_type) = content_type else {
+        return Ok(());
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime == "application/json"
+        || mime == "text/plain"
+        || mime == "application/octet-stream"
+        || mime.ends_with("+json")
+    {
+        Ok(())
+    } else {
+        Err(anyhow!("Expected a JSON document, got {}", content_type))
+    }
+}
+
+/// HTTPS-only client shared by everything catnip fetches or uploads
+pub fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .https_only(true)
+        .user_agent(concat!("catnip/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to set up HTTP client")
+}
+
+/// Fetch a text document over HTTPS, refusing anything larger than `max_bytes`
+pub async fn fetch_text(url: &str, max_bytes: usize) -> Result<String> {
+    if !url.starts_with("https://") {
+        return Err(anyhow!("Refusing to fetch {} over plain HTTP", url));
+    }
+
+    debug!("Fetching {}", url);
+    let mut response = http_client()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to fetch {}", url))?;
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    check_content_type(content_type)
+        .with_context(|| format!("Unexpected response from {}", url))?;
+
+    let too_large = || anyhow!("{} is larger than {} bytes", url, max_bytes);
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    // The declared length may be missing or wrong, so the limit is enforced while reading too
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("Failed to read {}", url))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(body).with_context(|| format!("{} is not valid UTF-8", url))
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result, anyhow};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+/// `command` run through the platform shell, so users can write pipelines and quoting as usual
+pub fn shell_command(command: &str) -> Command {
+    let (shell, flag) = if cfg!(target_os = "windows") {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let mut cmd = Command::new(shell);
+    cmd.arg(flag).arg(command);
+    cmd
+}
+
+/// Run `command` with `input` on stdin. With `capture`, its stdout is returned and stderr goes
+/// into the error on failure; otherwise both go to the terminal and nothing is returned.
+pub async fn run_with_stdin(command: &str, input: &[u8], capture: bool) -> Result<Vec<u8>> {
+    run_shell(shell_command(command), command, input, capture).await
+}
+
+/// Like [`run_with_stdin`], for a shell command already set up with extra environment
+pub async fn run_shell(
+    mut cmd: Command,
+    command: &str,
+    input: &[u8],
+    capture: bool,
+) -> Result<Vec<u8>> {
+    debug!("Running: {}", command);
+    let output = if capture {
+        Stdio::piped
+    } else {
+        Stdio::inherit
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(output())
+        .stderr(output())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+
+    // Feed stdin concurrently with reading stdout, so large inputs can't deadlock on full pipes
+    let mut stdin = child.stdin.take().context("Command stdin unavailable")?;
+    let input = input.to_vec();
+    let writer = tokio::spawn(async move {
+        // A command that ignores its input may close stdin early, which isn't an error
+        let _ = stdin.write_all(&input).await;
+    });
+    let output = child.wait_with_output().await?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => anyhow!("{} exited with {}", command, output.status),
+            stderr => anyhow!("{} exited with {}: {}", command, output.status, stderr),
+        });
+    }
+    Ok(output.stdout)
+}
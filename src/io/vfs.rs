@@ -0,0 +1,422 @@
+use anyhow::Result;
+use ignore::{WalkBuilder, WalkState};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::config::patterns::CATNIPIGNORE_FILE;
+use crate::io::file_operations::write_file_safe;
+use crate::utils::filetype::decode_text;
+
+#[derive(Clone, Debug)]
+pub struct VfsMetadata {
+    pub len: u64,
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub modified: Option<SystemTime>,
+}
+
+/// Files found by `Vfs::walk`, plus the entries that could not be read
+#[derive(Clone, Debug, Default)]
+pub struct Walk {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+/// Decides whether a walk skips a directory; owned, so walks can run it on blocking threads
+pub type Prune = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+/// Filesystem operations used by the collection, rendering and patch pipelines
+pub trait Vfs: Send + Sync {
+    fn read(&self, path: &Path) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    fn write(&self, path: &Path, content: &[u8]) -> impl Future<Output = Result<()>> + Send;
+
+    fn remove_file(&self, path: &Path) -> impl Future<Output = Result<()>> + Send;
+
+    fn metadata(&self, path: &Path) -> impl Future<Output = Result<VfsMetadata>> + Send;
+
+    fn create_dir_all(&self, path: &Path) -> impl Future<Output = Result<()>> + Send;
+
+    /// List all files below `root`, not descending into directories for which `prune` returns
+    /// true. With `ignore_files`, a walk may also leave out what ignore files ignore; the
+    /// collector applies the same rules to whatever it lists.
+    fn walk(
+        &self,
+        root: &Path,
+        prune: Prune,
+        ignore_files: bool,
+    ) -> impl Future<Output = Result<Walk>> + Send;
+
+    fn read_to_string(&self, path: &Path) -> impl Future<Output = Result<String>> + Send {
+        async move {
+            let content = self.read(path).await?;
+            String::from_utf8(content).map_err(|e| {
+                anyhow::anyhow!("Failed to read {}: invalid UTF-8: {}", path.display(), e)
+            })
+        }
+    }
+
+    /// Content of a text file, decoded from UTF-8 or UTF-16 as sniffing tells
+    fn read_text(&self, path: &Path) -> impl Future<Output = Result<String>> + Send {
+        async move {
+            let content = self.read(path).await?;
+            decode_text(content)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+        }
+    }
+
+    fn exists(&self, path: &Path) -> impl Future<Output = bool> + Send {
+        async move { self.metadata(path).await.is_ok() }
+    }
+}
+
+/// The real filesystem, accessed through tokio
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioFs;
+
+impl Vfs for TokioFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        tokio::fs::read(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+    }
+
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        // Symlinks are only ever written through after `resolve_write_target` approved them
+        write_file_safe(path, content, true).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        tokio::fs::remove_file(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to remove {}: {}", path.display(), e))
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to stat {}: {}", path.display(), e))?;
+
+        Ok(VfsMetadata {
+            len: metadata.len(),
+            is_file: metadata.is_file(),
+            is_dir: metadata.is_dir(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))
+    }
+
+    async fn walk(&self, root: &Path, prune: Prune, ignore_files: bool) -> Result<Walk> {
+        let walk_root = root.to_path_buf();
+
+        // The walker blocks until every thread is done, so keep it off the async workers
+        tokio::task::spawn_blocking(move || {
+            let walk = Mutex::new(Walk::default());
+
+            // Hidden files are the collector's business, so only the ignore files filter. Their
+            // rules also apply outside a git repository, as the collector's do.
+            let mut builder = WalkBuilder::new(&walk_root);
+            builder
+                .standard_filters(false)
+                .git_ignore(ignore_files)
+                .git_exclude(ignore_files)
+                .ignore(ignore_files)
+                .parents(ignore_files)
+                .require_git(false);
+            if ignore_files {
+                builder.add_custom_ignore_filename(".rgignore");
+            }
+            builder
+                .add_custom_ignore_filename(CATNIPIGNORE_FILE)
+                .build_parallel()
+                .run(|| {
+                    Box::new(|entry| {
+                        let entry = match entry {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                let path = walk_error_path(&e).unwrap_or(&walk_root).to_path_buf();
+                                walk.lock().unwrap().errors.push((path, e.to_string()));
+                                return WalkState::Continue;
+                            }
+                        };
+
+                        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                        if is_dir && prune(entry.path()) {
+                            WalkState::Skip
+                        } else {
+                            if entry.file_type().is_some_and(|t| t.is_file()) {
+                                walk.lock().unwrap().files.push(entry.into_path());
+                            }
+                            WalkState::Continue
+                        }
+                    })
+                });
+
+            // Threads finish in any order, so sort for stable output
+            let mut walk = walk.into_inner().unwrap();
+            walk.files.sort();
+            walk.errors.sort();
+            walk
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to walk {}: {}", root.display(), e))
+    }
+}
+
+/// Path an error from the walker is about, if it names one
+fn walk_error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_path(err)
+        }
+        ignore::Error::Loop { child, .. } => Some(child),
+        _ => None,
+    }
+}
+
+/// In-memory filesystem for hermetic tests; directories exist implicitly
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_files<P: AsRef<Path>, C: AsRef<[u8]>>(
+        files: impl IntoIterator<Item = (P, C)>,
+    ) -> Self {
+        let fs = Self::new();
+        {
+            let mut map = fs.files.lock().unwrap();
+            for (path, content) in files {
+                map.insert(path.as_ref().to_path_buf(), content.as_ref().to_vec());
+            }
+        }
+        fs
+    }
+
+    fn not_found(path: &Path) -> anyhow::Error {
+        anyhow::anyhow!("No such file or directory: {}", path.display())
+    }
+}
+
+impl Vfs for MemoryFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Self::not_found(path))
+    }
+
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), content.to_vec());
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| Self::not_found(path))
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        let files = self.files.lock().unwrap();
+
+        if let Some(content) = files.get(path) {
+            return Ok(VfsMetadata {
+                len: content.len() as u64,
+                is_file: true,
+                is_dir: false,
+                modified: None,
+            });
+        }
+
+        if files.keys().any(|file| file.starts_with(path)) {
+            return Ok(VfsMetadata {
+                len: 0,
+                is_file: false,
+                is_dir: true,
+                modified: None,
+            });
+        }
+
+        Err(Self::not_found(path))
+    }
+
+    async fn create_dir_all(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Ignore files are left to the collector
+    async fn walk(&self, root: &Path, prune: Prune, _ignore_files: bool) -> Result<Walk> {
+        let files = self.files.lock().unwrap();
+
+        let files = files
+            .keys()
+            .filter(|file| file.starts_with(root) && file.as_path() != root)
+            .filter(|file| {
+                // Outermost first, like a real walk that never visits a pruned directory's children
+                let dirs: Vec<_> = file
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| dir.starts_with(root))
+                    .collect();
+                dirs.iter().rev().all(|dir| !prune(dir))
+            })
+            .cloned()
+            .collect();
+        Ok(Walk {
+            files,
+            errors: Vec::new(),
+        })
+    }
+}
+
+/// Serves a fixed set of in-memory files on top of another filesystem
+#[derive(Debug)]
+pub struct OverlayFs<'a, V: Vfs> {
+    base: &'a V,
+    overlay: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl<'a, V: Vfs> OverlayFs<'a, V> {
+    pub fn new(base: &'a V, overlay: BTreeMap<PathBuf, Vec<u8>>) -> Self {
+        Self { base, overlay }
+    }
+}
+
+impl<V: Vfs> Vfs for OverlayFs<'_, V> {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.overlay.get(path) {
+            Some(content) => Ok(content.clone()),
+            None => self.base.read(path).await,
+        }
+    }
+
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.base.write(path, content).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.base.remove_file(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        match self.overlay.get(path) {
+            Some(content) => Ok(VfsMetadata {
+                len: content.len() as u64,
+                is_file: true,
+                is_dir: false,
+                modified: None,
+            }),
+            None => self.base.metadata(path).await,
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.base.create_dir_all(path).await
+    }
+
+    async fn walk(&self, root: &Path, prune: Prune, ignore_files: bool) -> Result<Walk> {
+        self.base.walk(root, prune, ignore_files).await
+    }
+}
+
+/// Resolves relative paths against `root` instead of the current directory, for working in
+/// another checkout of the same project. Absolute paths and paths climbing out with `..` are
+/// refused, as they would reach outside that checkout.
+#[derive(Debug)]
+pub struct RootedFs<'a, V: Vfs> {
+    base: &'a V,
+    root: PathBuf,
+}
+
+impl<'a, V: Vfs> RootedFs<'a, V> {
+    pub fn new(base: &'a V, root: impl Into<PathBuf>) -> Self {
+        Self {
+            base,
+            root: root.into(),
+        }
+    }
+
+    fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        let inside = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            return Err(anyhow::anyhow!(
+                "{} is outside {}",
+                path.display(),
+                self.root.display()
+            ));
+        }
+        Ok(self.root.join(path))
+    }
+}
+
+impl<V: Vfs> Vfs for RootedFs<'_, V> {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.base.read(&self.resolve(path)?).await
+    }
+
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.base.write(&self.resolve(path)?, content).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.base.remove_file(&self.resolve(path)?).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        self.base.metadata(&self.resolve(path)?).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.base.create_dir_all(&self.resolve(path)?).await
+    }
+
+    async fn walk(&self, root: &Path, prune: Prune, ignore_files: bool) -> Result<Walk> {
+        let unroot = |path: PathBuf| match path.strip_prefix(&self.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        };
+
+        let base_root = self.root.clone();
+        let walk = self
+            .base
+            .walk(
+                &self.resolve(root)?,
+                Arc::new(move |dir| prune(dir.strip_prefix(&base_root).unwrap_or(dir))),
+                ignore_files,
+            )
+            .await?;
+        Ok(Walk {
+            files: walk.files.into_iter().map(unroot).collect(),
+            errors: walk
+                .errors
+                .into_iter()
+                .map(|(path, error)| (unroot(path), error))
+                .collect(),
+        })
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod config;
 pub mod core;
+pub mod integrations;
 pub mod io;
 pub mod utils;
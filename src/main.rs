@@ -1,49 +1,130 @@
 use anyhow::Result;
-use catnip::cli::commands::{cat, patch};
+use catnip::cli::commands::patch::{OnError, PatchOptions};
+use catnip::cli::commands::{
+    bench, cat, changed, index, inspect, patch, schema, share, stats, undo,
+};
+use catnip::cli::guards::GuardFailure;
 use catnip::cli::{Args, Commands, Parser};
+use catnip::io::clipboard::SystemClipboard;
+use catnip::io::lock;
+use catnip::io::vfs::TokioFs;
+use catnip::utils::timings::Timings;
+use tracing_subscriber::prelude::*;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
+    let args = Args::parse();
+
+    // Stage spans are trace-level, so timings get their own layer unaffected by RUST_LOG
+    let timings = args.timings.then(Timings::new);
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(env_filter))
+        .with(timings.as_ref().map(Timings::layer))
         .init();
 
-    let args = Args::parse();
+    let clipboard = SystemClipboard;
 
-    match args.command {
-        Commands::Cat {
-            paths,
-            output,
-            no_copy,
-            exclude,
-            include,
-            ignore_comments,
-            ignore_docstrings,
-            prompt,
-            max_size_mb,
-        } => {
-            cat::execute(
-                paths,
-                output,
-                no_copy,
-                exclude,
-                include,
-                ignore_comments,
-                ignore_docstrings,
-                prompt,
-                max_size_mb,
-            )
-            .await?;
+    if args.no_lock {
+        lock::disable_locking();
+    }
+
+    let result = run(args.command, &clipboard).await;
+
+    if let Some(timings) = &timings {
+        println!("\n⏱️  Timings:\n{}", timings.format_table());
+    }
+
+    // A tripped --fail-* guard exits with its own code
+    if let Err(e) = &result
+        && let Some(failure) = e.downcast_ref::<GuardFailure>()
+    {
+        eprintln!("Error: {}", failure);
+        std::process::exit(failure.guard.exit_code());
+    }
+    result
+}
+
+async fn run(command: Commands, clipboard: &SystemClipboard) -> Result<()> {
+    match command {
+        Commands::Cat(cat_args) => {
+            cat::execute(clipboard, &TokioFs, *cat_args).await?;
+        }
+        Commands::Changed(collect_args) => {
+            changed::execute(&TokioFs, collect_args).await?;
+        }
+        Commands::Index(collect_args) => {
+            index::execute(&TokioFs, collect_args).await?;
+        }
+        Commands::Stats(collect_args) => {
+            stats::execute(&TokioFs, collect_args).await?;
+        }
+        Commands::Bench { path, iterations } => {
+            bench::execute(&TokioFs, path, iterations).await?;
         }
         Commands::Patch {
             json_file,
             dry_run,
             backup,
+            force,
+            emit_script,
+            changelog,
+            lint,
+            fail_fast,
+            atomic,
+            continue_on_error: _,
+            porcelain,
+            onto,
+            fuzzy,
+            interactive,
+            identity,
+            deanonymize,
+            keep_placeholders,
+        } => {
+            let options = PatchOptions {
+                dry_run,
+                backup,
+                force,
+                emit_script,
+                changelog,
+                lint,
+                on_error: if atomic {
+                    OnError::Atomic
+                } else if fail_fast {
+                    OnError::FailFast
+                } else {
+                    OnError::Continue
+                },
+                porcelain,
+                onto,
+                fuzzy: fuzzy.map(f64::from),
+                interactive,
+                identity,
+                deanonymize,
+                keep_placeholders,
+            };
+            patch::execute(clipboard, &TokioFs, json_file, &options).await?;
+        }
+        Commands::Undo { dry_run, force } => {
+            undo::execute(&TokioFs, dry_run, force).await?;
+        }
+        Commands::Schema { patch } => {
+            schema::execute(patch)?;
+        }
+        Commands::Share {
+            file,
+            provider,
+            no_copy,
+        } => {
+            share::execute(clipboard, &TokioFs, file, provider, no_copy).await?;
+        }
+        Commands::Inspect {
+            file,
+            identity,
+            output,
         } => {
-            patch::execute(json_file, dry_run, backup).await?;
+            inspect::execute(&TokioFs, file, identity, output).await?;
         }
     }
 
@@ -0,0 +1,65 @@
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// Length of the sha256 hex prefix shown in rendered metadata
+const HASH_PREFIX_LEN: usize = 12;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FileMetadata {
+    pub size: u64,
+    pub lines: usize,
+    #[serde(serialize_with = "serialize_rfc3339")]
+    pub modified: Option<SystemTime>,
+    pub sha256: String,
+}
+
+impl FileMetadata {
+    pub fn compute(content: &str, modified: Option<SystemTime>) -> Self {
+        Self {
+            size: content.len() as u64,
+            lines: content.lines().count(),
+            modified,
+            sha256: sha256_hex(content.as_bytes()),
+        }
+    }
+
+    pub fn sha256_prefix(&self) -> &str {
+        &self.sha256[..HASH_PREFIX_LEN]
+    }
+
+    /// One-line summary rendered under each file heading
+    pub fn summary(&self) -> String {
+        let modified = self
+            .modified
+            .map(|time| humantime::format_rfc3339_seconds(time).to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        format!(
+            "{} bytes · {} lines · modified {} · sha256 {}",
+            self.size,
+            self.lines,
+            modified,
+            self.sha256_prefix()
+        )
+    }
+}
+
+pub fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn serialize_rfc3339<S: Serializer>(
+    modified: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match modified {
+        Some(time) => {
+            serializer.serialize_some(&humantime::format_rfc3339_seconds(*time).to_string())
+        }
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+
+/// Bytes looked at to tell text from binary
+const SNIFF_LEN: usize = 1024;
+
+/// How a file's bytes encode its text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// What a file's content turned out to be
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Text(Encoding),
+    /// Binary, with the MIME type its magic number gave away, if any
+    Binary(Option<&'static str>),
+}
+
+/// Leading bytes of common binary formats. Some, like PDF, start out as ASCII and would
+/// otherwise pass for text.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\0asm", "application/wasm"),
+    (b"\x7fELF", "application/x-elf"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"SQLite format 3\0", "application/vnd.sqlite3"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// MIME type given away by the first bytes of `content`, for the formats in `MAGIC`
+pub fn magic_mime(content: &[u8]) -> Option<&'static str> {
+    MAGIC
+        .iter()
+        .find(|(magic, _)| content.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+/// Encoding of UTF-16 without a byte order mark, told by ASCII characters leaving every
+/// other byte zero
+fn unmarked_utf16(sample: &[u8]) -> Option<Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+
+    let zero_at = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|pair| pair[offset] == 0 && pair[1 - offset] != 0)
+            .count()
+    };
+    // Non-ASCII characters have both bytes set, so most rather than all pairs must fit
+    let mostly = |count: usize| count * 10 >= pairs * 9;
+    if mostly(zero_at(1)) {
+        Some(Encoding::Utf16Le)
+    } else if mostly(zero_at(0)) {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Tell text from binary by the content's first bytes: a UTF-16 byte order mark or
+/// pattern of zeros means text, a known magic number, other NUL bytes or mostly control
+/// characters mean binary
+pub fn sniff(content: &[u8]) -> FileType {
+    if content.starts_with(b"\xff\xfe") {
+        return FileType::Text(Encoding::Utf16Le);
+    }
+    if content.starts_with(b"\xfe\xff") {
+        return FileType::Text(Encoding::Utf16Be);
+    }
+    if let Some(mime) = magic_mime(content) {
+        return FileType::Binary(Some(mime));
+    }
+
+    let sample = &content[..content.len().min(SNIFF_LEN)];
+    if sample.contains(&0) {
+        return match unmarked_utf16(sample) {
+            Some(encoding) => FileType::Text(encoding),
+            None => FileType::Binary(None),
+        };
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    if control * 10 > sample.len() * 3 {
+        FileType::Binary(None)
+    } else {
+        FileType::Text(Encoding::Utf8)
+    }
+}
+
+/// Whether `content` is binary rather than text, as `sniff` tells
+pub fn is_binary(content: &[u8]) -> bool {
+    matches!(sniff(content), FileType::Binary(_))
+}
+
+/// `content` as text, decoding UTF-16 without its byte order mark. Anything else is read
+/// as UTF-8, since `.gitattributes` may declare text what looks binary.
+pub fn decode_text(content: Vec<u8>) -> Result<String> {
+    let big_endian = match sniff(&content) {
+        FileType::Text(Encoding::Utf16Le) => false,
+        FileType::Text(Encoding::Utf16Be) => true,
+        FileType::Text(Encoding::Utf8) | FileType::Binary(_) => {
+            return String::from_utf8(content).map_err(|e| anyhow::anyhow!("invalid UTF-8: {}", e));
+        }
+    };
+
+    let units = content.chunks_exact(2).map(|pair| {
+        let pair = [pair[0], pair[1]];
+        if big_endian {
+            u16::from_be_bytes(pair)
+        } else {
+            u16::from_le_bytes(pair)
+        }
+    });
+    let text = char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| anyhow::anyhow!("invalid UTF-16: {}", e))?;
+    Ok(text
+        .strip_prefix('\u{feff}')
+        .map(str::to_string)
+        .unwrap_or(text))
+}
@@ -0,0 +1,69 @@
+const CODE_STYLE: &str = "font-family: monospace; background: #f6f8fa; padding: 8px; \
+                          border-radius: 4px; white-space: pre";
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Level and text of an ATX heading line, `# ` through `###### `
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&ch| ch == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+/// Convert catnip's markdown output (headings of any level, fenced code, emphasis lines) to
+/// HTML that keeps code formatting, though not syntax colors, when pasted into rich editors
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::with_capacity(markdown.len() * 2);
+    let mut in_code = false;
+
+    for line in markdown.lines() {
+        if let Some(language) = line.strip_prefix("```") {
+            if in_code {
+                html.push_str("</code></pre>\n");
+            } else if language.is_empty() {
+                html.push_str(&format!("<pre style=\"{}\"><code>", CODE_STYLE));
+            } else {
+                html.push_str(&format!(
+                    "<pre style=\"{}\"><code class=\"language-{}\">",
+                    CODE_STYLE,
+                    escape_html(language)
+                ));
+            }
+            in_code = !in_code;
+            continue;
+        }
+
+        if in_code {
+            html.push_str(&escape_html(line));
+            html.push('\n');
+        } else if let Some((level, heading)) = heading(line) {
+            html.push_str(&format!("<h{level}>{}</h{level}>\n", escape_html(heading)));
+        } else if line.len() > 2 && line.starts_with('*') && line.ends_with('*') {
+            html.push_str(&format!(
+                "<p><em>{}</em></p>\n",
+                escape_html(&line[1..line.len() - 1])
+            ));
+        } else if !line.trim().is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(line)));
+        }
+    }
+
+    if in_code {
+        html.push_str("</code></pre>\n");
+    }
+
+    html
+}
@@ -62,3 +62,21 @@ pub fn get_language_from_extension(path: &Path) -> &'static str {
         }
     }
 }
+
+/// Broad kind of file a language identifier belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LanguageCategory {
+    Code,
+    Config,
+    Docs,
+}
+
+pub fn get_language_category(language: &str) -> LanguageCategory {
+    match language {
+        "json" | "yaml" | "toml" | "xml" | "hcl" | "dockerfile" | "makefile" | "cmake" => {
+            LanguageCategory::Config
+        }
+        "markdown" | "latex" | "text" => LanguageCategory::Docs,
+        _ => LanguageCategory::Code,
+    }
+}
@@ -1,2 +1,8 @@
+pub mod file_metadata;
+pub mod filetype;
+pub mod html;
 pub mod language_detection;
+pub mod path_encoding;
 pub mod text_processing;
+pub mod timings;
+pub mod token_counter;
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+/// Device names Windows reserves regardless of extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows does not allow in file names
+const WINDOWS_RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+fn is_windows_reserved_name(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or_default();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem))
+}
+
+fn push_escaped(out: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        out.push_str(&format!("%{:02X}", byte));
+    }
+}
+
+#[cfg(unix)]
+fn component_bytes(component: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    component.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn component_bytes(component: &OsStr) -> Vec<u8> {
+    component.to_string_lossy().into_owned().into_bytes()
+}
+
+fn encode_component(component: &OsStr, out: &mut String) {
+    let bytes = component_bytes(component);
+    let mut rest = bytes.as_slice();
+
+    // A reserved device name is escaped by its first character, keeping the rest readable
+    if std::str::from_utf8(&bytes).is_ok_and(is_windows_reserved_name)
+        && let Some((first, tail)) = rest.split_first()
+    {
+        push_escaped(out, &[*first]);
+        rest = tail;
+    }
+
+    // Windows strips a trailing dot or space, so it has to be escaped too
+    let trailing = match rest.last() {
+        Some(&byte @ (b'.' | b' ')) => {
+            rest = &rest[..rest.len() - 1];
+            Some(byte)
+        }
+        _ => None,
+    };
+
+    for chunk in rest.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            if ch == '%' || ch.is_control() || WINDOWS_RESERVED_CHARS.contains(&ch) {
+                let mut buf = [0; 4];
+                push_escaped(out, ch.encode_utf8(&mut buf).as_bytes());
+            } else {
+                out.push(ch);
+            }
+        }
+        push_escaped(out, chunk.invalid());
+    }
+
+    if let Some(byte) = trailing {
+        push_escaped(out, &[byte]);
+    }
+}
+
+/// Drop Windows' extended-length `\\?\` prefix: `\\?\C:\x` becomes `C:\x` and
+/// `\\?\UNC\server\share` becomes `\\server\share`, so canonicalized and plain paths compare
+/// equal. The standard library adds the prefix back by itself when a long path is opened.
+pub fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path.to_path_buf();
+    };
+
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{}", unc));
+    }
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => PathBuf::from(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// `path` relative to `base`, ignoring extended-length prefixes on either side; paths
+/// outside `base` are returned unchanged
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let normalized = strip_verbatim_prefix(path);
+    match normalized.strip_prefix(strip_verbatim_prefix(base)) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => normalized,
+    }
+}
+
+/// Render a path as a `/`-separated string that survives markdown headings and patch
+/// round-trips: invalid UTF-8, control characters, `%` and characters or names Windows
+/// reserves are percent-encoded, so `decode_path` gets the exact original back
+pub fn encode_path(path: &Path) -> String {
+    let mut out = String::new();
+
+    for component in path.components() {
+        if !out.is_empty() && !out.ends_with('/') && component != Component::RootDir {
+            out.push('/');
+        }
+        match component {
+            Component::RootDir => out.push('/'),
+            Component::CurDir => out.push('.'),
+            Component::ParentDir => out.push_str(".."),
+            Component::Prefix(prefix) => {
+                let prefix = strip_verbatim_prefix(Path::new(prefix.as_os_str()));
+                out.push_str(&prefix.to_string_lossy().replace('\\', "/"));
+            }
+            Component::Normal(name) => encode_component(name, &mut out),
+        }
+    }
+
+    out
+}
+
+/// Reverse `encode_path`, turning `%XX` escapes back into the original bytes
+pub fn decode_path(encoded: &str) -> Result<PathBuf> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut input = encoded.as_bytes();
+
+    while let Some((&byte, rest)) = input.split_first() {
+        if byte == b'%'
+            && let Some(hex) = rest.get(..2)
+            && let Ok(hex) = std::str::from_utf8(hex)
+            && let Ok(value) = u8::from_str_radix(hex, 16)
+        {
+            bytes.push(value);
+            input = &rest[2..];
+            continue;
+        }
+        bytes.push(byte);
+        input = rest;
+    }
+
+    bytes_to_path(bytes, encoded)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>, _encoded: &str) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>, encoded: &str) -> Result<PathBuf> {
+    String::from_utf8(bytes).map(PathBuf::from).map_err(|_| {
+        anyhow::anyhow!(
+            "Path '{}' decodes to invalid UTF-8, which this platform can't represent",
+            encoded
+        )
+    })
+}
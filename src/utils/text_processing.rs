@@ -1,4 +1,6 @@
 use regex::Regex;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 
 pub fn remove_comments_and_docstrings(
     content: &str,
@@ -15,14 +17,14 @@ pub fn remove_comments_and_docstrings(
     if ignore_comments || ignore_docstrings {
         match language {
             "rust" | "javascript" | "typescript" | "java" | "kotlin" | "scala" | "c" | "cpp"
-            | "csharp" | "go" | "swift" | "dart" => {
-                if ignore_comments {
-                    let re = Regex::new(r"//.*$").unwrap();
-                    result = re.replace_all(&result, "").to_string();
+            | "csharp" | "go" | "swift" | "dart"
+                if ignore_comments =>
+            {
+                let re = Regex::new(r"//.*$").unwrap();
+                result = re.replace_all(&result, "").to_string();
 
-                    let re = Regex::new(r"/\*.*?\*/").unwrap();
-                    result = re.replace_all(&result, "").to_string();
-                }
+                let re = Regex::new(r"/\*.*?\*/").unwrap();
+                result = re.replace_all(&result, "").to_string();
             }
             "python" => {
                 if ignore_comments {
@@ -36,11 +38,9 @@ pub fn remove_comments_and_docstrings(
                     result = re.replace_all(&result, "").to_string();
                 }
             }
-            "ruby" | "bash" | "sh" | "zsh" | "fish" => {
-                if ignore_comments {
-                    let re = Regex::new(r"#.*$").unwrap();
-                    result = re.replace_all(&result, "").to_string();
-                }
+            "ruby" | "bash" | "sh" | "zsh" | "fish" if ignore_comments => {
+                let re = Regex::new(r"#.*$").unwrap();
+                result = re.replace_all(&result, "").to_string();
             }
             _ => {}
         }
@@ -52,3 +52,83 @@ pub fn remove_comments_and_docstrings(
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+/// Reduce source code to its declarations (functions, types, classes), dropping bodies
+pub fn extract_outline(content: &str, language: &str) -> String {
+    let pattern = match language {
+        "rust" => {
+            r"^\s*(pub(\([^)]*\))?\s+)?((async|const|unsafe|extern)\s+)*(fn|struct|enum|trait|impl|mod|type)\b"
+        }
+        "python" => r"^\s*(async\s+)?(def|class)\s",
+        "go" => r"^(func|type)\s",
+        "javascript" | "typescript" | "jsx" => {
+            r"^\s*(export\s+)?(default\s+)?(async\s+)?(function|class|interface|type)\b"
+        }
+        "java" | "kotlin" | "scala" | "csharp" | "swift" | "dart" => {
+            r"^\s*((public|private|protected|internal|static|final|abstract|open|override|suspend)\s+)*(class|interface|enum|fun|func|def|object|struct|void|[A-Z][\w<>\[\]]*\s+\w+\s*\()"
+        }
+        "ruby" => r"^\s*(def|class|module)\s",
+        _ => return content.to_string(),
+    };
+
+    let re = Regex::new(pattern).unwrap();
+    content
+        .lines()
+        .filter(|line| re.is_match(line))
+        .map(|line| line.trim_end().trim_end_matches('{').trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Shrink source code without changing its meaning: drop blank lines, full-line
+/// comments and trailing whitespace, keeping indentation
+pub fn minify(content: &str, language: &str) -> String {
+    let comment_prefix = match language {
+        "rust" | "javascript" | "typescript" | "jsx" | "java" | "kotlin" | "scala" | "c"
+        | "cpp" | "csharp" | "go" | "swift" | "dart" => Some("//"),
+        "python" | "ruby" | "bash" | "sh" | "zsh" | "fish" | "toml" | "yaml" => Some("#"),
+        _ => None,
+    };
+
+    content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty()
+                && !comment_prefix
+                    .is_some_and(|prefix| trimmed.starts_with(prefix) && !trimmed.starts_with("#!"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// First JSON object in `text` that deserializes as `T`, skipping any prose, code fences
+/// or other JSON around it
+pub fn find_json_object<T: DeserializeOwned>(text: &str) -> Option<T> {
+    text.match_indices('{').find_map(|(start, _)| {
+        serde_json::Deserializer::from_str(&text[start..])
+            .into_iter::<T>()
+            .next()?
+            .ok()
+    })
+}
+
+/// Count lines added and removed between two versions, ignoring line order
+pub fn count_line_changes(old: &str, new: &str) -> (usize, usize) {
+    let mut remaining: HashMap<&str, usize> = HashMap::new();
+    for line in new.lines() {
+        *remaining.entry(line).or_default() += 1;
+    }
+
+    let mut removed = 0;
+    for line in old.lines() {
+        match remaining.get_mut(line) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => removed += 1,
+        }
+    }
+
+    let added = remaining.values().sum();
+    (added, removed)
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Subscriber;
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Pipeline stages reported by `--timings`, in pipeline order
+pub const TIMED_SPANS: [&str; 6] = ["walk", "filter", "read", "strip", "render", "clipboard"];
+
+/// Total wall time and number of closed spans with one name
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SpanTiming {
+    pub count: usize,
+    pub total: Duration,
+}
+
+/// Span durations aggregated by span name
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    spans: Arc<Mutex<BTreeMap<&'static str, SpanTiming>>>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Layer feeding closed spans into these timings
+    pub fn layer(&self) -> TimingLayer {
+        TimingLayer {
+            timings: self.clone(),
+        }
+    }
+
+    pub fn record(&self, name: &'static str, elapsed: Duration) {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let timing = spans.entry(name).or_default();
+        timing.count += 1;
+        timing.total += elapsed;
+    }
+
+    pub fn get(&self, name: &str) -> Option<SpanTiming> {
+        let spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        spans.get(name).copied()
+    }
+
+    /// Table of the pipeline stages that ran, one row per stage
+    pub fn format_table(&self) -> String {
+        let mut table = format!("{:<10} {:>7} {:>12}\n", "stage", "spans", "total");
+        for name in TIMED_SPANS {
+            if let Some(timing) = self.get(name) {
+                table.push_str(&format!(
+                    "{:<10} {:>7} {:>12}\n",
+                    name,
+                    timing.count,
+                    format!("{:.2?}", timing.total)
+                ));
+            }
+        }
+        table
+    }
+}
+
+/// When a span was created, stored in its extensions
+struct Started(Instant);
+
+/// Tracing layer measuring each span from creation to close
+pub struct TimingLayer {
+    timings: Timings,
+}
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id)
+            && let Some(Started(started)) = span.extensions().get::<Started>()
+        {
+            self.timings.record(span.name(), started.elapsed());
+        }
+    }
+}
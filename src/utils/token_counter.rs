@@ -0,0 +1,32 @@
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+use tracing::warn;
+
+static ENCODER: OnceLock<Option<CoreBPE>> = OnceLock::new();
+
+fn encoder() -> Option<&'static CoreBPE> {
+    ENCODER
+        .get_or_init(|| match tiktoken_rs::o200k_base() {
+            Ok(bpe) => Some(bpe),
+            Err(e) => {
+                warn!("Falling back to approximate token counts: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Count tokens with the `o200k_base` encoding, a close enough proxy for current models
+pub fn count_tokens(text: &str) -> usize {
+    match encoder() {
+        Some(bpe) => bpe.encode_ordinary(text).len(),
+        // Roughly four characters per token for English text and code
+        None => text.chars().count().div_ceil(4),
+    }
+}
+
+/// Rough token count of a file from its size alone, for previews where reading every
+/// file would be too slow
+pub fn estimate_tokens(bytes: u64) -> usize {
+    (bytes as usize).div_ceil(4)
+}
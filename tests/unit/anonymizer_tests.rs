@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+use catnip::cli::commands::patch::{PatchOptions, UpdateRequest, execute};
+use catnip::config::settings::AnonymizeSettings;
+use catnip::core::anonymizer::{Anonymization, AnonymizeMap, Anonymizer};
+use catnip::core::content_processor::{OutputFormat, RenderOptions, concatenate_files_with};
+use catnip::core::file_collector::{CollectOptions, CollectedFile};
+use catnip::core::manifest::Manifest;
+use catnip::core::snapshot::Snapshot;
+use catnip::io::clipboard::MemoryClipboard;
+use catnip::io::vfs::{MemoryFs, Vfs};
+
+fn settings() -> AnonymizeSettings {
+    AnonymizeSettings {
+        emails: true,
+        hosts: vec!["corp.internal".to_string()],
+        words: BTreeMap::from([
+            ("user".to_string(), vec!["alice".to_string()]),
+            ("codename".to_string(), vec!["falcon".to_string()]),
+        ]),
+        patterns: BTreeMap::from([("ticket".to_string(), r"OPS-\d+".to_string())]),
+    }
+}
+
+#[test]
+fn test_anonymize_consistently_and_restore() {
+    let anonymizer = Anonymizer::new(&settings()).unwrap().unwrap();
+    let mut map = AnonymizeMap::default();
+
+    let source = [
+        "// Owner: alice (alice@corp.internal), see OPS-42",
+        "const API: &str = \"https://git.corp.internal/falcon\";",
+        "const MIRROR: &str = \"https://corp.internal/falcon\";",
+        "// falconry and malice are other words",
+    ]
+    .join("\n");
+    let (anonymized, replaced) = anonymizer.anonymize(&source, &mut map);
+    assert_eq!(
+        anonymized,
+        [
+            "// Owner: USER_1 (user1@example.invalid), see TICKET_1",
+            "const API: &str = \"https://host1.example.invalid/CODENAME_1\";",
+            "const MIRROR: &str = \"https://host2.example.invalid/CODENAME_1\";",
+            "// falconry and malice are other words",
+        ]
+        .join("\n")
+    );
+    assert_eq!(replaced, 7);
+
+    // Values keep their placeholders in later runs
+    let (again, _) = anonymizer.anonymize("ping alice@corp.internal", &mut map);
+    assert_eq!(again, "ping user1@example.invalid");
+    assert_eq!(map.restore(&anonymized).0, source);
+
+    assert!(
+        Anonymizer::new(&AnonymizeSettings::default())
+            .unwrap()
+            .is_none()
+    );
+    let invalid = AnonymizeSettings {
+        patterns: BTreeMap::from([("bad".to_string(), "(".to_string())]),
+        ..AnonymizeSettings::default()
+    };
+    assert!(Anonymizer::new(&invalid).is_err());
+}
+
+#[test]
+fn test_placeholders_are_not_anonymized_again() {
+    let anonymizer = Anonymizer::new(&settings()).unwrap().unwrap();
+    let mut map = AnonymizeMap::default();
+    let (once, _) = anonymizer.anonymize("mail alice@corp.internal", &mut map);
+    let (twice, replaced) = anonymizer.anonymize(&once, &mut map);
+    assert_eq!(twice, once);
+    assert_eq!(replaced, 0);
+    assert_eq!(map.placeholders.len(), 1);
+}
+
+#[tokio::test]
+async fn test_anonymize_rendered_paths_and_manifest() {
+    let vfs = MemoryFs::with_files([("src/falcon/api.rs", "fn api() {}\n")]);
+    let paths = vec![PathBuf::from("src/falcon/api.rs")];
+    let files = CollectedFile::load_all(&vfs, &paths).await.unwrap();
+    let anonymizer = Anonymizer::new(&settings()).unwrap().unwrap();
+    let anonymization = Arc::new(Anonymization::new(anonymizer, AnonymizeMap::default()));
+
+    for format in [
+        OutputFormat::Markdown,
+        OutputFormat::Xml,
+        OutputFormat::Json,
+    ] {
+        let mut options = RenderOptions {
+            format,
+            quiet: true,
+            anonymization: Some(anonymization.clone()),
+            ..RenderOptions::default()
+        };
+        let working_dir = std::env::current_dir().unwrap();
+        let manifest = Manifest::new(
+            vec![
+                "/home/alice/.cargo/bin/catnip".to_string(),
+                "cat".to_string(),
+                working_dir.join("src/falcon").display().to_string(),
+            ],
+            &[working_dir.join("src/falcon")],
+            &CollectOptions::default(),
+            &options,
+            &[],
+            &Snapshot::capture(&vfs, &paths).await,
+        );
+        assert_eq!(manifest.working_dir, None);
+        assert_eq!(manifest.command, ["catnip", "cat", "src/falcon"]);
+        if format != OutputFormat::Json {
+            options.manifest = Some(manifest.embedded().unwrap());
+        }
+
+        let result = concatenate_files_with(&vfs, &files, &options)
+            .await
+            .unwrap();
+        assert!(result.contains("CODENAME_1"), "{}", result);
+        assert!(!result.contains("falcon"), "{}", result);
+        assert!(!result.contains(&working_dir.display().to_string()));
+    }
+    assert_eq!(
+        anonymization.map().placeholders["falcon"],
+        "CODENAME_1".to_string()
+    );
+}
+
+#[test]
+fn test_restore_placeholders_in_patch() {
+    let anonymizer = Anonymizer::new(&settings()).unwrap().unwrap();
+    let mut map = AnonymizeMap::default();
+    anonymizer.anonymize("alice wrote falcon/api.rs", &mut map);
+
+    let mut request = UpdateRequest::parse(
+        &serde_json::json!({
+            "analysis": "Credit USER_1",
+            "files": [{
+                "path": "CODENAME_1/api.rs",
+                "updates": [{
+                    "old_content": "// TODO",
+                    "new_content": "// Reviewed by USER_1 for USER_1, not USER_12",
+                    "description": "Sign off"
+                }]
+            }]
+        })
+        .to_string(),
+    )
+    .unwrap();
+    assert_eq!(request.restore_placeholders(&map), 4);
+    assert_eq!(request.analysis, "Credit alice");
+    assert_eq!(request.files[0].path, "falcon/api.rs");
+    assert_eq!(
+        request.files[0].updates[0].new_content,
+        "// Reviewed by alice for alice, not USER_12"
+    );
+    assert_eq!(request.files[0].updates[0].old_content, "// TODO");
+}
+
+#[tokio::test]
+async fn test_patch_deanonymize() {
+    let vfs = MemoryFs::with_files([(
+        "src/config.rs",
+        "const HOST: &str = \"build.corp.internal\";\n",
+    )]);
+    let anonymizer = Anonymizer::new(&settings()).unwrap().unwrap();
+    let mut map = AnonymizeMap::default();
+    let (snapshot, _) = anonymizer.anonymize(
+        &vfs.read_to_string(Path::new("src/config.rs"))
+            .await
+            .unwrap(),
+        &mut map,
+    );
+    assert_eq!(snapshot, "const HOST: &str = \"host1.example.invalid\";\n");
+    map.save(&vfs, Path::new("anonymize.json")).await.unwrap();
+
+    // The model answers in terms of the placeholders it was shown
+    let json = serde_json::json!({
+        "analysis": "Point at the new host",
+        "files": [{
+            "path": "src/config.rs",
+            "updates": [{
+                "old_content": "\"host1.example.invalid\"",
+                "new_content": "\"host1.example.invalid\";\nconst PORT: u16 = 443"
+            }]
+        }]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions {
+            deanonymize: Some(Some(PathBuf::from("anonymize.json"))),
+            ..PatchOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/config.rs"))
+            .await
+            .unwrap(),
+        "const HOST: &str = \"build.corp.internal\";\nconst PORT: u16 = 443;\n"
+    );
+
+    let missing = PatchOptions {
+        deanonymize: Some(Some(PathBuf::from("missing.json"))),
+        ..PatchOptions::default()
+    };
+    let err = execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &missing,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("No anonymization map"));
+}
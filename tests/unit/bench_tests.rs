@@ -0,0 +1,28 @@
+use catnip::core::bench::{PHASES, bench_pipeline};
+use catnip::io::vfs::MemoryFs;
+use std::path::PathBuf;
+
+#[tokio::test]
+async fn test_bench_pipeline() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}\n"),
+        ("project/src/lib.rs", "pub fn lib() {}\n"),
+        ("project/target/debug/out.rs", "fn skipped() {}\n"),
+    ]);
+
+    let report = bench_pipeline(&vfs, &[PathBuf::from("project")], 2)
+        .await
+        .unwrap();
+
+    assert_eq!(report.files, 2);
+    assert_eq!(report.bytes, 29);
+    assert!(report.tokens > 0);
+    assert_eq!(report.timings.len(), PHASES.len());
+    assert!(report.timings.iter().all(|samples| samples.len() == 2));
+
+    let table = report.format_table();
+    for phase in PHASES {
+        assert!(table.contains(phase));
+    }
+    assert!(table.contains("total"));
+}
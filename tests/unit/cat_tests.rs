@@ -0,0 +1,187 @@
+use catnip::cli::commands::cat;
+use catnip::cli::guards::{Guard, GuardFailure};
+use catnip::cli::{Args, Commands, Parser};
+use catnip::io::clipboard::MemoryClipboard;
+use catnip::io::vfs::{MemoryFs, Vfs};
+use catnip::utils::token_counter::count_tokens;
+
+#[tokio::test]
+async fn test_cat_copies_to_clipboard() {
+    let vfs = MemoryFs::with_files([("main.rs", "fn main() {}")]);
+
+    let clipboard = MemoryClipboard::new();
+    let Commands::Cat(args) = Args::parse_from(["catnip", "cat", "main.rs"]).command else {
+        unreachable!()
+    };
+    cat::execute(&clipboard, &vfs, *args).await.unwrap();
+
+    let content = clipboard.content();
+    assert!(content.contains("# Project Structure"));
+    assert!(content.contains("fn main() {}"));
+}
+
+#[tokio::test]
+async fn test_cat_fail_guards() {
+    let vfs = MemoryFs::with_files([("main.rs", "fn main() {}")]);
+    let clipboard = MemoryClipboard::new();
+
+    let Commands::Cat(args) =
+        Args::parse_from(["catnip", "cat", "main.rs", "--fail-over-tokens", "5"]).command
+    else {
+        unreachable!()
+    };
+    let err = cat::execute(&clipboard, &vfs, *args).await.unwrap_err();
+    let failure = err.downcast_ref::<GuardFailure>().unwrap();
+    assert_eq!(failure.guard, Guard::OverLimit);
+    assert_eq!(failure.guard.exit_code(), 4);
+    // The output is still delivered before the guard fails the run
+    assert!(clipboard.content().contains("fn main() {}"));
+
+    let Commands::Cat(args) = Args::parse_from([
+        "catnip",
+        "cat",
+        "main.rs",
+        "--exclude",
+        "main.rs",
+        "--fail-if-empty",
+    ])
+    .command
+    else {
+        unreachable!()
+    };
+    let err = cat::execute(&clipboard, &vfs, *args).await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<GuardFailure>().unwrap().guard,
+        Guard::Empty
+    );
+}
+
+#[tokio::test]
+async fn test_cat_rich_copy() {
+    let clipboard = MemoryClipboard::new();
+    let vfs = MemoryFs::with_files([("main.rs", "fn main() {}\n")]);
+
+    let Commands::Cat(args) = Args::parse_from(["catnip", "cat", "main.rs", "--rich-copy"]).command
+    else {
+        panic!("expected cat command");
+    };
+    cat::execute(&clipboard, &vfs, *args).await.unwrap();
+
+    assert!(clipboard.content().contains("fn main() {}"));
+    let html = clipboard.html().expect("HTML flavor should be copied");
+    assert!(html.contains("<pre"));
+    assert!(html.contains("fn main() {}"));
+}
+
+#[tokio::test]
+async fn test_cat_fail_on_secrets_and_redactions() {
+    let vfs = MemoryFs::with_files([
+        ("main.rs", "fn main() {}\n"),
+        ("credentials.json", "{\"token\": \"secret\"}\n"),
+        (
+            "gpl.rs",
+            "// SPDX-License-Identifier: GPL-3.0-only\nfn copyleft() {}\n",
+        ),
+    ]);
+    let clipboard = MemoryClipboard::new();
+    let run = |extra: &[&str]| {
+        let mut argv = vec!["catnip", "cat", "main.rs", "credentials.json", "gpl.rs"];
+        argv.extend(extra);
+        let Commands::Cat(args) = Args::parse_from(argv).command else {
+            unreachable!()
+        };
+        cat::execute(&clipboard, &vfs, *args)
+    };
+    let guard = |result: anyhow::Result<()>| {
+        result
+            .unwrap_err()
+            .downcast_ref::<GuardFailure>()
+            .unwrap()
+            .guard
+    };
+
+    // A sensitive file let in is a secret, and a tripped guard copies nothing
+    assert_eq!(
+        guard(run(&["--allow-sensitive", "--fail-on-secrets"]).await),
+        Guard::Secrets
+    );
+    assert!(clipboard.content().is_empty());
+    // Nothing was redacted, though
+    run(&["--allow-sensitive", "--fail-on-redactions"])
+        .await
+        .unwrap();
+    assert!(clipboard.content().contains("fn main()"));
+
+    // Left out, it is a redaction, as is a file under an excluded license
+    assert_eq!(
+        guard(run(&["--fail-on-redactions"]).await),
+        Guard::Redactions
+    );
+    let err = run(&[
+        "--allow-sensitive",
+        "--exclude-license",
+        "gpl-3.0",
+        "--fail-on-redactions",
+    ])
+    .await
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<GuardFailure>().unwrap().guard,
+        Guard::Redactions
+    );
+    assert!(
+        err.to_string()
+            .contains("1 files left out for their license")
+    );
+    assert_eq!(Guard::Redactions.exit_code(), 6);
+}
+
+#[tokio::test]
+async fn test_cat_split_parts_stay_under_the_limit() {
+    let line = "let value = compute_something(input);\n";
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        ("a.rs".to_string(), line.repeat(30).into_bytes()),
+        ("big.rs".to_string(), line.repeat(400).into_bytes()),
+        ("c.rs".to_string(), line.repeat(30).into_bytes()),
+        ("d.rs".to_string(), line.repeat(30).into_bytes()),
+    ];
+    // Unreadable files make the section closing the last part longer than estimated
+    for i in 0..40 {
+        files.push((
+            format!("unreadable_file_with_a_long_name_{}.rs", i),
+            b"// caf\xe9".to_vec(),
+        ));
+    }
+    let paths: Vec<String> = files.iter().map(|(path, _)| path.clone()).collect();
+    let vfs = MemoryFs::with_files(files);
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let output = temp_dir.path().join("out.md");
+
+    let limit = 1500;
+    let mut argv = vec!["catnip", "cat", "--split-tokens", "1500", "-o"];
+    let output_arg = output.to_string_lossy().to_string();
+    argv.push(&output_arg);
+    argv.extend(paths.iter().map(String::as_str));
+    let Commands::Cat(args) = Args::parse_from(argv).command else {
+        unreachable!()
+    };
+    cat::execute(&MemoryClipboard::new(), &vfs, *args)
+        .await
+        .unwrap();
+
+    let mut parts = Vec::new();
+    for i in 1.. {
+        let path = temp_dir.path().join(format!("out.part{}.md", i));
+        match vfs.read_to_string(&path).await {
+            Ok(part) => parts.push(part),
+            Err(_) => break,
+        }
+    }
+    assert!(parts.len() > 1);
+    for part in &parts {
+        let tokens = count_tokens(part);
+        // Only the file too big for any part may push its part over the limit
+        assert!(tokens <= limit || part.contains("## big.rs"));
+    }
+    assert!(parts.last().unwrap().contains("# Unavailable Files"));
+}
@@ -0,0 +1,194 @@
+use catnip::core::classifier::*;
+use catnip::core::content_processor::{RenderOptions, concatenate_files_with};
+use catnip::core::file_collector::{CollectOptions, CollectedFile, collect_files_with};
+use catnip::io::vfs::{MemoryFs, TokioFs};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_is_test_path() {
+    assert!(is_test_path(Path::new("tests/integration.rs")));
+    assert!(is_test_path(Path::new("src/__tests__/app.js")));
+    assert!(is_test_path(Path::new("pkg/server_test.go")));
+    assert!(is_test_path(Path::new("test_utils.py")));
+    assert!(is_test_path(Path::new("conftest.py")));
+    assert!(is_test_path(Path::new("src/app.spec.ts")));
+    assert!(is_test_path(Path::new("src/ParserTest.java")));
+    assert!(is_test_path(Path::new("benches/throughput.rs")));
+    assert!(is_test_path(Path::new("fixtures/sample.json")));
+
+    assert!(!is_test_path(Path::new("src/main.rs")));
+    assert!(!is_test_path(Path::new("pkg/server.go")));
+    assert!(!is_test_path(Path::new("src/testing.py")));
+    assert!(!is_test_path(Path::new("src/contest.ts")));
+}
+
+#[test]
+fn test_strip_rust_test_modules() {
+    let code = r#"pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        assert_eq!(add(1, 2), 3);
+    }
+}
+
+#[cfg(test)]
+use std::collections::HashMap;
+"#;
+
+    let result = strip_test_code(code, "rust");
+    assert!(result.contains("pub fn add"));
+    assert!(!result.contains("mod tests"));
+    assert!(!result.contains("fn test_add"));
+    assert!(result.contains("use std::collections::HashMap;"));
+    assert!(result.ends_with('\n'));
+
+    assert_eq!(strip_test_code(code, "python"), code);
+}
+
+#[test]
+fn test_strip_rust_tests_ignores_braces_in_literals_and_comments() {
+    let code = r#"pub fn open() -> char { '{' }
+
+#[cfg(test)]
+mod tests {
+    // a stray } in a comment
+    #[test]
+    fn braces() {
+        assert_eq!(format!("{}", "}}"), "}");
+        assert_eq!('}', '}');
+    }
+}
+
+pub fn close() -> &'static str { "}" }
+"#;
+
+    let result = strip_test_code(code, "rust");
+    assert!(result.contains("pub fn open"));
+    assert!(result.contains("pub fn close"));
+    assert!(!result.contains("fn braces"));
+    assert!(!result.contains("stray"));
+}
+
+#[tokio::test]
+async fn test_collect_files_without_tests() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/lib.rs", "pub fn lib() {}"),
+        ("project/src/lib_test.rs", "fn test() {}"),
+        ("project/tests/integration.rs", "fn test() {}"),
+        ("project/benches/bench.rs", "fn bench() {}"),
+    ]);
+
+    let options = CollectOptions {
+        test_filter: TestFilter::ExcludeTests,
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &[PathBuf::from("project")], &options)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("project/src/lib.rs")]
+    );
+}
+
+#[tokio::test]
+async fn test_collect_tests_and_code_under_test() {
+    let vfs = MemoryFs::with_files([
+        ("project/README.md", "# Project"),
+        ("project/src/parser.rs", "pub fn parse() {}"),
+        ("project/src/lexer/mod.rs", "pub struct Token;"),
+        ("project/src/unrelated.rs", "pub fn other() {}"),
+        // Named like a standard library root, which the test only imports from std
+        ("project/src/core.rs", "pub fn core() {}"),
+        ("project/src/alloc/mod.rs", "pub fn arena() {}"),
+        (
+            "project/src/inline.rs",
+            "pub fn add() {}\n\n#[cfg(test)]\nmod tests {\n    fn t() {}\n}\n",
+        ),
+        (
+            "project/tests/parser_tests.rs",
+            "use core::mem;\nuse super::*;\nuse crate::alloc::arena;\nuse project::lexer::Token;\n\n#[test]\nfn parses() {}\n",
+        ),
+        ("project/web/api.ts", "export function get() {}"),
+        ("project/web/app.spec.ts", "import { get } from './api';\n"),
+    ]);
+
+    let options = CollectOptions {
+        test_filter: TestFilter::OnlyTests,
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let mut files = CollectedFile::paths(
+        &collect_files_with(&vfs, &[PathBuf::from("project")], &options)
+            .await
+            .unwrap(),
+    );
+    files.sort();
+
+    assert_eq!(
+        files,
+        [
+            "project/src/alloc/mod.rs",
+            "project/src/inline.rs",
+            "project/src/lexer/mod.rs",
+            "project/src/parser.rs",
+            "project/tests/parser_tests.rs",
+            "project/web/api.ts",
+            "project/web/app.spec.ts",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_extract_test_code() {
+    let code =
+        "pub fn add() {}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn it_adds() {}\n}\n";
+
+    let tests = extract_test_code(code, "rust");
+    assert!(tests.starts_with("#[cfg(test)]\nmod tests {"));
+    assert!(tests.contains("fn it_adds()"));
+    assert!(!tests.contains("pub fn add"));
+}
+
+#[tokio::test]
+async fn test_tests_only_outlines_source() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let lib = temp_dir.path().join("lib.rs");
+    let test = temp_dir.path().join("lib_test.rs");
+    std::fs::write(
+        &lib,
+        "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\n#[cfg(test)]\nmod tests {\n    fn inline() {}\n}\n",
+    )
+    .unwrap();
+    std::fs::write(&test, "fn test_add() {\n    assert_eq!(add(1, 2), 3);\n}\n").unwrap();
+
+    let options = RenderOptions {
+        test_filter: TestFilter::OnlyTests,
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(
+        &TokioFs,
+        &CollectedFile::load_all(&TokioFs, &[lib, test])
+            .await
+            .unwrap(),
+        &options,
+    )
+    .await
+    .unwrap();
+
+    assert!(result.contains("pub fn add(a: i32, b: i32) -> i32"));
+    assert!(!result.contains("a + b"));
+    assert!(result.contains("fn inline()"));
+    assert!(result.contains("assert_eq!(add(1, 2), 3);"));
+}
@@ -1,4 +1,9 @@
+use catnip::cli::commands::patch;
+use catnip::io::clipboard::{ClipboardProvider, MemoryClipboard};
+use catnip::io::vfs::TokioFs;
+use catnip::utils::html::markdown_to_html;
 use std::process::Command;
+use tempfile::TempDir;
 
 // We can't easily test the actual clipboard functionality without mocking,
 // but we can test some of the helper functions and logic
@@ -34,17 +39,17 @@ fn test_platform_detection() {
     // Test that we can detect the current platform
     #[cfg(target_os = "windows")]
     {
-        assert!(cfg!(target_os = "windows"));
+        const { assert!(cfg!(target_os = "windows")) };
     }
 
     #[cfg(target_os = "macos")]
     {
-        assert!(cfg!(target_os = "macos"));
+        const { assert!(cfg!(target_os = "macos")) };
     }
 
     #[cfg(target_os = "linux")]
     {
-        assert!(cfg!(target_os = "linux"));
+        const { assert!(cfg!(target_os = "linux")) };
     }
 }
 
@@ -86,3 +91,51 @@ fn test_clipboard_command_selection() {
         assert_eq!(x11_args, ["-selection", "clipboard"]);
     }
 }
+
+#[test]
+fn test_memory_clipboard_round_trip() {
+    let clipboard = MemoryClipboard::new();
+    assert!(clipboard.read().is_err(), "Empty clipboard should error");
+
+    clipboard.copy("hello").unwrap();
+    assert_eq!(clipboard.read().unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn test_patch_from_clipboard() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("lib.rs");
+    std::fs::write(&file, "fn old() {}\n\nfn keep() {}\n").unwrap();
+
+    let json = serde_json::json!({
+        "analysis": "Rename function",
+        "files": [{
+            "path": file.to_string_lossy(),
+            "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+        }]
+    });
+    let clipboard = MemoryClipboard::with_content(&json.to_string());
+
+    patch::execute(&clipboard, &TokioFs, None, &patch::PatchOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "fn new() {}\n\nfn keep() {}\n"
+    );
+}
+
+#[test]
+fn test_markdown_to_html_keeps_code_blocks() {
+    let html = markdown_to_html(
+        "# Title\n\n## src/main.rs\n```rust\nfn main() { a < b }\n```\n### Directory Summaries\n###### Deep\n####### Not a heading\n",
+    );
+
+    assert!(html.contains("<h1>Title</h1>"));
+    assert!(html.contains("<h2>src/main.rs</h2>"));
+    assert!(html.contains("<h3>Directory Summaries</h3>"));
+    assert!(html.contains("<h6>Deep</h6>"));
+    assert!(html.contains("<p>####### Not a heading</p>"));
+    assert!(html.contains("<code class=\"language-rust\">fn main() { a &lt; b }\n</code></pre>"));
+}
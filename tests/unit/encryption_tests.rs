@@ -0,0 +1,84 @@
+use age::secrecy::ExposeSecret;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+use catnip::core::content_processor::{RenderOptions, concatenate_files_with};
+use catnip::core::file_collector::{CollectOptions, collect_files_with};
+use catnip::io::encryption::{EncryptionTarget, decrypt_text, is_encrypted};
+use catnip::io::vfs::{MemoryFs, Vfs};
+
+#[test]
+fn test_encryption_target_parse() {
+    let identity = age::x25519::Identity::generate();
+    let recipient = identity.to_public().to_string();
+
+    let target = EncryptionTarget::parse(&format!("age:{}", recipient)).unwrap();
+    assert_eq!(target.to_string(), format!("age:{}", recipient));
+
+    assert!(EncryptionTarget::parse(&recipient).is_err());
+    assert!(EncryptionTarget::parse("gpg:alice@example.com").is_err());
+    assert!(EncryptionTarget::parse("age:age1notakey").is_err());
+}
+
+#[tokio::test]
+async fn test_encrypted_output_round_trip() {
+    let identity = age::x25519::Identity::generate();
+    let target = EncryptionTarget::parse(&format!("age:{}", identity.to_public())).unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let key_file = temp_dir.path().join("key.txt");
+    std::fs::write(
+        &key_file,
+        format!(
+            "# created: today\n{}\n",
+            identity.to_string().expose_secret()
+        ),
+    )
+    .unwrap();
+
+    let vfs = MemoryFs::with_files([("project/src/secret.rs", b"const TOKEN: u8 = 1;\n")]);
+    let files = collect_files_with(
+        &vfs,
+        &[PathBuf::from("project")],
+        &CollectOptions::default(),
+    )
+    .await
+    .unwrap();
+    let options = RenderOptions {
+        output_file: Some("snapshot.md.age".to_string()),
+        encrypt: Some(target),
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let rendered = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+
+    let written = vfs
+        .read(std::path::Path::new("snapshot.md.age"))
+        .await
+        .unwrap();
+    assert!(is_encrypted(&written));
+    assert!(!String::from_utf8_lossy(&written).contains("TOKEN"));
+
+    let decrypted = decrypt_text(written.clone(), Some(&key_file), "snapshot").unwrap();
+    assert_eq!(decrypted, rendered);
+
+    // Without a key, or with the wrong one, the snapshot stays sealed
+    let err = decrypt_text(written.clone(), None, "snapshot").unwrap_err();
+    assert!(err.to_string().contains("--identity"));
+    let other_key = temp_dir.path().join("other.txt");
+    std::fs::write(
+        &other_key,
+        age::x25519::Identity::generate()
+            .to_string()
+            .expose_secret(),
+    )
+    .unwrap();
+    assert!(decrypt_text(written, Some(&other_key), "snapshot").is_err());
+
+    // Plain content passes through whether or not a key is given
+    assert_eq!(
+        decrypt_text(b"# Project".to_vec(), Some(&key_file), "snapshot").unwrap(),
+        "# Project"
+    );
+}
@@ -1,9 +1,22 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use tokio::fs;
+use tokio_stream::StreamExt;
 
+use catnip::config::settings::{ProcessingRule, WorkspaceRoot};
+use catnip::core::binary_assets::{BinaryMode, guess_mime};
+use catnip::core::complexity::Complexity;
 use catnip::core::content_processor::*;
+use catnip::core::dir_summary::*;
 use catnip::core::file_collector::*;
+use catnip::core::io_errors::IoErrors;
+use catnip::core::pruned_dirs::tree_size;
+use catnip::core::snapshot::*;
+use catnip::core::warnings::{WarningKind, Warnings};
+use catnip::io::file_operations::write_file_safe;
+use catnip::io::vfs::{MemoryFs, Vfs};
+use catnip::utils::file_metadata::*;
+use catnip::utils::filetype::{Encoding, FileType, decode_text, sniff};
 use catnip::utils::{language_detection::*, text_processing::*};
 
 #[test]
@@ -48,6 +61,28 @@ fn test_is_binary_file() {
 
     let utf8_content = "Hello 世界".as_bytes();
     assert!(!is_binary_file(utf8_content));
+
+    let utf16_le: Vec<u8> = "\u{feff}fn main() {}\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    assert!(!is_binary_file(&utf16_le));
+    assert_eq!(decode_text(utf16_le).unwrap(), "fn main() {}\n");
+
+    // Without a byte order mark, UTF-16 is told by its zero bytes
+    let utf16_be: Vec<u8> = "let x = 1;\n"
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect();
+    assert_eq!(sniff(&utf16_be), FileType::Text(Encoding::Utf16Be));
+    assert_eq!(decode_text(utf16_be).unwrap(), "let x = 1;\n");
+
+    // Magic numbers give away binaries that start out as ASCII
+    assert_eq!(
+        sniff(b"%PDF-1.7\n%comment\n"),
+        FileType::Binary(Some("application/pdf"))
+    );
+    assert!(is_binary_file(b"\x01\x02\x03\x04abc"));
 }
 
 #[test]
@@ -82,6 +117,20 @@ return True"#;
     assert!(result.contains("return True"));
 }
 
+#[test]
+fn test_extract_outline() {
+    let rust_code = "pub struct Point {\n    x: i32,\n}\n\nimpl Point {\n    pub fn new() -> Self {\n        Point { x: 0 }\n    }\n}";
+    let outline = extract_outline(rust_code, "rust");
+    assert_eq!(
+        outline,
+        "pub struct Point\nimpl Point\n    pub fn new() -> Self"
+    );
+
+    let python_code = "class Greeter:\n    def greet(self):\n        return 'hi'";
+    let outline = extract_outline(python_code, "python");
+    assert_eq!(outline, "class Greeter:\n    def greet(self):");
+}
+
 #[test]
 fn test_remove_comments_disabled() {
     let code = "fn test() {\n    println!(\"test\");\n}";
@@ -100,7 +149,7 @@ async fn test_collect_files_single_file() {
         .unwrap();
 
     assert_eq!(files.len(), 1);
-    assert_eq!(files[0], test_file);
+    assert_eq!(files[0].path, test_file);
 }
 
 #[tokio::test]
@@ -128,7 +177,7 @@ async fn test_collect_files_with_filters() {
 
     let file_names: Vec<String> = files
         .iter()
-        .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+        .map(|p| p.path.file_name().unwrap().to_string_lossy().to_string())
         .collect();
 
     assert!(file_names.contains(&"main.rs".to_string()));
@@ -136,6 +185,434 @@ async fn test_collect_files_with_filters() {
     assert!(!file_names.contains(&"data.json".to_string()));
 }
 
+#[tokio::test]
+async fn test_collect_files_in_memory() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}"),
+        ("project/target/debug/build.rs", "fn build() {}"),
+        ("project/notes.log", "log data"),
+        ("project/empty.rs", ""),
+        ("project/blob.rs", "\0\0\0"),
+    ]);
+
+    let files = collect_files_with(
+        &vfs,
+        &[PathBuf::from("project")],
+        &CollectOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("project/src/main.rs")]
+    );
+}
+
+#[tokio::test]
+async fn test_diagnose_empty_collection() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}"),
+        ("project/src/lib.rs", "pub fn lib() {}"),
+        ("project/target/debug/build.rs", "fn build() {}"),
+        ("project/notes.log", "log data"),
+    ]);
+    let options = CollectOptions {
+        includes: vec!["*.ts".to_string()],
+        ..CollectOptions::default()
+    };
+    let paths = [PathBuf::from("project"), PathBuf::from("missing")];
+
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert!(files.is_empty());
+    assert_eq!(
+        diagnose_empty(&vfs, &paths, &options).await,
+        vec![
+            "missing does not exist",
+            "2 file(s) not matched by any include pattern",
+            "1 file(s) excluded by '*.log' (default)",
+            "1 file(s) in a skipped directory (directory skip list)",
+            "Most common extensions no include pattern matched: *.rs (2)",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_collected_files_carry_metadata() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}\n"),
+        ("project/README.md", "# Project"),
+    ]);
+
+    let files = collect_files_with(
+        &vfs,
+        &[PathBuf::from("project")],
+        &CollectOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let main = files
+        .iter()
+        .find(|file| file.path == Path::new("project/src/main.rs"))
+        .unwrap();
+    assert_eq!(main.relative_path, PathBuf::from("src/main.rs"));
+    assert_eq!(main.size, 13);
+    assert_eq!(main.language, "rust");
+    assert_eq!(main.sha256, sha256_hex(b"fn main() {}\n"));
+
+    let readme = files
+        .iter()
+        .find(|file| file.relative_path == Path::new("README.md"))
+        .unwrap();
+    assert_eq!(readme.language, "markdown");
+    assert_eq!(
+        readme,
+        &CollectedFile::load(&vfs, &readme.path).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_binary_files_listed_as_stubs() {
+    let logo: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", b"fn main() {}\n".as_slice()),
+        ("project/assets/logo.png", logo),
+        ("project/src/table.rs", b"\0\x01\x02\x03".as_slice()),
+    ]);
+    let paths = [PathBuf::from("project")];
+
+    // By default binaries leave no trace
+    let options = CollectOptions::default();
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(options.binary_assets.assets().is_empty());
+
+    let options = CollectOptions {
+        binaries: BinaryMode::Stub,
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("project/src/main.rs")]
+    );
+    let assets = options.binary_assets.assets();
+    assert_eq!(assets.len(), 2);
+    assert_eq!(assets[0].display_path, "project/assets/logo.png");
+    assert_eq!(assets[0].mime, "image/png");
+    assert_eq!(assets[0].size, logo.len() as u64);
+    assert_eq!(assets[0].sha256.as_deref(), Some(sha256_hex(logo).as_str()));
+    assert_eq!(assets[1].mime, "application/octet-stream");
+
+    let render_options = RenderOptions {
+        binary_assets: options.binary_assets.clone(),
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    assert!(output.contains("logo.png [binary]"));
+    assert!(output.contains(&format!(
+        "# Binary Assets\n\n*These binary files exist but their contents are not included:*\n\n- `project/assets/logo.png`: image/png · {} bytes · sha256 {}\n",
+        logo.len(),
+        &sha256_hex(logo)[..12]
+    )));
+    assert!(!output.contains("## project/assets/logo.png"));
+
+    let render_options = RenderOptions {
+        format: OutputFormat::Xml,
+        ..render_options
+    };
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    let tail = &output[output.find("</documents>").unwrap()..];
+    assert!(tail.contains(&format!(
+        "<binary_assets>\n<asset path=\"project/assets/logo.png\" mime=\"image/png\" size=\"{}\" sha256=\"{}\"/>\n",
+        logo.len(),
+        sha256_hex(logo)
+    )));
+    assert!(!tail.contains("# Binary Assets"));
+
+    assert_eq!(guess_mime(Path::new("font.WOFF2"), b""), "font/woff2");
+    assert_eq!(
+        guess_mime(Path::new("data"), b"%PDF-1.7"),
+        "application/pdf"
+    );
+}
+
+#[tokio::test]
+async fn test_utf16_sources_collected_and_decoded() {
+    let source: Vec<u8> = "\u{feff}fn greet() {}\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let vfs = MemoryFs::with_files([("project/src/lib.rs", source)]);
+
+    let files = collect_files_with(
+        &vfs,
+        &[PathBuf::from("project")],
+        &CollectOptions::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(files.len(), 1);
+
+    let render_options = RenderOptions {
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    assert!(output.contains("fn greet() {}"));
+    assert!(!output.contains('\u{feff}'));
+}
+
+#[tokio::test]
+async fn test_collect_files_anchors_patterns_at_the_root() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}"),
+        ("project/src/gen/schema.rs", "struct Schema;"),
+        ("project/tools/src/gen/build.rs", "fn build() {}"),
+        ("project/docs/guide.md", "# Guide"),
+        ("project/src/docs/notes.md", "# Notes"),
+    ]);
+    let options = CollectOptions {
+        excludes: vec!["/src/gen".to_string(), "docs/".to_string()],
+        ..CollectOptions::default()
+    };
+
+    let mut files = collect_files_with(&vfs, &[PathBuf::from("project")], &options)
+        .await
+        .unwrap();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("project/src/main.rs"),
+            PathBuf::from("project/tools/src/gen/build.rs"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_collect_files_records_pruned_directories() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}"),
+        ("project/target/debug/build.rs", "fn build() {}"),
+        ("project/target/release/build.rs", "fn build() {}"),
+        ("project/src/generated/api.rs", "struct Api;"),
+    ]);
+    let options = CollectOptions {
+        excludes: vec!["generated/".to_string()],
+        quiet: true,
+        ..CollectOptions::default()
+    };
+
+    collect_files_with(&vfs, &[PathBuf::from("project")], &options)
+        .await
+        .unwrap();
+
+    let mut pruned = options.pruned.dirs();
+    pruned.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(pruned.len(), 2);
+    assert_eq!(pruned[0].relative_path, PathBuf::from("src/generated"));
+    assert_eq!(pruned[0].reason, "excluded by 'generated/' (command line)");
+    assert!(!pruned[0].is_top_level());
+    assert_eq!(pruned[1].relative_path, PathBuf::from("target"));
+    assert_eq!(pruned[1].reason, "directory skip list");
+
+    let top_level = options.pruned.top_level();
+    assert_eq!(top_level.len(), 1);
+    let size = tree_size(&vfs, &top_level[0].path).await;
+    assert_eq!(size.files, 2);
+    assert_eq!(size.bytes, 26);
+    assert_eq!(size.to_string(), "2 files, 26 B");
+}
+
+#[tokio::test]
+async fn test_collect_files_directory_skip_list() {
+    let vfs = MemoryFs::with_files([
+        ("build/src/main.rs", "fn main() {}"),
+        ("build/node_modules/lib/index.js", "module.exports = {};"),
+        ("build/vendor/lib.rs", "pub fn lib() {}"),
+    ]);
+
+    // The skip list applies below the input directory, not to the input itself
+    let files = collect_files_with(&vfs, &[PathBuf::from("build")], &CollectOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("build/src/main.rs"),
+            PathBuf::from("build/vendor/lib.rs"),
+        ]
+    );
+
+    let options = CollectOptions {
+        skip_directories: vec!["vendor".to_string()],
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &[PathBuf::from("build")], &options)
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("build/src/main.rs")]
+    );
+
+    // Without the skip list, the default exclude patterns still apply
+    let reasons: Vec<_> = options
+        .pruned
+        .dirs()
+        .into_iter()
+        .map(|dir| dir.reason)
+        .collect();
+    assert!(reasons.contains(&"directory skip list".to_string()));
+    assert!(reasons.contains(&"excluded by 'node_modules' (default)".to_string()));
+}
+
+#[tokio::test]
+async fn test_collect_files_dotfile_allowlist() {
+    let vfs = MemoryFs::with_files([
+        ("app/.env", "SECRET=1"),
+        ("app/.env.local", "SECRET=2"),
+        ("app/.env.example", "SECRET="),
+        ("app/.editorconfig", "root = true"),
+        ("app/.prettierrc", "{}"),
+        ("app/main.rs", "fn main() {}"),
+    ]);
+
+    let files = collect_files_with(&vfs, &[PathBuf::from("app")], &CollectOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("app/.editorconfig"),
+            PathBuf::from("app/.env.example"),
+            PathBuf::from("app/main.rs"),
+        ]
+    );
+
+    // Allowing every .env file still leaves out the ones with real values
+    let options = CollectOptions {
+        dotfiles_allow: vec![".prettierrc".to_string(), ".env*".to_string()],
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &[PathBuf::from("app")], &options)
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("app/.editorconfig"),
+            PathBuf::from("app/.env.example"),
+            PathBuf::from("app/.prettierrc"),
+            PathBuf::from("app/main.rs"),
+        ]
+    );
+}
+
+#[test]
+fn test_is_env_file() {
+    assert!(is_env_file(".env"));
+    assert!(is_env_file(".env.local"));
+    assert!(is_env_file(".env.production"));
+    assert!(!is_env_file(".env.example"));
+    assert!(!is_env_file(".env.dist"));
+    assert!(!is_env_file(".envrc"));
+    assert!(!is_env_file("main.env"));
+}
+
+#[tokio::test]
+async fn test_collect_files_stream() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}"),
+        ("project/src/lib.rs", "pub fn lib() {}"),
+        ("project/target/debug/build.rs", "fn build() {}"),
+        ("notes.md", "# Notes"),
+    ]);
+    let options = CollectOptions {
+        virtual_entries: vec![VirtualEntry {
+            name: PathBuf::from("task.md"),
+            content: "Fix the parser".to_string(),
+        }],
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let paths = [PathBuf::from("project"), PathBuf::from("notes.md")];
+
+    let streamed: Vec<CollectedFile> = collect_files_stream(&vfs, &paths, &options)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let collected = collect_files_with(&vfs, &paths, &options).await.unwrap();
+
+    assert_eq!(
+        streamed.iter().map(|f| f.path.clone()).collect::<Vec<_>>(),
+        CollectedFile::paths(&collected)
+    );
+    assert_eq!(streamed[0].relative_path, PathBuf::from("src/lib.rs"));
+    assert_eq!(streamed[2].relative_path, PathBuf::from("notes.md"));
+    assert_eq!(streamed[3].path, PathBuf::from("task.md"));
+}
+
+#[tokio::test]
+async fn test_collect_files_stream_reports_errors_last() {
+    let vfs = MemoryFs::with_files([("src/main.rs", "fn main() {}")]);
+    let options = CollectOptions {
+        io_errors: IoErrors::new(true),
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let paths = [PathBuf::from("src"), PathBuf::from("missing.rs")];
+
+    let results: Vec<_> = collect_files_stream(&vfs, &paths, &options).collect().await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0].as_ref().unwrap().path,
+        PathBuf::from("src/main.rs")
+    );
+    assert!(
+        results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("--strict-io")
+    );
+}
+
+#[tokio::test]
+async fn test_virtual_entries() {
+    let vfs = MemoryFs::with_files([("notes/task.md", "Fix the parser")]);
+
+    let from_file = VirtualEntry::resolve(&vfs, "TASK.md=notes/task.md")
+        .await
+        .unwrap();
+    assert_eq!(from_file.name, PathBuf::from("TASK.md"));
+    assert_eq!(from_file.content, "Fix the parser");
+
+    let literal = VirtualEntry::resolve(&vfs, "TODO.md=- write docs")
+        .await
+        .unwrap();
+    assert_eq!(literal.content, "- write docs");
+
+    assert!(VirtualEntry::resolve(&vfs, "no-separator").await.is_err());
+
+    let options = CollectOptions {
+        virtual_entries: vec![literal],
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &[], &options).await.unwrap();
+    assert_eq!(CollectedFile::paths(&files), vec![PathBuf::from("TODO.md")]);
+}
+
 #[tokio::test]
 async fn test_concatenate_files() {
     let temp_dir = TempDir::new().unwrap();
@@ -160,3 +637,863 @@ async fn test_concatenate_files() {
     assert!(result.contains("pub fn helper()"));
     assert!(result.contains("```rust"));
 }
+
+#[test]
+fn test_file_metadata() {
+    let metadata = FileMetadata::compute("hello\nworld\n", None);
+
+    assert_eq!(metadata.size, 12);
+    assert_eq!(metadata.lines, 2);
+    assert_eq!(metadata.sha256.len(), 64);
+    assert_eq!(metadata.sha256_prefix(), &metadata.sha256[..12]);
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert!(metadata.summary().contains("modified unknown"));
+}
+
+#[tokio::test]
+async fn test_concatenate_files_with_metadata() {
+    let vfs = MemoryFs::with_files([("main.rs", "fn main() {}\n")]);
+    let options = RenderOptions {
+        file_meta: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(
+        &vfs,
+        &CollectedFile::load_all(&vfs, &[PathBuf::from("main.rs")])
+            .await
+            .unwrap(),
+        &options,
+    )
+    .await
+    .unwrap();
+
+    assert!(result.contains("## main.rs\n\n*13 bytes · 1 lines · modified unknown · sha256 "));
+}
+
+#[tokio::test]
+async fn test_concatenate_with_fence_aliases() {
+    let vfs = MemoryFs::with_files([
+        ("app.jsx", "export default App;\n"),
+        ("main.rs", "fn main() {}\n"),
+    ]);
+    let files =
+        CollectedFile::load_all(&vfs, &[PathBuf::from("app.jsx"), PathBuf::from("main.rs")])
+            .await
+            .unwrap();
+    let language = get_language_from_extension(Path::new("app.jsx"));
+    let options = RenderOptions {
+        fence_aliases: [(language.to_string(), "tsx".to_string())].into(),
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.contains("```tsx\nexport default App;"));
+    assert!(result.contains("```rust\nfn main() {}"));
+}
+
+#[tokio::test]
+async fn test_concatenate_with_editor_anchors() {
+    let long: String = (1..=150).map(|i| format!("// line {}\n", i)).collect();
+    let vfs = MemoryFs::with_files([("src/lib.rs", long.as_str())]);
+    let files = CollectedFile::load_all(&vfs, &[PathBuf::from("src/lib.rs")])
+        .await
+        .unwrap();
+    let options = RenderOptions {
+        anchors: Anchors::Editor,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.contains("## src/lib.rs:1\n\n```rust\n// line 1\n"));
+    assert!(result.contains("// line 100\n```\n\n`src/lib.rs:101`\n\n```rust\n// line 101\n"));
+
+    // Stripped content no longer lines up with the file, so it isn't chunked
+    let options = RenderOptions {
+        ignore_comments: true,
+        ..options
+    };
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.contains("## src/lib.rs:1\n"));
+    assert!(!result.contains("`src/lib.rs:101`"));
+}
+
+#[tokio::test]
+async fn test_complexity() {
+    let source = "struct Point(i32, i32);\n\nimpl Point {\n    fn norm(&self) -> i32 {\n        if self.0 > 0 {\n            self.0\n        } else {\n            0\n        }\n    }\n}\n\nfn main() {}\n";
+    assert_eq!(
+        Complexity::measure(source, "rust"),
+        Complexity {
+            loc: 11,
+            functions: 2,
+            max_nesting: 3,
+        }
+    );
+    assert_eq!(
+        Complexity::measure("# Title\n\ntext\n", "markdown").functions,
+        0
+    );
+
+    let vfs = MemoryFs::with_files([("src/main.rs", source)]);
+    let files = CollectedFile::load_all(&vfs, &[PathBuf::from("src/main.rs")])
+        .await
+        .unwrap();
+    let options = RenderOptions {
+        complexity: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.contains("## src/main.rs\n\n*11 LOC, 2 functions, nesting 3*\n\n```rust\n"));
+}
+
+#[tokio::test]
+async fn test_concatenate_as_xml() {
+    let vfs = MemoryFs::with_files([
+        ("src/lib.rs", b"fn lib() {}\n".to_vec()),
+        ("docs/a&b.md", b"# Notes\n".to_vec()),
+        ("src/we<i>rd.rs", b"// caf\xe9".to_vec()),
+    ]);
+    let files = CollectedFile::load_all(
+        &vfs,
+        &[
+            PathBuf::from("src/lib.rs"),
+            PathBuf::from("docs/a&b.md"),
+            PathBuf::from("src/we<i>rd.rs"),
+        ],
+    )
+    .await
+    .unwrap();
+    let options = RenderOptions {
+        format: OutputFormat::Xml,
+        caveats: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.starts_with("<project_structure>\n"));
+    assert!(result.contains(
+        "<documents>\n<document index=\"1\" path=\"src/lib.rs\" language=\"rust\">\n\
+         <document_content>\nfn lib() {}\n\n</document_content>\n</document>\n"
+    ));
+    assert!(result.contains("<document index=\"2\" path=\"docs/a&amp;b.md\""));
+    assert!(result.contains("</document>\n</documents>\n"));
+    assert!(!result.contains("```"));
+
+    // Names in the tree are escaped, and what follows the documents is XML too
+    let tree = &result[..result.find("</project_structure>").unwrap()];
+    assert!(tree.contains("a&amp;b.md") && tree.contains("we&lt;i&gt;rd.rs"));
+    assert!(!tree.contains("<i>"));
+    let tail = &result[result.find("</documents>").unwrap()..];
+    assert!(tail.contains(
+        "<unavailable_files>\n<file path=\"src/we%3Ci%3Erd.rs\" reason=\"invalid UTF-8\"/>\n"
+    ));
+    assert!(tail.contains("<caveats>\n<caveat path=\"src/we%3Ci%3Erd.rs\" kind=\"unreadable\">"));
+    assert!(!tail.contains("\n# "));
+}
+
+#[tokio::test]
+async fn test_concatenate_as_json() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn lib() {}\n"), ("README.md", "# Hi\n")]);
+    let files = CollectedFile::load_all(
+        &vfs,
+        &[PathBuf::from("src/lib.rs"), PathBuf::from("README.md")],
+    )
+    .await
+    .unwrap();
+    let options = RenderOptions {
+        format: OutputFormat::Json,
+        complexity: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+    assert_eq!(json["structure"]["type"], "directory");
+    assert_eq!(json["structure"]["children"].as_array().unwrap().len(), 2);
+    assert_eq!(json["files"][0]["path"], "src/lib.rs");
+    assert_eq!(json["files"][0]["language"], "rust");
+    assert_eq!(json["files"][0]["representation"], "full");
+    assert_eq!(json["files"][0]["content"], "fn lib() {}\n");
+    assert_eq!(json["files"][0]["complexity"]["functions"], 1);
+    assert_eq!(json["files"][1]["language"], "markdown");
+    assert!(json.get("unavailable").is_none());
+}
+
+#[tokio::test]
+async fn test_concatenate_part() {
+    assert_eq!(
+        part_path(Path::new("out/snapshot.md"), 2),
+        PathBuf::from("out/snapshot.part2.md")
+    );
+    assert_eq!(
+        part_path(Path::new("snapshot"), 1),
+        PathBuf::from("snapshot.part1")
+    );
+
+    let vfs = MemoryFs::with_files([("a.rs", "fn a() {}\n"), ("b.rs", "fn b() {}\n")]);
+    let files = CollectedFile::load_all(&vfs, &[PathBuf::from("a.rs"), PathBuf::from("b.rs")])
+        .await
+        .unwrap();
+    let options = RenderOptions {
+        part: Some(Part {
+            index: 1,
+            total: 2,
+            files: [PathBuf::from("b.rs")].into_iter().collect(),
+        }),
+        caveats: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    options
+        .warnings
+        .push(WarningKind::Reduced, Path::new("a.rs"), "outlined");
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(result.contains("├── a.rs\n"));
+    assert!(result.contains("*Part 1 of 2: "));
+    assert!(result.contains("## b.rs\n"));
+    assert!(!result.contains("## a.rs\n"));
+    // Caveats close the last part only
+    assert!(!result.contains("# Caveats"));
+}
+
+#[tokio::test]
+async fn test_concatenate_with_processing_rules() {
+    let vfs = MemoryFs::with_files([
+        ("src/lib.rs", "/* why this matters */\nfn lib() {}\n"),
+        ("vendor/dep.rs", "/* vendored */\nfn dep() {}\n"),
+    ]);
+    let files = CollectedFile::load_all(
+        &vfs,
+        &[PathBuf::from("src/lib.rs"), PathBuf::from("vendor/dep.rs")],
+    )
+    .await
+    .unwrap();
+    let options = RenderOptions {
+        ignore_comments: true,
+        processing: vec![
+            ProcessingRule {
+                pattern: "src/**".to_string(),
+                ignore_comments: Some(false),
+                ..ProcessingRule::default()
+            },
+            ProcessingRule {
+                pattern: "*.rs".to_string(),
+                ignore_docstrings: Some(true),
+                ..ProcessingRule::default()
+            },
+        ],
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+
+    // Rules override the command-line flags only for the files they match
+    assert!(result.contains("/* why this matters */"));
+    assert!(!result.contains("/* vendored */"));
+    assert!(result.contains("fn dep() {}"));
+}
+
+#[tokio::test]
+async fn test_snapshot_changes() {
+    let vfs = MemoryFs::with_files([
+        ("src/kept.rs", "fn kept() {}\n"),
+        ("src/changed.rs", "fn a() {}\nfn b() {}\n"),
+        ("src/removed.rs", "fn gone() {}\n"),
+    ]);
+    let files = vec![
+        PathBuf::from("src/changed.rs"),
+        PathBuf::from("src/kept.rs"),
+        PathBuf::from("src/removed.rs"),
+    ];
+    let previous = Snapshot::capture(&vfs, &files).await;
+
+    let snapshot_file = PathBuf::from("history/profile.json");
+    previous.save(&vfs, &snapshot_file).await.unwrap();
+    let previous = Snapshot::load(&vfs, &snapshot_file).await.unwrap().unwrap();
+
+    vfs.write(
+        Path::new("src/changed.rs"),
+        b"fn a() {}\nfn c() {}\nfn d() {}\n",
+    )
+    .await
+    .unwrap();
+    vfs.write(Path::new("src/added.rs"), b"fn new() {}\n")
+        .await
+        .unwrap();
+    let files = vec![
+        PathBuf::from("src/added.rs"),
+        PathBuf::from("src/changed.rs"),
+        PathBuf::from("src/kept.rs"),
+    ];
+    let current = Snapshot::capture(&vfs, &files).await;
+
+    assert_eq!(
+        current.changes_since(&previous),
+        vec![
+            FileChange::Added {
+                path: PathBuf::from("src/added.rs"),
+                lines: 1
+            },
+            FileChange::Modified {
+                path: PathBuf::from("src/changed.rs"),
+                added: 2,
+                removed: 1
+            },
+            FileChange::Removed {
+                path: PathBuf::from("src/removed.rs"),
+                lines: 1
+            },
+        ]
+    );
+
+    assert!(
+        Snapshot::load(&vfs, Path::new("history/missing.json"))
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_profile_key() {
+    let options = CollectOptions::default();
+    let key = profile_key(&[PathBuf::from("src")], &options);
+
+    assert_eq!(key.len(), 16);
+    assert_eq!(key, profile_key(&[PathBuf::from("src")], &options));
+    assert_ne!(key, profile_key(&[PathBuf::from("tests")], &options));
+}
+
+#[tokio::test]
+async fn test_concatenate_delta() {
+    let vfs = MemoryFs::with_files([
+        ("src/kept.rs", "fn kept() {}"),
+        ("src/changed.rs", "fn before() {}"),
+        ("src/removed.rs", "fn gone() {}"),
+    ]);
+    let previous_files = vec![
+        PathBuf::from("src/changed.rs"),
+        PathBuf::from("src/kept.rs"),
+        PathBuf::from("src/removed.rs"),
+    ];
+    let previous = Snapshot::capture(&vfs, &previous_files).await;
+
+    vfs.write(Path::new("src/changed.rs"), b"fn after() {}")
+        .await
+        .unwrap();
+    let files = vec![
+        PathBuf::from("src/changed.rs"),
+        PathBuf::from("src/kept.rs"),
+    ];
+    let current = Snapshot::capture(&vfs, &files).await;
+
+    let options = RenderOptions {
+        delta: Some(current.statuses_since(&previous)),
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(
+        &vfs,
+        &CollectedFile::load_all(&vfs, &files).await.unwrap(),
+        &options,
+    )
+    .await
+    .unwrap();
+
+    assert!(result.contains("changed.rs [modified]"));
+    assert!(result.contains("kept.rs [unchanged]"));
+    assert!(result.contains("removed.rs [removed]"));
+    assert!(result.contains("fn after() {}"));
+    assert!(!result.contains("fn kept() {}"));
+}
+
+#[test]
+fn test_readme_excerpt() {
+    let readme = "# Parser\n\n[![CI](badge.svg)](ci)\n\nTokenizes and parses\nthe query language.\n\n## Usage\n";
+    assert_eq!(
+        readme_excerpt(readme).as_deref(),
+        Some("Tokenizes and parses the query language.")
+    );
+    assert_eq!(readme_excerpt("# Title only\n"), None);
+}
+
+#[tokio::test]
+async fn test_summarize_directories() {
+    let vfs = MemoryFs::with_files([
+        ("parser/README.md", "# Parser\n\nParses queries.\n"),
+        ("parser/lexer.rs", "fn lex() {}"),
+        ("tests/parse.rs", "fn test() {}"),
+        ("misc/a.rs", "fn a() {}"),
+    ]);
+    let files = vec![
+        PathBuf::from("misc/a.rs"),
+        PathBuf::from("parser/lexer.rs"),
+        PathBuf::from("tests/parse.rs"),
+    ];
+
+    let summaries = summarize_directories(&vfs, &files).await;
+    let text: Vec<_> = summaries.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(
+        text,
+        vec!["Files: a.rs", "Parses queries.", "Tests. Files: parse.rs"]
+    );
+    assert_eq!(directory_purpose("Docs"), Some("Documentation"));
+}
+
+#[tokio::test]
+async fn test_concatenate_grouped_layout() {
+    let vfs = MemoryFs::with_files([
+        ("src/main.rs", "fn main() {}"),
+        ("README.md", "# Readme"),
+        ("src/lib/util.rs", "fn util() {}"),
+        ("docs/guide.md", "Guide"),
+    ]);
+    let files = vec![
+        PathBuf::from("src/main.rs"),
+        PathBuf::from("README.md"),
+        PathBuf::from("src/lib/util.rs"),
+        PathBuf::from("docs/guide.md"),
+    ];
+
+    let options = RenderOptions {
+        layout: Layout::Grouped,
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(
+        &vfs,
+        &CollectedFile::load_all(&vfs, &files).await.unwrap(),
+        &options,
+    )
+    .await
+    .unwrap();
+
+    let headings: Vec<_> = result
+        .lines()
+        .filter(|line| line.starts_with("## ") || line.starts_with("### "))
+        .collect();
+    assert_eq!(
+        headings,
+        vec![
+            "## ./",
+            "### README.md",
+            "## docs/",
+            "### docs/guide.md",
+            "## src/",
+            "### src/main.rs",
+            "### src/lib/util.rs",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_concatenate_grouped_by_language() {
+    let vfs = MemoryFs::with_files([
+        ("README.md", "# Readme"),
+        ("Cargo.toml", "[package]"),
+        ("src/main.rs", "fn main() {}"),
+        ("schema.sql", "CREATE TABLE t (id INT);"),
+        ("src/lib.rs", "pub fn lib() {}"),
+    ]);
+    let files = vec![
+        PathBuf::from("README.md"),
+        PathBuf::from("Cargo.toml"),
+        PathBuf::from("src/main.rs"),
+        PathBuf::from("schema.sql"),
+        PathBuf::from("src/lib.rs"),
+    ];
+
+    let options = RenderOptions {
+        layout: Layout::Grouped,
+        group_by: GroupBy::Language,
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(
+        &vfs,
+        &CollectedFile::load_all(&vfs, &files).await.unwrap(),
+        &options,
+    )
+    .await
+    .unwrap();
+
+    let headings: Vec<_> = result
+        .lines()
+        .filter(|line| line.starts_with("## ") || line.starts_with("### "))
+        .collect();
+    assert_eq!(
+        headings,
+        vec![
+            "## rust",
+            "### src/main.rs",
+            "### src/lib.rs",
+            "## sql",
+            "### schema.sql",
+            "## configuration",
+            "### Cargo.toml",
+            "## documentation",
+            "### README.md",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_write_file_safe_is_atomic() {
+    let temp_dir = TempDir::new().unwrap();
+    let output = temp_dir.path().join("out.md");
+    fs::write(&output, "old").await.unwrap();
+
+    write_file_safe(&output, b"new", false).await.unwrap();
+
+    assert_eq!(fs::read_to_string(&output).await.unwrap(), "new");
+    let entries = std::fs::read_dir(temp_dir.path()).unwrap().count();
+    assert_eq!(entries, 1, "temporary file should be renamed away");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_write_file_safe_symlinks() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("elsewhere.md");
+    let link = temp_dir.path().join("out.md");
+    fs::write(&target, "precious").await.unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let err = write_file_safe(&link, b"snapshot", false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("symlink"));
+    assert_eq!(fs::read_to_string(&target).await.unwrap(), "precious");
+
+    write_file_safe(&link, b"snapshot", true).await.unwrap();
+    assert_eq!(fs::read_to_string(&target).await.unwrap(), "snapshot");
+    assert!(
+        fs::symlink_metadata(&link)
+            .await
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[tokio::test]
+async fn test_pinned_files_bypass_filters() {
+    let vfs = MemoryFs::with_files([
+        ("README.md", "# Project"),
+        ("src/main.rs", "fn main() {}"),
+        ("src/schema.sql", "CREATE TABLE t ();"),
+        ("build/generated.rs", "pub const X: u8 = 1;"),
+    ]);
+    let options = CollectOptions {
+        includes: vec!["*.rs".to_string()],
+        excludes: vec!["*.sql".to_string()],
+        pinned: vec![
+            PathBuf::from("./README.md"),
+            PathBuf::from("src/schema.sql"),
+            PathBuf::from("src/main.rs"),
+            PathBuf::from("build/generated.rs"),
+        ],
+        quiet: true,
+        ..CollectOptions::default()
+    };
+
+    let files = collect_files_with(&vfs, &[PathBuf::from("src")], &options)
+        .await
+        .unwrap();
+    let paths = CollectedFile::paths(&files);
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("src/main.rs"),
+            PathBuf::from("README.md"),
+            PathBuf::from("src/schema.sql"),
+            PathBuf::from("build/generated.rs"),
+        ]
+    );
+    assert!(options.is_pinned(Path::new("README.md")));
+    assert!(!options.is_pinned(Path::new("src/lib.rs")));
+}
+
+#[tokio::test]
+async fn test_collect_files_stream_includes_pinned_files() {
+    let vfs = MemoryFs::with_files([
+        ("README.md", "# Project"),
+        ("src/main.rs", "fn main() {}"),
+        ("src/schema.sql", "CREATE TABLE t ();"),
+    ]);
+    let options = CollectOptions {
+        includes: vec!["*.rs".to_string()],
+        pinned: vec![PathBuf::from("./README.md"), PathBuf::from("src/main.rs")],
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let paths = [PathBuf::from("src")];
+
+    let streamed: Vec<CollectedFile> = collect_files_stream(&vfs, &paths, &options)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(
+        CollectedFile::paths(&streamed),
+        vec![PathBuf::from("src/main.rs"), PathBuf::from("README.md")]
+    );
+    let collected = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(
+        CollectedFile::paths(&streamed),
+        CollectedFile::paths(&collected)
+    );
+}
+
+#[tokio::test]
+async fn test_workspace_roots_use_their_own_patterns() {
+    let vfs = MemoryFs::with_files([
+        ("backend/app.py", "print('hi')"),
+        ("backend/migrations/0001.py", "pass"),
+        ("backend/schema.sql", "CREATE TABLE t ();"),
+        ("frontend/app.ts", "export {}"),
+        ("frontend/app.py", "print('tooling')"),
+        ("infra/main.tf", "resource {}"),
+    ]);
+    let options = CollectOptions {
+        roots: vec![
+            WorkspaceRoot {
+                path: PathBuf::from("backend"),
+                exclude: vec!["migrations/*".to_string()],
+                priority: 10,
+                ..WorkspaceRoot::default()
+            },
+            WorkspaceRoot {
+                path: PathBuf::from("frontend"),
+                include: vec!["*.ts".to_string()],
+                priority: -10,
+                ..WorkspaceRoot::default()
+            },
+        ],
+        quiet: true,
+        ..CollectOptions::default()
+    };
+
+    let paths = [
+        PathBuf::from("backend"),
+        PathBuf::from("frontend"),
+        PathBuf::from("infra"),
+    ];
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("backend/app.py"),
+            PathBuf::from("backend/schema.sql"),
+            PathBuf::from("frontend/app.ts"),
+            PathBuf::from("infra/main.tf"),
+        ]
+    );
+
+    let priority = |path: &str| options.priority(Path::new(path));
+    assert!(priority("backend/app.py") > priority("infra/app.py"));
+    assert!(priority("frontend/app.ts") < priority("infra/app.ts"));
+}
+
+#[test]
+fn test_file_tree_lines_show_token_estimates() {
+    let small = vec![b'a'; 400];
+    let big = vec![b'a'; 4 * (HOT_FILE_TOKENS + 1)];
+    let files = vec![
+        CollectedFile::new(Path::new("src/main.rs"), Path::new("main.rs"), &small, None),
+        CollectedFile::new(
+            Path::new("src/gen/api.rs"),
+            Path::new("gen/api.rs"),
+            &big,
+            None,
+        ),
+    ];
+
+    let lines = file_tree_lines(&files);
+    assert_eq!(
+        lines,
+        vec![
+            "└── 📁 src/ (~5.1k tokens)",
+            "    ├── 📁 gen/ (~5.0k tokens)",
+            "    │   └── 📄 api.rs (~5.0k tokens) 🔥",
+            "    └── 📄 main.rs (~100 tokens)",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_io_errors_are_reported() {
+    let vfs = MemoryFs::with_files([
+        ("src/main.rs", b"fn main() {}".to_vec()),
+        ("src/latin1.rs", b"// caf\xe9".to_vec()),
+    ]);
+    let paths = vec![PathBuf::from("src"), PathBuf::from("missing.rs")];
+
+    let collect_options = CollectOptions::default();
+    let files = collect_files_with(&vfs, &paths, &collect_options)
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 2);
+
+    let render_options = RenderOptions {
+        io_errors: collect_options.io_errors.clone(),
+        ..RenderOptions::default()
+    };
+    concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+
+    let skipped: Vec<_> = collect_options
+        .io_errors
+        .skipped()
+        .into_iter()
+        .map(|s| s.path)
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![PathBuf::from("missing.rs"), PathBuf::from("src/latin1.rs")]
+    );
+    assert!(collect_options.io_errors.report().contains("invalid UTF-8"));
+}
+
+#[tokio::test]
+async fn test_caveats_section_lists_warnings() {
+    let vfs = MemoryFs::with_files([
+        ("src/main.rs", b"fn main() {}".to_vec()),
+        ("src/latin1.rs", b"// caf\xe9".to_vec()),
+    ]);
+    let files = CollectedFile::load_all(
+        &vfs,
+        &[PathBuf::from("src/main.rs"), PathBuf::from("src/latin1.rs")],
+    )
+    .await
+    .unwrap();
+
+    let warnings = Warnings::default();
+    warnings.push(
+        WarningKind::Redacted,
+        Path::new("deploy/server.pem"),
+        "matches *.pem",
+    );
+    warnings.push(
+        WarningKind::Redacted,
+        Path::new("deploy/<host>.key"),
+        "matches *.key",
+    );
+    let options = RenderOptions {
+        warnings: warnings.clone(),
+        caveats: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    let caveats = &result[result.find("# Caveats").unwrap()..];
+    let unreadable = caveats.find("- `src/latin1.rs` (unreadable)").unwrap();
+    let redacted = caveats
+        .find("- `deploy/server.pem` (redacted): matches *.pem")
+        .unwrap();
+    assert!(unreadable < redacted);
+    // Paths are encoded as in the headings
+    assert!(caveats.contains("- `deploy/%3Chost%3E.key` (redacted): matches *.key"));
+    assert_eq!(warnings.all().len(), 3);
+
+    // Without warnings there is nothing to caveat
+    let options = RenderOptions {
+        caveats: true,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let result = concatenate_files_with(&vfs, &files[..1], &options)
+        .await
+        .unwrap();
+    assert!(!result.contains("# Caveats"));
+}
+
+#[tokio::test]
+async fn test_unreadable_files_are_gathered_at_the_end() {
+    let vfs = MemoryFs::with_files([
+        ("src/a.rs", b"// caf\xe9".to_vec()),
+        ("src/b.rs", b"// caf\xef".to_vec()),
+        ("src/main.rs", b"fn main() {}".to_vec()),
+    ]);
+    let paths = [
+        PathBuf::from("src/a.rs"),
+        PathBuf::from("src/b.rs"),
+        PathBuf::from("src/main.rs"),
+    ];
+    let files = CollectedFile::load_all(&vfs, &paths).await.unwrap();
+    let options = RenderOptions {
+        quiet: true,
+        ..RenderOptions::default()
+    };
+
+    let result = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(!result.contains("## src/a.rs"));
+    assert!(!result.contains("Error reading file"));
+    let section = &result[result.find("# Unavailable Files").unwrap()..];
+    assert!(section.contains("- `src/a.rs`, `src/b.rs`: invalid UTF-8\n"));
+    assert_eq!(section.matches("\n- ").count(), 1);
+    assert!(result.find("## src/main.rs").unwrap() < result.find("# Unavailable Files").unwrap());
+}
+
+#[tokio::test]
+async fn test_strict_io_fails_on_unreadable_files() {
+    let vfs = MemoryFs::with_files([("src/main.rs", "fn main() {}")]);
+    let options = CollectOptions {
+        io_errors: IoErrors::new(true),
+        ..CollectOptions::default()
+    };
+
+    let err = collect_files_with(&vfs, &[PathBuf::from("missing.rs")], &options)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("--strict-io"));
+}
+
+#[tokio::test]
+async fn test_snapshot_token_counts_largest_first() {
+    let vfs = MemoryFs::with_files([
+        ("small.rs", "fn a() {}"),
+        (
+            "large.rs",
+            "fn main() {\n    println!(\"hello world\");\n}\n",
+        ),
+    ]);
+    let snapshot = Snapshot::capture(
+        &vfs,
+        &[PathBuf::from("small.rs"), PathBuf::from("large.rs")],
+    )
+    .await;
+
+    let counts = snapshot.token_counts();
+    assert_eq!(counts[0].0, PathBuf::from("large.rs"));
+    assert_eq!(counts[1].0, PathBuf::from("small.rs"));
+    assert!(counts[0].1 > counts[1].1);
+}
@@ -0,0 +1,113 @@
+use catnip::core::file_collector::{CollectOptions, CollectedFile, collect_files_with};
+use catnip::core::filter_expr::{FileFacts, FilterExpr};
+use catnip::io::vfs::MemoryFs;
+use std::path::{Path, PathBuf};
+
+fn facts(path: &str, content: &str) -> FileFacts {
+    let file = CollectedFile::new(Path::new(path), Path::new(path), content.as_bytes(), None);
+    FileFacts::new(&file, content.as_bytes())
+}
+
+fn matches(expression: &str, facts: &FileFacts) -> bool {
+    FilterExpr::parse(expression).unwrap().matches(facts)
+}
+
+#[test]
+fn test_file_facts() {
+    let facts = facts("src/gen/parser.rs", "fn a() {}\nfn b() {}\n");
+    assert_eq!(facts.path, "src/gen/parser.rs");
+    assert_eq!(facts.name, "parser.rs");
+    assert_eq!(facts.ext, "rs");
+    assert_eq!(facts.lang, "rust");
+    assert_eq!(facts.size, 20);
+    assert_eq!(facts.lines, 2);
+}
+
+#[test]
+fn test_filter_expressions() {
+    let parser = facts("src/generated/parser.rs", &"x\n".repeat(3000));
+    let main = facts("src/main.rs", "fn main() {}\n");
+    let script = facts("scripts/build.py", "print('hi')\n");
+
+    let expression = r#"lang == "rust" && lines < 2000 && !path.contains("generated")"#;
+    assert!(!matches(expression, &parser));
+    assert!(matches(expression, &main));
+    assert!(!matches(expression, &script));
+
+    assert!(matches("ext == 'py' || size > 1000", &parser));
+    assert!(matches("ext == 'py' || size > 1000", &script));
+    assert!(!matches("ext == 'py' || size > 1000", &main));
+
+    // `&&` binds tighter than `||`, and parentheses override it
+    assert!(matches(
+        "lang == 'python' || lang == 'rust' && lines > 1",
+        &script
+    ));
+    assert!(!matches(
+        "(lang == 'python' || lang == 'rust') && lines > 1",
+        &script
+    ));
+
+    assert!(matches(r#"path.matches("src/**/*.rs")"#, &main));
+    assert!(!matches(r#"path.matches("src/**/*.rs")"#, &script));
+    assert!(matches(
+        r#"name.starts_with("build") && name.ends_with(".py")"#,
+        &script
+    ));
+    assert!(matches("lines >= 3_000 && lines <= 3000 && true", &parser));
+    assert!(matches(r#"language != "go""#, &main));
+}
+
+#[test]
+fn test_filter_errors() {
+    let error = |expression: &str| FilterExpr::parse(expression).unwrap_err().to_string();
+
+    assert_eq!(
+        error("lang = 'rust'"),
+        "Use '==' to compare in filters, not '='"
+    );
+    assert_eq!(
+        error("owner == 'me'"),
+        "Unknown field 'owner' in filter; expected path, name, ext, lang, size or lines"
+    );
+    assert_eq!(
+        error("lines < '2000'"),
+        "Comparing expects a number, not a string"
+    );
+    assert_eq!(
+        error("lang < 'rust'"),
+        "Only numbers can be ordered in filters"
+    );
+    assert_eq!(error("!lines"), "'!' expects a boolean, not a number");
+    assert_eq!(
+        error("path"),
+        "Filter must be a condition, such as lines < 2000"
+    );
+    assert_eq!(error("(lines > 1"), "Expected ')' in filter, found the end");
+    assert_eq!(error("lines > 1 lines"), "Unexpected 'lines' in filter");
+    assert_eq!(error("path.contains(\"x"), "Unterminated string in filter");
+}
+
+#[tokio::test]
+async fn test_collect_files_with_filter() {
+    let vfs = MemoryFs::with_files([
+        ("project/src/main.rs", "fn main() {}\n"),
+        ("project/src/generated/parser.rs", "fn parse() {}\n"),
+        ("project/scripts/build.py", "print('hi')\n"),
+    ]);
+    let options = CollectOptions {
+        filter: Some(
+            FilterExpr::parse(r#"lang == "rust" && !path.contains("generated")"#).unwrap(),
+        ),
+        ..CollectOptions::default()
+    };
+
+    let files = collect_files_with(&vfs, &[PathBuf::from("project")], &options)
+        .await
+        .unwrap();
+    let paths: Vec<_> = files
+        .iter()
+        .map(|file| file.relative_path.clone())
+        .collect();
+    assert_eq!(paths, vec![PathBuf::from("src/main.rs")]);
+}
@@ -0,0 +1,102 @@
+use catnip::core::file_collector::{CollectOptions, CollectedFile};
+use catnip::core::framework::{Framework, detect_framework};
+use catnip::io::vfs::MemoryFs;
+use std::path::{Path, PathBuf};
+
+#[tokio::test]
+async fn test_detect_framework() {
+    let cases = [
+        (
+            vec![(
+                "web/package.json",
+                r#"{"dependencies": {"next": "15.0.0"}}"#,
+            )],
+            Some(Framework::Nextjs),
+        ),
+        (
+            vec![("web/next.config.mjs", "export default {}")],
+            Some(Framework::Nextjs),
+        ),
+        (
+            vec![
+                (
+                    "web/config/routes.rb",
+                    "Rails.application.routes.draw do\nend\n",
+                ),
+                ("web/Gemfile", "gem \"rails\", \"~> 7.1\"\n"),
+            ],
+            Some(Framework::Rails),
+        ),
+        (
+            vec![(
+                "web/manage.py",
+                "os.environ.setdefault(\"DJANGO_SETTINGS_MODULE\", \"site.settings\")\n",
+            )],
+            Some(Framework::Django),
+        ),
+        (
+            vec![
+                ("web/vite.config.ts", "export default {}"),
+                ("web/package.json", r#"{"devDependencies": {"vite": "5"}}"#),
+            ],
+            Some(Framework::Vite),
+        ),
+        (vec![("web/package.json", r#"{"dependencies": {}}"#)], None),
+    ];
+
+    for (files, expected) in cases {
+        let vfs = MemoryFs::with_files(files);
+        assert_eq!(detect_framework(&vfs, Path::new("web")).await, expected);
+    }
+}
+
+#[test]
+fn test_framework_profile_order_and_patterns() {
+    let mut files: Vec<CollectedFile> = [
+        "README.md",
+        "components/Button.tsx",
+        "app/blog/page.tsx",
+        "lib/db.ts",
+        "app/layout.tsx",
+        "next.config.mjs",
+        "app/api/posts/route.ts",
+        "package.json",
+    ]
+    .iter()
+    .map(|path| CollectedFile::new(Path::new(path), Path::new(path), b"", None))
+    .collect();
+
+    let profile = Framework::Nextjs.profile();
+    profile.order_files(&mut files);
+    assert_eq!(
+        CollectedFile::paths(&files),
+        [
+            "package.json",
+            "next.config.mjs",
+            "app/layout.tsx",
+            "app/blog/page.tsx",
+            "app/api/posts/route.ts",
+            "components/Button.tsx",
+            "lib/db.ts",
+            "README.md",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>()
+    );
+
+    // Profile includes come on top of the defaults, unless --include replaced them
+    let mut options = CollectOptions::default();
+    Framework::Rails.profile().apply(&mut options);
+    assert!(options.excludes.contains(&"tmp/".to_string()));
+    assert!(options.includes.contains(&"*.erb".to_string()));
+    assert!(options.includes.contains(&"*.rb".to_string()));
+
+    let mut options = CollectOptions {
+        includes: vec!["*.py".to_string()],
+        ..CollectOptions::default()
+    };
+    Framework::Django.profile().apply(&mut options);
+    assert_eq!(options.includes, vec!["*.py".to_string()]);
+    assert!(options.excludes.contains(&"**/migrations/**".to_string()));
+}
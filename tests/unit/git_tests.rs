@@ -0,0 +1,281 @@
+use catnip::cli::commands::patch::{PatchOptions, execute};
+use catnip::core::content_processor::{RenderOptions, concatenate_files_with};
+use catnip::core::file_collector::{CollectOptions, CollectedFile, collect_files_with};
+use catnip::integrations::git::{
+    Eol, GitAttributes, GitRevisionFs, NotMaterialized, PathAttributes, SubmoduleMode,
+    onto_branch_name, parse_cat_file_batch, parse_gitmodules, parse_ls_tree, parse_skip_worktree,
+};
+use catnip::io::clipboard::MemoryClipboard;
+use catnip::io::vfs::{MemoryFs, RootedFs, Vfs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[test]
+fn test_parse_gitattributes() {
+    let attributes = GitAttributes::parse(
+        "# comment\n* text=auto\n*.png binary\n*.bat eol=crlf\nscripts/*.bat -text\n",
+    );
+
+    assert_eq!(
+        attributes.get(Path::new("logo.png")),
+        PathAttributes {
+            binary: Some(true),
+            eol: None
+        }
+    );
+    assert_eq!(
+        attributes.get(Path::new("run.bat")),
+        PathAttributes {
+            binary: Some(false),
+            eol: Some(Eol::Crlf)
+        }
+    );
+    // Later lines win attribute by attribute
+    assert_eq!(
+        attributes.get(Path::new("scripts/build.bat")),
+        PathAttributes {
+            binary: Some(true),
+            eol: Some(Eol::Crlf)
+        }
+    );
+    assert_eq!(
+        attributes.get(Path::new("src/main.rs")),
+        PathAttributes::default()
+    );
+    assert_eq!(Eol::Crlf.apply("a\r\nb\n"), "a\r\nb\r\n");
+}
+
+#[tokio::test]
+async fn test_gitattributes_decide_binary_files() {
+    let vfs = MemoryFs::with_files([
+        ("repo/.gitattributes", "vendored.rs binary\n"),
+        ("repo/main.rs", "fn main() {}"),
+        ("repo/vendored.rs", "fn vendored() {}"),
+    ]);
+
+    let files = collect_files_with(&vfs, &[PathBuf::from("repo")], &CollectOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("repo/.gitattributes"),
+            PathBuf::from("repo/main.rs")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_patch_keeps_declared_line_endings() {
+    let vfs = MemoryFs::with_files([
+        (".gitattributes", "*.rs eol=crlf\n"),
+        ("lib.rs", "fn a() {\r\n    1\r\n}\r\n"),
+    ]);
+
+    let json = serde_json::json!({
+        "analysis": "Return two",
+        "files": [{
+            "path": "lib.rs",
+            "updates": [{ "old_content": "fn a() {\n    1\n}", "new_content": "fn a() {\n    2\n}" }]
+        }]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let lib = vfs.read_to_string(Path::new("lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn a() {\r\n    2\r\n}\r\n");
+}
+
+#[tokio::test]
+async fn test_submodule_modes() {
+    assert_eq!(
+        parse_gitmodules("[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = ../lib.git\n"),
+        vec![PathBuf::from("vendor/lib")]
+    );
+
+    let vfs = MemoryFs::with_files([
+        (
+            "repo/.gitmodules",
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n",
+        ),
+        ("repo/main.rs", "fn main() {}"),
+        (
+            "repo/vendor/lib/lib.rs",
+            "pub fn helper() {\n    let hidden = 1;\n}\n",
+        ),
+    ]);
+    let paths = [PathBuf::from("repo")];
+
+    let options = CollectOptions {
+        submodule_mode: SubmoduleMode::Skip,
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("repo/main.rs")]
+    );
+    let render_options = RenderOptions {
+        submodules: options.submodules.clone(),
+        submodule_mode: options.submodule_mode,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    assert!(output.contains("Git submodules left out of this snapshot: `repo/vendor/lib`"));
+
+    let options = CollectOptions {
+        submodule_mode: SubmoduleMode::Outline,
+        quiet: true,
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(files.len(), 2);
+    let render_options = RenderOptions {
+        submodules: options.submodules.clone(),
+        submodule_mode: options.submodule_mode,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    assert!(output.contains("pub fn helper()"));
+    assert!(!output.contains("let hidden"));
+}
+
+#[tokio::test]
+async fn test_not_materialized_files_are_listed_in_the_structure() {
+    assert_eq!(
+        parse_skip_worktree("H src/main.rs\0S docs/guide.md\0S web/app.ts\0"),
+        vec![PathBuf::from("docs/guide.md"), PathBuf::from("web/app.ts")]
+    );
+
+    let vfs = MemoryFs::with_files([("repo/main.rs", "fn main() {}")]);
+    let files = CollectedFile::load_all(&vfs, &[PathBuf::from("repo/main.rs")])
+        .await
+        .unwrap();
+    let not_materialized = NotMaterialized::default();
+    not_materialized.record(PathBuf::from("repo/web/app.ts"));
+
+    let options = RenderOptions {
+        not_materialized,
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let output = concatenate_files_with(&vfs, &files, &options)
+        .await
+        .unwrap();
+    assert!(output.contains("app.ts [not materialized]"));
+    assert!(!output.contains("## repo/web/app.ts"));
+}
+
+#[tokio::test]
+async fn test_git_revision_fs_serves_the_tree() {
+    let listing = [
+        "100644 blob 1111\tsrc/lib.rs",
+        "160000 commit 2222\tvendor/lib",
+        "100644 blob 3333\tREADME.md",
+    ]
+    .join("\0");
+    assert_eq!(
+        parse_ls_tree(&listing),
+        vec![
+            ("1111".to_string(), PathBuf::from("src/lib.rs")),
+            ("3333".to_string(), PathBuf::from("README.md"))
+        ]
+    );
+    assert_eq!(
+        parse_cat_file_batch(b"1111 blob 3\nabc\n3333 blob 0\n\n").unwrap(),
+        vec![b"abc".to_vec(), Vec::new()]
+    );
+    assert!(parse_cat_file_batch(b"1111 blob 9\nabc\n").is_err());
+
+    let base = MemoryFs::with_files([("src/lib.rs", "fn working_tree() {}")]);
+    let revision = GitRevisionFs::new(
+        &base,
+        [
+            (PathBuf::from("src/lib.rs"), b"fn released() {}".to_vec()),
+            (PathBuf::from("src/old.rs"), b"fn old() {}".to_vec()),
+        ]
+        .into(),
+    );
+
+    let walk = revision
+        .walk(Path::new("."), Arc::new(|_: &Path| false), false)
+        .await
+        .unwrap();
+    assert_eq!(
+        walk.files,
+        vec![PathBuf::from("./src/lib.rs"), PathBuf::from("./src/old.rs")]
+    );
+    assert_eq!(
+        revision
+            .read_to_string(Path::new("./src/lib.rs"))
+            .await
+            .unwrap(),
+        "fn released() {}"
+    );
+    assert!(revision.metadata(Path::new("src")).await.unwrap().is_dir);
+
+    // Writes land in the working tree
+    revision
+        .write(Path::new("out.md"), b"snapshot")
+        .await
+        .unwrap();
+    assert!(base.exists(Path::new("out.md")).await);
+    assert!(!revision.exists(Path::new("out.md")).await);
+}
+
+#[tokio::test]
+async fn test_onto_worktree_paths() {
+    assert_eq!(
+        onto_branch_name("release/1.2", 1700000000),
+        "catnip/onto-release-1.2-1700000000"
+    );
+    assert_eq!(onto_branch_name("HEAD~2", 5), "catnip/onto-HEAD-2-5");
+
+    let vfs = MemoryFs::with_files([("/tmp/wt/src/lib.rs", "old")]);
+    let rooted = RootedFs::new(&vfs, "/tmp/wt");
+    assert_eq!(rooted.read(Path::new("src/lib.rs")).await.unwrap(), b"old");
+
+    rooted.write(Path::new("src/lib.rs"), b"new").await.unwrap();
+    assert_eq!(
+        vfs.read(Path::new("/tmp/wt/src/lib.rs")).await.unwrap(),
+        b"new"
+    );
+
+    let walk = rooted
+        .walk(Path::new("src"), Arc::new(|_: &Path| false), false)
+        .await
+        .unwrap();
+    assert_eq!(walk.files, vec![PathBuf::from("src/lib.rs")]);
+
+    // Nothing outside the worktree can be reached
+    for path in [
+        "/tmp/wt/src/lib.rs",
+        "../wt/src/lib.rs",
+        "src/../../etc/passwd",
+    ] {
+        assert!(rooted.write(Path::new(path), b"escaped").await.is_err());
+        assert!(!rooted.exists(Path::new(path)).await);
+    }
+    assert_eq!(
+        vfs.read(Path::new("/tmp/wt/src/lib.rs")).await.unwrap(),
+        b"new"
+    );
+}
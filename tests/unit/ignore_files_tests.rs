@@ -0,0 +1,213 @@
+use catnip::core::file_collector::{CollectOptions, CollectedFile, collect_files_with};
+use catnip::core::ignore_files::IgnoreRules;
+use catnip::io::vfs::{MemoryFs, TokioFs, Vfs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_ignore_file_last_match_wins() {
+    let vfs = MemoryFs::with_files([(
+        "repo/.gitignore",
+        "# build output\n/target\n\n*.log  \n!keep.log\n\\#notes.md\nkeep.log\n!debug.log\n",
+    )]);
+    let mut rules = IgnoreRules::default();
+    rules.load_dir(&vfs, Path::new("repo"), Path::new("")).await;
+
+    assert!(rules.matched(Path::new("target"), true).is_some());
+    assert!(rules.matched(Path::new("src/target"), true).is_none());
+    assert!(rules.matched(Path::new("#notes.md"), false).is_some());
+    // A later line overrides an earlier `!` and the other way around
+    assert!(rules.matched(Path::new("keep.log"), false).is_some());
+    assert!(rules.matched(Path::new("debug.log"), false).is_none());
+    assert!(rules.matched(Path::new("trace.log"), false).is_some());
+}
+
+/// Files a walk of the real filesystem lists below `root`, relative to it
+async fn walked(root: &Path, ignore_files: bool) -> Vec<PathBuf> {
+    let walk = TokioFs
+        .walk(root, Arc::new(|_: &Path| false), ignore_files)
+        .await
+        .unwrap();
+    walk.files
+        .iter()
+        .map(|file| file.strip_prefix(root).unwrap().to_path_buf())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_walk_skips_ignored_files() {
+    let repo = TempDir::new().unwrap();
+    for (path, content) in [
+        (".gitignore", "*.log\n"),
+        ("src/.catnipignore", "fixtures/\n"),
+        ("src/main.rs", "fn main() {}"),
+        ("src/debug.log", "noise"),
+        ("src/fixtures/data.rs", "data"),
+    ] {
+        let path = repo.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    assert_eq!(
+        walked(repo.path(), true).await,
+        vec![
+            PathBuf::from(".gitignore"),
+            PathBuf::from("src/.catnipignore"),
+            PathBuf::from("src/main.rs"),
+        ]
+    );
+    // `.catnipignore` applies even when the other ignore files don't
+    assert_eq!(walked(repo.path(), false).await.len(), 4);
+}
+
+#[tokio::test]
+async fn test_ignore_rules_precedence() {
+    let vfs = MemoryFs::with_files([
+        ("repo/.gitignore", "*.log\ngenerated/\n"),
+        ("repo/.rgignore", "!keep.log\n"),
+        ("repo/web/.ignore", "dist.js\n!generated/\n"),
+    ]);
+    let root = Path::new("repo");
+    let mut rules = IgnoreRules::default();
+    rules.load_dir(&vfs, root, Path::new("")).await;
+    rules
+        .load_nested(&vfs, root, &[PathBuf::from("repo/web/.ignore")])
+        .await;
+
+    let matched = rules.matched(Path::new("logs/debug.log"), false).unwrap();
+    assert_eq!(matched.to_string(), "'*.log' (gitignore)");
+    assert!(rules.matched(Path::new("keep.log"), false).is_none());
+    assert!(rules.matched(Path::new("generated"), true).is_some());
+
+    // Deeper ignore files decide for the paths below them
+    let matched = rules.matched(Path::new("web/dist.js"), false).unwrap();
+    assert_eq!(matched.to_string(), "'dist.js' (ignore file)");
+    assert!(rules.matched(Path::new("web/generated"), true).is_none());
+    assert!(rules.matched(Path::new("dist.js"), false).is_none());
+}
+
+#[tokio::test]
+async fn test_collect_files_honors_ignore_files() {
+    let vfs = MemoryFs::with_files([
+        ("repo/.gitignore", "gen/\n"),
+        ("repo/.ignore", "notes.md\n"),
+        ("repo/gen/api.rs", "pub fn api() {}"),
+        ("repo/notes.md", "todo"),
+        ("repo/src/main.rs", "fn main() {}"),
+        ("repo/src/.rgignore", "scratch.rs\n"),
+        ("repo/src/scratch.rs", "fn scratch() {}"),
+    ]);
+
+    let options = CollectOptions::default();
+    let files = collect_files_with(&vfs, &[PathBuf::from("repo")], &options)
+        .await
+        .unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("repo/src/main.rs")]
+    );
+    let reasons: Vec<_> = options
+        .pruned
+        .dirs()
+        .into_iter()
+        .map(|dir| dir.reason)
+        .collect();
+    assert_eq!(reasons, vec!["ignored by 'gen/' (gitignore)"]);
+
+    let options = CollectOptions {
+        ignore_files: false,
+        ..CollectOptions::default()
+    };
+    let files = collect_files_with(&vfs, &[PathBuf::from("repo")], &options)
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 4);
+}
+
+#[tokio::test]
+async fn test_collect_files_honors_repository_ignores_above_the_input() {
+    let repo = TempDir::new().unwrap();
+    for (path, content) in [
+        (".git/info/exclude", "*.local\n"),
+        (".gitignore", "*_gen.rs\n!keep_gen.rs\n"),
+        ("app/.gitignore", "fixtures/\n"),
+        ("app/src/main.rs", "fn main() {}"),
+        ("app/src/debug_gen.rs", "noise"),
+        ("app/src/keep_gen.rs", "kept"),
+        ("app/src/settings.local", "secret"),
+        ("app/src/fixtures/sample.rs", "generated"),
+    ] {
+        let path = repo.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    let input = repo.path().join("app/src");
+    let options = CollectOptions::default();
+    let files = collect_files_with(&TokioFs, std::slice::from_ref(&input), &options)
+        .await
+        .unwrap();
+    let mut paths = CollectedFile::paths(&files);
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![input.join("keep_gen.rs"), input.join("main.rs")]
+    );
+    // The walk leaves ignored directories out itself, unless asked to report why
+    assert!(options.pruned.dirs().is_empty());
+
+    let options = CollectOptions {
+        prune_report: true,
+        ..CollectOptions::default()
+    };
+    let reported = collect_files_with(&TokioFs, std::slice::from_ref(&input), &options)
+        .await
+        .unwrap();
+    assert_eq!(CollectedFile::paths(&reported).len(), 2);
+    let reasons: Vec<_> = options
+        .pruned
+        .dirs()
+        .into_iter()
+        .map(|dir| dir.reason)
+        .collect();
+    assert_eq!(reasons, vec!["ignored by 'fixtures/' (gitignore)"]);
+
+    let mut rules = IgnoreRules::default();
+    rules.load_repository(&TokioFs, &input).await;
+    let matched = rules.matched(Path::new("settings.local"), false).unwrap();
+    assert_eq!(matched.to_string(), "'*.local' (git exclude)");
+}
+
+#[tokio::test]
+async fn test_collect_files_honors_catnipignore() {
+    let vfs = MemoryFs::with_files([
+        (
+            "repo/.catnipignore",
+            "# Large fixtures\nfixtures/\n\n*_snapshot.rs\n",
+        ),
+        ("repo/fixtures/data.rs", "pub const DATA: &str = \"\";"),
+        ("repo/src/main.rs", "fn main() {}"),
+        ("repo/src/parser_snapshot.rs", "// snapshot"),
+        ("other/lib_snapshot.rs", "// not covered"),
+    ]);
+
+    let options = CollectOptions::default();
+    let paths = [PathBuf::from("repo"), PathBuf::from("other")];
+    let files = collect_files_with(&vfs, &paths, &options).await.unwrap();
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![
+            PathBuf::from("repo/src/main.rs"),
+            PathBuf::from("other/lib_snapshot.rs"),
+        ]
+    );
+    let reasons: Vec<_> = options
+        .pruned
+        .dirs()
+        .into_iter()
+        .map(|dir| dir.reason)
+        .collect();
+    assert_eq!(reasons, vec!["excluded by 'fixtures/' (catnipignore)"]);
+}
@@ -0,0 +1,145 @@
+use catnip::core::content_processor::OutputFormat;
+use catnip::core::file_collector::CollectedFile;
+use catnip::core::licenses::{
+    LicenseFilter, LicenseNotice, detect_licenses, identify_license_text, spdx_header,
+};
+use catnip::io::vfs::MemoryFs;
+use std::path::{Path, PathBuf};
+
+fn collected(path: &str, content: &str) -> CollectedFile {
+    CollectedFile::new(Path::new(path), Path::new(path), content.as_bytes(), None)
+}
+
+#[test]
+fn test_spdx_header_and_license_texts() {
+    assert_eq!(
+        spdx_header("/* SPDX-License-Identifier: GPL-3.0-or-later */\nint main;\n").as_deref(),
+        Some("GPL-3.0-or-later")
+    );
+    assert_eq!(
+        spdx_header("<!-- SPDX-License-Identifier: MIT OR Apache-2.0 -->\n").as_deref(),
+        Some("MIT OR Apache-2.0")
+    );
+    assert_eq!(spdx_header("fn main() {}\n"), None);
+
+    let gpl = "GNU GENERAL PUBLIC LICENSE\n   Version 3, 29 June 2007\n";
+    assert_eq!(identify_license_text(gpl), Some("GPL-3.0"));
+    let lgpl =
+        "GNU LESSER GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007\nGNU GENERAL PUBLIC LICENSE";
+    assert_eq!(identify_license_text(lgpl), Some("LGPL-3.0"));
+    let mit = "MIT License\n\nPermission is hereby granted, free of\ncharge, to any person";
+    assert_eq!(identify_license_text(mit), Some("MIT"));
+    assert_eq!(identify_license_text("All rights reserved."), None);
+}
+
+#[test]
+fn test_license_filter() {
+    let filter = LicenseFilter::new(&["gpl-3.0".to_string()]);
+    assert!(filter.excludes("GPL-3.0"));
+    assert!(filter.excludes("GPL-3.0-only"));
+    assert!(filter.excludes("GPL-3.0-or-later"));
+    assert!(filter.excludes("MIT AND GPL-3.0+"));
+    assert!(!filter.excludes("LGPL-3.0"));
+    // Another license to choose keeps the file in
+    assert!(!filter.excludes("GPL-3.0 OR MIT"));
+    assert!(filter.excludes("(GPL-3.0-only WITH Classpath-exception-2.0)"));
+    assert!(!LicenseFilter::default().excludes("GPL-3.0"));
+}
+
+#[tokio::test]
+async fn test_detect_licenses_and_notice() {
+    let vfs = MemoryFs::with_files([
+        (
+            "Cargo.toml",
+            "[package]\nname = \"app\"\nlicense = \"MIT OR Apache-2.0\"\n",
+        ),
+        ("src/main.rs", "fn main() {}\n"),
+        (
+            "src/gpl.c",
+            "// SPDX-License-Identifier: GPL-3.0-only\nint x;\n",
+        ),
+        (
+            "vendor/lib/COPYING",
+            "GNU GENERAL PUBLIC LICENSE\nVersion 2, June 1991\n",
+        ),
+        ("vendor/lib/lib.c", "int y;\n"),
+    ]);
+    let files = vec![
+        collected("src/main.rs", "fn main() {}\n"),
+        collected(
+            "src/gpl.c",
+            "// SPDX-License-Identifier: GPL-3.0-only\nint x;\n",
+        ),
+        collected("vendor/lib/lib.c", "int y;\n"),
+    ];
+
+    let licenses = detect_licenses(&vfs, &files).await;
+    assert_eq!(
+        licenses[Path::new("src/main.rs")].expression,
+        "MIT OR Apache-2.0"
+    );
+    assert_eq!(
+        licenses[Path::new("src/main.rs")].source,
+        PathBuf::from("Cargo.toml")
+    );
+    assert_eq!(licenses[Path::new("src/gpl.c")].expression, "GPL-3.0-only");
+    assert_eq!(
+        licenses[Path::new("vendor/lib/lib.c")].expression,
+        "GPL-2.0"
+    );
+
+    let mut paths = CollectedFile::paths(&files);
+    paths.push(PathBuf::from("elsewhere.txt"));
+    let notice = LicenseNotice::new(&licenses, &paths);
+    assert_eq!(notice.unlicensed, 1);
+    let section = notice.section(OutputFormat::Markdown);
+    assert!(section.starts_with("# License Notice"));
+    assert!(section.contains("- `GPL-3.0-only`: 1 files (SPDX headers)"));
+    assert!(section.contains("- `MIT OR Apache-2.0`: 1 files (`Cargo.toml`)"));
+    assert!(section.contains("- No license found: 1 files"));
+
+    let section = notice.section(OutputFormat::Xml);
+    assert!(section.starts_with("<license_notice>\n<note>"));
+    assert!(section.contains(
+        "<license expression=\"MIT OR Apache-2.0\" files=\"1\">\n<source>Cargo.toml</source>\n</license>\n"
+    ));
+    assert!(section.contains("<unlicensed files=\"1\"/>\n</license_notice>\n"));
+}
+
+#[tokio::test]
+async fn test_detect_licenses_stops_at_input_root() {
+    // An input outside the current directory is searched up to itself, not up to `/`
+    let vfs = MemoryFs::with_files([
+        (
+            "/elsewhere/LICENSE",
+            "GNU GENERAL PUBLIC LICENSE\nVersion 3\n",
+        ),
+        ("/elsewhere/project/src/main.rs", "fn main() {}\n"),
+        (
+            "/elsewhere/other/LICENSE-MIT",
+            "Permission is hereby granted, free of charge",
+        ),
+        ("/elsewhere/other/lib.rs", "pub fn lib() {}\n"),
+    ]);
+    let files = vec![
+        CollectedFile::new(
+            Path::new("/elsewhere/project/src/main.rs"),
+            Path::new("src/main.rs"),
+            b"fn main() {}\n",
+            None,
+        ),
+        CollectedFile::new(
+            Path::new("/elsewhere/other/lib.rs"),
+            Path::new("lib.rs"),
+            b"pub fn lib() {}\n",
+            None,
+        ),
+    ];
+
+    let licenses = detect_licenses(&vfs, &files).await;
+    assert!(!licenses.contains_key(Path::new("/elsewhere/project/src/main.rs")));
+    assert_eq!(
+        licenses[Path::new("/elsewhere/other/lib.rs")].expression,
+        "MIT"
+    );
+}
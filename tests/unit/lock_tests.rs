@@ -0,0 +1,46 @@
+use catnip::io::lock::StateLock;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_state_lock_is_exclusive() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("state").join("catnip.lock");
+
+    let lock = StateLock::acquire(&path, Duration::ZERO).await.unwrap();
+    assert!(path.exists());
+
+    let err = StateLock::acquire(&path, Duration::from_millis(150))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("--no-lock"));
+
+    drop(lock);
+    StateLock::acquire(&path, Duration::ZERO).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_leftover_lock_file_is_reused() {
+    // A run that was killed leaves its lock file behind, but not the lock on it
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("catnip.lock");
+    std::fs::write(&path, "12345\n").unwrap();
+
+    StateLock::acquire(&path, Duration::ZERO).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_waiting_run_gets_the_lock_once_released() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("catnip.lock");
+
+    let lock = StateLock::acquire(&path, Duration::ZERO).await.unwrap();
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(lock);
+    });
+    StateLock::acquire(&path, Duration::from_secs(5))
+        .await
+        .unwrap();
+    release.await.unwrap();
+}
@@ -0,0 +1,47 @@
+use catnip::core::content_processor::{RenderOptions, concatenate_files_with};
+use catnip::core::file_collector::{CollectOptions, CollectedFile};
+use catnip::core::manifest::{Manifest, manifest_path};
+use catnip::core::snapshot::Snapshot;
+use catnip::io::vfs::MemoryFs;
+use std::path::{Path, PathBuf};
+
+#[tokio::test]
+async fn test_manifest_round_trips_through_the_snapshot() {
+    let vfs = MemoryFs::with_files([("src/main.rs", "fn main() {}")]);
+    let paths = vec![PathBuf::from("src/main.rs")];
+    let files = CollectedFile::load_all(&vfs, &paths).await.unwrap();
+    let snapshot = Snapshot::capture(&vfs, &paths).await;
+
+    let collect_options = CollectOptions {
+        excludes: vec!["*.log".to_string()],
+        ..CollectOptions::default()
+    };
+    let mut render_options = RenderOptions {
+        quiet: true,
+        ..RenderOptions::default()
+    };
+    let manifest = Manifest::new(
+        vec!["catnip".to_string(), "cat".to_string(), "src".to_string()],
+        &[PathBuf::from("src")],
+        &collect_options,
+        &render_options,
+        &[],
+        &snapshot,
+    );
+    assert_eq!(manifest.files.len(), 1);
+    render_options.manifest = Some(manifest.embedded().unwrap());
+
+    let output = concatenate_files_with(&vfs, &files, &render_options)
+        .await
+        .unwrap();
+    let extracted = Manifest::extract(&output).unwrap().unwrap();
+    assert_eq!(extracted.command, manifest.command);
+    assert_eq!(extracted.collect.excludes, vec!["*.log"]);
+    assert_eq!(extracted.files, manifest.files);
+    assert!(Manifest::extract("# Project Structure\n").is_none());
+
+    assert_eq!(
+        manifest_path(Path::new("out/snapshot.md")),
+        PathBuf::from("out/snapshot.manifest.json")
+    );
+}
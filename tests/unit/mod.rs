@@ -1,5 +1,31 @@
+pub mod anonymizer_tests;
+pub mod bench_tests;
+pub mod cat_tests;
+pub mod classifier_tests;
 pub mod clipboard_tests;
+pub mod encryption_tests;
 pub mod file_processor_tests;
+pub mod filter_expr_tests;
+pub mod framework_tests;
+pub mod git_tests;
+pub mod ignore_files_tests;
+pub mod licenses_tests;
+pub mod lock_tests;
+pub mod manifest_tests;
+pub mod models_tests;
+pub mod opener_tests;
+pub mod packer_tests;
+pub mod patch_tests;
 pub mod pattern_matcher_tests;
 pub mod patterns_tests;
+pub mod prompt_tests;
+pub mod refine_tests;
+pub mod relevance_tests;
+pub mod remote_tests;
+pub mod rust_cfg_tests;
+pub mod sensitive_tests;
+pub mod share_tests;
+pub mod shell_tests;
 pub mod structure_generator_tests;
+pub mod timings_tests;
+pub mod transform_tests;
@@ -0,0 +1,97 @@
+use catnip::config::Settings;
+use catnip::config::models::{ModelSpec, model_table, resolve_model};
+use catnip::config::settings::BudgetPercent;
+use catnip::utils::token_counter::count_tokens;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[test]
+fn test_count_tokens() {
+    assert_eq!(count_tokens(""), 0);
+
+    let short = count_tokens("fn main() {}");
+    let long = count_tokens(&"fn main() {}\n".repeat(100));
+    assert!(short > 0);
+    assert!(long > short * 50);
+}
+
+#[test]
+fn test_input_cost() {
+    let spec = ModelSpec {
+        input_per_million: 3.0,
+        context_window: 200_000,
+    };
+    assert!((spec.input_cost(500_000) - 1.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_model_table_overrides() {
+    let mut configured = BTreeMap::new();
+    configured.insert(
+        "gpt-4o".to_string(),
+        ModelSpec {
+            input_per_million: 1.0,
+            context_window: 128_000,
+        },
+    );
+    configured.insert(
+        "local-llama".to_string(),
+        ModelSpec {
+            input_per_million: 0.0,
+            context_window: 8_192,
+        },
+    );
+
+    let table = model_table(&configured);
+    assert_eq!(
+        resolve_model(&table, "gpt-4o").unwrap().input_per_million,
+        1.0
+    );
+    assert_eq!(
+        resolve_model(&table, "local-llama").unwrap().context_window,
+        8_192
+    );
+    assert!(resolve_model(&table, "claude-3.5").is_ok());
+
+    let err = resolve_model(&table, "nope").unwrap_err().to_string();
+    assert!(err.contains("Unknown model 'nope'"));
+}
+
+#[test]
+fn test_models_from_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &config,
+        "[models.in-house]\ninput_per_million = 0.5\ncontext_window = 32000\n",
+    )
+    .unwrap();
+
+    let settings = Settings::load_from(&config).unwrap();
+    assert_eq!(
+        settings.models["in-house"],
+        ModelSpec {
+            input_per_million: 0.5,
+            context_window: 32_000,
+        }
+    );
+}
+
+#[test]
+fn test_budget_from_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &config,
+        "[budget]\n\"src/core\" = \"40%\"\n\"src/ui\" = 20\n",
+    )
+    .unwrap();
+
+    let settings = Settings::load_from(&config).unwrap();
+    assert_eq!(settings.budget[Path::new("src/core")], BudgetPercent(40.0));
+    assert_eq!(settings.budget[Path::new("src/ui")], BudgetPercent(20.0));
+
+    std::fs::write(&config, "[budget]\n\"src\" = \"140%\"\n").unwrap();
+    assert!(Settings::load_from(&config).is_err());
+}
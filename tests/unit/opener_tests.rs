@@ -0,0 +1,22 @@
+use catnip::io::opener::opener_command;
+use std::path::Path;
+
+#[test]
+fn test_opener_prefers_editor_for_markdown() {
+    let command = opener_command(Path::new("out.md"), Some("code --wait"), Some("firefox"));
+    assert_eq!(command, vec!["code", "--wait", "out.md"]);
+}
+
+#[test]
+fn test_opener_prefers_browser_for_html() {
+    let command = opener_command(Path::new("out.html"), Some("vim"), Some("firefox"));
+    assert_eq!(command, vec!["firefox", "out.html"]);
+}
+
+#[test]
+fn test_opener_falls_back_to_platform_handler() {
+    let command = opener_command(Path::new("out.md"), Some("  "), None);
+    assert_eq!(command.last().map(String::as_str), Some("out.md"));
+    assert!(command.len() >= 2);
+    assert_ne!(command[0], "  ");
+}
@@ -0,0 +1,213 @@
+use catnip::config::models::{context_window_for, model_table};
+use catnip::config::prompt::PROMPT;
+use catnip::core::classifier::is_generated_file;
+use catnip::core::content_processor::OutputFormat;
+use catnip::core::packer::{
+    BudgetShare, PINNED_PRIORITY, Representation, file_priority, plan_packing, plan_packing_shared,
+    plan_packing_with, plan_packing_with_prompt, split_into_parts,
+};
+use catnip::utils::text_processing::minify;
+use catnip::utils::token_counter::count_tokens;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+fn sample_files() -> Vec<(PathBuf, String)> {
+    let body =
+        "    // Compute and print\n    let value = compute();\n\n    println!(\"{}\", value);\n"
+            .repeat(50);
+    vec![
+        (
+            PathBuf::from("src/main.rs"),
+            format!("// Entry point\n\nfn main() {{\n{}}}\n", body),
+        ),
+        (
+            PathBuf::from("src/util/helpers.rs"),
+            format!("/// Helper\npub fn helper() {{\n{}}}\n", body),
+        ),
+        (
+            PathBuf::from("Cargo.lock"),
+            "# This file is automatically @generated by Cargo.\n[[package]]\nname = \"a\"\n"
+                .to_string(),
+        ),
+    ]
+}
+
+#[test]
+fn test_is_generated_file() {
+    assert!(is_generated_file(Path::new("Cargo.lock"), ""));
+    assert!(is_generated_file(Path::new("dist/app.min.js"), ""));
+    assert!(is_generated_file(
+        Path::new("api.rs"),
+        "// Code generated by protoc. DO NOT EDIT.\n"
+    ));
+    assert!(!is_generated_file(Path::new("main.rs"), "fn main() {}\n"));
+}
+
+#[test]
+fn test_minify() {
+    let minified = minify("// comment\nfn main() {\n\n    run();   \n}\n", "rust");
+    assert_eq!(minified, "fn main() {\n    run();\n}");
+
+    let minified = minify("#!/bin/sh\n# comment\necho hi\n", "bash");
+    assert_eq!(minified, "#!/bin/sh\necho hi");
+}
+
+#[test]
+fn test_file_priority() {
+    assert!(file_priority(Path::new("README.md")) > file_priority(Path::new("src/util.rs")));
+    assert!(file_priority(Path::new("src/main.rs")) > file_priority(Path::new("src/util.rs")));
+    assert!(file_priority(Path::new("src/util.rs")) > file_priority(Path::new("tests/util.rs")));
+}
+
+#[test]
+fn test_plan_packing_keeps_everything_when_it_fits() {
+    let plan = plan_packing(&sample_files(), 1_000_000);
+
+    assert!(plan.fits());
+    assert_eq!(
+        plan.decisions[Path::new("src/main.rs")],
+        Representation::Full
+    );
+    assert_eq!(
+        plan.decisions[Path::new("Cargo.lock")],
+        Representation::Summary
+    );
+}
+
+#[test]
+fn test_plan_packing_degrades_least_important_first() {
+    let files = sample_files();
+    let full = plan_packing(&files, 1_000_000).total_tokens;
+    let plan = plan_packing(&files, full - 5);
+
+    assert!(plan.fits());
+    assert_eq!(
+        plan.decisions[Path::new("src/main.rs")],
+        Representation::Full
+    );
+    assert_eq!(
+        plan.decisions[Path::new("src/util/helpers.rs")],
+        Representation::Minified
+    );
+}
+
+#[test]
+fn test_plan_packing_omits_as_last_resort() {
+    let plan = plan_packing(&sample_files(), 10);
+
+    assert!(plan.count(Representation::Omitted) > 0);
+    assert!(plan.count(Representation::Full) == 0);
+}
+
+#[test]
+fn test_plan_packing_keeps_pinned_files_in_full() {
+    let plan = plan_packing_with(&sample_files(), 10, |path| {
+        if path == Path::new("src/util/helpers.rs") || path == Path::new("Cargo.lock") {
+            PINNED_PRIORITY
+        } else {
+            file_priority(path)
+        }
+    });
+
+    assert_eq!(
+        plan.decisions[Path::new("src/util/helpers.rs")],
+        Representation::Full
+    );
+    assert_eq!(
+        plan.decisions[Path::new("Cargo.lock")],
+        Representation::Full
+    );
+    assert_eq!(
+        plan.decisions[Path::new("src/main.rs")],
+        Representation::Omitted
+    );
+}
+
+#[test]
+fn test_plan_packing_shared_reserves_directory_shares() {
+    let body = "    let value = compute();\n    println!(\"{}\", value);\n".repeat(50);
+    let files = vec![
+        (
+            PathBuf::from("src/core/engine.rs"),
+            format!("fn run() {{\n{}}}\n", body),
+        ),
+        (
+            PathBuf::from("./src/ui/view.rs"),
+            format!("fn draw() {{\n{}}}\n", body),
+        ),
+    ];
+    let full = plan_packing(&files, 1_000_000).total_tokens;
+    let shares = [BudgetShare {
+        dir: PathBuf::from("./src/core"),
+        percent: 90.0,
+    }];
+
+    let plan = plan_packing_shared(&files, full * 6 / 10, file_priority, &shares);
+    assert!(plan.fits());
+    assert_eq!(
+        plan.decisions[Path::new("src/core/engine.rs")],
+        Representation::Full
+    );
+    assert!(plan.decisions[Path::new("./src/ui/view.rs")] > Representation::Full);
+
+    // Whatever a share doesn't need goes to the other files
+    let plan = plan_packing_shared(&files, full, file_priority, &shares);
+    assert_eq!(plan.count(Representation::Full), 2);
+}
+
+#[test]
+fn test_context_window_for() {
+    let table = model_table(&BTreeMap::new());
+
+    assert_eq!(context_window_for(&table, "gpt-4o").unwrap(), 128_000);
+    assert_eq!(context_window_for(&table, "claude-200k").unwrap(), 200_000);
+    assert_eq!(context_window_for(&table, "gemini-1m").unwrap(), 1_000_000);
+    assert!(context_window_for(&table, "mystery").is_err());
+}
+
+#[test]
+fn test_plan_packing_trims_the_prompt_before_files() {
+    let files = sample_files();
+    let full = plan_packing(&files, 1_000_000).total_tokens;
+
+    // Room for every file in full, but not for the whole prompt as well
+    let budget = full + count_tokens(PROMPT) - 10;
+    let (plan, prompt) = plan_packing_with_prompt(&files, budget, file_priority, &[], PROMPT);
+    assert!(!prompt.contains("## Examples"));
+    assert!(prompt.contains("## Critical Rules"));
+    assert_eq!(plan.count(Representation::Full), 2);
+
+    let (_, prompt) = plan_packing_with_prompt(&files, budget + 10, file_priority, &[], PROMPT);
+    assert_eq!(prompt, PROMPT);
+}
+
+#[test]
+fn test_split_into_parts() {
+    let line = "let value = compute_something(input);\n";
+    let files: Vec<(PathBuf, String)> = ["a.rs", "b.rs", "c.rs"]
+        .iter()
+        .map(|name| (PathBuf::from(name), line.repeat(20)))
+        .collect();
+    let one_file = count_tokens(&format!("## a.rs\n\n```rust\n{}\n```\n\n", line.repeat(20)));
+
+    // Two files fit per part, in order
+    let parts = split_into_parts(&files, one_file * 2 + 1, OutputFormat::Markdown);
+    assert_eq!(
+        parts,
+        vec![
+            vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")],
+            vec![PathBuf::from("c.rs")],
+        ]
+    );
+
+    // A file over the limit still gets a part of its own
+    assert_eq!(split_into_parts(&files, 1, OutputFormat::Markdown).len(), 3);
+    assert_eq!(
+        split_into_parts(&files, usize::MAX, OutputFormat::Markdown).len(),
+        1
+    );
+
+    // XML documents cost more than markdown headings, so fewer fit
+    let parts = split_into_parts(&files, one_file * 2 + 1, OutputFormat::Xml);
+    assert_eq!(parts.len(), 3);
+}
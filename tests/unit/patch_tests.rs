@@ -0,0 +1,1233 @@
+use catnip::cli::commands::patch::*;
+use catnip::config::settings::{LintSeverity, PatchLint, PatchSafety, Settings};
+use catnip::core::approval::Approver;
+use catnip::core::patch_journal::{PatchJournal, Restored, UndoJournal, journal_path};
+use catnip::core::snapshot::Snapshot;
+use catnip::core::unified_diff::format_diff;
+use catnip::io::clipboard::MemoryClipboard;
+use catnip::io::vfs::{MemoryFs, RootedFs, TokioFs, Vfs};
+use catnip::utils::path_encoding::{decode_path, encode_path, relative_to, strip_verbatim_prefix};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn update(old_content: &str, new_content: &str) -> CodeUpdate {
+    CodeUpdate {
+        old_content: old_content.to_string(),
+        new_content: new_content.to_string(),
+        description: None,
+        line: None,
+    }
+}
+
+#[test]
+fn test_apply_updates() {
+    let original = "fn main() {\n    println!(\"old\");\n}";
+    let (updated, count) = apply_updates(
+        original,
+        &[update("println!(\"old\")", "println!(\"new\")")],
+    )
+    .unwrap();
+
+    assert_eq!(count, 1);
+    assert!(updated.contains("println!(\"new\")"));
+    assert!(!updated.contains("println!(\"old\")"));
+}
+
+#[test]
+fn test_apply_updates_appends_empty_old_content() {
+    let (updated, count) = apply_updates(
+        "fn main() {}\n",
+        &[
+            update("", "fn helper() {}\n"),
+            update("fn helper()", "fn help()"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(updated, "fn main() {}\nfn help() {}\n");
+}
+
+#[test]
+fn test_apply_updates_missing_content() {
+    let result = apply_updates("fn main() {}", &[update("fn other() {}", "")]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_deleted_line_percentage() {
+    assert_eq!(deleted_line_percentage("a\nb\nc\nd", "a\nb\nc\nd"), 0.0);
+    assert_eq!(deleted_line_percentage("a\nb\nc\nd", "a\nb"), 50.0);
+    assert_eq!(deleted_line_percentage("a\nb\nc\nd", "x\ny\nz\nw"), 100.0);
+    assert_eq!(deleted_line_percentage("", "a"), 0.0);
+}
+
+#[tokio::test]
+async fn test_check_patch_safety_too_many_files() {
+    let request = UpdateRequest {
+        analysis: String::new(),
+        files: (0..3)
+            .map(|i| FileUpdate {
+                path: format!("missing_{}.rs", i),
+                action: None,
+                new_path: None,
+                updates: vec![],
+            })
+            .collect(),
+    };
+    let safety = PatchSafety {
+        max_files: 2,
+        max_deleted_percent: 50.0,
+    };
+
+    assert!(
+        check_patch_safety(&MemoryFs::new(), &request, &safety)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_check_patch_safety_mass_deletion() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "a\nb\nc\nd\n")]);
+
+    let request = UpdateRequest {
+        analysis: String::new(),
+        files: vec![FileUpdate {
+            path: "src/lib.rs".to_string(),
+            action: None,
+            new_path: None,
+            updates: vec![update("b\nc\nd\n", "")],
+        }],
+    };
+
+    assert!(
+        check_patch_safety(&vfs, &request, &PatchSafety::default())
+            .await
+            .is_err()
+    );
+
+    let lenient = PatchSafety {
+        max_files: 20,
+        max_deleted_percent: 80.0,
+    };
+    assert!(check_patch_safety(&vfs, &request, &lenient).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_patch_in_memory() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+
+    let json = serde_json::json!({
+        "analysis": "Rename and add module",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+            },
+            {
+                "path": "src/extra.rs",
+                "updates": [{ "old_content": "", "new_content": "pub fn extra() {}\n" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions {
+            backup: true,
+            ..PatchOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn new() {}\n");
+    let backup = vfs
+        .read_to_string(Path::new("src/lib.rs.backup"))
+        .await
+        .unwrap();
+    assert_eq!(backup, "fn old() {}\n");
+    assert!(vfs.exists(Path::new("src/extra.rs")).await);
+}
+
+#[test]
+fn test_parse_unified_diff() {
+    let diff = [
+        "```diff",
+        "diff --git a/src/lib.rs b/src/lib.rs",
+        "index 1234567..89abcde 100644",
+        "--- a/src/lib.rs",
+        "+++ b/src/lib.rs",
+        "@@ -1,4 +1,4 @@",
+        " fn first() {}",
+        "",
+        "-fn old() {}",
+        "+fn new() {}",
+        "@@ -9 +9,2 @@ fn tail() {}",
+        "-const LAST: u8 = 1;",
+        "\\ No newline at end of file",
+        "+const LAST: u8 = 2;",
+        "+const MORE: u8 = 3;",
+        "",
+        "--- /dev/null",
+        "+++ b/src/extra.rs\t2024-01-01 00:00:00",
+        "@@ -0,0 +1 @@",
+        "+pub fn extra() {}",
+        "```",
+    ]
+    .join("\n");
+
+    let request = UpdateRequest::parse(&diff).unwrap();
+    assert_eq!(request.files.len(), 2);
+
+    let lib = &request.files[0];
+    assert_eq!(lib.path, "src/lib.rs");
+    assert_eq!(lib.updates[0].old_content, "fn first() {}\n\nfn old() {}\n");
+    assert_eq!(lib.updates[0].new_content, "fn first() {}\n\nfn new() {}\n");
+    assert_eq!(lib.updates[1].old_content, "const LAST: u8 = 1;");
+    assert_eq!(
+        lib.updates[1].new_content,
+        "const LAST: u8 = 2;\nconst MORE: u8 = 3;\n"
+    );
+
+    let extra = &request.files[1];
+    assert_eq!(extra.path, "src/extra.rs");
+    assert_eq!(extra.updates[0].old_content, "");
+    assert_eq!(extra.updates[0].new_content, "pub fn extra() {}\n");
+
+    assert_eq!(extra.action, Some(FileAction::Create));
+
+    let request =
+        UpdateRequest::parse("--- a/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-x\n").unwrap();
+    assert_eq!(request.files[0].path, "gone.rs");
+    assert_eq!(request.files[0].action, Some(FileAction::Delete));
+    assert!(request.files[0].updates.is_empty());
+
+    let err = UpdateRequest::parse("--- /dev/null\n+++ /dev/null\n@@ -0,0 +0,0 @@\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("Both sides of a diff are /dev/null"));
+}
+
+#[tokio::test]
+async fn test_patch_applies_unified_diff() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn keep() {}\nfn old() {}\n")]);
+    let temp_dir = TempDir::new().unwrap();
+    let diff_file = temp_dir.path().join("change.diff");
+    std::fs::write(
+        &diff_file,
+        "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n fn keep() {}\n-fn old() {}\n+fn new() {}\n",
+    )
+    .unwrap();
+
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(diff_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn keep() {}\nfn new() {}\n");
+}
+
+#[test]
+fn test_unified_diff_hunks_apply_at_their_lines() {
+    let original = "a\nx\nb\nx\nc\n";
+    let diff = [
+        "--- a/f.txt",
+        "+++ b/f.txt",
+        // A zero-context insertion lands after line 1, not at the end
+        "@@ -1,0 +2 @@",
+        "+inserted",
+        // Only the second `x`, now on line 5, is replaced
+        "@@ -4 +5 @@",
+        "-x",
+        "+y",
+        // Removing the last line leaves an empty new range
+        "@@ -5 +5,0 @@",
+        "-c",
+    ]
+    .join("\n");
+
+    let request = UpdateRequest::parse(&diff).unwrap();
+    let lines: Vec<_> = request.files[0]
+        .updates
+        .iter()
+        .map(|update| update.line)
+        .collect();
+    assert_eq!(lines, vec![Some(2), Some(5), Some(6)]);
+
+    let (updated, _) = apply_updates(original, &request.files[0].updates).unwrap();
+    assert_eq!(updated, "a\ninserted\nx\nb\ny\n");
+}
+
+#[tokio::test]
+async fn test_patch_mixed_creation_and_modification() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+
+    let json = serde_json::json!({
+        "analysis": "Append to one file, create and refine another",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [
+                    { "old_content": "fn old()", "new_content": "fn new()" },
+                    { "old_content": "", "new_content": "fn added() {}\n" }
+                ]
+            },
+            {
+                "path": "src/extra.rs",
+                "updates": [
+                    { "old_content": "", "new_content": "pub fn extra() {}\n" },
+                    { "old_content": "pub fn extra()", "new_content": "pub fn extra(x: u8)" }
+                ]
+            },
+            {
+                "path": "src/missing.rs",
+                "updates": [{ "old_content": "fn a()", "new_content": "fn b()" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    let err = execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("1 of 3 files"));
+
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn new() {}\nfn added() {}\n");
+    let extra = vfs.read_to_string(Path::new("src/extra.rs")).await.unwrap();
+    assert_eq!(extra, "pub fn extra(x: u8) {}\n");
+    assert!(!vfs.exists(Path::new("src/missing.rs")).await);
+}
+
+#[tokio::test]
+async fn test_patch_appends_to_existing_file() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+
+    let json = serde_json::json!({
+        "analysis": "Append to an existing file, and create one that already exists",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "", "new_content": "fn added() {}\n" }]
+            },
+            {
+                "path": "src/lib.rs",
+                "action": "create",
+                "updates": [{ "old_content": "", "new_content": "fn again() {}\n" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    let err = execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("1 of 2 files"));
+
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn old() {}\nfn added() {}\n");
+}
+
+#[tokio::test]
+async fn test_patch_extracts_json_from_clipboard_prose() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+    let answer = r#"Sure! Here is the patch, with {braces} in the prose:
+
+```json
+{
+  "analysis": "Rename old",
+  "files": [
+    {
+      "path": "src/lib.rs",
+      "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+    }
+  ]
+}
+```
+
+Let me know if you need anything else."#;
+
+    assert!(UpdateRequest::parse(answer).is_err());
+    let request = UpdateRequest::parse_lenient(answer).unwrap();
+    assert_eq!(request.analysis, "Rename old");
+    assert!(UpdateRequest::parse_lenient("No JSON here {at all}").is_err());
+
+    execute(
+        &MemoryClipboard::with_content(answer),
+        &vfs,
+        None,
+        &PatchOptions {
+            force: true,
+            ..PatchOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap(),
+        "fn new() {}\n"
+    );
+}
+
+#[tokio::test]
+async fn test_patch_deletes_and_renames_files() {
+    let vfs = MemoryFs::with_files([
+        ("src/old.rs", "fn old() {}\n"),
+        ("src/util.rs", "fn util() {}\n"),
+        ("src/taken.rs", "fn taken() {}\n"),
+    ]);
+
+    let json = serde_json::json!({
+        "analysis": "Drop old, move util",
+        "files": [
+            { "path": "src/old.rs", "action": "delete" },
+            {
+                "path": "src/util.rs",
+                "action": "rename",
+                "new_path": "src/helpers/util.rs",
+                "updates": [{ "old_content": "fn util()", "new_content": "pub fn util()" }]
+            }
+        ]
+    });
+    let request: UpdateRequest = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(request.files[0].action, Some(FileAction::Delete));
+    assert!(request.files[0].updates.is_empty());
+
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert!(!vfs.exists(Path::new("src/old.rs")).await);
+    assert!(!vfs.exists(Path::new("src/util.rs")).await);
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/helpers/util.rs"))
+            .await
+            .unwrap(),
+        "pub fn util() {}\n"
+    );
+
+    // Renaming onto an existing file or deleting with updates is refused
+    for file in [
+        serde_json::json!({ "path": "src/helpers/util.rs", "action": "rename", "new_path": "src/taken.rs" }),
+        serde_json::json!({
+            "path": "src/taken.rs",
+            "action": "delete",
+            "updates": [{ "old_content": "fn taken()", "new_content": "" }]
+        }),
+        serde_json::json!({ "path": "src/old.rs", "action": "delete" }),
+    ] {
+        let json = serde_json::json!({ "analysis": "Bad", "files": [file] });
+        std::fs::write(&json_file, json.to_string()).unwrap();
+        assert!(
+            execute(
+                &MemoryClipboard::new(),
+                &vfs,
+                Some(json_file.to_string_lossy().to_string()),
+                &PatchOptions::default(),
+            )
+            .await
+            .is_err()
+        );
+    }
+    assert!(vfs.exists(Path::new("src/taken.rs")).await);
+}
+
+#[tokio::test]
+async fn test_patch_fail_fast_changes_nothing() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+
+    let json = serde_json::json!({
+        "analysis": "Rename twice, then touch a missing file",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "fn old()", "new_content": "fn newer()" }]
+            },
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "fn newer()", "new_content": "fn newest()" }]
+            },
+            {
+                "path": "src/missing.rs",
+                "updates": [{ "old_content": "fn a()", "new_content": "fn b()" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+    let json_file = Some(json_file.to_string_lossy().to_string());
+
+    let options = PatchOptions {
+        on_error: OnError::FailFast,
+        ..PatchOptions::default()
+    };
+    let err = execute(&MemoryClipboard::new(), &vfs, json_file.clone(), &options)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("No files were changed"));
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn old() {}\n");
+
+    // Continuing past the missing file applies both entries for the same file in order
+    let err = execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        json_file,
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("1 of 3 files"));
+    let lib = vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap();
+    assert_eq!(lib, "fn newest() {}\n");
+}
+
+#[tokio::test]
+async fn test_atomic_patch_puts_back_written_files() {
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join("src")).unwrap();
+    std::fs::write(project.path().join("src/lib.rs"), "fn old() {}\n").unwrap();
+    std::fs::write(project.path().join("notes.txt"), "notes\n").unwrap();
+    let vfs = RootedFs::new(&TokioFs, project.path());
+
+    // Every update matches, but the second file can't be written below a regular file
+    let json = serde_json::json!({
+        "analysis": "Rename and document",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+            },
+            {
+                "path": "notes.txt/extra.md",
+                "updates": [{ "old_content": "", "new_content": "# Extra\n" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    let options = PatchOptions {
+        on_error: OnError::Atomic,
+        ..PatchOptions::default()
+    };
+    let err = execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &options,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("were put back (--atomic)"));
+    assert_eq!(
+        std::fs::read_to_string(project.path().join("src/lib.rs")).unwrap(),
+        "fn old() {}\n"
+    );
+}
+
+#[test]
+fn test_settings_from_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = temp_dir.path().join("catnip.toml");
+    std::fs::write(&config, "[patch.safety]\nmax_files = 5\n").unwrap();
+
+    let settings = Settings::load_from(&config).unwrap();
+    assert_eq!(settings.patch.safety.max_files, 5);
+    assert_eq!(settings.patch.safety.max_deleted_percent, 50.0);
+
+    let defaults = Settings::load_from(&temp_dir.path().join("missing.toml")).unwrap();
+    assert_eq!(defaults.patch.safety.max_files, 20);
+    assert_eq!(defaults.collect.skip_directories, None);
+
+    std::fs::write(
+        &config,
+        "[collect]\nskip_directories = [\".git\", \"vendor\"]\n",
+    )
+    .unwrap();
+    let settings = Settings::load_from(&config).unwrap();
+    assert_eq!(
+        settings.collect.skip_directories,
+        Some(vec![".git".to_string(), "vendor".to_string()])
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_pre_patch_hook_can_veto() {
+    let request: UpdateRequest = serde_json::from_value(serde_json::json!({
+        "analysis": "Touch secrets",
+        "files": [{ "path": "secrets/key.txt", "updates": [] }]
+    }))
+    .unwrap();
+
+    run_pre_patch_hook("grep -q '\"path\": \"src/'", &request)
+        .await
+        .unwrap_err();
+    run_pre_patch_hook("! grep -q '\"path\": \"src/'", &request)
+        .await
+        .unwrap();
+
+    let error = run_pre_patch_hook("exit 1", &request).await.unwrap_err();
+    assert_eq!(error.to_string(), "Patch vetoed by pre-patch hook");
+}
+
+#[test]
+fn test_pre_patch_hook_comes_from_the_user_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let user_config = temp_dir.path().join("config.toml");
+    let project_config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &user_config,
+        "[hooks]\npre_patch = \"./scripts/check-patch-policy\"\n",
+    )
+    .unwrap();
+    // A project can neither run its own hook nor switch off the user's policy check
+    std::fs::write(&project_config, "[hooks]\npre_patch = \"true\"\n").unwrap();
+
+    let settings = Settings::load_layers(Some(&user_config), &project_config).unwrap();
+    assert_eq!(
+        settings.hooks.pre_patch.as_deref(),
+        Some("./scripts/check-patch-policy")
+    );
+    let settings = Settings::load_layers(None, &project_config).unwrap();
+    assert_eq!(settings.hooks.pre_patch, None);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_patch_dry_run_emits_script() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    std::fs::create_dir(root.join("src")).unwrap();
+    std::fs::write(root.join("src/lib.rs"), "fn old() {}\n").unwrap();
+
+    let json = serde_json::json!({
+        "analysis": "Rename and add files",
+        "files": [
+            {
+                "path": root.join("src/lib.rs").to_string_lossy(),
+                "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+            },
+            {
+                "path": root.join("docs/it's.md").to_string_lossy(),
+                "updates": [{ "old_content": "", "new_content": "CATNIP_EOF\n" }]
+            },
+            {
+                "path": root.join("VERSION").to_string_lossy(),
+                "updates": [{ "old_content": "", "new_content": "1.0" }]
+            }
+        ]
+    });
+    let json_file = root.join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+    let script_path = root.join("patch.sh");
+
+    execute(
+        &MemoryClipboard::new(),
+        &catnip::io::vfs::TokioFs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions {
+            dry_run: true,
+            emit_script: Some(script_path.to_string_lossy().to_string()),
+            ..PatchOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    // The dry run itself leaves everything alone
+    assert_eq!(
+        std::fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+        "fn old() {}\n"
+    );
+    let script = std::fs::read_to_string(&script_path).unwrap();
+    assert!(script.starts_with("#!/bin/sh\n"));
+    assert!(script.contains("# Rename and add files\n"));
+
+    let status = std::process::Command::new("sh")
+        .arg(&script_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+        "fn new() {}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("docs/it's.md")).unwrap(),
+        "CATNIP_EOF\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("VERSION")).unwrap(),
+        "1.0"
+    );
+
+    // Creations refuse to overwrite, so a second run fails
+    let status = std::process::Command::new("sh")
+        .arg(&script_path)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[tokio::test]
+async fn test_patch_changelog() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n")]);
+    let json = serde_json::json!({
+        "analysis": "Rename old to new",
+        "files": [
+            {
+                "path": "src/lib.rs",
+                "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+            },
+            {
+                "path": "src/extra.rs",
+                "action": "create",
+                "updates": [{ "old_content": "", "new_content": "pub fn extra() {}\n" }]
+            }
+        ]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    let options = PatchOptions {
+        changelog: Some("CHANGES.catnip.md".to_string()),
+        ..PatchOptions::default()
+    };
+    for _ in 0..2 {
+        // The second run fails on both files, so it logs nothing
+        let _ = execute(
+            &MemoryClipboard::new(),
+            &vfs,
+            Some(json_file.to_string_lossy().to_string()),
+            &options,
+        )
+        .await;
+    }
+
+    let changelog = vfs
+        .read_to_string(Path::new("CHANGES.catnip.md"))
+        .await
+        .unwrap();
+    assert!(changelog.starts_with("# Changes applied with catnip\n\n## "));
+    assert!(changelog.ends_with(
+        "\n\nRename old to new\n\n- `src/lib.rs` (1 update)\n- `src/extra.rs` (created)\n"
+    ));
+    assert_eq!(changelog.matches("## ").count(), 1);
+}
+
+#[test]
+fn test_changelog_entry_format() {
+    use catnip::core::changelog::{ChangedFile, format_entry};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let entry = format_entry(
+        "  Fix parser\n",
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        &[ChangedFile {
+            path: PathBuf::from("src/parser.rs"),
+            updates: 3,
+            created: false,
+            deleted: false,
+            renamed_from: None,
+        }],
+    );
+    assert_eq!(
+        entry,
+        "\n## 2023-11-14T22:13:20Z\n\nFix parser\n\n- `src/parser.rs` (3 updates)\n"
+    );
+}
+
+#[test]
+fn test_path_encoding_round_trip() {
+    for path in [
+        "src/main.rs",
+        "docs/100% done.md",
+        "src/a:b?.rs",
+        "src/line\nbreak.rs",
+        "src/con.rs",
+        "src/trailing. ",
+    ] {
+        let encoded = encode_path(Path::new(path));
+        assert!(!encoded.contains('\n'));
+        assert_eq!(decode_path(&encoded).unwrap(), PathBuf::from(path));
+    }
+
+    assert_eq!(encode_path(Path::new("src/main.rs")), "src/main.rs");
+    assert_eq!(
+        encode_path(Path::new("docs/100% done.md")),
+        "docs/100%25 done.md"
+    );
+    assert_eq!(encode_path(Path::new("aux.txt")), "%61ux.txt");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_path_encoding_invalid_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = Path::new(OsStr::from_bytes(b"src/caf\xe9.rs"));
+    let encoded = encode_path(path);
+    assert_eq!(encoded, "src/caf%E9.rs");
+    assert_eq!(decode_path(&encoded).unwrap(), path);
+}
+
+#[tokio::test]
+async fn test_patch_decodes_encoded_paths() {
+    let vfs = MemoryFs::with_files([("notes/50% off.md", "old price\n")]);
+
+    let json = serde_json::json!({
+        "analysis": "Update price",
+        "files": [{
+            "path": "notes/50%25 off.md",
+            "updates": [{ "old_content": "old price", "new_content": "new price" }]
+        }]
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    std::fs::write(&json_file, json.to_string()).unwrap();
+
+    execute(
+        &MemoryClipboard::new(),
+        &vfs,
+        Some(json_file.to_string_lossy().to_string()),
+        &PatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let content = vfs
+        .read_to_string(Path::new("notes/50% off.md"))
+        .await
+        .unwrap();
+    assert_eq!(content, "new price\n");
+}
+
+#[test]
+fn test_strip_verbatim_prefix() {
+    assert_eq!(
+        strip_verbatim_prefix(Path::new(r"\\?\C:\repo\src\main.rs")),
+        PathBuf::from(r"C:\repo\src\main.rs")
+    );
+    assert_eq!(
+        strip_verbatim_prefix(Path::new(r"\\?\UNC\server\share\repo")),
+        PathBuf::from(r"\\server\share\repo")
+    );
+    assert_eq!(
+        strip_verbatim_prefix(Path::new("src/main.rs")),
+        PathBuf::from("src/main.rs")
+    );
+    assert_eq!(
+        relative_to(Path::new("/repo/src/main.rs"), Path::new("/repo")),
+        PathBuf::from("src/main.rs")
+    );
+    assert_eq!(
+        relative_to(Path::new("/elsewhere/main.rs"), Path::new("/repo")),
+        PathBuf::from("/elsewhere/main.rs")
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn test_windows_long_and_unc_paths() {
+    assert_eq!(
+        relative_to(Path::new(r"\\?\C:\repo\src\main.rs"), Path::new(r"C:\repo")),
+        PathBuf::from(r"src\main.rs")
+    );
+    assert_eq!(
+        encode_path(Path::new(r"\\?\UNC\server\share\repo\a.rs")),
+        "//server/share/repo/a.rs"
+    );
+    assert_eq!(encode_path(Path::new(r"C:\repo\a.rs")), "C:/repo/a.rs");
+}
+
+#[tokio::test]
+async fn test_patch_journal_detects_external_changes() {
+    let vfs = MemoryFs::with_files([("src/lib.rs", "fn old() {}\n"), ("src/other.rs", "")]);
+
+    let mut journal = PatchJournal::default();
+    journal.record(Path::new("src/lib.rs"), b"fn old() {}\n");
+    journal.record(Path::new("src/gone.rs"), b"fn gone() {}\n");
+    assert_eq!(
+        journal.drifted(&vfs).await,
+        vec![PathBuf::from("src/gone.rs")]
+    );
+
+    let path = journal_path(Path::new("history"));
+    journal.save(&vfs, &path).await.unwrap();
+    let loaded = PatchJournal::load(&vfs, &path).await.unwrap();
+    assert_eq!(loaded.files.len(), 2);
+
+    vfs.write(Path::new("src/lib.rs"), b"fn edited() {}\n")
+        .await
+        .unwrap();
+    assert_eq!(
+        loaded.drifted(&vfs).await,
+        vec![PathBuf::from("src/gone.rs"), PathBuf::from("src/lib.rs")]
+    );
+}
+
+#[tokio::test]
+async fn test_undo_journal_restores_pre_images() {
+    let vfs = MemoryFs::with_files([
+        ("src/lib.rs", "fn newer() {}\n"),
+        ("src/new.rs", "fn created() {}\n"),
+    ]);
+
+    let mut journal = UndoJournal::new("Rename old");
+    journal.record(
+        Path::new("src/lib.rs"),
+        Some("fn old() {}\n"),
+        "fn new() {}\n",
+    );
+    journal.record(Path::new("src/new.rs"), None, "fn created() {}\n");
+    // A second entry for the same file keeps the first pre-image
+    journal.record(Path::new("src/lib.rs"), None, "fn newer() {}\n");
+    assert_eq!(journal.files.len(), 2);
+    assert!(journal.drifted(&vfs).await.is_empty());
+
+    let restored = journal.restore(&vfs, true, false).await.unwrap();
+    assert_eq!(
+        restored,
+        vec![
+            Restored::Reverted(PathBuf::from("src/lib.rs")),
+            Restored::Removed(PathBuf::from("src/new.rs")),
+        ]
+    );
+    assert!(vfs.exists(Path::new("src/new.rs")).await);
+
+    // Edits made after the patch aren't thrown away without --force
+    vfs.write(Path::new("src/new.rs"), b"fn edited() {}\n")
+        .await
+        .unwrap();
+    let err = journal.restore(&vfs, false, false).await.unwrap_err();
+    assert!(err.to_string().contains("src/new.rs"));
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap(),
+        "fn newer() {}\n"
+    );
+
+    journal.restore(&vfs, false, true).await.unwrap();
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap(),
+        "fn old() {}\n"
+    );
+    assert!(!vfs.exists(Path::new("src/new.rs")).await);
+
+    // A deleted file is drifted once it comes back, and undoing writes it again
+    let mut journal = UndoJournal::new("Delete lib");
+    journal.record_removed(Path::new("src/lib.rs"), Some("fn old() {}\n"));
+    vfs.remove_file(Path::new("src/lib.rs")).await.unwrap();
+    assert!(journal.drifted(&vfs).await.is_empty());
+    journal.restore(&vfs, false, false).await.unwrap();
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap(),
+        "fn old() {}\n"
+    );
+    assert_eq!(
+        journal.drifted(&vfs).await,
+        vec![PathBuf::from("src/lib.rs")]
+    );
+}
+
+#[tokio::test]
+async fn test_undo_reverts_a_delete_only_patch() {
+    let vfs = MemoryFs::with_files([
+        ("src/lib.rs", "fn old() {}\n"),
+        ("src/gone.rs", "fn gone() {}\n"),
+    ]);
+    let temp_dir = TempDir::new().unwrap();
+    let json_file = temp_dir.path().join("patch.json");
+    let clipboard = MemoryClipboard::new();
+    let options = PatchOptions::default();
+    let apply = |json: serde_json::Value| {
+        std::fs::write(&json_file, json.to_string()).unwrap();
+        execute(
+            &clipboard,
+            &vfs,
+            Some(json_file.to_string_lossy().to_string()),
+            &options,
+        )
+    };
+
+    apply(serde_json::json!({
+        "analysis": "Rename old",
+        "files": [{
+            "path": "src/lib.rs",
+            "updates": [{ "old_content": "fn old()", "new_content": "fn new()" }]
+        }]
+    }))
+    .await
+    .unwrap();
+    apply(serde_json::json!({
+        "analysis": "Drop gone",
+        "files": [{ "path": "src/gone.rs", "action": "delete" }]
+    }))
+    .await
+    .unwrap();
+    assert!(!vfs.exists(Path::new("src/gone.rs")).await);
+
+    // Undo takes back the deletion, not the patch before it
+    catnip::cli::commands::undo::execute(&vfs, false, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/gone.rs")).await.unwrap(),
+        "fn gone() {}\n"
+    );
+    assert_eq!(
+        vfs.read_to_string(Path::new("src/lib.rs")).await.unwrap(),
+        "fn new() {}\n"
+    );
+}
+
+#[test]
+fn test_patch_schema() {
+    let schema = patch_schema();
+    assert_eq!(schema["title"], "UpdateRequest");
+    assert_eq!(schema["required"], serde_json::json!(["analysis", "files"]));
+
+    let code_update = &schema["$defs"]["CodeUpdate"];
+    assert_eq!(
+        code_update["required"],
+        serde_json::json!(["old_content", "new_content"])
+    );
+    assert!(code_update["properties"]["description"].is_object());
+}
+
+#[tokio::test]
+async fn test_lint_patch() {
+    let request = UpdateRequest {
+        analysis: "Cleanup".to_string(),
+        files: vec![
+            FileUpdate {
+                path: "src/main.rs".to_string(),
+                action: None,
+                new_path: None,
+                updates: vec![
+                    update(&"let x = 1;\n".repeat(30), ""),
+                    update("fn main() {}  \n", "fn main() {}\n"),
+                    update("old", "new"),
+                ],
+            },
+            FileUpdate {
+                path: "src/other.rs".to_string(),
+                action: None,
+                new_path: None,
+                updates: vec![update("a", "b")],
+            },
+            FileUpdate {
+                path: "src/new.rs".to_string(),
+                action: None,
+                new_path: None,
+                updates: vec![update("", "pub fn new() {}\n")],
+            },
+        ],
+    };
+    let vfs = MemoryFs::with_files([("src/main.rs", "fn main() {}")]);
+    let mut snapshot = Snapshot::capture(&vfs, &[PathBuf::from("src/main.rs")]).await;
+    // Snapshots of `catnip cat .` record paths with a leading `./`
+    let entry = snapshot.files.remove(Path::new("src/main.rs")).unwrap();
+    snapshot.files.insert(PathBuf::from("./src/main.rs"), entry);
+
+    let findings = lint_patch(&request, Some(&snapshot), &PatchLint::default(), None);
+    let summary: Vec<_> = findings
+        .iter()
+        .map(|finding| (finding.path.as_str(), finding.update, finding.severity))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("src/main.rs", Some(1), LintSeverity::Warn),
+            ("src/main.rs", Some(2), LintSeverity::Warn),
+            ("src/other.rs", None, LintSeverity::Warn),
+        ]
+    );
+    assert!(findings[0].to_string().contains("deletes 30 lines"));
+
+    // Without a snapshot there is nothing to compare against
+    let findings = lint_patch(
+        &request,
+        None,
+        &PatchLint::default(),
+        Some(LintSeverity::Error),
+    );
+    assert_eq!(findings.len(), 2);
+    assert!(findings.iter().all(|f| f.severity == LintSeverity::Error));
+
+    let lint = PatchLint {
+        whitespace_only: LintSeverity::Off,
+        ..PatchLint::default()
+    };
+    assert_eq!(lint_patch(&request, None, &lint, None).len(), 1);
+    assert!(lint_patch(&request, None, &lint, Some(LintSeverity::Off)).is_empty());
+}
+
+#[test]
+fn test_lint_settings() {
+    let settings: Settings =
+        toml::from_str("[patch.lint]\nlarge_deletion = \"error\"\nmax_deleted_lines = 5\n")
+            .unwrap();
+    assert_eq!(settings.patch.lint.large_deletion, LintSeverity::Error);
+    assert_eq!(settings.patch.lint.whitespace_only, LintSeverity::Warn);
+    assert_eq!(settings.patch.lint.max_deleted_lines, 5);
+}
+
+#[test]
+fn test_format_diff() {
+    let old = "fn main() {\n    start();\n    wait();\n}\n";
+    let new = "fn main() {\n    init();\n    start();\n}\n";
+    assert_eq!(
+        format_diff(old, new, false),
+        " fn main() {\n+    init();\n     start();\n-    wait();\n }\n"
+    );
+    assert_eq!(
+        format_diff("a\n", "b\n", true),
+        "\x1b[31m-a\x1b[0m\n\x1b[32m+b\x1b[0m\n"
+    );
+    assert_eq!(format_diff("", "new\n", false), "+new\n");
+}
+
+#[test]
+fn test_approver_answers() {
+    // Unknown answers ask again; `a` approves everything after it without asking
+    let mut approver = Approver::new("y\nmaybe\nn\na\n".as_bytes());
+    let answers: Vec<bool> = (0..5)
+        .map(|_| approver.approve("update", "-a\n+b\n").unwrap())
+        .collect();
+    assert_eq!(answers, vec![true, false, true, true, true]);
+
+    // `q` and the end of input leave the rest out
+    let mut approver = Approver::new("Y\nq\ny\n".as_bytes());
+    assert!(approver.approve("update", "").unwrap());
+    assert!(!approver.approve("update", "").unwrap());
+    assert!(!approver.approve("update", "").unwrap());
+    let mut approver = Approver::new("".as_bytes());
+    assert!(!approver.approve("update", "").unwrap());
+
+    assert_eq!(
+        progress_line("src/lib.rs", 1, 2, UpdateStatus::Skipped),
+        "file=src/lib.rs update=1/2 status=skipped"
+    );
+}
+
+#[test]
+fn test_apply_update_fuzzy() {
+    let content = [
+        "impl Server {",
+        "    fn start(&self) {",
+        "        self.bind();  ",
+        "        self.listen();",
+        "    }",
+        "}",
+        "",
+    ]
+    .join("\n");
+    // Written without the impl block's indentation, as models often do
+    let update = update(
+        "fn start(&self) {\n    self.bind();\n    self.listen();\n}\n",
+        "fn start(&self) {\n    self.bind();\n    self.serve();\n}\n",
+    );
+
+    let error = apply_update_fuzzy(&content, &update, None).unwrap_err();
+    assert!(error.to_string().contains("pass --fuzzy to accept"));
+
+    let (updated, confidence) = apply_update_fuzzy(&content, &update, Some(80.0)).unwrap();
+    assert_eq!(
+        updated,
+        [
+            "impl Server {",
+            "    fn start(&self) {",
+            "        self.bind();",
+            "        self.serve();",
+            "    }",
+            "}",
+            "",
+        ]
+        .join("\n")
+    );
+    let confidence = confidence.unwrap();
+    assert!((80.0..100.0).contains(&confidence), "{}", confidence);
+
+    assert!(apply_update_fuzzy(&content, &update, Some(99.0)).is_err());
+
+    // Exact matches and unrelated content behave as without --fuzzy
+    let exact = self::update("self.listen();", "self.serve();");
+    assert_eq!(
+        apply_update_fuzzy(&content, &exact, Some(80.0)).unwrap().1,
+        None
+    );
+    let missing = self::update("fn stop(&self) {}", "");
+    assert!(apply_update_fuzzy(&content, &missing, Some(80.0)).is_err());
+}
+
+#[test]
+fn test_apply_update_fuzzy_removes_lines_and_rejects_ambiguity() {
+    let content = "a\n    x  =  1\nb\n";
+    let (updated, _) = apply_update_fuzzy(content, &update("x = 1\n", ""), Some(50.0)).unwrap();
+    assert_eq!(updated, "a\nb\n");
+
+    let error = apply_update_fuzzy("  x y\n\tx y\n", &update("x  y", "z"), Some(50.0))
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Old content matches 2 places equally well with whitespace ignored"
+    );
+}
+
+#[test]
+fn test_apply_update_and_progress_line() {
+    let update = CodeUpdate {
+        old_content: "a".to_string(),
+        new_content: "b".to_string(),
+        description: None,
+        line: None,
+    };
+    assert_eq!(apply_update("xax", &update).unwrap(), "xbx");
+    assert!(apply_update("xyz", &update).is_err());
+
+    assert_eq!(
+        progress_line("src/lib.rs", 2, 5, UpdateStatus::Applied),
+        "file=src/lib.rs update=2/5 status=applied"
+    );
+}
@@ -1,4 +1,4 @@
-use catnip::core::pattern_matcher::PatternMatcher;
+use catnip::core::pattern_matcher::{MatchInfo, PatternMatcher, PatternSource};
 use std::path::PathBuf;
 
 #[test]
@@ -88,3 +88,173 @@ fn test_performance_with_patterns() {
         duration
     );
 }
+
+#[test]
+fn test_match_with_reason_reports_pattern_source() {
+    let matcher = PatternMatcher::builder()
+        .add_all(["target/*", "*.lock"], PatternSource::Default)
+        .add("docs/**/*.png", PatternSource::Config)
+        .add("secrets.env", PatternSource::Cli)
+        .build();
+
+    assert_eq!(
+        matcher.match_with_reason(&PathBuf::from("target/debug/catnip")),
+        Some(MatchInfo {
+            pattern: "target/*".to_string(),
+            source: PatternSource::Default,
+        })
+    );
+    assert_eq!(
+        matcher
+            .match_with_reason(&PathBuf::from("docs/img/logo.png"))
+            .map(|info| info.source),
+        Some(PatternSource::Config)
+    );
+
+    let info = matcher
+        .match_with_reason(&PathBuf::from("app/secrets.env"))
+        .unwrap();
+    assert_eq!(info.to_string(), "'secrets.env' (command line)");
+
+    assert_eq!(
+        matcher.match_with_reason(&PathBuf::from("src/main.rs")),
+        None
+    );
+}
+
+#[test]
+fn test_later_pattern_source_takes_precedence() {
+    let matcher = PatternMatcher::builder()
+        .add("*.log", PatternSource::Default)
+        .add("*.log", PatternSource::Cli)
+        .build();
+
+    let info = matcher
+        .match_with_reason(&PathBuf::from("out.log"))
+        .unwrap();
+    assert_eq!(info.source, PatternSource::Cli);
+}
+
+#[test]
+fn test_glob_matching_multibyte_filenames() {
+    let matcher = PatternMatcher::new(&[
+        "docs/?.md".to_string(),
+        "src/*_测试.rs".to_string(),
+        "**/🦀*.txt".to_string(),
+    ]);
+
+    // `?` consumes a whole character, whatever its width in bytes
+    assert!(matcher.matches_path(&PathBuf::from("docs/é.md")));
+    assert!(matcher.matches_path(&PathBuf::from("docs/日.md")));
+    assert!(matcher.matches_path(&PathBuf::from("docs/🐈.md")));
+    assert!(!matcher.matches_path(&PathBuf::from("docs/日本.md")));
+
+    assert!(matcher.matches_path(&PathBuf::from("src/模块_测试.rs")));
+    assert!(!matcher.matches_path(&PathBuf::from("src/模块/子_测试.rs")));
+
+    assert!(matcher.matches_path(&PathBuf::from("notes/日記/🦀蟹.txt")));
+    assert!(matcher.matches_path(&PathBuf::from("🦀.txt")));
+    assert!(!matcher.matches_path(&PathBuf::from("notes/蟹🦀.txt")));
+}
+
+#[test]
+fn test_glob_matching_does_not_panic_on_multibyte_paths() {
+    let matcher = PatternMatcher::new(&[
+        "a?c/*".to_string(),
+        "**/x?".to_string(),
+        "*ü*/**".to_string(),
+    ]);
+
+    for path in ["äöü/ß", "日本語/テスト.rs", "👨‍👩‍👧/🏳️‍🌈", "x/xé", "aé/c"]
+    {
+        let _ = matcher.matches_path(&PathBuf::from(path));
+    }
+    assert!(matcher.matches_path(&PathBuf::from("x/xé")));
+    assert!(matcher.matches_path(&PathBuf::from("aéc/日")));
+    assert!(matcher.matches_path(&PathBuf::from("güt/a/b")));
+}
+
+#[test]
+fn test_unanchored_patterns_match_at_any_depth() {
+    let matcher = PatternMatcher::new(&["*.rs".to_string(), "target".to_string()]);
+
+    assert!(matcher.matches_path(&PathBuf::from("main.rs")));
+    assert!(matcher.matches_path(&PathBuf::from("a/b/c/lib.rs")));
+    // A matching directory matches everything below it
+    assert!(matcher.matches_path(&PathBuf::from("crates/core/target/debug/build.log")));
+    assert!(matcher.matches_dir(&PathBuf::from("crates/target")));
+    assert!(!matcher.matches_path(&PathBuf::from("targets/readme.txt")));
+}
+
+#[test]
+fn test_anchored_patterns_match_from_the_root() {
+    let matcher = PatternMatcher::new(&[
+        "/Cargo.toml".to_string(),
+        "src/*.rs".to_string(),
+        "docs/**/*.md".to_string(),
+        "**/fixtures".to_string(),
+    ]);
+
+    assert!(matcher.matches_path(&PathBuf::from("Cargo.toml")));
+    assert!(!matcher.matches_path(&PathBuf::from("crates/core/Cargo.toml")));
+
+    assert!(matcher.matches_path(&PathBuf::from("src/lib.rs")));
+    assert!(!matcher.matches_path(&PathBuf::from("crates/src/lib.rs")));
+
+    // `**/` matches zero or more directories
+    assert!(matcher.matches_path(&PathBuf::from("docs/intro.md")));
+    assert!(matcher.matches_path(&PathBuf::from("docs/guide/setup/install.md")));
+    assert!(!matcher.matches_path(&PathBuf::from("book/docs/intro.md")));
+
+    assert!(matcher.matches_path(&PathBuf::from("fixtures/sample.json")));
+    assert!(matcher.matches_path(&PathBuf::from("tests/unit/fixtures/sample.json")));
+}
+
+#[test]
+fn test_trailing_slash_matches_only_directories() {
+    let matcher = PatternMatcher::new(&["build/".to_string(), "/out/".to_string()]);
+
+    assert!(matcher.matches_dir(&PathBuf::from("build")));
+    assert!(matcher.matches_dir(&PathBuf::from("app/build")));
+    assert!(matcher.matches_path(&PathBuf::from("app/build/output.js")));
+    assert!(!matcher.matches_path(&PathBuf::from("scripts/build")));
+
+    assert!(matcher.matches_dir(&PathBuf::from("out")));
+    assert!(!matcher.matches_dir(&PathBuf::from("app/out")));
+    assert!(!matcher.matches_path(&PathBuf::from("out")));
+}
+
+#[test]
+fn test_trailing_double_star_matches_contents() {
+    let matcher = PatternMatcher::new(&["vendor/**".to_string()]);
+
+    assert!(matcher.matches_path(&PathBuf::from("vendor/lib.js")));
+    assert!(matcher.matches_path(&PathBuf::from("vendor/a/b/lib.js")));
+    assert!(!matcher.matches_path(&PathBuf::from("vendor")));
+    assert!(!matcher.matches_path(&PathBuf::from("src/vendor/lib.js")));
+}
+
+#[test]
+fn test_brace_alternation() {
+    let matcher = PatternMatcher::new(&[
+        "*.{rs,toml,md}".to_string(),
+        "src/{cli,core/{io,net}}/**".to_string(),
+        "{a}.txt".to_string(),
+        "{unclosed,brace".to_string(),
+    ]);
+
+    assert!(matcher.matches_path(&PathBuf::from("main.rs")));
+    assert!(matcher.matches_path(&PathBuf::from("docs/Cargo.toml")));
+    assert!(matcher.matches_path(&PathBuf::from("README.md")));
+    assert!(!matcher.matches_path(&PathBuf::from("app.py")));
+
+    assert!(matcher.matches_path(&PathBuf::from("src/cli/args.py")));
+    assert!(matcher.matches_path(&PathBuf::from("src/core/net/http.py")));
+    assert!(!matcher.matches_path(&PathBuf::from("src/core/db.py")));
+
+    assert!(matcher.matches_path(&PathBuf::from("{a}.txt")));
+    assert!(matcher.matches_path(&PathBuf::from("{unclosed,brace")));
+
+    let info = matcher.match_with_reason(&PathBuf::from("lib.rs")).unwrap();
+    assert_eq!(info.to_string(), "'*.{rs,toml,md}' (default)");
+}
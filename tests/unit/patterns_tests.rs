@@ -1,4 +1,6 @@
-use catnip::config::patterns::{DEFAULT_EXCLUDE_PATTERNS, DEFAULT_INCLUDE_PATTERNS};
+use catnip::config::patterns::{
+    DEFAULT_EXCLUDE_PATTERNS, DEFAULT_INCLUDE_PATTERNS, parse_pattern_list,
+};
 use std::collections::HashSet;
 
 #[test]
@@ -73,3 +75,12 @@ fn test_patterns_are_strings() {
         assert!(!pattern.is_empty(), "Empty include pattern found");
     }
 }
+
+#[test]
+fn test_parse_pattern_list() {
+    let content = "# generated code\n*.pb.go\n\n  vendor/*  \n#*.log\ndocs/**\n";
+    assert_eq!(
+        parse_pattern_list(content),
+        vec!["*.pb.go", "vendor/*", "docs/**"]
+    );
+}
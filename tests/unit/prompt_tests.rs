@@ -0,0 +1,54 @@
+use catnip::config::prompt::{
+    PROMPT, PROMPT_MARKER, PromptPosition, delta_preamble, place_prompt, remove_prompt_section,
+};
+use catnip::core::snapshot::FileStatus;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[test]
+fn test_place_prompt_position() {
+    let end = place_prompt("# Snapshot\n", PromptPosition::End, false);
+    assert!(end.starts_with("# Snapshot\n"));
+    assert!(end.ends_with(PROMPT));
+
+    let start = place_prompt("# Snapshot\n", PromptPosition::Start, false);
+    assert!(start.starts_with(PROMPT));
+    assert!(start.ends_with("# Snapshot\n"));
+    assert!(!start.contains(PROMPT_MARKER));
+}
+
+#[test]
+fn test_place_prompt_with_marker() {
+    let result = place_prompt("# Snapshot\n", PromptPosition::End, true);
+    let (snapshot, prompt) = result.split_once(PROMPT_MARKER).unwrap();
+    assert_eq!(snapshot, "# Snapshot\n\n");
+    assert_eq!(prompt, format!("\n{}", PROMPT));
+}
+
+#[test]
+fn test_delta_preamble() {
+    let delta = BTreeMap::from([
+        (PathBuf::from("src/lib.rs"), FileStatus::Modified),
+        (PathBuf::from("src/main.rs"), FileStatus::Unchanged),
+        (PathBuf::from("src/old.rs"), FileStatus::Removed),
+    ]);
+
+    let preamble = delta_preamble(&delta);
+    assert!(preamble.starts_with("The following files changed since the context"));
+    assert!(preamble.contains("- `src/lib.rs` (modified)\n- `src/old.rs` (removed)\n"));
+    assert!(!preamble.contains("main.rs"));
+
+    let unchanged = BTreeMap::from([(PathBuf::from("src/main.rs"), FileStatus::Unchanged)]);
+    assert!(delta_preamble(&unchanged).starts_with("Nothing changed"));
+}
+
+#[test]
+fn test_remove_prompt_section() {
+    let trimmed = remove_prompt_section(PROMPT, "Examples");
+    assert!(!trimmed.contains("## Examples"));
+    assert!(!trimmed.contains("Function replacement"));
+    // The fenced JSON before the headings and the sections after it are kept
+    assert!(trimmed.contains("\"analysis\""));
+    assert!(trimmed.contains("## Workflow"));
+    assert!(trimmed.len() < PROMPT.len());
+}
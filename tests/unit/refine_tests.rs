@@ -0,0 +1,49 @@
+use catnip::core::file_collector::CollectedFile;
+use catnip::core::refine::{exclude_matching, refine_files};
+use catnip::io::vfs::MemoryFs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_exclude_matching() {
+    let mut files: Vec<_> = ["src/main.rs", "src/gen/api.rs", "docs/guide.md"]
+        .iter()
+        .map(|path| CollectedFile::new(Path::new(path), Path::new(path), b"", None))
+        .collect();
+
+    let excluded = exclude_matching(&mut files, "src/gen/*");
+    assert_eq!(excluded, vec![PathBuf::from("src/gen/api.rs")]);
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("src/main.rs"), PathBuf::from("docs/guide.md")]
+    );
+    assert!(exclude_matching(&mut files, "*.py").is_empty());
+}
+
+#[tokio::test]
+async fn test_refine_files_until_empty_line() {
+    let vfs = MemoryFs::with_files([
+        ("src/main.rs", "fn main() {}\n"),
+        ("docs/guide.md", "# Guide\n"),
+        ("notes.txt", "todo\n"),
+    ]);
+    let mut files = CollectedFile::load_all(
+        &vfs,
+        &[
+            PathBuf::from("src/main.rs"),
+            PathBuf::from("docs/guide.md"),
+            PathBuf::from("notes.txt"),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let mut input = Cursor::new("*.md\n*.py\n\n*.rs\n");
+    let patterns = refine_files(&vfs, &mut files, &mut input).await.unwrap();
+    // Patterns matching nothing aren't kept, and the empty line ends the loop
+    assert_eq!(patterns, vec!["*.md"]);
+    assert_eq!(
+        CollectedFile::paths(&files),
+        vec![PathBuf::from("src/main.rs"), PathBuf::from("notes.txt")]
+    );
+}
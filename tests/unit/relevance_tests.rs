@@ -0,0 +1,90 @@
+use catnip::core::relevance::{query_terms, rank_by_relevance};
+use catnip::core::search_index::{IndexUpdate, SearchIndex};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_query_terms() {
+    assert_eq!(
+        query_terms("WebSocket reconnect-backoff, reconnect"),
+        vec!["websocket", "reconnect", "backoff"]
+    );
+    assert!(query_terms("  ,. ").is_empty());
+}
+
+#[test]
+fn test_rank_by_relevance() {
+    let files = vec![
+        (
+            PathBuf::from("src/net/websocket.rs"),
+            "fn reconnect() { backoff.next() }".to_string(),
+        ),
+        (
+            PathBuf::from("src/ui/button.rs"),
+            "// reconnect button".to_string(),
+        ),
+        (PathBuf::from("src/db.rs"), "fn query() {}".to_string()),
+    ];
+
+    let ranked = rank_by_relevance(&files, "websocket reconnect backoff");
+    let paths: Vec<_> = ranked.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("src/net/websocket.rs"),
+            PathBuf::from("src/ui/button.rs"),
+        ]
+    );
+    assert!(ranked[0].1 > ranked[1].1);
+}
+
+#[test]
+fn test_search_index_updates_incrementally() {
+    let dir = TempDir::new().unwrap();
+    let file = |path: &str, content: &str| (path.to_string(), content.to_string());
+    assert!(!SearchIndex::exists(dir.path()));
+
+    let index = SearchIndex::open_or_create(dir.path()).unwrap();
+    let update = index
+        .update(
+            &[
+                file("src/socket.rs", "fn reconnect_with_backoff() { backoff }"),
+                file("src/ui.rs", "fn render() {}"),
+                file("README.md", "reconnect docs"),
+            ],
+            true,
+        )
+        .unwrap();
+    assert_eq!(
+        update,
+        IndexUpdate {
+            added: 3,
+            ..IndexUpdate::default()
+        }
+    );
+    assert!(SearchIndex::exists(dir.path()));
+
+    let results = index.search("backoff", 10).unwrap();
+    assert_eq!(results[0].0, "src/socket.rs");
+    assert_eq!(results.len(), 1);
+
+    // Without removal, files left out of an update stay indexed
+    let update = index
+        .update(&[file("src/ui.rs", "fn render() { backoff }")], false)
+        .unwrap();
+    assert_eq!(update.updated, 1);
+    assert_eq!(index.search("backoff", 10).unwrap().len(), 2);
+
+    let update = index
+        .update(&[file("src/ui.rs", "fn render() { backoff }")], true)
+        .unwrap();
+    assert_eq!(
+        update,
+        IndexUpdate {
+            removed: 2,
+            unchanged: 1,
+            ..IndexUpdate::default()
+        }
+    );
+    assert_eq!(index.file_count().unwrap(), 1);
+}
@@ -0,0 +1,33 @@
+use catnip::io::remote::{check_content_type, fetch_text, is_url};
+
+#[test]
+fn test_is_url() {
+    assert!(is_url(
+        "https://gist.githubusercontent.com/u/1/raw/patch.json"
+    ));
+    assert!(is_url("http://paste.internal/1"));
+    assert!(!is_url("patch.json"));
+    assert!(!is_url("-"));
+}
+
+#[test]
+fn test_check_content_type() {
+    assert!(check_content_type(None).is_ok());
+    assert!(check_content_type(Some("application/json")).is_ok());
+    assert!(check_content_type(Some("text/plain; charset=utf-8")).is_ok());
+    assert!(check_content_type(Some("application/vnd.api+json")).is_ok());
+
+    let error = check_content_type(Some("text/html; charset=utf-8")).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected a JSON document, got text/html; charset=utf-8"
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_text_requires_https() {
+    let error = fetch_text("http://paste.internal/1", 1024)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("plain HTTP"));
+}
@@ -0,0 +1,199 @@
+use catnip::core::rust_cfg::{RustCfg, Target};
+use catnip::io::vfs::MemoryFs;
+use std::path::Path;
+
+fn cfg(features: &[&str], target: Option<&str>) -> RustCfg {
+    RustCfg {
+        features: features.iter().map(|f| f.to_string()).collect(),
+        target: target.map(|triple| Target::parse(triple).unwrap()),
+        no_default_features: false,
+    }
+}
+
+#[test]
+fn test_target_parse() {
+    let linux = Target::parse("x86_64-unknown-linux-gnu").unwrap();
+    assert_eq!(
+        (linux.arch.as_str(), linux.os.as_str(), linux.env.as_str()),
+        ("x86_64", "linux", "gnu")
+    );
+    assert_eq!(linux.family, vec!["unix"]);
+    assert_eq!(linux.pointer_width, 64);
+
+    let mac = Target::parse("aarch64-apple-darwin").unwrap();
+    assert_eq!((mac.os.as_str(), mac.vendor.as_str()), ("macos", "apple"));
+
+    let windows = Target::parse("i686-pc-windows-msvc").unwrap();
+    assert_eq!(windows.arch, "x86");
+    assert_eq!(windows.family, vec!["windows"]);
+    assert_eq!(windows.pointer_width, 32);
+
+    let android = Target::parse("aarch64-linux-android").unwrap();
+    assert_eq!(android.os, "android");
+    assert_eq!(
+        Target::parse("wasm32-unknown-unknown").unwrap().family,
+        vec!["wasm"]
+    );
+    assert!(Target::parse("x86_64").is_err());
+}
+
+#[test]
+fn test_evaluate_predicates() {
+    let linux = cfg(&["serde"], Some("x86_64-unknown-linux-gnu"));
+    assert_eq!(linux.evaluate("test"), Some(false));
+    assert_eq!(linux.evaluate(r#"feature = "serde""#), Some(true));
+    assert_eq!(linux.evaluate(r#"feature = "tokio""#), Some(false));
+    assert_eq!(linux.evaluate("unix"), Some(true));
+    assert_eq!(linux.evaluate(r#"target_os = "windows""#), Some(false));
+    assert_eq!(
+        linux.evaluate(r#"all(unix, not(target_os = "macos"), feature = "serde")"#),
+        Some(true)
+    );
+    assert_eq!(linux.evaluate("any(windows, test)"), Some(false));
+    // Unknown predicates keep their code unless the rest settles it
+    assert_eq!(linux.evaluate("debug_assertions"), None);
+    assert_eq!(linux.evaluate("any(debug_assertions, unix)"), Some(true));
+    assert_eq!(
+        linux.evaluate("all(debug_assertions, windows)"),
+        Some(false)
+    );
+    assert_eq!(linux.evaluate("all(unix"), None);
+
+    // Without a target, platform predicates are unknown
+    assert_eq!(cfg(&[], None).evaluate("windows"), None);
+}
+
+#[test]
+fn test_strip_inactive_items() {
+    let source = r#"use std::io;
+
+/// Only with serde
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub struct Wire {
+    field: u8,
+}
+
+pub struct Config {
+    name: String,
+    #[cfg(windows)]
+    registry_key: String,
+}
+
+#[cfg(unix)]
+fn platform() -> &'static str { "unix" }
+
+#[cfg(windows)]
+fn platform() -> &'static str {
+    "windows"
+}
+
+#[cfg(test)] use std::fs;
+
+fn main() {}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn works() {}
+}
+"#;
+
+    let stripped = cfg(&[], Some("x86_64-unknown-linux-gnu")).strip(source);
+    assert_eq!(
+        stripped,
+        r#"use std::io;
+
+pub struct Config {
+    name: String,
+}
+
+#[cfg(unix)]
+fn platform() -> &'static str { "unix" }
+
+fn main() {}
+
+"#
+    );
+
+    // A file compiled out by an inner attribute leaves nothing
+    assert_eq!(
+        cfg(&[], None).strip("#![cfg(feature = \"nightly\")]\nfn f() {}\n"),
+        ""
+    );
+    // Without a target, platform code stays
+    assert!(cfg(&[], None).strip(source).contains("\"windows\""));
+}
+
+#[test]
+fn test_strip_ignores_brackets_in_literals_and_comments() {
+    let source = r##"#[cfg(test)] const URL: &str = "http://example.com/{";
+pub fn after_url() {}
+
+#[cfg(test)]
+mod tests {
+    fn t() { let s = "}"; let c = '{'; let r = r#"}"#; } // }
+    /* { */
+}
+
+#[cfg(feature = "ui")]
+fn label<'a>(name: &'a str) -> &'a str { "]" }
+
+pub fn last() -> char { '}' }
+"##;
+
+    assert_eq!(
+        cfg(&[], None).strip(source),
+        r#"pub fn after_url() {}
+
+pub fn last() -> char { '}' }
+"#
+    );
+}
+
+#[tokio::test]
+async fn test_default_features_come_from_the_nearest_manifest() {
+    let vfs = MemoryFs::with_files([
+        (
+            "crate/Cargo.toml",
+            "[package]\nname = \"app\"\n\n[features]\ndefault = [\"cli\", \"dep:anyhow\"]\ncli = [\"color\", \"clap/derive\"]\ncolor = []\nui = []\n",
+        ),
+        ("crate/src/main.rs", ""),
+        ("other/src/lib.rs", ""),
+    ]);
+    let source =
+        "#[cfg(feature = \"color\")]\nfn color() {}\n#[cfg(feature = \"ui\")]\nfn ui() {}\n";
+
+    let defaults = cfg(&[], None)
+        .for_file(&vfs, Path::new("crate/src/main.rs"))
+        .await;
+    assert_eq!(
+        defaults
+            .features
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+        ["cli", "color"]
+    );
+    assert_eq!(
+        defaults.strip(source),
+        "#[cfg(feature = \"color\")]\nfn color() {}\n"
+    );
+
+    let no_defaults = RustCfg {
+        no_default_features: true,
+        ..cfg(&["ui"], None)
+    }
+    .for_file(&vfs, Path::new("crate/src/main.rs"))
+    .await;
+    assert_eq!(
+        no_defaults.strip(source),
+        "#[cfg(feature = \"ui\")]\nfn ui() {}\n"
+    );
+
+    // Outside any crate, only the features given are on
+    let outside = cfg(&[], None)
+        .for_file(&vfs, Path::new("other/src/lib.rs"))
+        .await;
+    assert!(outside.features.is_empty());
+}
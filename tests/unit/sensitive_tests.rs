@@ -0,0 +1,33 @@
+use catnip::core::file_collector::CollectedFile;
+use catnip::core::sensitive::find_sensitive;
+use std::path::{Path, PathBuf};
+
+fn collected(path: &str) -> CollectedFile {
+    CollectedFile::new(Path::new(path), Path::new(path), b"content", None)
+}
+
+#[test]
+fn test_find_sensitive() {
+    let files = vec![
+        collected("src/main.rs"),
+        collected("deploy/server.pem"),
+        collected(".ssh/id_rsa"),
+        collected("config/credentials.json"),
+        collected(".env"),
+        collected(".env.example"),
+        collected("docs/keys.md"),
+    ];
+
+    let sensitive = find_sensitive(&files);
+    let paths: Vec<_> = sensitive.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("deploy/server.pem"),
+            PathBuf::from(".ssh/id_rsa"),
+            PathBuf::from("config/credentials.json"),
+            PathBuf::from(".env"),
+        ]
+    );
+    assert_eq!(sensitive[0].matched.to_string(), "'*.pem' (default)");
+}
@@ -0,0 +1,74 @@
+use catnip::config::Settings;
+use catnip::config::settings::{ShareProviderKind, ShareSettings};
+use catnip::integrations::share::{gist_link, gist_payload, paste_link, presigned_link, share};
+use serde_json::json;
+
+#[test]
+fn test_share_settings() {
+    let settings: Settings = toml::from_str(
+        "[share]\nprovider = \"paste\"\nurl = \"https://paste.internal/api\"\ntoken_env = \"PASTE_TOKEN\"\n",
+    )
+    .unwrap();
+    assert_eq!(settings.share.provider, Some(ShareProviderKind::Paste));
+    assert_eq!(
+        settings.share.url.as_deref(),
+        Some("https://paste.internal/api")
+    );
+    assert_eq!(settings.share.token_env.as_deref(), Some("PASTE_TOKEN"));
+    assert!(!settings.share.public);
+
+    // A checked-in catnip.toml can't point the user's token at its own host
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let project_config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &project_config,
+        "[share]\nprovider = \"paste\"\nurl = \"https://evil.example\"\ntoken_env = \"GITHUB_TOKEN\"\n",
+    )
+    .unwrap();
+    let settings = Settings::load_layers(None, &project_config).unwrap();
+    assert_eq!(settings.share.provider, None);
+    assert_eq!(settings.share.url, None);
+    assert_eq!(settings.share.token_env, None);
+}
+
+#[test]
+fn test_share_links() {
+    assert_eq!(
+        presigned_link("https://bucket.s3.amazonaws.com/snap.md?X-Amz-Signature=abc"),
+        "https://bucket.s3.amazonaws.com/snap.md"
+    );
+    assert_eq!(
+        paste_link("\nhttps://paste.internal/xyz\n").unwrap(),
+        "https://paste.internal/xyz"
+    );
+    assert!(paste_link("  \n").is_err());
+
+    let payload = gist_payload("snapshot.md", "# Project", false);
+    assert_eq!(payload["public"], json!(false));
+    assert_eq!(
+        payload["files"]["snapshot.md"]["content"],
+        json!("# Project")
+    );
+    assert_eq!(
+        gist_link(&json!({ "html_url": "https://gist.github.com/u/1" })).unwrap(),
+        "https://gist.github.com/u/1"
+    );
+    assert!(gist_link(&json!({ "message": "Bad credentials" })).is_err());
+}
+
+#[tokio::test]
+async fn test_share_requires_configuration() {
+    let settings = ShareSettings::default();
+    let error = share(&settings, None, "snapshot.md", "# Project")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("No share provider configured"));
+
+    let error = share(&settings, Some(ShareProviderKind::Paste), "snapshot.md", "")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The paste share provider needs [share] url"
+    );
+}
